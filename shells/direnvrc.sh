@@ -0,0 +1,8 @@
+# Load a profile from env-select in a direnv .envrc:
+#   use env_select <application> <profile>
+use_env_select() {
+    # Capture first so a failure isn't hidden by eval
+    local output
+    output=$(ENV_SELECT_BINARY --shell bash export --format direnv "$@") || return
+    eval "$output"
+}
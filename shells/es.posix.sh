@@ -0,0 +1,17 @@
+es () {
+    # POSIX sh has no `local`, so use prefixed globals and clean them up after.
+    # Make a tmp file for env-select to dump sourceable output to. --source-file
+    # is a hidden flag, so consider it safe to pass it ourselves
+    __es_tmp_file=$(mktemp)
+    ENV_SELECT_BINARY --source-file "$__es_tmp_file" "$@"
+    # If env-select was successful, source whatever output it *might have* dumped
+    __es_return_code=$?
+    if [ "$__es_return_code" -eq 0 ]; then
+        . "$__es_tmp_file"
+    fi
+    rm "$__es_tmp_file"
+    # Stash the return code in the function's arguments, so the globals can go
+    set -- "$__es_return_code"
+    unset __es_tmp_file __es_return_code
+    return "$1"
+}
@@ -0,0 +1,57 @@
+//! Tracking which profile is active for each application in the shell, so
+//! shell prompts and `es show active` can display it
+
+use crate::config::Name;
+use std::env;
+
+/// Prefix of the variables that `es set` exports with the active profile of
+/// each application. The rest of the name is the sanitized application name
+pub const ACTIVE_VARIABLE_PREFIX: &str = "ENV_SELECT_ACTIVE_";
+
+/// Get the variable that holds the active profile for an application. The
+/// name is upper-cased, and characters that aren't allowed in variable names
+/// are replaced with underscores, e.g. `my-app` => `ENV_SELECT_ACTIVE_MY_APP`
+pub fn active_variable(application: &Name) -> String {
+    application_variable(ACTIVE_VARIABLE_PREFIX, application)
+}
+
+/// Get the name of a variable that holds per-application state, by appending
+/// the sanitized application name to a prefix. See [active_variable]
+pub fn application_variable(prefix: &str, application: &Name) -> String {
+    let sanitized: String = application
+        .0
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{prefix}{sanitized}")
+}
+
+/// Get every `(variable, profile)` pair of active profiles in the current
+/// environment, sorted by variable
+pub fn active_variables() -> Vec<(String, String)> {
+    let mut active: Vec<(String, String)> = env::vars()
+        .filter(|(variable, _)| variable.starts_with(ACTIVE_VARIABLE_PREFIX))
+        .collect();
+    active.sort();
+    active
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::simple("server", "ENV_SELECT_ACTIVE_SERVER")]
+    #[case::punctuation("my-app.v2", "ENV_SELECT_ACTIVE_MY_APP_V2")]
+    #[case::unicode("café", "ENV_SELECT_ACTIVE_CAF_")]
+    fn test_active_variable(#[case] application: &str, #[case] expected: &str) {
+        assert_eq!(active_variable(&application.parse().unwrap()), expected);
+    }
+}
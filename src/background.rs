@@ -0,0 +1,368 @@
+//! Processes left running by side effects with `background` set. `es run`
+//! kills them during teardown. `es set` has no teardown, so it records their
+//! PIDs in a file, and the next `es set` for the same application kills them.
+
+use crate::{
+    active::application_variable, config::Name, execute::IntoExecutable,
+    runtime_dir::runtime_dir,
+};
+use anyhow::Context;
+use indexmap::IndexMap;
+use log::info;
+use smol::process::Child;
+use std::{
+    env,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    process,
+};
+
+/// Prefix of the variables that `es set` exports with the path of each
+/// application's pidfile, so the next `es set` for that application can kill
+/// the processes listed in it. The rest of the name is the sanitized
+/// application name
+pub const PIDFILE_VARIABLE_PREFIX: &str = "ENV_SELECT_BACKGROUND_PIDFILE_";
+
+/// A process started by a background side effect. If this is dropped without
+/// being killed or detached, the process is killed, so an error partway
+/// through setup doesn't leave anything running.
+#[derive(Debug)]
+pub struct BackgroundProcess {
+    /// `None` once the process has been killed or detached
+    child: Option<Child>,
+}
+
+impl BackgroundProcess {
+    /// Kill the process and wait for it to exit. If it already exited on its
+    /// own, there's nothing to do.
+    pub async fn kill(mut self) -> anyhow::Result<()> {
+        let mut child = self.child.take().expect("Process already taken");
+        let pid = child.id();
+        if let Some(status) = child.try_status().with_context(|| {
+            format!("Error checking status of background process {pid}")
+        })? {
+            info!("Background process {pid} already exited with {status}");
+            return Ok(());
+        }
+        kill_pid(pid).await?;
+        // In case the process ignored the signal. This only kills the process
+        // itself, not its children
+        let _ = child.kill();
+        child.status().await?;
+        Ok(())
+    }
+
+    /// Leave the process running after we exit. Return its PID
+    fn detach(mut self) -> u32 {
+        self.child.take().expect("Process already taken").id()
+    }
+}
+
+impl From<Child> for BackgroundProcess {
+    fn from(child: Child) -> Self {
+        Self { child: Some(child) }
+    }
+}
+
+impl Drop for BackgroundProcess {
+    fn drop(&mut self) {
+        if let Some(child) = &mut self.child {
+            // Best effort; there's nobody to report an error to
+            let _ = smol::block_on(kill_pid(child.id()));
+            let _ = child.kill();
+        }
+    }
+}
+
+/// Processes started by the background side effects of a single side effect
+/// list, keyed by the index of the side effect that started each one
+#[derive(Debug, Default)]
+pub struct BackgroundProcesses(IndexMap<usize, BackgroundProcess>);
+
+impl BackgroundProcesses {
+    pub fn insert(&mut self, index: usize, child: Child) {
+        self.0.insert(index, child.into());
+    }
+
+    /// Take the process started by the side effect at the given index, if any
+    pub fn remove(&mut self, index: usize) -> Option<BackgroundProcess> {
+        self.0.shift_remove(&index)
+    }
+
+    /// Leave every process running after we exit. Return their PIDs
+    pub fn detach(self) -> Vec<u32> {
+        self.0
+            .into_values()
+            .map(BackgroundProcess::detach)
+            .collect()
+    }
+}
+
+/// File listing the PIDs of processes left running by `es set` for one
+/// application
+#[derive(Debug)]
+pub struct Pidfile {
+    path: PathBuf,
+}
+
+impl Pidfile {
+    /// Get the variable that holds an application's pidfile
+    pub fn variable(application: &Name) -> String {
+        application_variable(PIDFILE_VARIABLE_PREFIX, application)
+    }
+
+    /// Record PIDs in a new pidfile in the runtime directory. Each PID is
+    /// stored with its process's start time, so we can tell if the PID has
+    /// been reused by the time we kill it
+    pub fn write(pids: &[u32]) -> anyhow::Result<Self> {
+        let path = runtime_dir()?.join(format!(
+            "background-{}-{}.pid",
+            process::id(),
+            fastrand::u32(..)
+        ));
+        let contents = pids
+            .iter()
+            .map(|&pid| match process_start(pid) {
+                Some(start) => format!("{pid} {start}\n"),
+                None => format!("{pid}\n"),
+            })
+            .collect::<Vec<_>>()
+            .concat();
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        options
+            .open(&path)
+            .and_then(|mut file| file.write_all(contents.as_bytes()))
+            .with_context(|| {
+                format!("Error writing background PIDs to {path:?}")
+            })?;
+        Ok(Self { path })
+    }
+
+    /// Load the pidfile exported by a previous `es set` for an application,
+    /// if any
+    pub fn from_env(application: &Name) -> Option<Self> {
+        env::var_os(Self::variable(application))
+            .map(|path| Self { path: path.into() })
+    }
+
+    /// Load the pidfiles exported for every application, along with the
+    /// variable that holds each one
+    pub fn all_from_env() -> Vec<(String, Self)> {
+        env::vars_os()
+            .filter_map(|(variable, path)| {
+                let variable = variable.into_string().ok()?;
+                variable
+                    .starts_with(PIDFILE_VARIABLE_PREFIX)
+                    .then(|| (variable, Self { path: path.into() }))
+            })
+            .collect()
+    }
+
+    /// Kill every process listed in the file, then delete it. Processes that
+    /// already exited are skipped, as are PIDs that now belong to a different
+    /// process. If the file is already gone, there's nothing to kill.
+    pub fn kill(&self) -> anyhow::Result<()> {
+        let contents = match fs::read_to_string(&self.path) {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+                info!("Pidfile {:?} no longer exists", self.path);
+                return Ok(());
+            }
+            Err(error) => {
+                return Err(error).with_context(|| {
+                    format!("Error reading pidfile {:?}", self.path)
+                })
+            }
+        };
+        for (pid, start) in parse_pids(&contents)
+            .with_context(|| format!("Invalid pidfile {:?}", self.path))?
+        {
+            // If the process is gone but its group isn't, the PID can't have
+            // been reused yet, so it's still safe to kill the group
+            match (start, process_start(pid)) {
+                (Some(expected), Some(actual)) if expected != actual => {
+                    info!(
+                        "PID {pid} now belongs to a different process; \
+                        not killing it"
+                    );
+                }
+                _ => smol::block_on(kill_pid(pid))?,
+            }
+        }
+        fs::remove_file(&self.path)
+            .with_context(|| format!("Error deleting pidfile {:?}", self.path))
+    }
+
+    /// Value to store in the application's pidfile variable
+    pub fn to_variable(&self) -> String {
+        self.path.to_string_lossy().into_owned()
+    }
+}
+
+/// Parse PIDs from a pidfile, one per line. Each PID may be followed by the
+/// start time of its process
+fn parse_pids(contents: &str) -> anyhow::Result<Vec<(u32, Option<String>)>> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let (pid, start) = match line.split_once(' ') {
+                Some((pid, start)) => (pid, Some(start.to_owned())),
+                None => (line, None),
+            };
+            let pid = pid
+                .parse()
+                .with_context(|| format!("Invalid PID `{pid}`"))?;
+            Ok((pid, start))
+        })
+        .collect()
+}
+
+/// Get the start time of a process, to tell it apart from a later process
+/// that reuses its PID. This is in clock ticks since boot
+#[cfg(target_os = "linux")]
+fn process_start(pid: u32) -> Option<String> {
+    let stat = fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+    parse_stat_start(&stat)
+}
+
+/// Get the start time of a process, to tell it apart from a later process
+/// that reuses its PID
+#[cfg(all(unix, not(target_os = "linux")))]
+fn process_start(pid: u32) -> Option<String> {
+    let output = process::Command::new("ps")
+        .args(["-o", "lstart=", "-p", &pid.to_string()])
+        .output()
+        .ok()?;
+    let start = String::from_utf8(output.stdout).ok()?;
+    let start = start.trim();
+    (!start.is_empty()).then(|| start.to_owned())
+}
+
+#[cfg(not(unix))]
+fn process_start(_: u32) -> Option<String> {
+    None
+}
+
+/// Get the start time from the contents of `/proc/<pid>/stat`. The process
+/// name can contain spaces and parentheses, so count fields after the *last*
+/// closing parenthesis
+#[cfg(any(target_os = "linux", test))]
+fn parse_stat_start(stat: &str) -> Option<String> {
+    let (_, fields) = stat.rsplit_once(')')?;
+    // Fields after the name start at #3 (state), and start time is #22
+    fields.split_whitespace().nth(19).map(String::from)
+}
+
+/// Kill a background process, along with any children it started. On Unix,
+/// background processes lead their own process group, so we kill the whole
+/// group. If it fails, assume the process already exited, since there's no
+/// portable way to tell the difference
+async fn kill_pid(pid: u32) -> anyhow::Result<()> {
+    info!("Killing background process {pid}");
+    #[cfg(windows)]
+    let mut executable =
+        ("taskkill", ["/PID", &pid.to_string(), "/T", "/F"]).executable();
+    #[cfg(not(windows))]
+    let mut executable = ("kill", ["--", &format!("-{pid}")]).executable();
+    let output = executable.output().await?;
+    if !output.status.success() {
+        info!("Background process {pid} already exited");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::assert_result;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::empty("", Ok(vec![]))]
+    #[case::pids("123\n456\n\n", Ok(vec![(123, None), (456, None)]))]
+    #[case::start(
+        "123 4567\n456 Mon Jan  1 00:00:00 2024\n",
+        Ok(vec![
+            (123, Some("4567".into())),
+            (456, Some("Mon Jan  1 00:00:00 2024".into())),
+        ]),
+    )]
+    #[case::invalid(
+        "123\nabc 4567\n",
+        Err("Invalid PID `abc`: invalid digit found in string")
+    )]
+    fn test_parse_pids(
+        #[case] contents: &str,
+        #[case] expected: Result<Vec<(u32, Option<String>)>, &str>,
+    ) {
+        let result = parse_pids(contents);
+        assert_result(result, expected);
+    }
+
+    /// Killing a process works whether or not it's still running
+    #[rstest]
+    #[case::running("sleep 10")]
+    #[case::exited("true")]
+    fn test_kill(#[case] command: &str) {
+        let mut processes = BackgroundProcesses::default();
+        processes
+            .insert(0, ("sh", ["-c", command]).executable().spawn().unwrap());
+        let process = processes.remove(0).unwrap();
+        smol::block_on(process.kill()).unwrap();
+    }
+
+    /// Processes listed in a pidfile are killed, then the file is deleted
+    #[test]
+    fn test_pidfile() {
+        let mut processes = BackgroundProcesses::default();
+        processes.insert(
+            0,
+            ("sh", ["-c", "sleep 10"]).executable().spawn().unwrap(),
+        );
+        let running = processes.detach()[0];
+        // Include a process that already exited
+        let mut exited = ("sh", ["-c", "true"]).executable().spawn().unwrap();
+        smol::block_on(exited.status()).unwrap();
+        let pidfile = Pidfile::write(&[running, exited.id()]).unwrap();
+        pidfile.kill().unwrap();
+        assert!(!pidfile.path.exists());
+        // Killing again is a no-op
+        pidfile.kill().unwrap();
+    }
+
+    /// A PID that now belongs to a different process isn't killed
+    #[test]
+    fn test_pidfile_reused() {
+        let mut processes = BackgroundProcesses::default();
+        processes.insert(
+            0,
+            ("sh", ["-c", "sleep 10"]).executable().spawn().unwrap(),
+        );
+        let pid = processes.detach()[0];
+        let path = runtime_dir()
+            .unwrap()
+            .join(format!("es-test-pidfile-reused-{pid}.pid"));
+        fs::write(&path, format!("{pid} not-its-start-time\n")).unwrap();
+        Pidfile { path: path.clone() }.kill().unwrap();
+        assert!(!path.exists());
+        assert!(process_start(pid).is_some(), "Process should be running");
+        smol::block_on(kill_pid(pid)).unwrap();
+    }
+
+    #[test]
+    fn test_parse_stat_start() {
+        let stat = "123 (my (weird) name) S 1 123 123 0 -1 4194560 100 0 0 \
+            0 0 0 0 0 20 0 1 0 98765 1000 100";
+        assert_eq!(parse_stat_start(stat), Some("98765".into()));
+        assert_eq!(parse_stat_start("123 (name) S 1"), None);
+    }
+}
@@ -0,0 +1,140 @@
+use crate::{
+    commands::{CommandContext, Selection, SubcommandTrait},
+    completions::complete_profile,
+    config::Name,
+    environment::{Environment, EnvironmentDiff},
+};
+use clap::{Parser, ValueEnum};
+use clap_complete::ArgValueCompleter;
+use serde::Serialize;
+use std::fmt::Write;
+
+/// Compare the environments of two profiles of an application
+///
+/// Both profiles are resolved without running side effects. Variables only
+/// set by one profile are listed, followed by variables that both set to
+/// different values. Sensitive values are masked.
+#[derive(Clone, Debug, Parser)]
+pub struct DiffCommand {
+    #[command(flatten)]
+    selection: Selection,
+
+    /// Profile to compare against, from the same application
+    #[clap(
+        value_name = "OTHER_PROFILE",
+        required_unless_present = "current",
+        add = ArgValueCompleter::new(complete_profile),
+    )]
+    other: Option<Name>,
+
+    /// Compare against the current environment of your shell, instead of
+    /// another profile. Only variables that the profile sets or unsets are
+    /// compared
+    #[clap(long, conflicts_with = "other")]
+    current: bool,
+
+    /// Show sensitive values, instead of masking them
+    #[clap(long)]
+    reveal: bool,
+
+    /// Output format
+    #[clap(long, value_enum, default_value_t)]
+    format: DiffFormat,
+}
+
+#[derive(Copy, Clone, Debug, Default, ValueEnum)]
+enum DiffFormat {
+    /// A section for each kind of difference
+    #[default]
+    Table,
+    /// An object with `only_left`, `only_right`, and `changed` keys
+    Json,
+}
+
+/// JSON output, naming what was compared on each side
+#[derive(Serialize)]
+struct DiffOutput<'a> {
+    left: &'a str,
+    right: &'a str,
+    #[serde(flatten)]
+    diff: &'a EnvironmentDiff,
+}
+
+impl SubcommandTrait for DiffCommand {
+    fn execute(self, context: CommandContext) -> anyhow::Result<()> {
+        let (reference, profile) =
+            context.select_profile_reference(&self.selection)?;
+        let environment = context.resolve_environment(&profile)?;
+        let (other_label, other_environment) = match self.other {
+            Some(other) => {
+                let selection = Selection {
+                    application: reference.application().cloned(),
+                    profile: Some(other),
+                    multi: false,
+                    last: false,
+                    strict_deprecations: self.selection.strict_deprecations,
+                };
+                let (other_reference, other_profile) =
+                    context.select_profile_reference(&selection)?;
+                (
+                    other_reference.to_string(),
+                    context.resolve_environment(&other_profile)?,
+                )
+            }
+            None => (
+                "current".to_owned(),
+                Environment::from_current(
+                    environment.variables(),
+                    context.config()?.sensitive_patterns(),
+                ),
+            ),
+        };
+
+        let label = reference.to_string();
+        let diff =
+            environment.diff(&other_environment, context.masking(self.reveal)?);
+        let content = match self.format {
+            DiffFormat::Table => format_table(&label, &other_label, &diff),
+            DiffFormat::Json => serde_json::to_string_pretty(&DiffOutput {
+                left: &label,
+                right: &other_label,
+                diff: &diff,
+            })?,
+        };
+        println!("{}", content.trim_end());
+        Ok(())
+    }
+}
+
+/// Format a diff as a section for each kind of difference. Empty sections are
+/// left out
+fn format_table(left: &str, right: &str, diff: &EnvironmentDiff) -> String {
+    if diff.is_empty() {
+        return "No differences".to_owned();
+    }
+
+    let mut output = String::new();
+    let mut line = |line: String| {
+        writeln!(output, "{line}").expect("string writing is infallible")
+    };
+    for (side, variables) in
+        [(left, &diff.only_left), (right, &diff.only_right)]
+    {
+        if !variables.is_empty() {
+            line(format!("Only in {side}:"));
+            for (variable, value) in variables {
+                line(format!("  {variable} = {value}"));
+            }
+        }
+    }
+    if !diff.changed.is_empty() {
+        line(format!("Changed ({left} -> {right}):"));
+        for (variable, change) in &diff.changed {
+            line(format!(
+                "  {variable} = {} -> {}",
+                change.left, change.right
+            ));
+        }
+    }
+    output
+}
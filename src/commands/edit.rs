@@ -0,0 +1,102 @@
+use crate::{
+    commands::{CommandContext, SubcommandTrait},
+    config::Config,
+    execute::IntoExecutable,
+};
+use anyhow::{anyhow, bail, Context};
+use clap::Parser;
+use log::info;
+use std::{env, fs, path::Path};
+
+/// Editor to use if neither $VISUAL nor $EDITOR is set
+const DEFAULT_EDITOR: &str = "vi";
+
+/// Contents of a newly created global config file
+const GLOBAL_TEMPLATE: &str = "\
+# Personal profiles, available in every directory with the lowest precedence.
+# See https://env-select.lucaspickering.me/book/user_guide/inheritance.html
+
+# [applications.example.profiles.dev]
+# variables = {EXAMPLE_URL = \"http://localhost:3000\"}
+";
+
+/// Open the nearest config file in your editor. Once the editor exits, the
+/// config is loaded again so any errors are reported immediately
+///
+/// If a config file is given with `--config` or `ES_CONFIG_FILE`, that file is
+/// edited instead.
+#[derive(Clone, Debug, Parser)]
+pub struct EditCommand {
+    /// Edit your global config file instead. If it doesn't exist yet, it's
+    /// created from a template
+    #[clap(long)]
+    global: bool,
+}
+
+impl SubcommandTrait for EditCommand {
+    fn execute(self, context: CommandContext) -> anyhow::Result<()> {
+        let override_file =
+            Config::override_file(context.config_file.as_deref())?;
+        let path = if self.global {
+            let path = Config::global_file().ok_or_else(|| {
+                anyhow!("Unable to find a location for the global config file")
+            })?;
+            if !path.exists() {
+                create_global_file(&path)?;
+            }
+            path
+        } else if let Some(path) = &override_file {
+            path.clone()
+        } else {
+            Config::nearest_file()?.ok_or_else(|| {
+                anyhow!(
+                    "No config file found in the current directory or its \
+                    ancestors"
+                )
+            })?
+        };
+
+        edit(&path)?;
+
+        // When scanning, load *everything*, not just the edited file, because
+        // errors such as unknown parents depend on the other files. If a
+        // config file was given, it's the only one that gets loaded, so
+        // validate the edited file the same way
+        let validate_file = override_file.map(|_| path.as_path());
+        Config::load(validate_file, false).with_context(|| {
+            format!("{path:?} was saved, but the config is invalid")
+        })?;
+        println!("{} is valid", path.display());
+        Ok(())
+    }
+}
+
+/// Create the global config file from a template, along with its directory
+fn create_global_file(path: &Path) -> anyhow::Result<()> {
+    info!("Creating global config file {path:?}");
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Error creating directory {parent:?}"))?;
+    }
+    fs::write(path, GLOBAL_TEMPLATE)
+        .with_context(|| format!("Error creating {path:?}"))
+}
+
+/// Open a file in the user's editor, and wait for the editor to exit. The
+/// editor variable can include arguments, e.g. `code --wait`
+fn edit(path: &Path) -> anyhow::Result<()> {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| DEFAULT_EDITOR.to_owned());
+    let mut words = editor.split_whitespace().map(String::from);
+    let program = words
+        .next()
+        .ok_or_else(|| anyhow!("Editor command is empty"))?;
+    let arguments: Vec<String> =
+        words.chain([path.display().to_string()]).collect();
+    let status = smol::block_on((program, arguments).executable().status())?;
+    if !status.success() {
+        bail!("Editor `{editor}` exited with {status}");
+    }
+    Ok(())
+}
@@ -0,0 +1,116 @@
+use crate::{
+    commands::{
+        profile_cwd, run::ISOLATED_KEEP, CommandContext, Selection,
+        SubcommandTrait,
+    },
+    environment::{Base, VariableOverride},
+    execute::{Executable, IntoExecutable},
+    kubernetes::KubernetesRestore,
+};
+use clap::Parser;
+use log::warn;
+
+/// Replace env-select with a program in an augmented environment
+///
+/// Unlike `es run`, the program is executed directly instead of through your
+/// shell, and it takes over env-select's process, so it's a direct child of
+/// whatever started env-select. Setup side effects run first, but nothing is
+/// left to tear them down afterward, or to restore kubectl settings.
+/// Background processes are left running. On Windows, the program is run as
+/// a child process instead, and teardown is skipped all the same.
+#[derive(Clone, Debug, Parser)]
+pub struct ExecCommand {
+    #[command(flatten)]
+    selection: Selection,
+
+    /// Run the program with *only* the profile's variables, instead of
+    /// adding them to your shell's environment. A few essentials (PATH, HOME,
+    /// TERM, USER, and LANG) are kept. Side effects still run in your
+    /// shell's environment
+    #[clap(long)]
+    isolated: bool,
+
+    /// Keep this variable from your shell in an isolated environment. Can be
+    /// given multiple times
+    #[clap(long, value_name = "VARIABLE", requires = "isolated")]
+    keep: Vec<String>,
+
+    /// Don't run the profile's side effects
+    #[clap(long)]
+    no_side_effects: bool,
+
+    /// Set a variable on top of the profile, e.g. `-e DATABASE_URL=...`.
+    /// PATH is prepended to, like in a profile. Can be given multiple times
+    #[clap(long = "env", short = 'e', value_name = "VARIABLE=VALUE")]
+    overrides: Vec<VariableOverride>,
+
+    /// Program to execute, followed by its arguments. The program is found
+    /// in PATH, after the profile is applied. Arguments are passed as-is,
+    /// without any shell expansion
+    #[arg(required = true, last = true)]
+    command: Vec<String>,
+}
+
+impl SubcommandTrait for ExecCommand {
+    fn execute(self, mut context: CommandContext) -> anyhow::Result<()> {
+        context.overrides = self.overrides;
+        if self.isolated {
+            context.base = Base::Isolated {
+                keep: ISOLATED_KEEP
+                    .iter()
+                    .map(|variable| (*variable).to_owned())
+                    .chain(self.keep)
+                    .collect(),
+            };
+        }
+        let profile = context.select_profile(&self.selection)?;
+        let cwd = profile_cwd(&profile)?;
+        // There's nothing left to restore kubectl afterward, so the switch
+        // sticks, like with `es set`
+        KubernetesRestore::switch(&profile)?;
+        let loaded =
+            context.load_environment(&profile, !self.no_side_effects)?;
+
+        let (program, arguments) = self
+            .command
+            .split_first()
+            .expect("clap requires at least one token");
+        let mut executable: Executable = (program, arguments).executable();
+        if let Some(cwd) = cwd {
+            executable.current_dir(cwd);
+        }
+        if let Base::Isolated { keep } = &context.base {
+            executable.env_clear(keep);
+        }
+        executable.environment(&loaded.environment);
+
+        // Background processes would be killed when dropped, so let them go
+        // first. They outlive us either way
+        let pids = loaded.detach();
+        if !pids.is_empty() {
+            warn!(
+                "Left background processes running: {}",
+                pids.iter()
+                    .map(u32::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+
+        // This only returns if the program couldn't be started
+        #[cfg(unix)]
+        {
+            Err(executable.exec())
+        }
+        #[cfg(not(unix))]
+        {
+            let status =
+                smol::block_on(executable.status_forwarding_signals())?;
+            if status.success() {
+                Ok(())
+            } else {
+                Err(crate::error::ExitCodeError::from(&status).into())
+            }
+        }
+    }
+}
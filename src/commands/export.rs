@@ -0,0 +1,142 @@
+use crate::{
+    commands::{CommandContext, Selection, SubcommandTrait},
+    config::Source,
+    environment::Environment,
+    shell::{Shell, ShellKind},
+    source_file,
+};
+use anyhow::{bail, Context};
+use clap::{Parser, ValueEnum};
+use indexmap::IndexMap;
+use std::{fmt::Write, path::PathBuf};
+
+/// Print a profile's environment in a format other programs can read, such as
+/// a dotenv file or docker arguments. Unlike `show env`, sensitive values are
+/// *not* masked. Side effects are not run.
+#[derive(Clone, Debug, Parser)]
+pub struct ExportCommand {
+    #[command(flatten)]
+    selection: Selection,
+    /// Output format. Unset variables are omitted from every format except
+    /// `shell`
+    #[clap(long, value_enum, default_value_t)]
+    format: ExportFormat,
+    /// Write to this file instead of stdout
+    #[clap(long, short)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Copy, Clone, Debug, Default, ValueEnum)]
+enum ExportFormat {
+    /// `VARIABLE='value'` lines
+    #[default]
+    Dotenv,
+    /// A flat object of variables to values
+    Json,
+    /// `--env VARIABLE=value` arguments, escaped for `xargs docker run`
+    Docker,
+    /// Commands to export the variables in the current shell, as passed to
+    /// `source` by `es set`
+    Shell,
+    /// Bash for direnv to evaluate, which also watches the config files. Use
+    /// it via `use env_select` from `es init --direnv`
+    Direnv,
+}
+
+impl SubcommandTrait for ExportCommand {
+    fn execute(self, mut context: CommandContext) -> anyhow::Result<()> {
+        if let ExportFormat::Direnv = self.format {
+            // direnv can't take input, and it shows everything we write to
+            // stderr in its log
+            let selection = &self.selection;
+            if selection.application.is_none()
+                || (selection.profile.is_none() && !selection.last)
+            {
+                bail!(
+                    "direnv can't show prompts; pass an application and \
+                    profile, e.g. `use env_select server dev`"
+                );
+            }
+            context.quiet = true;
+        }
+
+        let profile = context.select_profile(&self.selection)?;
+        let environment = context.resolve_environment(&profile)?;
+        let content = match self.format {
+            ExportFormat::Dotenv => environment.to_dotenv_unmasked()?,
+            ExportFormat::Json => {
+                let object: IndexMap<&str, &str> =
+                    environment.iter_unmasked().collect();
+                serde_json::to_string_pretty(&object)? + "\n"
+            }
+            ExportFormat::Docker => format_docker(&environment),
+            ExportFormat::Shell => context.shell.export(&environment),
+            ExportFormat::Direnv => {
+                format_direnv(&context.config()?.sources, &environment)
+            }
+        };
+
+        match self.output {
+            // Values aren't masked, so keep them away from other users
+            Some(path) => source_file::write(&path, &content)
+                .with_context(|| format!("Error writing to file {path:?}"))?,
+            None => print!("{content}"),
+        }
+        Ok(())
+    }
+}
+
+/// Format an environment as `--env VARIABLE=value` docker arguments, one pair
+/// per line. Whitespace, quotes, and backslashes are escaped with a backslash,
+/// which is how `xargs` expects them
+fn format_docker(environment: &Environment) -> String {
+    let mut output = String::new();
+    for (variable, value) in environment.iter_unmasked() {
+        let mut argument = String::new();
+        for c in format!("{variable}={value}").chars() {
+            if c.is_whitespace() || matches!(c, '\'' | '"' | '\\') {
+                argument.push('\\');
+            }
+            argument.push(c);
+        }
+        writeln!(output, "--env {argument}")
+            .expect("string writing is infallible");
+    }
+    output
+}
+
+/// Format an environment as bash for direnv to evaluate. Each config file is
+/// watched, so direnv reloads the environment when the config changes
+fn format_direnv(sources: &[Source], environment: &Environment) -> String {
+    let bash = Shell::from_kind(ShellKind::Bash);
+    let mut output = String::new();
+    for source in sources {
+        writeln!(
+            output,
+            "watch_file {}",
+            bash.escape(&source.path.display().to_string())
+        )
+        .expect("string writing is infallible");
+    }
+    output + &bash.export(environment)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::plain("plain", "--env VAR=plain\n")]
+    #[case::empty("", "--env VAR=\n")]
+    #[case::space("two words", "--env VAR=two\\ words\n")]
+    #[case::quotes("it's \"quoted\"", "--env VAR=it\\'s\\ \\\"quoted\\\"\n")]
+    #[case::backslash("C:\\dir", "--env VAR=C:\\\\dir\n")]
+    #[case::newline("line1\nline2", "--env VAR=line1\\\nline2\n")]
+    fn test_format_docker(#[case] value: &str, #[case] expected: &str) {
+        let mut environment = Environment::default();
+        environment.insert_internal("VAR", value.into());
+        assert_eq!(format_docker(&environment), expected);
+    }
+}
@@ -0,0 +1,16 @@
+use crate::{
+    commands::{CommandContext, SubcommandTrait},
+    history,
+};
+use clap::Parser;
+
+/// Forget which profiles were last selected, in every directory. Afterwards,
+/// `--last` has nothing to reuse, and prompts start on the first profile
+#[derive(Clone, Debug, Parser)]
+pub struct ForgetCommand {}
+
+impl SubcommandTrait for ForgetCommand {
+    fn execute(self, _: CommandContext) -> anyhow::Result<()> {
+        history::clear()
+    }
+}
@@ -0,0 +1,172 @@
+use crate::{
+    commands::{CommandContext, SubcommandTrait},
+    config::{Config, Name, ProfileReference, FILE_NAME},
+};
+use anyhow::{anyhow, bail, Context};
+use clap::Parser;
+use std::{env, fs, path::PathBuf};
+use toml_edit::{DocumentMut, InlineTable, Item, Table, Value};
+
+/// Words that suggest a variable holds a secret, when they appear as a
+/// component of its name. Components are separated by anything other than
+/// letters and digits
+const SENSITIVE_WORDS: &[&str] = &[
+    "AUTH",
+    "CREDENTIAL",
+    "CREDENTIALS",
+    "KEY",
+    "PASS",
+    "PASSWD",
+    "PASSWORD",
+    "PRIVATE",
+    "SECRET",
+    "TOKEN",
+];
+
+/// Convert a dotenv file into a profile, and add it to the nearest config
+/// file. If there is no config file, one is created in the current directory.
+/// Variables that look like secrets are marked sensitive.
+#[derive(Clone, Debug, Parser)]
+pub struct ImportCommand {
+    /// Application to add the profile to. Created if it doesn't exist
+    application: Name,
+    /// Name of the new profile
+    profile: Name,
+    /// Path to the dotenv file to import
+    file: PathBuf,
+    /// Replace the profile if it already exists in the config file
+    #[clap(long)]
+    force: bool,
+}
+
+impl SubcommandTrait for ImportCommand {
+    fn execute(self, context: CommandContext) -> anyhow::Result<()> {
+        let content = fs::read_to_string(&self.file)
+            .with_context(|| format!("Error reading file {:?}", self.file))?;
+        let variables = dotenv_parser::parse_dotenv(&content)
+            .map_err(|error| anyhow!(error))
+            .with_context(|| {
+                format!("Error parsing dotenv file {:?}", self.file)
+            })?;
+
+        let path = match context.config_file.clone() {
+            Some(path) => path,
+            None => match Config::nearest_file()? {
+                Some(path) => path,
+                None => env::current_dir()?.join(FILE_NAME),
+            },
+        };
+        // Missing file is fine, we'll create it
+        let existing = if path.exists() {
+            fs::read_to_string(&path)
+                .with_context(|| format!("Error reading file {path:?}"))?
+        } else {
+            String::new()
+        };
+        let mut document: DocumentMut = existing
+            .parse()
+            .with_context(|| format!("Error parsing config file {path:?}"))?;
+
+        let reference =
+            ProfileReference::from((self.application, self.profile));
+        let profiles = profiles_table(&mut document, &reference)?;
+        let profile_key = reference.profile().as_str();
+        if profiles.contains_key(profile_key) && !self.force {
+            bail!(
+                "Profile `{reference}` already exists in {path:?}; pass \
+                `--force` to replace it"
+            );
+        }
+
+        let mut variables_table = Table::new();
+        // Render as `variables.NAME = ...` under the profile's header
+        variables_table.set_dotted(true);
+        let mut sensitive = Vec::new();
+        for (variable, value) in &variables {
+            let value = if is_sensitive(variable) {
+                sensitive.push(variable.as_str());
+                let mut table = InlineTable::new();
+                table.insert("type", "literal".into());
+                table.insert("value", value.as_str().into());
+                table.insert("sensitive", true.into());
+                Value::InlineTable(table)
+            } else {
+                value.as_str().into()
+            };
+            variables_table.insert(variable, Item::Value(value));
+        }
+        let mut profile_table = Table::new();
+        profile_table.insert("variables", Item::Table(variables_table));
+        profiles.insert(profile_key, Item::Table(profile_table));
+
+        fs::write(&path, document.to_string())
+            .with_context(|| format!("Error writing config file {path:?}"))?;
+        println!(
+            "Imported {} variables into profile `{reference}` in {}",
+            variables.len(),
+            path.display()
+        );
+        if !sensitive.is_empty() {
+            println!("Marked as sensitive: {}", sensitive.join(", "));
+        }
+        Ok(())
+    }
+}
+
+/// Get the `profiles` table of the referenced profile's application, creating
+/// it and any parent tables as needed. Tables that only exist to hold other
+/// tables are implicit, so they don't get their own headers
+pub(super) fn profiles_table<'a>(
+    document: &'a mut DocumentMut,
+    reference: &ProfileReference,
+) -> anyhow::Result<&'a mut Table> {
+    let application = reference
+        .application()
+        .expect("Profile reference is qualified")
+        .as_str();
+    let mut table = document.as_table_mut();
+    for (key, description) in [
+        ("applications", "applications".to_owned()),
+        (application, format!("applications.{application}")),
+        ("profiles", format!("applications.{application}.profiles")),
+    ] {
+        let mut implicit = Table::new();
+        implicit.set_implicit(true);
+        table = table
+            .entry(key)
+            .or_insert(Item::Table(implicit))
+            .as_table_mut()
+            .ok_or_else(|| {
+                anyhow!(
+                    "`{description}` in the config file must be a table, \
+                    e.g. `[{description}]`"
+                )
+            })?;
+    }
+    Ok(table)
+}
+
+/// Guess if a variable holds a secret, based on its name
+fn is_sensitive(variable: &str) -> bool {
+    variable
+        .to_ascii_uppercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .any(|word| SENSITIVE_WORDS.contains(&word))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::plain("DATABASE_HOST", false)]
+    #[case::password("DB_PASSWORD", true)]
+    #[case::lowercase("api_key", true)]
+    #[case::whole_word("SECRET", true)]
+    #[case::substring("KEYBOARD_LAYOUT", false)]
+    #[case::dotted("aws.secret", true)]
+    fn test_is_sensitive(#[case] variable: &str, #[case] expected: bool) {
+        assert_eq!(is_sensitive(variable), expected);
+    }
+}
@@ -1,5 +1,6 @@
 use crate::{
     commands::{CommandContext, SubcommandTrait},
+    shell::{Shell, ShellKind},
     Args, COMMAND_NAME,
 };
 use anyhow::Context;
@@ -11,6 +12,11 @@ use std::env;
 /// to `source` as part of your shell startup.
 #[derive(Clone, Debug, Parser)]
 pub struct InitCommand {
+    /// Print a script for direnv instead, which defines
+    /// `use env_select <application> <profile>` for `.envrc` files. Load it
+    /// from your direnvrc, e.g. `eval "$(es init --direnv)"`
+    #[clap(long)]
+    direnv: bool,
     /// Don't include completion script in output
     #[clap(long, hide = true)] // Only for testing
     no_completions: bool,
@@ -18,6 +24,11 @@ pub struct InitCommand {
 
 impl SubcommandTrait for InitCommand {
     fn execute(self, context: CommandContext) -> anyhow::Result<()> {
+        if self.direnv {
+            print!("{}", Shell::direnv_script()?);
+            return Ok(());
+        }
+
         let script = context
             .shell
             .init_script()
@@ -26,8 +37,10 @@ impl SubcommandTrait for InitCommand {
 
         // Print the command to enable shell completions as well. CompleteEnv
         // doesn't expose the inner machinery that would allow us to print the
-        // line directly, so we have to enable the env var that triggers it
-        if !self.no_completions {
+        // line directly, so we have to enable the env var that triggers it.
+        // POSIX sh has no programmable completion, so there's nothing to print
+        if !self.no_completions && !matches!(context.shell.kind, ShellKind::Sh)
+        {
             env::set_var("COMPLETE", context.shell.kind.to_string());
             CompleteEnv::with_factory(Args::command)
                 .try_complete([COMMAND_NAME], None)?;
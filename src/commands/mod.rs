@@ -3,45 +3,93 @@
 //! in this root module.
 
 use crate::{
+    background::BackgroundProcesses,
     commands::{
-        init::InitCommand, run::RunCommand, set::SetCommand, show::ShowCommand,
+        diff::DiffCommand, edit::EditCommand, exec::ExecCommand,
+        export::ExportCommand, forget::ForgetCommand, import::ImportCommand,
+        init::InitCommand, new::NewCommand, run::RunCommand, set::SetCommand,
+        show::ShowCommand, unset::UnsetCommand,
     },
     completions::{complete_application, complete_profile},
-    config::{Config, Name, Profile},
-    console::prompt_options,
-    environment::Environment,
-    execute::apply_side_effects,
-    shell::{Shell, ShellKind},
-    GlobalArgs,
+    config::{
+        Application, Config, Masking, Name, Profile, ProfileReference,
+        COMPOSE_SEPARATOR,
+    },
+    console::{print_hint, prompt_multiple, prompt_options, PromptMode},
+    environment::{Base, Environment, ResolveOptions, VariableOverride},
+    execute::{apply_side_effects, revert_side_effects},
+    history,
+    shell::{Shell, ShellArg},
+    source_file, GlobalArgs,
 };
+use anyhow::{anyhow, bail, Context};
 use clap::Subcommand;
 use clap_complete::ArgValueCompleter;
+use log::warn;
 use smol::lock::OnceCell;
-use std::path::PathBuf;
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+const WEBSITE: &str = "https://env-select.lucaspickering.me";
 
+mod diff;
+mod edit;
+mod exec;
+mod export;
+mod forget;
+mod import;
 mod init;
+mod new;
 mod run;
 mod set;
 mod show;
+mod unset;
 
 /// Subcommand to execute
 #[derive(Clone, Debug, Subcommand)]
 pub enum Commands {
+    Diff(DiffCommand),
+    Edit(EditCommand),
+    Exec(ExecCommand),
+    Export(ExportCommand),
+    Forget(ForgetCommand),
+    Import(ImportCommand),
     Init(InitCommand),
+    New(NewCommand),
     Run(RunCommand),
     Set(SetCommand),
     Show(ShowCommand),
+    Unset(UnsetCommand),
 }
 
 impl Commands {
     /// Execute a non-TUI command
     pub fn execute(self, global: GlobalArgs) -> anyhow::Result<()> {
-        let context = CommandContext::new(global.source_file, global.shell)?;
+        let context = CommandContext::new(
+            global.source_file,
+            global.config,
+            global.ignore_invalid_config,
+            global.shell,
+            !global.no_cache,
+            global.non_interactive,
+            global.plain_prompt,
+        )?;
         match self {
+            Self::Diff(command) => command.execute(context),
+            Self::Edit(command) => command.execute(context),
+            Self::Exec(command) => command.execute(context),
+            Self::Export(command) => command.execute(context),
+            Self::Forget(command) => command.execute(context),
+            Self::Import(command) => command.execute(context),
             Self::Init(command) => command.execute(context),
+            Self::New(command) => command.execute(context),
             Self::Run(command) => command.execute(context),
             Self::Set(command) => command.execute(context),
             Self::Show(command) => command.execute(context),
+            Self::Unset(command) => command.execute(context),
         }
     }
 }
@@ -64,42 +112,96 @@ pub struct Selection {
     pub application: Option<Name>,
 
     /// Profile to select. If omitted, an interactive prompt will be shown to
-    /// select between possible options.
+    /// select between possible options. Join several profiles with `+` to
+    /// combine them, e.g. `dev+debug`. Later profiles take precedence
     #[clap(add = ArgValueCompleter::new(complete_profile))]
     pub profile: Option<Name>,
+
+    /// Prompt for any number of profiles to combine, instead of one
+    #[clap(long, conflicts_with_all = ["profile", "last"])]
+    pub multi: bool,
+
+    /// Select the profile that was last selected for the application in the
+    /// current directory, instead of prompting
+    #[clap(long, conflicts_with = "profile")]
+    pub last: bool,
+
+    /// Fail if the selected profile is deprecated, instead of printing a
+    /// warning. Useful for CI
+    #[clap(long)]
+    pub strict_deprecations: bool,
+}
+
+/// An environment built from a profile, plus processes left running by the
+/// profile's background side effects. If this is dropped without tearing
+/// down, the processes are killed.
+struct LoadedEnvironment {
+    environment: Environment,
+    pre_export: BackgroundProcesses,
+    post_export: BackgroundProcesses,
 }
 
 /// Data container with helper methods for all CLI subcommands
 struct CommandContext {
     source_file: Option<PathBuf>,
+    /// Config file to load instead of scanning for config files
+    config_file: Option<PathBuf>,
+    /// Skip config files that fail to parse, instead of failing
+    ignore_invalid_config: bool,
     /// Config is lazy loaded, so it doesn't have to be loaded for subcommands
     /// that don't need it
     config: OnceCell<Config>,
     shell: Shell,
+    /// Should value sources read from and write to the value cache?
+    use_cache: bool,
+    /// How to prompt the user to select an application or profile
+    prompt_mode: PromptMode,
+    /// Suppress hints, because output is being consumed by another program
+    quiet: bool,
+    /// Environment that resolved environments will be applied on top of
+    base: Base,
+    /// Variables from the command line, applied on top of resolved
+    /// environments
+    overrides: Vec<VariableOverride>,
 }
 
 impl CommandContext {
     fn new(
         source_file: Option<PathBuf>,
-        shell_kind: Option<ShellKind>,
+        config_file: Option<PathBuf>,
+        ignore_invalid_config: bool,
+        shell: Option<ShellArg>,
+        use_cache: bool,
+        non_interactive: bool,
+        plain_prompt: bool,
     ) -> anyhow::Result<Self> {
         // This handler will put the terminal cursor back if the user ctrl-c's
         // during the interactive dialogue
         // https://github.com/mitsuhiko/dialoguer/issues/77
+        // Handling SIGINT also means we aren't killed by it, so `es run` can
+        // wait for its command to exit and then tear down
         ctrlc::set_handler(move || {
             let term = dialoguer::console::Term::stdout();
             let _ = term.show_cursor();
         })?;
 
-        let shell = match shell_kind {
-            Some(kind) => Shell::from_kind(kind),
-            None => Shell::detect()?,
+        let shell = match shell {
+            Some(ShellArg::Kind(kind)) => Shell::from_kind(kind),
+            Some(ShellArg::AutoStrict) => Shell::detect(true)?,
+            None => Shell::detect(false)?,
         };
 
         Ok(Self {
             source_file,
+            config_file,
+            ignore_invalid_config,
             config: OnceCell::new(),
             shell,
+            use_cache,
+            prompt_mode: PromptMode::detect(non_interactive, plain_prompt),
+            quiet: false,
+            base: Base::Inherit,
+            overrides: Vec::new(),
         })
     }
 
@@ -112,38 +214,337 @@ impl CommandContext {
     fn config(&self) -> anyhow::Result<&Config> {
         // TODO replace with std::cell::OnceCell after get_or_try_init is stable
         // https://github.com/rust-lang/rust/issues/109737
-        self.config.get_or_try_init_blocking(Config::load)
+        self.config.get_or_try_init_blocking(|| {
+            Config::load(
+                self.config_file.as_deref(),
+                self.ignore_invalid_config,
+            )
+        })
+    }
+
+    /// How sensitive values should be shown to the user. `--reveal` takes
+    /// precedence over the `masking` setting
+    fn masking(&self, reveal: bool) -> anyhow::Result<Masking> {
+        if reveal {
+            Ok(Masking::Reveal)
+        } else {
+            Ok(self.config()?.masking())
+        }
+    }
+
+    /// Load config without resolving inheritance, i.e. as it's written in the
+    /// config files. This isn't cached, since it's rarely needed
+    fn unresolved_config(&self) -> anyhow::Result<Config> {
+        Config::load_unresolved(
+            self.config_file.as_deref(),
+            self.ignore_invalid_config,
+        )
     }
 
     /// Select an application+profile, based on user input. For both application
     /// and profile, if a default name was given, then that will be used.
     /// Otherwise, the user will be prompted to select an option via  TUI.
+    /// If the profile is deprecated, print a warning, or fail if deprecations
+    /// are strict.
     fn select_profile<'a>(
         &'a self,
         selection: &'a Selection,
-    ) -> anyhow::Result<&'a Profile> {
-        let application = prompt_options(
+    ) -> anyhow::Result<Cow<'a, Profile>> {
+        Ok(self.select_profile_reference(selection)?.1)
+    }
+
+    /// Get the application from the selection, or prompt the user to pick
+    /// one if it wasn't given
+    fn select_application<'a>(
+        &'a self,
+        selection: &Selection,
+    ) -> anyhow::Result<(&'a Name, &'a Application)> {
+        prompt_options(
             &self.config()?.applications,
             selection.application.as_ref(),
-        )?;
-        prompt_options(&application.profiles, selection.profile.as_ref())
+            None,
+            self.prompt_mode,
+        )
     }
 
-    /// Build an [Environment] from a profile. This will also run pre-setup and
-    /// post-setup side effects.
-    fn load_environment(
+    /// Select a profile like [Self::select_profile], and also return a
+    /// reference to it
+    fn select_profile_reference<'a>(
+        &'a self,
+        selection: &Selection,
+    ) -> anyhow::Result<(ProfileReference, Cow<'a, Profile>)> {
+        let (application_name, application) =
+            self.select_application(selection)?;
+        let last = history::last_profile(application_name);
+        if selection.last && last.is_none() {
+            bail!(
+                "No profile has been selected for application \
+                `{application_name}` in this directory yet"
+            );
+        }
+        let profile_name = selection
+            .profile
+            .as_ref()
+            .or(last.as_ref().filter(|_| selection.last));
+        let (profile_name, profile) = match profile_name {
+            Some(profile_name) => application.get_profile(profile_name)?,
+            // Combine the chosen profiles as if they were joined with `+`.
+            // If prompting, start on the last selection
+            None if selection.multi => {
+                let last_names = last
+                    .iter()
+                    .flat_map(|last| last.as_str().split(COMPOSE_SEPARATOR))
+                    .collect::<Vec<_>>();
+                let checked = application
+                    .profiles
+                    .keys()
+                    .filter(|name| last_names.contains(&name.as_str()))
+                    .collect::<Vec<_>>();
+                let names = prompt_multiple(
+                    &application.profiles,
+                    &checked,
+                    self.prompt_mode,
+                )?;
+                let joined = names
+                    .iter()
+                    .map(|name| name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(COMPOSE_SEPARATOR);
+                application.get_profile(&Name(joined))?
+            }
+            None => {
+                let (profile_name, profile) = prompt_options(
+                    &application.profiles,
+                    None,
+                    last.as_ref(),
+                    self.prompt_mode,
+                )?;
+                (profile_name.clone(), Cow::Borrowed(profile))
+            }
+        };
+        // Profiles named on the command line are one-offs, e.g. in `es diff`.
+        // Only remember what was picked in a prompt or reused with `--last`
+        if selection.profile.is_none() {
+            history::record(application_name, &profile_name);
+        }
+
+        // ProfileReference gives us consistent formatting
+        let reference: ProfileReference =
+            (application_name.clone(), profile_name).into();
+        if let Some(warning) = profile.deprecation_warning(&reference) {
+            if selection.strict_deprecations {
+                bail!(warning);
+            }
+            if !self.quiet {
+                print_hint(&warning)?;
+            }
+        }
+        Ok((reference, profile))
+    }
+
+    /// Compose profiles from other applications, given as `--with` pairs of
+    /// application and profile names, on top of the selected profile. Later
+    /// profiles take precedence. If two profiles set the same variable, warn
+    /// (or fail, if conflicts are strict) with both profiles named. Return the
+    /// composed profile, and references to the profiles that were added
+    fn compose_with<'a>(
+        &'a self,
+        selection: &Selection,
+        reference: &ProfileReference,
+        profile: Cow<'a, Profile>,
+        with: &[Name],
+    ) -> anyhow::Result<(Cow<'a, Profile>, Vec<ProfileReference>)> {
+        if with.is_empty() {
+            return Ok((profile, Vec::new()));
+        }
+
+        let mut layers = vec![(reference.clone(), profile)];
+        for pair in with.chunks_exact(2) {
+            let selection = Selection {
+                application: Some(pair[0].clone()),
+                profile: Some(pair[1].clone()),
+                multi: false,
+                last: false,
+                strict_deprecations: selection.strict_deprecations,
+            };
+            layers.push(self.select_profile_reference(&selection)?);
+        }
+
+        // Track which profile set each variable, so we can detect conflicts
+        let mut provenance: HashMap<&str, &ProfileReference> = HashMap::new();
+        for (reference, profile) in &layers {
+            for variable in profile.variables.keys() {
+                let Some(previous) = provenance.insert(variable, reference)
+                else {
+                    continue;
+                };
+                if self.config()?.strict_conflicts() {
+                    bail!(
+                        "Variable {variable} is set by both `{previous}` and \
+                        `{reference}`"
+                    );
+                }
+                warn!(
+                    "Variable {variable} is set by both `{previous}` and \
+                    `{reference}`; using the value from `{reference}`"
+                );
+            }
+        }
+        let profile = Profile::compose(
+            layers.iter().map(|(_, profile)| profile.as_ref()),
+        );
+        let references = layers
+            .into_iter()
+            .skip(1)
+            .map(|(reference, _)| reference)
+            .collect();
+        Ok((Cow::Owned(profile), references))
+    }
+
+    /// Build an [Environment] from a profile, *without* running any side
+    /// effects. Overrides from the command line are applied on top
+    fn resolve_environment(
         &self,
         profile: &Profile,
     ) -> anyhow::Result<Environment> {
+        let options = ResolveOptions {
+            use_cache: self.use_cache,
+            strict_templates: self.config()?.strict_templates(),
+            strict_conflicts: self.config()?.strict_conflicts(),
+            sensitive_patterns: self.config()?.sensitive_patterns().to_vec(),
+            base: self.base.clone(),
+            prompt_mode: self.prompt_mode,
+        };
+        let mut environment =
+            Environment::from_profile(&self.shell, profile, &options)?;
+        for variable_override in &self.overrides {
+            environment.apply_override(&options, variable_override);
+        }
+        Ok(environment)
+    }
+
+    /// Build an [Environment] from a profile. If `side_effects` is enabled,
+    /// this will also run pre-setup and post-setup side effects.
+    fn load_environment(
+        &self,
+        profile: &Profile,
+        side_effects: bool,
+    ) -> anyhow::Result<LoadedEnvironment> {
+        if !side_effects {
+            return Ok(LoadedEnvironment {
+                environment: self.resolve_environment(profile)?,
+                pre_export: BackgroundProcesses::default(),
+                post_export: BackgroundProcesses::default(),
+            });
+        }
+
         // Run pre- and post-resolution side effects
-        apply_side_effects(
+        let pre_export = apply_side_effects(
             &profile.pre_export,
             &self.shell,
             &Environment::default(),
         )?;
-        let environment = Environment::from_profile(&self.shell, profile)?;
-        apply_side_effects(&profile.post_export, &self.shell, &environment)?;
+        let environment = self.resolve_environment(profile)?;
+        let post_export = apply_side_effects(
+            &profile.post_export,
+            &self.shell,
+            &environment,
+        )?;
 
-        Ok(environment)
+        Ok(LoadedEnvironment {
+            environment,
+            pre_export,
+            post_export,
+        })
+    }
+
+    /// Apply changes to the parent shell. Internal state variables are
+    /// exported along with the environment, but aren't listed for the user.
+    /// If --source-file was passed, we were probably called from the shell
+    /// wrapper function, so write sourceable output to that file. Otherwise,
+    /// print it.
+    fn export(
+        &self,
+        environment: &Environment,
+        state: &Environment,
+        cwd: Option<&Path>,
+        heading: &str,
+        masking: Masking,
+    ) -> anyhow::Result<()> {
+        let mut source_output =
+            self.shell.export(environment) + &self.shell.export(state);
+        if let Some(cwd) = cwd {
+            source_output += &self.shell.change_directory(cwd);
+        }
+
+        if let Some(source_file) = self.source_file.as_ref() {
+            // The output may contain secrets, so wipe it once it's sourced
+            source_output += &self.shell.truncate_file(source_file);
+            source_file::write(source_file, &source_output).with_context(
+                || {
+                    format!(
+                        "Error writing sourceable output to file \
+                        {source_file:?}"
+                    )
+                },
+            )?;
+            // Tell the user what we exported
+            println!("{heading}");
+            println!("{:#}", environment.masked(masking));
+            if let Some(cwd) = cwd {
+                println!("Changing directory to {}", cwd.display());
+            }
+        } else {
+            // We were *not* called from the shell wrapper here, so just print
+            // the output and let the user know about a pro tip
+            print!("{source_output}");
+            print_hint(&format!(
+                "This output must be piped to `source` to be applied. \
+                    Install the `es` shell function to apply automatically: \
+                    {WEBSITE}/book/install.html#install-shell-function",
+            ))?;
+        }
+        Ok(())
+    }
+}
+
+impl LoadedEnvironment {
+    /// Clean up the profile's side effects, in reverse order of setup
+    fn tear_down(self, profile: &Profile, shell: &Shell) -> anyhow::Result<()> {
+        revert_side_effects(
+            &profile.post_export,
+            self.post_export,
+            shell,
+            &self.environment,
+        )?;
+        // Teardown of pre-export should *not* have access to the environment,
+        // to mirror the setup conditions
+        revert_side_effects(
+            &profile.pre_export,
+            self.pre_export,
+            shell,
+            &Environment::default(),
+        )
+    }
+
+    /// Skip teardown, and leave background processes running after we exit.
+    /// Return their PIDs
+    fn detach(self) -> Vec<u32> {
+        self.pre_export
+            .detach()
+            .into_iter()
+            .chain(self.post_export.detach())
+            .collect()
+    }
+}
+
+/// Get the directory that a profile switches to, if any. Check that it exists
+/// now, rather than failing partway through activation.
+fn profile_cwd(profile: &Profile) -> anyhow::Result<Option<&Path>> {
+    match &profile.cwd {
+        Some(cwd) if !cwd.is_dir() => {
+            Err(anyhow!("Profile cwd {cwd:?} is not a directory"))
+        }
+        cwd => Ok(cwd.as_deref()),
     }
 }
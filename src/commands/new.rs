@@ -0,0 +1,234 @@
+use crate::{
+    commands::{import::profiles_table, CommandContext, SubcommandTrait},
+    config::{Name, ProfileReference, FILE_NAME},
+};
+use anyhow::{anyhow, bail, Context};
+use clap::Parser;
+use dialoguer::{theme::ColorfulTheme, Confirm, Input};
+use std::{
+    env, fs,
+    io::{self, IsTerminal},
+};
+use toml_edit::{DocumentMut, Item, Table};
+
+/// Comment at the top of a newly created config file
+const HEADER: &str = "\
+# env-select config. Each application has profiles, and each profile is a set
+# of variables to export. Values can also be loaded from files, commands, and
+# more. See https://env-select.lucaspickering.me/book/ for all the options.
+
+";
+
+/// Create a config file in the current directory, by answering a few
+/// questions. If a config file already exists, you can add a new application
+/// to it instead.
+#[derive(Clone, Debug, Parser)]
+pub struct NewCommand {
+    /// Overwrite an existing config file, instead of offering to add to it
+    #[clap(long)]
+    force: bool,
+}
+
+/// Everything the user entered for a new application
+#[derive(Debug, PartialEq)]
+struct NewApplication {
+    name: Name,
+    profiles: Vec<NewProfile>,
+}
+
+#[derive(Debug, PartialEq)]
+struct NewProfile {
+    name: Name,
+    variables: Vec<(String, String)>,
+}
+
+impl SubcommandTrait for NewCommand {
+    fn execute(self, _: CommandContext) -> anyhow::Result<()> {
+        if !io::stderr().is_terminal() {
+            bail!("`es new` is interactive; it must run in a terminal");
+        }
+        let theme = ColorfulTheme::default();
+        let path = env::current_dir()?.join(FILE_NAME);
+
+        // toml_edit keeps a comment-only document as trailing text, so the
+        // header for a new file is prepended after rendering instead
+        let (header, mut document) = if path.exists() && !self.force {
+            let append = Confirm::with_theme(&theme)
+                .with_prompt(format!(
+                    "{} already exists. Add a new application to it?",
+                    path.display()
+                ))
+                .default(true)
+                .interact()?;
+            if !append {
+                bail!(
+                    "{} already exists; pass `--force` to overwrite it",
+                    path.display()
+                );
+            }
+            let document = fs::read_to_string(&path)
+                .with_context(|| format!("Error reading file {path:?}"))?
+                .parse::<DocumentMut>()
+                .with_context(|| {
+                    format!("Error parsing config file {path:?}")
+                })?;
+            ("", document)
+        } else {
+            (HEADER, DocumentMut::new())
+        };
+
+        let application = prompt_application(&theme, &document)?;
+        add_application(&mut document, &application)?;
+        fs::write(&path, format!("{header}{document}"))
+            .with_context(|| format!("Error writing config file {path:?}"))?;
+        println!(
+            "Saved application `{}` to {}. Try it out with `es set {}`",
+            application.name,
+            path.display(),
+            application.name
+        );
+        Ok(())
+    }
+}
+
+/// Ask the user for an application, its profiles, and their variables. The
+/// application can't already be in the document
+fn prompt_application(
+    theme: &ColorfulTheme,
+    document: &DocumentMut,
+) -> anyhow::Result<NewApplication> {
+    let existing = document.get("applications").and_then(Item::as_table_like);
+    let name: Name = Input::with_theme(theme)
+        .with_prompt("Application name (e.g. server)")
+        .validate_with(|name: &Name| match existing {
+            Some(existing) if existing.contains_key(name.as_str()) => {
+                Err(format!("Application `{name}` already exists"))
+            }
+            _ => Ok(()),
+        })
+        .interact_text()?;
+
+    let mut profiles: Vec<NewProfile> = Vec::new();
+    loop {
+        let profile_name: Name = Input::with_theme(theme)
+            .with_prompt("Profile name (e.g. dev)")
+            .validate_with(|profile_name: &Name| {
+                if profiles.iter().any(|profile| profile.name == *profile_name)
+                {
+                    Err(format!("Profile `{profile_name}` already entered"))
+                } else {
+                    Ok(())
+                }
+            })
+            .interact_text()?;
+        let mut variables: Vec<(String, String)> = Vec::new();
+        loop {
+            let variable: String = Input::with_theme(theme)
+                .with_prompt(format!(
+                    "Variable for `{profile_name}` (leave empty to finish)"
+                ))
+                .allow_empty(true)
+                .interact_text()?;
+            let variable = variable.trim();
+            if variable.is_empty() {
+                break;
+            }
+            let value: String = Input::with_theme(theme)
+                .with_prompt(format!("Value of {variable}"))
+                .allow_empty(true)
+                .interact_text()?;
+            variables.push((variable.to_owned(), value));
+        }
+        profiles.push(NewProfile {
+            name: profile_name,
+            variables,
+        });
+
+        if !Confirm::with_theme(theme)
+            .with_prompt("Add another profile?")
+            .default(false)
+            .interact()?
+        {
+            break;
+        }
+    }
+    Ok(NewApplication { name, profiles })
+}
+
+/// Add an application to a config document, after everything already in it
+fn add_application(
+    document: &mut DocumentMut,
+    application: &NewApplication,
+) -> anyhow::Result<()> {
+    for profile in &application.profiles {
+        let reference = ProfileReference::from((
+            application.name.clone(),
+            profile.name.clone(),
+        ));
+        let profiles = profiles_table(document, &reference)?;
+        if profiles.contains_key(profile.name.as_str()) {
+            return Err(anyhow!("Profile `{reference}` already exists"));
+        }
+        let mut variables = Table::new();
+        // Render as `variables.NAME = ...` under the profile's header
+        variables.set_dotted(true);
+        for (variable, value) in &profile.variables {
+            variables.insert(variable, value.as_str().into());
+        }
+        let mut table = Table::new();
+        table.insert("variables", Item::Table(variables));
+        profiles.insert(profile.name.as_str(), Item::Table(table));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::new_file(
+        "",
+        "[applications.server.profiles.dev]
+variables.HOST = \"localhost\"
+variables.PORT = \"3000\"
+
+[applications.server.profiles.prd]
+"
+    )]
+    #[case::existing_file(
+        "[applications.db.profiles.dev] # Keep me\nvariables = {USER = \"root\"}\n",
+        "[applications.db.profiles.dev] # Keep me
+variables = {USER = \"root\"}
+
+[applications.server.profiles.dev]
+variables.HOST = \"localhost\"
+variables.PORT = \"3000\"
+
+[applications.server.profiles.prd]
+",
+    )]
+    fn test_add_application(#[case] existing: &str, #[case] expected: &str) {
+        let mut document: DocumentMut = existing.parse().unwrap();
+        let application = NewApplication {
+            name: "server".into(),
+            profiles: vec![
+                NewProfile {
+                    name: "dev".into(),
+                    variables: vec![
+                        ("HOST".into(), "localhost".into()),
+                        ("PORT".into(), "3000".into()),
+                    ],
+                },
+                NewProfile {
+                    name: "prd".into(),
+                    variables: vec![],
+                },
+            ],
+        };
+        add_application(&mut document, &application).unwrap();
+        assert_eq!(document.to_string(), expected);
+    }
+}
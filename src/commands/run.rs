@@ -1,10 +1,30 @@
 use crate::{
-    commands::{CommandContext, Selection, SubcommandTrait},
-    environment::Environment,
+    commands::{profile_cwd, CommandContext, Selection, SubcommandTrait},
+    completions::complete_profile,
+    config::{Name, Profile, ProfileReference},
+    environment::{Base, VariableOverride},
     error::ExitCodeError,
-    execute::{revert_side_effects, Executable},
+    execute::{Executable, IntoExecutable},
+    kubernetes::KubernetesRestore,
 };
+use anyhow::bail;
 use clap::Parser;
+use clap_complete::ArgValueCompleter;
+use log::{error, warn};
+use std::{
+    borrow::Cow,
+    num::NonZeroUsize,
+    process::ExitStatus,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+};
+
+/// Variables kept from your shell by `--isolated`, in addition to `--keep`
+pub(super) const ISOLATED_KEEP: &[&str] =
+    &["PATH", "HOME", "TERM", "USER", "LANG"];
 
 /// Run a shell command in an augmented environment
 ///
@@ -12,50 +32,367 @@ use clap::Parser;
 /// and other shell features. See
 /// https://env-select.lucaspickering.me/book/user_guide/run_advanced.html for
 /// more details on shell interactions.
+///
+/// Pass several profiles (or `--all-profiles`) to run the command once per
+/// profile. Each run gets its own environment and side effects. If any run
+/// fails, env-select fails once they're all done.
 #[derive(Clone, Debug, Parser)]
 #[clap(visible_alias = "r")]
 pub struct RunCommand {
     #[command(flatten)]
     selection: Selection,
 
+    /// Additional profiles to run the command in. The command is run once per
+    /// profile, one after another unless `--parallel` is given
+    #[clap(
+        value_name = "PROFILE",
+        requires = "profile",
+        add = ArgValueCompleter::new(complete_profile),
+    )]
+    extra_profiles: Vec<Name>,
+
+    /// Run the command once for each profile in the application. Hidden and
+    /// deprecated profiles are skipped
+    #[clap(long, conflicts_with_all = ["profile", "multi", "last"])]
+    all_profiles: bool,
+
+    /// When running in multiple profiles, run up to this many at once. Output
+    /// from concurrent runs is interleaved, and stdin is closed
+    #[clap(long, value_name = "N")]
+    parallel: Option<NonZeroUsize>,
+
+    /// Compose a profile from another application on top of the selected
+    /// profile, e.g. `--with aws-account sandbox`. Later profiles take
+    /// precedence. Can be given multiple times
+    #[clap(long, num_args = 2, value_names = ["APPLICATION", "PROFILE"])]
+    with: Vec<Name>,
+
+    /// Run the command with *only* the profile's variables, instead of
+    /// adding them to your shell's environment. A few essentials (PATH, HOME,
+    /// TERM, USER, and LANG) are kept. Use this to check that a profile
+    /// declares everything the command needs. Side effects still run in your
+    /// shell's environment
+    #[clap(long)]
+    isolated: bool,
+
+    /// Keep this variable from your shell in an isolated environment. Can be
+    /// given multiple times
+    #[clap(long, value_name = "VARIABLE", requires = "isolated")]
+    keep: Vec<String>,
+
+    /// Don't run the profile's side effects, neither setup nor teardown
+    #[clap(long)]
+    no_side_effects: bool,
+
+    /// Run the profile's setup side effects, but not their teardown. Any
+    /// background processes are left running
+    #[clap(long, conflicts_with = "no_side_effects")]
+    no_teardown: bool,
+
+    /// Execute the command directly, instead of through your shell. The
+    /// first token is the program, which is found in PATH, and the rest are
+    /// passed to it as-is. This skips the shell's startup time, but shell
+    /// features such as aliases and pipes aren't available
+    #[clap(long)]
+    no_shell: bool,
+
+    /// Set a variable on top of the profile, e.g. `-e DATABASE_URL=...`.
+    /// PATH is prepended to, like in a profile. Can be given multiple times
+    #[clap(long = "env", short = 'e', value_name = "VARIABLE=VALUE")]
+    overrides: Vec<VariableOverride>,
+
     /// Shell command to execute. Can include multiple space-separated tokens.
-    /// Will be executed as if passed directly to your shell.
+    /// Will be executed as if passed directly to your shell, unless
+    /// `--no-shell` is given.
     #[arg(required = true, last = true)]
     command: Vec<String>,
 }
 
 impl SubcommandTrait for RunCommand {
-    fn execute(self, context: CommandContext) -> anyhow::Result<()> {
-        let profile = context.select_profile(&self.selection)?;
-        let environment = context.load_environment(profile)?;
-
-        // Undo clap's tokenization
-        let mut executable: Executable =
-            context.shell.executable_from_slice(&self.command);
-
-        // Execute the command
-        let status =
-            smol::block_on(executable.environment(&environment).status())?;
-
-        // Clean up side effects, in reverse order
-        revert_side_effects(
-            &profile.post_export,
-            &context.shell,
-            &environment,
-        )?;
-        // Teardown of pre-export should *not* have access to the environment,
-        // to mirror the setup conditions
-        revert_side_effects(
-            &profile.pre_export,
-            &context.shell,
-            &Environment::default(),
-        )?;
-
-        if status.success() {
+    fn execute(mut self, mut context: CommandContext) -> anyhow::Result<()> {
+        context.overrides = std::mem::take(&mut self.overrides);
+        if self.isolated {
+            // Lists such as PATH should only be combined with what the
+            // command will actually see
+            context.base = Base::Isolated {
+                keep: ISOLATED_KEEP
+                    .iter()
+                    .map(|variable| (*variable).to_owned())
+                    .chain(std::mem::take(&mut self.keep))
+                    .collect(),
+            };
+        }
+
+        if !self.all_profiles && self.extra_profiles.is_empty() {
+            let (reference, profile) =
+                context.select_profile_reference(&self.selection)?;
+            let (profile, _) = context.compose_with(
+                &self.selection,
+                &reference,
+                profile,
+                &self.with,
+            )?;
+            let status = self.run(&context, &profile, false)?;
+            return if status.success() {
+                Ok(())
+            } else {
+                // Map to our own exit code error type so we can forward to
+                // the user
+                Err(ExitCodeError::from(&status).into())
+            };
+        }
+
+        let selections = self.matrix_selections(&context)?;
+        let profiles = selections
+            .iter()
+            .map(|selection| {
+                let (reference, profile) =
+                    context.select_profile_reference(selection)?;
+                let (profile, _) = context
+                    .compose_with(selection, &reference, profile, &self.with)?;
+                Ok((reference, profile))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let parallel = self.parallel.map_or(1, NonZeroUsize::get);
+        if parallel > 1 {
+            // kubectl config is global, so concurrent runs would clobber
+            // each other's context
+            if let Some((reference, _)) =
+                profiles.iter().find(|(_, profile)| {
+                    profile.kubernetes_context.is_some()
+                        || profile.kubernetes_namespace.is_some()
+                })
+            {
+                bail!(
+                    "Profile `{reference}` switches the kubectl context, so \
+                    it can't be run with `--parallel`"
+                );
+            }
+        }
+
+        let statuses = self.run_matrix(&context, &profiles, parallel);
+        print_summary(&profiles, &statuses);
+        let codes = statuses
+            .iter()
+            .filter_map(|status| match status {
+                Ok(status) if status.success() => None,
+                Ok(status) => Some(ExitCodeError::from(status).exit_code()),
+                Err(_) => Some(None),
+            })
+            .collect::<Vec<_>>();
+        if codes.is_empty() {
             Ok(())
         } else {
-            // Map to our own exit code error type so we can forward to the user
-            Err(ExitCodeError::from(&status).into())
+            Err(ExitCodeError::Aggregate {
+                codes,
+                total: profiles.len(),
+            }
+            .into())
         }
     }
 }
+
+impl RunCommand {
+    /// Get one selection per profile to run the command in
+    fn matrix_selections(
+        &self,
+        context: &CommandContext,
+    ) -> anyhow::Result<Vec<Selection>> {
+        let (application_name, profile_names) = if self.all_profiles {
+            // Hidden profiles are left out, the same as in the selection
+            // prompt. Deprecated profiles would just warn on every run
+            let (application_name, application) =
+                context.select_application(&self.selection)?;
+            let profile_names = application
+                .profiles
+                .iter()
+                .filter(|(profile_name, profile)| {
+                    if profile.hidden {
+                        false
+                    } else if profile.is_deprecated() {
+                        warn!(
+                            "Skipping deprecated profile \
+                            `{application_name}/{profile_name}`"
+                        );
+                        false
+                    } else {
+                        true
+                    }
+                })
+                .map(|(profile_name, _)| profile_name.clone())
+                .collect();
+            (application_name.clone(), profile_names)
+        } else {
+            // clap ensures the application and first profile are given
+            // alongside any additional profiles
+            let application_name = self
+                .selection
+                .application
+                .clone()
+                .expect("clap requires application");
+            let profile_names: Vec<_> = self
+                .selection
+                .profile
+                .iter()
+                .chain(&self.extra_profiles)
+                .cloned()
+                .collect();
+            (application_name, profile_names)
+        };
+        Ok(profile_names
+            .into_iter()
+            .map(|profile_name| Selection {
+                application: Some(application_name.clone()),
+                profile: Some(profile_name),
+                multi: false,
+                last: false,
+                strict_deprecations: self.selection.strict_deprecations,
+            })
+            .collect())
+    }
+
+    /// Run the command in each profile, at most `parallel` at a time. The
+    /// returned statuses are in the same order as the profiles. A failure to
+    /// run in one profile doesn't stop the others.
+    fn run_matrix(
+        &self,
+        context: &CommandContext,
+        profiles: &[(ProfileReference, Cow<'_, Profile>)],
+        parallel: usize,
+    ) -> Vec<anyhow::Result<ExitStatus>> {
+        // Parallel runs can't share the terminal, so they run in the
+        // background
+        let background = parallel > 1;
+        let run = |(reference, profile): &(ProfileReference, Cow<Profile>)| {
+            println!("=== {reference} ===");
+            let status = self.run(context, profile, background);
+            if let Err(error) = &status {
+                error!("{error:#}");
+            }
+            status
+        };
+
+        if parallel == 1 {
+            return profiles.iter().map(run).collect();
+        }
+
+        // Workers pull the next profile off a shared counter until they run
+        // out
+        let next = AtomicUsize::new(0);
+        let statuses =
+            Mutex::new(profiles.iter().map(|_| None).collect::<Vec<_>>());
+        thread::scope(|scope| {
+            for _ in 0..parallel.min(profiles.len()) {
+                scope.spawn(|| loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(profile) = profiles.get(index) else {
+                        break;
+                    };
+                    let status = run(profile);
+                    statuses.lock().expect("Status lock poisoned")[index] =
+                        Some(status);
+                });
+            }
+        });
+        statuses
+            .into_inner()
+            .expect("Status lock poisoned")
+            .into_iter()
+            .map(|status| status.expect("Every profile is run"))
+            .collect()
+    }
+
+    /// Run the command once in a profile, including its side effects and
+    /// kubectl switch. If `background` is set, the command doesn't get the
+    /// terminal or stdin, so other runs can happen at the same time. See
+    /// [Executable::status_forwarding_signals_background]
+    fn run(
+        &self,
+        context: &CommandContext,
+        profile: &Profile,
+        background: bool,
+    ) -> anyhow::Result<ExitStatus> {
+        let cwd = profile_cwd(profile)?;
+        // kubectl is switched before anything else, and restored last, even
+        // if something in between fails
+        KubernetesRestore::switched(profile, || {
+            let loaded =
+                context.load_environment(profile, !self.no_side_effects)?;
+
+            let mut executable: Executable = if self.no_shell {
+                let (program, arguments) = self
+                    .command
+                    .split_first()
+                    .expect("clap requires at least one token");
+                (program, arguments).executable()
+            } else {
+                // Undo clap's tokenization
+                context.shell.executable_from_slice(&self.command)
+            };
+            if let Some(cwd) = cwd {
+                executable.current_dir(cwd);
+            }
+            if let Base::Isolated { keep } = &context.base {
+                executable.env_clear(keep);
+            }
+
+            // Execute the command. Even if it can't be started, we still need
+            // to tear down, so check for that afterward. If we're interrupted
+            // or terminated, the command gets the signal and we wait for it to
+            // exit, so the teardown still happens
+            executable.environment(&loaded.environment);
+            let status = smol::block_on(async {
+                if background {
+                    executable.status_forwarding_signals_background().await
+                } else {
+                    executable.status_forwarding_signals().await
+                }
+            });
+
+            if self.no_teardown {
+                let pids = loaded.detach();
+                if !pids.is_empty() {
+                    warn!(
+                        "Left background processes running: {}",
+                        pids.iter()
+                            .map(u32::to_string)
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    );
+                }
+            } else if !self.no_side_effects {
+                // If the command failed, its exit code is more useful than
+                // the teardown error, so only log that
+                if let Err(error) = loaded.tear_down(profile, &context.shell) {
+                    match &status {
+                        Ok(status) if status.success() => return Err(error),
+                        _ => error!("{error:#}"),
+                    }
+                }
+            }
+
+            status
+        })
+    }
+}
+
+/// Print the outcome of each run in a multi-profile run to stderr
+fn print_summary(
+    profiles: &[(ProfileReference, Cow<'_, Profile>)],
+    statuses: &[anyhow::Result<ExitStatus>],
+) {
+    let references = profiles
+        .iter()
+        .map(|(reference, _)| reference.to_string())
+        .collect::<Vec<_>>();
+    let width = references.iter().map(String::len).max().unwrap_or(0);
+    eprintln!("Summary:");
+    for (reference, status) in references.iter().zip(statuses) {
+        let outcome = match status {
+            Ok(status) if status.success() => "ok".to_owned(),
+            Ok(status) => ExitCodeError::from(status).to_string(),
+            Err(error) => format!("error: {error}"),
+        };
+        eprintln!("  {reference:<width$}  {outcome}");
+    }
+}
@@ -1,12 +1,18 @@
 use crate::{
-    commands::{CommandContext, Selection, SubcommandTrait},
+    active::active_variable,
+    background::Pidfile,
+    commands::{
+        profile_cwd, CommandContext, LoadedEnvironment, Selection,
+        SubcommandTrait,
+    },
+    config::Name,
     console::print_hint,
+    environment::{Environment, VariableOverride},
+    kubernetes::{KubernetesRestore, RESTORE_VARIABLE},
+    last_export::{LastExport, LAST_EXPORT_VARIABLE},
 };
-use anyhow::Context;
 use clap::Parser;
-use std::fs;
-
-const WEBSITE: &str = "https://env-select.lucaspickering.me";
+use std::env;
 
 /// Modify current shell environment
 #[derive(Clone, Debug, Parser)]
@@ -14,37 +20,130 @@ const WEBSITE: &str = "https://env-select.lucaspickering.me";
 pub struct SetCommand {
     #[command(flatten)]
     selection: Selection,
+
+    /// Don't run the profile's side effects. `es unset` won't tear them down
+    /// either
+    #[clap(long)]
+    no_side_effects: bool,
+
+    /// Compose a profile from another application on top of the selected
+    /// profile, e.g. `--with aws-account sandbox`. Later profiles take
+    /// precedence. Can be given multiple times
+    #[clap(long, num_args = 2, value_names = ["APPLICATION", "PROFILE"])]
+    with: Vec<Name>,
+
+    /// Set a variable on top of the profile, e.g. `-e DATABASE_URL=...`.
+    /// PATH is prepended to, like in a profile. Can be given multiple times
+    #[clap(long = "env", short = 'e', value_name = "VARIABLE=VALUE")]
+    overrides: Vec<VariableOverride>,
+
+    /// Show sensitive values in the summary of exported variables, instead
+    /// of masking them. Exported values are never masked
+    #[clap(long)]
+    reveal: bool,
 }
 
 impl SubcommandTrait for SetCommand {
-    fn execute(self, context: CommandContext) -> anyhow::Result<()> {
-        let profile = context.select_profile(&self.selection)?;
-        let environment = context.load_environment(profile)?;
-
-        let source_output = context.shell.export(&environment);
-
-        // If --source-file was passed, we were probably called from the shell
-        // wrapper function. Write sourceable output to the given file.
-        if let Some(source_file) = context.source_file.as_ref() {
-            fs::write(source_file, source_output).with_context(|| {
-                format!(
-                    "Error writing sourceable output to file {source_file:?}"
-                )
-            })?;
-            // Tell the user what we exported
-            println!("The following variables will be set:");
-            println!("{environment:#}");
-        } else {
-            // We were *not* called from the shell wrapper here, so just print
-            // the output and let the user know about a pro tip
-            print!("{source_output}");
-            print_hint(&format!(
-                "This output must be piped to `source` to be applied. \
-                    Install the `es` shell function to apply automatically: \
-                    {WEBSITE}/book/install.html#install-shell-function",
-            ))?;
+    fn execute(self, mut context: CommandContext) -> anyhow::Result<()> {
+        context.overrides = self.overrides;
+        let (reference, profile) =
+            context.select_profile_reference(&self.selection)?;
+        let (profile, with) = context.compose_with(
+            &self.selection,
+            &reference,
+            profile,
+            &self.with,
+        )?;
+        let application = reference
+            .application()
+            .expect("Selected profile reference is qualified");
+        let active_variable = active_variable(application);
+        if let Ok(active) = env::var(&active_variable) {
+            if active != reference.profile().0 {
+                print_hint(&format!(
+                    "Switching application `{application}` from profile \
+                    `{active}` to `{}`",
+                    reference.profile()
+                ))?;
+            }
+        }
+        let cwd = profile_cwd(&profile)?;
+
+        // If a previous profile switched kubectl settings, undo that before
+        // switching again, so we always capture the user's original settings
+        let previous = KubernetesRestore::from_env()?;
+        if let Some(previous) = &previous {
+            previous.restore()?;
+        }
+        // Same for processes left running by this application's background
+        // side effects. Other applications' processes are left alone
+        let pidfile_variable = Pidfile::variable(application);
+        let previous_pidfile = Pidfile::from_env(application);
+        if let Some(previous_pidfile) = &previous_pidfile {
+            previous_pidfile.kill()?;
+        }
+        let kubernetes = KubernetesRestore::switch(&profile)?;
+
+        let LoadedEnvironment {
+            mut environment,
+            pre_export,
+            post_export,
+        } = context.load_environment(&profile, !self.no_side_effects)?;
+        // Variables that track our own state, so the next `es set` or
+        // `es unset` knows what to undo
+        let mut state = Environment::default();
+        state
+            .insert_internal(&active_variable, reference.profile().to_string());
+        let previous_export = LastExport::from_env()?;
+        // Variables from this application's previous profile that the new one
+        // doesn't set would otherwise linger in the shell
+        let sensitive_patterns = context.config()?.sensitive_patterns();
+        let stale = previous_export
+            .as_ref()
+            .map(|previous| {
+                previous.stale(&reference, &environment, sensitive_patterns)
+            })
+            .unwrap_or_default();
+        let last_export = LastExport::new(
+            reference,
+            with,
+            !self.no_side_effects,
+            &environment,
+            cwd,
+            previous_export,
+        );
+        state.insert_internal(LAST_EXPORT_VARIABLE, last_export.to_variable());
+        environment.extend(stale);
+        // Remember how to undo the switch for next time
+        match kubernetes {
+            Some(kubernetes) => state
+                .insert_internal(RESTORE_VARIABLE, kubernetes.to_variable()),
+            None if previous.is_some() => {
+                state.unset(RESTORE_VARIABLE.to_owned())
+            }
+            None => {}
+        }
+        // There's no teardown for `es set`, so leave background processes
+        // running and record them for the next `es set` or `es unset` to kill
+        let pids: Vec<u32> = pre_export
+            .detach()
+            .into_iter()
+            .chain(post_export.detach())
+            .collect();
+        if !pids.is_empty() {
+            let pidfile = Pidfile::write(&pids)?;
+            state.insert_internal(&pidfile_variable, pidfile.to_variable());
+        } else if previous_pidfile.is_some() {
+            state.unset(pidfile_variable);
         }
 
+        context.export(
+            &environment,
+            &state,
+            cwd,
+            "The following variables will be set:",
+            context.masking(self.reveal)?,
+        )?;
         Ok(())
     }
 }
@@ -1,10 +1,19 @@
 use crate::{
-    commands::{CommandContext, SubcommandTrait},
+    active::{active_variable, active_variables, ACTIVE_VARIABLE_PREFIX},
+    commands::{CommandContext, Selection, SubcommandTrait},
     completions::{complete_application, complete_profile},
-    config::{MapExt, Name},
+    config::{
+        get_aliased, schema, Config, Definer, Definitions, Name, Origin,
+        Origins, Profile, ProfileReference, Source,
+    },
+    kubernetes::KubernetesRestore,
 };
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 use clap_complete::ArgValueCompleter;
+use derive_more::Display;
+use serde::Serialize;
+use std::{fmt::Write, path::PathBuf};
+use toml::ser::ValueSerializer;
 
 /// Print configuration and meta information
 #[derive(Clone, Debug, Parser)]
@@ -15,7 +24,8 @@ pub struct ShowCommand {
 
 #[derive(Clone, Debug, Subcommand)]
 enum ShowSubcommand {
-    /// Print configuration for a profile, in TOML format
+    /// Print configuration for a profile, in TOML format. By default, this
+    /// shows the config as written, without inheritance
     Config {
         // We can't use the Selection helper type here because the doc strings
         // are incorrect for this use case
@@ -27,7 +37,45 @@ enum ShowSubcommand {
         /// for the selected application.
         #[clap(add = ArgValueCompleter::new(complete_profile))]
         profile: Option<Name>,
+        /// Show profiles after resolving inheritance, with all inherited
+        /// variables and side effects. When showing a single profile, each
+        /// inherited variable is annotated with where it was defined
+        #[clap(long)]
+        resolved: bool,
+        /// Annotate every variable with the file that defined it, and the
+        /// chain of profiles it was inherited through. Implies `--resolved`.
+        /// Only applies when showing a single profile in TOML
+        #[clap(long)]
+        origin: bool,
+        /// Output format. Inherited variables are only annotated in TOML
+        #[clap(long, value_enum, default_value_t)]
+        format: Format,
+    },
+    /// Print the environment that a profile resolves to, without modifying
+    /// your shell. Sensitive values are masked
+    Env {
+        #[command(flatten)]
+        selection: Selection,
+        /// Run the profile's side effects and switch kubectl settings while
+        /// resolving, like `es run`. Everything is torn down before exiting
+        #[clap(long)]
+        run_side_effects: bool,
+        /// Show sensitive values instead of masking them
+        #[clap(long)]
+        reveal: bool,
+        /// Output format. Unset variables are omitted from dotenv output
+        #[clap(long, value_enum, default_value_t)]
+        format: EnvFormat,
     },
+    /// Print the profile that `es set` activated for each application in the
+    /// current shell, as `application/profile`
+    Active,
+    /// Print the path of every config file that was loaded, including
+    /// imported and included files, and the global config file
+    Sources,
+    /// Print a JSON Schema for the config file format, for editor validation
+    /// and completion
+    Schema,
     /// Print the name or path to the shell in use
     Shell,
 }
@@ -38,28 +86,266 @@ impl SubcommandTrait for ShowCommand {
             ShowSubcommand::Config {
                 application,
                 profile,
+                resolved,
+                origin,
+                format,
             } => {
+                let mut config = context.unresolved_config()?;
+                let origins = if resolved || origin {
+                    config.resolve()?
+                } else {
+                    Origins::new()
+                };
                 // Serialize isn't object-safe, so there's no way to return a
                 // dynamic object of what to serialize. That means each branch
                 // has to serialize itself
-                let config = context.config()?;
                 let content = if let Some(application) = application {
-                    let application =
-                        config.applications.try_get(&application)?;
+                    let (application_name, application) =
+                        get_aliased(&config.applications, &application)?;
                     if let Some(profile) = profile {
-                        let profile = application.profiles.try_get(&profile)?;
-                        toml::to_string(profile)
+                        let (profile_name, profile) =
+                            get_aliased(&application.profiles, &profile)?;
+                        let reference = ProfileReference::from((
+                            application_name.clone(),
+                            profile_name.clone(),
+                        ));
+                        let origins = origins.get(&reference);
+                        let annotate = |variable: &str| {
+                            let variable_origin = origins?.get(variable)?;
+                            if origin {
+                                Some(describe_origin(
+                                    &config.definitions,
+                                    &reference,
+                                    variable,
+                                    variable_origin,
+                                ))
+                            } else if variable_origin.is_inherited(&reference) {
+                                Some(variable_origin.to_string())
+                            } else {
+                                None
+                            }
+                        };
+                        match format {
+                            Format::Toml => profile_to_toml(profile, annotate)?,
+                            _ => format.serialize(profile)?,
+                        }
                     } else {
-                        toml::to_string(application)
+                        format.serialize(application)?
                     }
                 } else {
                     // Print entire config
-                    toml::to_string(config)
-                }?;
-                println!("{}", content);
+                    format.serialize(&config)?
+                };
+                // Formats disagree on trailing newlines, so normalize them
+                println!("{}", content.trim_end());
+            }
+            ShowSubcommand::Env {
+                selection,
+                run_side_effects,
+                reveal,
+                format,
+            } => {
+                let profile = context.select_profile(&selection)?;
+                let environment = if run_side_effects {
+                    KubernetesRestore::switched(&profile, || {
+                        let loaded =
+                            context.load_environment(&profile, true)?;
+                        let environment = loaded.environment.clone();
+                        loaded.tear_down(&profile, &context.shell)?;
+                        Ok(environment)
+                    })?
+                } else {
+                    context.resolve_environment(&profile)?
+                };
+                let masking = context.masking(reveal)?;
+                let content = match format {
+                    EnvFormat::Table => {
+                        format!("{:#}", environment.masked(masking))
+                    }
+                    EnvFormat::Dotenv => environment.to_dotenv(masking)?,
+                    EnvFormat::Json => serde_json::to_string_pretty(
+                        &environment.masked(masking),
+                    )?,
+                };
+                println!("{}", content.trim_end());
+            }
+            ShowSubcommand::Active => {
+                let config = context.config()?;
+                for (variable, profile) in active_variables() {
+                    // Variable names are sanitized, so find the application
+                    // that the variable belongs to. If it's not in the config
+                    // anymore, the best we can do is the variable's suffix
+                    let application = config
+                        .applications
+                        .keys()
+                        .find(|application| {
+                            active_variable(application) == variable
+                        })
+                        .map(ToString::to_string)
+                        .unwrap_or_else(|| {
+                            variable[ACTIVE_VARIABLE_PREFIX.len()..].to_owned()
+                        });
+                    println!("{application}/{profile}");
+                }
+            }
+            ShowSubcommand::Sources => {
+                let global_file = Config::global_file()
+                    .and_then(|path| path.canonicalize().ok());
+                // Invalid files are listed rather than fatal, since finding
+                // them is a big part of what this is for. Inheritance isn't
+                // needed, so skip it in case it fails
+                let config = Config::load_unresolved(
+                    context.config_file.as_deref(),
+                    true,
+                )?;
+                for source in &config.sources {
+                    println!("{}", format_source(source, global_file.as_ref()));
+                }
+            }
+            ShowSubcommand::Schema => {
+                println!("{}", serde_json::to_string_pretty(&schema())?)
             }
             ShowSubcommand::Shell => println!("{}", context.shell),
         }
         Ok(())
     }
 }
+
+/// Describe a loaded config file, for `show sources`
+fn format_source(source: &Source, global_file: Option<&PathBuf>) -> String {
+    let mut details = Vec::new();
+    if Some(&source.path) == global_file {
+        details.push("global".to_owned());
+    }
+    if source.invalid {
+        details.push("invalid, skipped".to_owned());
+    } else {
+        details.push(count(source.applications, "application"));
+        details.push(count(source.profiles, "profile"));
+    }
+    format!("{} ({})", source.path.display(), details.join(", "))
+}
+
+/// Format a count of things, e.g. `1 profile` or `2 profiles`
+fn count(count: usize, noun: &str) -> String {
+    if count == 1 {
+        format!("{count} {noun}")
+    } else {
+        format!("{count} {noun}s")
+    }
+}
+
+/// Output format for `show config`
+#[derive(Copy, Clone, Debug, Default, Display, ValueEnum)]
+enum Format {
+    #[default]
+    #[display("toml")]
+    Toml,
+    #[display("json")]
+    Json,
+    #[display("yaml")]
+    Yaml,
+}
+
+impl Format {
+    /// Serialize a value in this format. Map ordering is preserved in all
+    /// formats
+    fn serialize<T: Serialize>(self, value: &T) -> anyhow::Result<String> {
+        Ok(match self {
+            Self::Toml => toml::to_string(value)?,
+            Self::Json => serde_json::to_string_pretty(value)?,
+            Self::Yaml => serde_yaml::to_string(value)?,
+        })
+    }
+}
+
+/// Output format for `show env`
+#[derive(Copy, Clone, Debug, Default, Display, ValueEnum)]
+enum EnvFormat {
+    /// `VARIABLE = value` lines
+    #[default]
+    #[display("table")]
+    Table,
+    #[display("dotenv")]
+    Dotenv,
+    #[display("json")]
+    Json,
+}
+
+/// Serialize a profile as TOML. Variables go last, so each variable can be
+/// annotated with a comment naming where it was defined.
+fn profile_to_toml(
+    profile: &Profile,
+    annotate: impl Fn(&str) -> Option<String>,
+) -> anyhow::Result<String> {
+    let mut table = toml::Table::try_from(profile)?;
+    table.remove("variables");
+    let mut output = toml::to_string(&table)?;
+    if profile.variables.is_empty() {
+        return Ok(output);
+    }
+
+    if !output.is_empty() {
+        output.push('\n');
+    }
+    output.push_str("[variables]\n");
+    for (variable, definition) in &profile.variables {
+        if let Some(annotation) = annotate(variable) {
+            writeln!(output, "# from {annotation}")?;
+        }
+        let mut value = String::new();
+        definition.serialize(ValueSerializer::new(&mut value))?;
+        writeln!(output, "{} = {value}", toml_key(variable))?;
+    }
+    Ok(output)
+}
+
+/// Describe where a variable was defined: the file it came from, and if it
+/// was inherited, the chain of profiles it passed through to get here
+fn describe_origin(
+    definitions: &Definitions,
+    reference: &ProfileReference,
+    variable: &str,
+    origin: &Origin,
+) -> String {
+    let path = match &origin.definer {
+        Definer::Profile(definer) => definitions.profiles.get(definer),
+        Definer::Application(application) => definitions
+            .application_variables
+            .get(&(application.clone(), variable.to_owned())),
+        Definer::Defaults => definitions.defaults.get(variable),
+    };
+    let description = match path {
+        Some(path) => path.display().to_string(),
+        None => "unknown file".to_owned(),
+    };
+    if origin.is_inherited(reference) {
+        // Profiles in the chain are named without a prefix
+        let definer = match &origin.definer {
+            Definer::Profile(definer) => definer.to_string(),
+            definer => definer.to_string(),
+        };
+        let chain: Vec<String> = origin
+            .via
+            .iter()
+            .map(ToString::to_string)
+            .chain([definer])
+            .collect();
+        format!("{description} via {}", chain.join(" -> "))
+    } else {
+        description
+    }
+}
+
+/// Format a TOML key, quoting it if it isn't a valid bare key
+fn toml_key(key: &str) -> String {
+    let is_bare = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if is_bare {
+        key.to_owned()
+    } else {
+        toml::Value::String(key.to_owned()).to_string()
+    }
+}
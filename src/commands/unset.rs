@@ -0,0 +1,115 @@
+use crate::{
+    active::active_variables,
+    background::{BackgroundProcesses, Pidfile},
+    commands::{CommandContext, SubcommandTrait},
+    config::Profile,
+    environment::Environment,
+    execute::revert_side_effects,
+    kubernetes::{KubernetesRestore, RESTORE_VARIABLE},
+    last_export::{LastExport, LAST_EXPORT_VARIABLE},
+};
+use anyhow::anyhow;
+use clap::Parser;
+use log::warn;
+use std::iter;
+
+/// Revert the changes made to the current shell by `es set`
+#[derive(Clone, Debug, Parser)]
+pub struct UnsetCommand {}
+
+impl SubcommandTrait for UnsetCommand {
+    fn execute(self, context: CommandContext) -> anyhow::Result<()> {
+        let last_export = LastExport::from_env()?.ok_or_else(|| {
+            anyhow!("Nothing to unset; `es set` hasn't been run in this shell")
+        })?;
+        let config = context.config()?;
+        let environment = last_export.revert(config.sensitive_patterns());
+        // Go back to where the shell was before a profile changed directory
+        let cwd = last_export.cwd().filter(|cwd| {
+            let exists = cwd.is_dir();
+            if !exists {
+                warn!(
+                    "Previous directory {cwd:?} no longer exists; staying in \
+                    the current directory"
+                );
+            }
+            exists
+        });
+
+        // Processes left running by background side effects are killed before
+        // any teardown commands run, same as `es run`
+        let pidfiles = Pidfile::all_from_env();
+        for (_, pidfile) in &pidfiles {
+            pidfile.kill()?;
+        }
+        // Profiles combined via `--with` are torn down along with the main
+        // one, in reverse order of setup
+        let references =
+            iter::once(&last_export.profile).chain(&last_export.with);
+        let profiles = references
+            .clone()
+            .map(|reference| config.get_profile(reference))
+            .collect::<Option<Vec<_>>>()
+            .map(|profiles| {
+                Profile::compose(
+                    profiles.iter().map(|profile| profile.as_ref()),
+                )
+            });
+        match profiles {
+            // Side effects were skipped, so there's nothing to tear down
+            _ if !last_export.side_effects => {}
+            Some(profile) => {
+                // The shell still has the profile's environment, so post-export
+                // teardown can see it. Pre-export teardown gets the original
+                // values instead, to mirror the setup conditions
+                revert_side_effects(
+                    &profile.post_export,
+                    BackgroundProcesses::default(),
+                    &context.shell,
+                    &Environment::default(),
+                )?;
+                revert_side_effects(
+                    &profile.pre_export,
+                    BackgroundProcesses::default(),
+                    &context.shell,
+                    &environment,
+                )?;
+            }
+            None => {
+                for reference in references {
+                    if config.get_profile(reference).is_none() {
+                        warn!(
+                            "Profile `{reference}` no longer exists; side \
+                            effects will not be torn down",
+                        );
+                    }
+                }
+            }
+        }
+        let kubernetes = KubernetesRestore::from_env()?;
+        if let Some(kubernetes) = &kubernetes {
+            kubernetes.restore()?;
+        }
+
+        // Clear out our own state, so the next `es set` starts fresh
+        let mut state = Environment::default();
+        state.unset(LAST_EXPORT_VARIABLE.to_owned());
+        for (variable, _) in active_variables() {
+            state.unset(variable);
+        }
+        if kubernetes.is_some() {
+            state.unset(RESTORE_VARIABLE.to_owned());
+        }
+        for (variable, _) in pidfiles {
+            state.unset(variable);
+        }
+
+        context.export(
+            &environment,
+            &state,
+            cwd,
+            "The following variables will be restored:",
+            context.masking(false)?,
+        )
+    }
+}
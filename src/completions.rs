@@ -1,19 +1,27 @@
-use crate::config::{Config, Name};
+use crate::{
+    config::{Aliased, Config, Name},
+    console::Prompt,
+};
 use clap_complete::CompletionCandidate;
-use std::ffi::OsStr;
+use indexmap::IndexMap;
+use std::{
+    env,
+    ffi::{OsStr, OsString},
+    path::PathBuf,
+};
 
 /// Provide completions for application names
 pub fn complete_application(current: &OsStr) -> Vec<CompletionCandidate> {
-    let Ok(config) = Config::load() else {
+    let Ok(config) = load_config() else {
         return Vec::new();
     };
 
-    get_candidates(config.applications.keys().map(Name::as_str), current)
+    get_candidates(names_and_aliases(&config.applications), current)
 }
 
 /// Provide completions for profile names
 pub fn complete_profile(current: &OsStr) -> Vec<CompletionCandidate> {
-    let Ok(config) = Config::load() else {
+    let Ok(config) = load_config() else {
         return Vec::new();
     };
 
@@ -24,12 +32,50 @@ pub fn complete_profile(current: &OsStr) -> Vec<CompletionCandidate> {
         config
             .applications
             .values()
-            .flat_map(|application| application.profiles.keys())
-            .map(Name::as_str),
+            .flat_map(|application| names_and_aliases(&application.profiles)),
         current,
     )
 }
 
+/// Load config the same way the command being completed will. Completers
+/// only get the word being completed, so `--config` is read from the full
+/// command line in this process's args. `ES_CONFIG_FILE` is handled by
+/// [Config::load]
+fn load_config() -> anyhow::Result<Config> {
+    Config::load(config_argument(env::args_os()).as_deref(), true)
+}
+
+/// Find the value of `--config` in a command line. If given more than once,
+/// the last one wins
+fn config_argument(
+    args: impl IntoIterator<Item = OsString>,
+) -> Option<PathBuf> {
+    let mut args = args.into_iter();
+    let mut config_file = None;
+    while let Some(arg) = args.next() {
+        if arg == "--config" {
+            config_file = args.next().map(PathBuf::from);
+        } else if let Some(value) =
+            arg.to_str().and_then(|arg| arg.strip_prefix("--config="))
+        {
+            config_file = Some(PathBuf::from(value));
+        }
+    }
+    config_file
+}
+
+/// Get every name and alias in a group of applications or profiles, excluding
+/// hidden ones
+fn names_and_aliases<T: Aliased + Prompt>(
+    options: &IndexMap<Name, T>,
+) -> impl Iterator<Item = &str> {
+    options
+        .iter()
+        .filter(|(_, option)| !option.is_hidden())
+        .flat_map(|(name, option)| [name].into_iter().chain(option.aliases()))
+        .map(Name::as_str)
+}
+
 fn get_candidates<'a>(
     iter: impl Iterator<Item = &'a str>,
     current: &OsStr,
@@ -42,3 +88,33 @@ fn get_candidates<'a>(
         .map(CompletionCandidate::new)
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    /// Completion runs as `es -- es <args>`, so `--config` can appear
+    /// anywhere in the args
+    #[rstest]
+    #[case::none(&["es", "--", "es", "set", ""], None)]
+    #[case::separate(
+        &["es", "--", "es", "--config", "es.toml", "set", ""],
+        Some("es.toml"),
+    )]
+    #[case::equals(&["es", "--", "es", "--config=es.toml", "set"], Some("es.toml"))]
+    #[case::missing_value(&["es", "--", "es", "--config"], None)]
+    #[case::last_wins(
+        &["es", "--config", "a.toml", "--config", "b.toml"],
+        Some("b.toml"),
+    )]
+    fn test_config_argument(
+        #[case] args: &[&str],
+        #[case] expected: Option<&str>,
+    ) {
+        assert_eq!(
+            config_argument(args.iter().map(OsString::from)),
+            expected.map(PathBuf::from)
+        );
+    }
+}
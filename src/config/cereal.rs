@@ -1,8 +1,16 @@
 //! Config serialization and deserialization
 
-use crate::config::{Name, ProfileReference, ValueSource, ValueSourceInner};
+use crate::config::{
+    BuiltinSideEffect, ConfigDuration, Name, ProfileReference,
+    SensitivePattern, ShellCommand, ShellValueSources, SideEffect, ValueSource,
+    ValueSourceInner, ValueSourceKind, VariableDefinition,
+};
 use serde::{
-    de::{self, value::MapAccessDeserializer, MapAccess, Visitor},
+    de::{
+        self,
+        value::{MapAccessDeserializer, SeqAccessDeserializer},
+        MapAccess, SeqAccess, Visitor,
+    },
     Deserialize, Deserializer, Serialize, Serializer,
 };
 use std::str::FromStr;
@@ -13,7 +21,26 @@ macro_rules! visit_primitive {
         where
             E: de::Error,
         {
-            Ok(ValueSource::from_literal(value))
+            Ok(ValueSource::from_literal(value).into())
+        }
+    };
+}
+
+// A null value (in formats that support it) unsets the variable
+macro_rules! visit_null {
+    () => {
+        fn visit_unit<E>(self) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            Ok(ValueSource::from(ValueSourceKind::Unset).into())
+        }
+
+        fn visit_none<E>(self) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            self.visit_unit()
         }
     };
 }
@@ -33,7 +60,7 @@ impl<'de> Deserialize<'de> for ValueSource {
                 &self,
                 formatter: &mut std::fmt::Formatter,
             ) -> std::fmt::Result {
-                formatter.write_str("string, boolean, number, or map")
+                formatter.write_str("string, boolean, number, null, or map")
             }
 
             visit_primitive!(visit_bool, bool);
@@ -43,6 +70,7 @@ impl<'de> Deserialize<'de> for ValueSource {
             visit_primitive!(visit_i128, i128);
             visit_primitive!(visit_f64, f64);
             visit_primitive!(visit_str, &str);
+            visit_null!();
 
             fn visit_map<M>(self, map: M) -> Result<Self::Value, M::Error>
             where
@@ -58,6 +86,118 @@ impl<'de> Deserialize<'de> for ValueSource {
     }
 }
 
+// Custom deserialization for VariableDefinition. A list is a set of candidate
+// sources, and a map keyed only by shell kinds is a set of per-shell sources.
+// Anything else is a single value source
+impl<'de> Deserialize<'de> for VariableDefinition {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct VariableDefinitionVisitor;
+
+        impl<'de> Visitor<'de> for VariableDefinitionVisitor {
+            type Value = VariableDefinition;
+
+            fn expecting(
+                &self,
+                formatter: &mut std::fmt::Formatter,
+            ) -> std::fmt::Result {
+                formatter
+                    .write_str("string, boolean, number, null, map, or list")
+            }
+
+            visit_primitive!(visit_bool, bool);
+            visit_primitive!(visit_u64, u64);
+            visit_primitive!(visit_u128, u128);
+            visit_primitive!(visit_i64, i64);
+            visit_primitive!(visit_i128, i128);
+            visit_primitive!(visit_f64, f64);
+            visit_primitive!(visit_str, &str);
+            visit_null!();
+
+            fn visit_map<M>(self, map: M) -> Result<Self::Value, M::Error>
+            where
+                M: MapAccess<'de>,
+            {
+                // We need to see all the keys to know which kind of map this
+                // is, so buffer the whole thing first
+                let map = serde_json::Map::deserialize(
+                    MapAccessDeserializer::new(map),
+                )?;
+                let is_per_shell = !map.is_empty()
+                    && map
+                        .keys()
+                        .all(|key| ShellValueSources::KEYS.contains(&&**key));
+                let value = serde_json::Value::Object(map);
+                if is_per_shell {
+                    ShellValueSources::deserialize(value)
+                        .map(VariableDefinition::PerShell)
+                } else {
+                    ValueSource::deserialize(value)
+                        .map(VariableDefinition::from)
+                }
+                .map_err(de::Error::custom)
+            }
+
+            fn visit_seq<A>(self, seq: A) -> Result<Self::Value, A::Error>
+            where
+                A: SeqAccess<'de>,
+            {
+                let value_sources = Vec::<ValueSource>::deserialize(
+                    SeqAccessDeserializer::new(seq),
+                )?;
+                if value_sources.is_empty() {
+                    return Err(de::Error::custom(
+                        "List of value sources cannot be empty",
+                    ));
+                }
+                Ok(VariableDefinition::Candidates(value_sources))
+            }
+        }
+
+        deserializer.deserialize_any(VariableDefinitionVisitor)
+    }
+}
+
+// Custom deserialization for SideEffect. A map with a `type` field is a
+// built-in side effect. Anything else is a pair of shell commands
+impl<'de> Deserialize<'de> for SideEffect {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct ShellSideEffect {
+            setup: Option<ShellCommand>,
+            teardown: Option<ShellCommand>,
+            #[serde(default)]
+            background: bool,
+        }
+
+        let map = serde_json::Map::deserialize(deserializer)?;
+        let is_builtin = map.contains_key("type");
+        let value = serde_json::Value::Object(map);
+        if is_builtin {
+            BuiltinSideEffect::deserialize(value).map(SideEffect::Builtin)
+        } else {
+            ShellSideEffect::deserialize(value).map(
+                |ShellSideEffect {
+                     setup,
+                     teardown,
+                     background,
+                 }| SideEffect::Shell {
+                    setup,
+                    teardown,
+                    background,
+                },
+            )
+        }
+        .map_err(de::Error::custom)
+    }
+}
+
 // Deserialize Name using its FromStr
 impl<'de> Deserialize<'de> for Name {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
@@ -89,3 +229,45 @@ impl<'de> Deserialize<'de> for ProfileReference {
         FromStr::from_str(&s).map_err(de::Error::custom)
     }
 }
+
+// Serialize ConfigDuration using its Display
+impl Serialize for ConfigDuration {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+// Deserialize ConfigDuration using its FromStr
+impl<'de> Deserialize<'de> for ConfigDuration {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        FromStr::from_str(&s).map_err(de::Error::custom)
+    }
+}
+
+// Serialize SensitivePattern using its Display
+impl Serialize for SensitivePattern {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+// Deserialize SensitivePattern using its FromStr
+impl<'de> Deserialize<'de> for SensitivePattern {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        FromStr::from_str(&s).map_err(de::Error::custom)
+    }
+}
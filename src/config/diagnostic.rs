@@ -0,0 +1,196 @@
+//! Rendering config parse errors, with enough context to find the problem
+
+use std::{fmt::Write, ops::Range, path::Path};
+
+/// Number of source lines to show before and after the offending line
+const CONTEXT_LINES: usize = 2;
+/// Minimum similarity for an unknown field to be matched to a known one, from
+/// 0 (nothing in common) to 1 (identical)
+const SUGGESTION_THRESHOLD: f64 = 0.8;
+
+/// Render an error from parsing a config file. This includes the file path,
+/// the location of the error, a snippet of the surrounding source, and for
+/// unknown fields, a suggestion for what the user may have meant.
+pub(super) fn render_toml_error(
+    path: &Path,
+    content: &str,
+    error: &toml::de::Error,
+) -> String {
+    let mut output = format!("Error parsing file {path:?}");
+    let location = error.span().map(|span| Location::new(content, span));
+    if let Some(location) = &location {
+        write!(
+            output,
+            " at line {}, column {}",
+            location.line + 1,
+            location.column + 1
+        )
+        .unwrap();
+    }
+    write!(output, ": {}", error.message()).unwrap();
+    if let Some(suggestion) = suggest_field(error.message()) {
+        write!(output, "\nDid you mean `{suggestion}`?").unwrap();
+    }
+    if let Some(location) = &location {
+        output.push('\n');
+        output.push_str(&snippet(content, location));
+    }
+    output
+}
+
+/// Position of an error within a file, all 0-indexed
+#[derive(Debug, PartialEq)]
+struct Location {
+    line: usize,
+    column: usize,
+    /// Number of characters to underline. Always at least 1
+    length: usize,
+}
+
+impl Location {
+    /// Find the line and column of a byte span in the source. Spans that run
+    /// past the end of their first line are cut off there.
+    fn new(content: &str, span: Range<usize>) -> Self {
+        let start = span.start.min(content.len());
+        let before = &content[..start];
+        let line = before.matches('\n').count();
+        let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+        let column = before[line_start..].chars().count();
+        let length = content[start..span.end.clamp(start, content.len())]
+            .lines()
+            .next()
+            .map_or(0, |text| text.chars().count())
+            .max(1);
+        Self {
+            line,
+            column,
+            length,
+        }
+    }
+}
+
+/// Show the lines around an error, with the error underlined
+fn snippet(content: &str, location: &Location) -> String {
+    let lines: Vec<&str> = content.lines().collect();
+    let first = location.line.saturating_sub(CONTEXT_LINES);
+    let last = (location.line + CONTEXT_LINES).min(lines.len().max(1) - 1);
+    // Width of the widest line number, so the gutters line up
+    let width = (last + 1).to_string().len();
+
+    let mut output = format!("{:width$} |\n", "");
+    for number in first..=last {
+        let line = lines.get(number).copied().unwrap_or_default();
+        writeln!(output, "{:>width$} | {line}", number + 1).unwrap();
+        if number == location.line {
+            writeln!(
+                output,
+                "{:width$} | {}{}",
+                "",
+                " ".repeat(location.column),
+                "^".repeat(location.length)
+            )
+            .unwrap();
+        }
+    }
+    output
+}
+
+/// If the error is for an unknown field, find the expected field that's most
+/// similar to it. The message is formatted by serde as:
+/// ``unknown field `x`, expected one of `a`, `b` `` or
+/// ``unknown field `x`, expected `a` ``
+fn suggest_field(message: &str) -> Option<&str> {
+    let rest = message.strip_prefix("unknown field ")?;
+    // Every other piece is a quoted field name, starting with the unknown one
+    let mut fields = rest.split('`').skip(1).step_by(2);
+    let unknown = fields.next()?;
+    fields
+        .map(|field| (field, strsim::jaro_winkler(unknown, field)))
+        .filter(|(_, similarity)| *similarity >= SUGGESTION_THRESHOLD)
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(field, _)| field)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indexmap::IndexMap;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+    use serde::Deserialize;
+
+    #[rstest]
+    #[case::start("abc\ndef", 0..1, (0, 0, 1))]
+    #[case::middle("abc\ndef\nghi", 5..7, (1, 1, 2))]
+    // Spans are cut off at the end of the line
+    #[case::multiline("abc\ndef\nghi", 1..6, (0, 1, 2))]
+    #[case::end("abc\n", 4..4, (1, 0, 1))]
+    fn test_location(
+        #[case] content: &str,
+        #[case] span: Range<usize>,
+        #[case] expected: (usize, usize, usize),
+    ) {
+        let (line, column, length) = expected;
+        assert_eq!(
+            Location::new(content, span),
+            Location {
+                line,
+                column,
+                length
+            }
+        );
+    }
+
+    #[rstest]
+    #[case::one_of(
+        "unknown field `varibles`, expected one of `extends`, `variables`",
+        Some("variables")
+    )]
+    #[case::single(
+        "unknown field `extend`, expected `extends`",
+        Some("extends")
+    )]
+    #[case::no_match(
+        "unknown field `xyz`, expected one of `extends`, `variables`",
+        None
+    )]
+    #[case::other_error("invalid type: integer, expected a string", None)]
+    fn test_suggest_field(
+        #[case] message: &str,
+        #[case] expected: Option<&str>,
+    ) {
+        assert_eq!(suggest_field(message), expected);
+    }
+
+    #[test]
+    fn test_render_toml_error() {
+        #[derive(Debug, Deserialize)]
+        #[serde(deny_unknown_fields)]
+        #[allow(dead_code)]
+        struct Profile {
+            extends: Vec<String>,
+            variables: IndexMap<String, String>,
+        }
+
+        let content = "\
+# Comment
+[dev]
+extends = []
+varibles = {A = \"1\"}
+";
+        let error =
+            toml::from_str::<IndexMap<String, Profile>>(content).unwrap_err();
+        assert_eq!(
+            render_toml_error(Path::new("config.toml"), content, &error),
+            "Error parsing file \"config.toml\" at line 4, column 1: \
+            unknown field `varibles`, expected `extends` or `variables`
+Did you mean `variables`?
+  |
+2 | [dev]
+3 | extends = []
+4 | varibles = {A = \"1\"}
+  | ^^^^^^^^
+"
+        );
+    }
+}
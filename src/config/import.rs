@@ -0,0 +1,397 @@
+//! Loading config files, along with any other files they import or include
+
+use super::{
+    diagnostic::render_toml_error, inherit::display_cycle, Config, Definitions,
+    ProfileReference, Source, FILE_NAME, LOCAL_FILE_NAME,
+};
+use crate::glob_files::glob_files;
+use anyhow::{bail, Context};
+use indexmap::{IndexMap, IndexSet};
+use log::{debug, error, Level};
+use std::{
+    fs, mem,
+    path::{Path, PathBuf},
+};
+
+/// Tracks which files have been loaded, so each file is only merged in once,
+/// and import cycles can be detected
+#[derive(Debug, Default)]
+pub(super) struct Imports {
+    /// Every file that's been loaded so far, in load order
+    loaded: IndexMap<PathBuf, Source>,
+    /// Chain of files currently being loaded, each one imported by the last
+    stack: IndexSet<PathBuf>,
+    /// Skip files that fail to parse, instead of failing
+    ignore_invalid: bool,
+    /// Which file each profile and shared variable came from
+    pub(super) definitions: Definitions,
+}
+
+impl Imports {
+    pub(super) fn new(ignore_invalid: bool) -> Self {
+        Self {
+            ignore_invalid,
+            ..Self::default()
+        }
+    }
+
+    /// Get every file that was loaded, in load order
+    pub(super) fn into_loaded(self) -> Vec<Source> {
+        self.loaded.into_values().collect()
+    }
+}
+
+impl Config {
+    /// Load a config file and merge it into this config, then do the same for
+    /// each file that it imports or includes, recursively. The importing file
+    /// takes precedence over the files it imports. Files that were already
+    /// loaded are skipped, so two files can import the same file.
+    pub(super) fn load_file(
+        &mut self,
+        path: &Path,
+        imports: &mut Imports,
+    ) -> anyhow::Result<()> {
+        // Canonicalize so the same file is recognized under different paths
+        let path = path
+            .canonicalize()
+            .with_context(|| format!("Error reading file {path:?}"))?;
+        if let Some(index) = imports.stack.get_index_of(&path) {
+            let cycle: IndexSet<String> = imports.stack[index..]
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect();
+            bail!("Import cycle detected: {}", display_cycle(&cycle));
+        }
+        if imports.loaded.contains_key(&path) {
+            debug!("Skipping {path:?}, already loaded");
+            return Ok(());
+        }
+
+        debug!("Loading config from file {path:?}");
+        let source = imports
+            .loaded
+            .entry(path.clone())
+            .or_insert_with(|| Source::new(path.clone()));
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Error reading file {path:?}"))?;
+        let mut parsed = match toml::from_str::<Config>(&content) {
+            Ok(parsed) => parsed,
+            Err(error) => {
+                let message = render_toml_error(&path, &content, &error);
+                if imports.ignore_invalid {
+                    error!("{message}\n{path:?} will be ignored");
+                    source.invalid = true;
+                    return Ok(());
+                }
+                bail!(message);
+            }
+        };
+        source.applications = parsed.applications.len();
+        source.profiles = parsed
+            .applications
+            .values()
+            .map(|application| application.profiles.len())
+            .sum();
+        debug!("Loaded from file {path:?}: {parsed:?}");
+        // Qualify relative paths to be absolute
+        parsed.qualify(&path);
+        imports.definitions.record(&parsed, &path);
+        let mut imported = mem::take(&mut parsed.imports);
+        for pattern in mem::take(&mut parsed.includes) {
+            let included = glob_files(&pattern)?;
+            debug!("Files included by {pattern:?}: {included:?}");
+            imported.extend(included);
+        }
+        // Overriding the main file is the whole point of the local file, so
+        // don't warn about it
+        let overridden = path.file_name() == Some(FILE_NAME.as_ref())
+            && imports
+                .loaded
+                .contains_key(&path.with_file_name(LOCAL_FILE_NAME));
+        let duplicate_level = if overridden {
+            Level::Debug
+        } else {
+            Level::Warn
+        };
+        self.merge(parsed, &path, duplicate_level);
+
+        imports.stack.insert(path.clone());
+        for import in imported {
+            self.load_file(&import, imports).with_context(|| {
+                format!("Error loading {import:?}, referenced by {path:?}")
+            })?;
+        }
+        imports.stack.pop();
+        Ok(())
+    }
+}
+
+impl Definitions {
+    /// Record everything defined by a loaded file. Anything already recorded
+    /// came from a file with higher precedence, so it's kept
+    fn record(&mut self, config: &Config, path: &Path) {
+        for (application_name, application) in &config.applications {
+            for variable in application.variables.keys() {
+                self.application_variables
+                    .entry((application_name.clone(), variable.clone()))
+                    .or_insert_with(|| path.to_owned());
+            }
+            for profile_name in application.profiles.keys() {
+                let reference = ProfileReference {
+                    application: Some(application_name.clone()),
+                    profile: profile_name.clone(),
+                };
+                self.profiles
+                    .entry(reference)
+                    .or_insert_with(|| path.to_owned());
+            }
+        }
+        for variable in config.defaults.variables.keys() {
+            self.defaults
+                .entry(variable.clone())
+                .or_insert_with(|| path.to_owned());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::Profile,
+        test_util::{file, literal, map, set},
+    };
+    use pretty_assertions::assert_eq;
+    use std::env;
+
+    /// Write config files into a fresh temp directory, and return the path to
+    /// the directory
+    fn write_files(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let directory = env::temp_dir().join(format!("es-test-import-{name}"));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        for (file, content) in files {
+            let path = directory.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        directory.canonicalize().unwrap()
+    }
+
+    /// Imported profiles are available for inheritance, and the importing
+    /// file takes precedence over what it imports
+    #[test]
+    fn test_import() {
+        let directory = write_files(
+            "basic",
+            &[
+                (
+                    "packages/web/.env-select.toml",
+                    r#"
+imports = ["../shared.toml"]
+[applications.web.profiles.dev]
+extends = ["shared/base"]
+variables = {VAR2 = "web"}
+[applications.shared.profiles.base]
+variables = {VAR1 = "web"}
+"#,
+                ),
+                (
+                    "packages/shared.toml",
+                    r#"
+[applications.shared.profiles.base]
+variables = {VAR1 = "shared"}
+[applications.shared.profiles.other]
+variables = {VAR1 = "other"}
+"#,
+                ),
+            ],
+        );
+        let mut config = Config::default();
+        config
+            .load_file(
+                &directory.join("packages/web/.env-select.toml"),
+                &mut Imports::default(),
+            )
+            .unwrap();
+        config.inherit().unwrap();
+
+        let shared = &config.applications[1].profiles;
+        assert_eq!(
+            shared.keys().map(|name| name.as_str()).collect::<Vec<_>>(),
+            ["base", "other"]
+        );
+        assert_eq!(
+            config.applications[0].profiles[0],
+            Profile {
+                extends: set(["shared/base"]),
+                variables: map([
+                    ("VAR1", literal("web")),
+                    ("VAR2", literal("web")),
+                ]),
+                ..Default::default()
+            }
+        );
+    }
+
+    /// Two files can import the same file without it being loaded twice
+    #[test]
+    fn test_import_diamond() {
+        let directory = write_files(
+            "diamond",
+            &[
+                ("root.toml", r#"imports = ["a.toml", "b.toml"]"#),
+                ("a.toml", r#"imports = ["shared.toml"]"#),
+                ("b.toml", r#"imports = ["./shared.toml"]"#),
+                ("shared.toml", "[applications.shared.profiles.base]"),
+            ],
+        );
+        let mut imports = Imports::default();
+        let mut config = Config::default();
+        config
+            .load_file(&directory.join("root.toml"), &mut imports)
+            .unwrap();
+        assert_eq!(imports.loaded.len(), 4);
+        assert_eq!(config.applications.len(), 1);
+    }
+
+    /// Included files are loaded in order of path, and qualified relative to
+    /// their own location
+    #[test]
+    fn test_include() {
+        let directory = write_files(
+            "include",
+            &[
+                (
+                    ".env-select.toml",
+                    r#"
+includes = ["env-select.d/*.toml"]
+[applications.b.profiles.dev]
+variables = {VAR1 = "root"}
+"#,
+                ),
+                (
+                    "env-select.d/b.toml",
+                    r#"
+[applications.b.profiles.dev]
+variables = {VAR1 = "b"}
+[applications.b.profiles.prd]
+variables = {VAR1 = {type = "file", path = "prd.env"}}
+"#,
+                ),
+                ("env-select.d/a.toml", "[applications.a.profiles.dev]"),
+                // Not matched by the pattern
+                ("env-select.d/c.txt", "[applications.c.profiles.dev]"),
+            ],
+        );
+        let mut imports = Imports::default();
+        let mut config = Config::default();
+        config
+            .load_file(&directory.join(".env-select.toml"), &mut imports)
+            .unwrap();
+
+        assert_eq!(
+            imports.into_loaded(),
+            [
+                Source {
+                    applications: 1,
+                    profiles: 1,
+                    ..Source::new(directory.join(".env-select.toml"))
+                },
+                Source {
+                    applications: 1,
+                    profiles: 1,
+                    ..Source::new(directory.join("env-select.d/a.toml"))
+                },
+                Source {
+                    applications: 1,
+                    profiles: 2,
+                    ..Source::new(directory.join("env-select.d/b.toml"))
+                },
+            ]
+        );
+        assert_eq!(
+            config
+                .applications
+                .keys()
+                .map(|name| name.as_str())
+                .collect::<Vec<_>>(),
+            ["b", "a"]
+        );
+        let profiles = &config.applications[0].profiles;
+        assert_eq!(profiles[0].variables, map([("VAR1", literal("root"))]));
+        assert_eq!(
+            profiles[1].variables,
+            map([("VAR1", file(directory.join("env-select.d/prd.env")))])
+        );
+    }
+
+    /// Files that fail to parse are an error, unless they're ignored
+    #[test]
+    fn test_invalid() {
+        let directory = write_files(
+            "invalid",
+            &[
+                ("root.toml", "imports = [\"invalid.toml\"]"),
+                ("invalid.toml", "[applications.server]\nprofile = {}"),
+            ],
+        );
+        let path = directory.join("root.toml");
+
+        let error = Config::default()
+            .load_file(&path, &mut Imports::new(false))
+            .unwrap_err();
+        let message = format!("{error:#}");
+        assert!(
+            message.starts_with(&format!(
+                "Error loading {:?}, referenced by {path:?}: \
+                Error parsing file {:?} at line 2, column 1",
+                directory.join("invalid.toml"),
+                directory.join("invalid.toml"),
+            )),
+            "{message}"
+        );
+
+        let mut config = Config::default();
+        let mut imports = Imports::new(true);
+        config.load_file(&path, &mut imports).unwrap();
+        assert_eq!(config, Config::default());
+        // The invalid file is still recorded, so it can be reported
+        assert_eq!(
+            imports.into_loaded(),
+            vec![
+                Source::new(path),
+                Source {
+                    invalid: true,
+                    ..Source::new(directory.join("invalid.toml"))
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_import_cycle() {
+        let directory = write_files(
+            "cycle",
+            &[
+                ("root.toml", r#"imports = ["a.toml"]"#),
+                ("a.toml", r#"imports = ["b.toml"]"#),
+                ("b.toml", r#"imports = ["a.toml"]"#),
+            ],
+        );
+        let error = Config::default()
+            .load_file(&directory.join("root.toml"), &mut Imports::default())
+            .unwrap_err();
+        let a = directory.join("a.toml");
+        let b = directory.join("b.toml");
+        assert_eq!(
+            error.root_cause().to_string(),
+            format!(
+                "Import cycle detected: {} -> {} -> {}",
+                a.display(),
+                b.display(),
+                a.display()
+            )
+        );
+    }
+}
@@ -1,42 +1,154 @@
 //! Utilitied related to profile inheritance resolution
 
-use crate::config::{Config, MapExt, Profile, ProfileReference};
+use crate::config::{
+    Config, Defaults, MapExt, Name, Profile, ProfileReference,
+    VariableDefinition,
+};
 use anyhow::{anyhow, bail};
+use derive_more::Display;
 use indexmap::{IndexMap, IndexSet};
 use log::trace;
-use std::{collections::HashMap, fmt::Display, hash::Hash};
+use std::{collections::HashMap, fmt, hash::Hash};
+
+/// Where each variable of each profile was defined, including variables that
+/// a profile defines itself
+pub type Origins = HashMap<ProfileReference, IndexMap<String, Origin>>;
+
+/// Where a variable was defined, and which profiles it was inherited through
+#[derive(Clone, Debug, Display, Eq, PartialEq)]
+#[display("{definer}")]
+pub struct Origin {
+    /// Whatever defined the variable
+    pub definer: Definer,
+    /// Profiles the variable was inherited through, between the profile that
+    /// has it and the definer, nearest first
+    pub via: Vec<ProfileReference>,
+}
+
+/// Something that can define a variable
+#[derive(Clone, Debug, Display, Eq, PartialEq)]
+pub enum Definer {
+    #[display("profile {_0}")]
+    Profile(ProfileReference),
+    /// Shared variable of an application
+    #[display("application {_0}")]
+    Application(Name),
+    /// Global defaults
+    #[display("defaults")]
+    Defaults,
+}
+
+impl Origin {
+    fn new(definer: Definer) -> Self {
+        Self {
+            definer,
+            via: Vec::new(),
+        }
+    }
+
+    /// Was the variable inherited, rather than defined by the given profile
+    /// itself?
+    pub fn is_inherited(&self, reference: &ProfileReference) -> bool {
+        !matches!(&self.definer, Definer::Profile(definer) if definer == reference)
+    }
+
+    /// Get the origin of a variable for a child that inherits it from the
+    /// given parent
+    fn through(mut self, parent: &ProfileReference) -> Self {
+        if self.is_inherited(parent) {
+            self.via.insert(0, parent.clone());
+        }
+        self
+    }
+}
 
 impl Config {
     /// Resolve inheritance for all profiles. Each profile will have its parents
-    /// (as specified in its `extends` field) merged into it, recursively.
-    pub(super) fn inherit(&mut self) -> anyhow::Result<()> {
+    /// (as specified in its `extends` field) merged into it, recursively. Then
+    /// its application's shared variables are merged in, and finally the
+    /// global defaults, underneath everything else. Return where each
+    /// variable came from.
+    pub(super) fn inherit(&mut self) -> anyhow::Result<Origins> {
         let mut resolver = InheritanceResolver::from_config(self)?;
-        resolver.resolve_all()
+        resolver.resolve_all()?;
+        let mut origins = resolver.origins;
+
+        // Defaults are merged in only after all parents are resolved. If they
+        // were merged during resolution, a child would get their side effects
+        // twice: once from its parent and once directly
+        if !self.defaults.is_empty() {
+            trace!("Merging defaults into all profiles");
+            for (application_name, application) in &mut self.applications {
+                for (profile_name, profile) in &mut application.profiles {
+                    let reference = ProfileReference {
+                        application: Some(application_name.clone()),
+                        profile: profile_name.clone(),
+                    };
+                    let defaults = self.defaults.clone();
+                    record_origins(
+                        &mut origins,
+                        &reference,
+                        profile,
+                        defaults.variables.keys(),
+                        |_| Origin::new(Definer::Defaults),
+                    );
+                    profile.variables.inherit_from(defaults.variables);
+                    profile.pre_export.inherit_from(defaults.pre_export);
+                    profile.post_export.inherit_from(defaults.post_export);
+                }
+            }
+        }
+        Ok(origins)
     }
 }
 
 struct InheritanceResolver<'a> {
     profiles: HashMap<ProfileReference, &'a mut Profile>,
     unresolved: IndexMap<ProfileReference, IndexSet<ProfileReference>>,
+    /// Shared variables for each application that has any
+    application_variables: HashMap<Name, IndexMap<String, VariableDefinition>>,
+    origins: Origins,
 }
 
 impl<'a> InheritanceResolver<'a> {
     fn from_config(config: &'a mut Config) -> anyhow::Result<Self> {
         let mut profiles = HashMap::new();
         let mut unresolved = IndexMap::new();
+        let mut application_variables = HashMap::new();
+        let mut origins = Origins::new();
 
         // Flatten profiles into a map, keyed by their path. For each profile,
         // we'll also track a list of parents that haven't been resolved+merged
         // in yet
         for (application_name, application) in &mut config.applications {
+            let has_variables = !application.variables.is_empty();
+            if has_variables {
+                application_variables.insert(
+                    application_name.clone(),
+                    application.variables.clone(),
+                );
+            }
+
             for (profile_name, profile) in &mut application.profiles {
                 let reference = ProfileReference {
                     application: Some(application_name.clone()),
                     profile: profile_name.clone(),
                 };
+                origins.insert(
+                    reference.clone(),
+                    profile
+                        .variables
+                        .keys()
+                        .map(|variable| {
+                            let definer = Definer::Profile(reference.clone());
+                            (variable.clone(), Origin::new(definer))
+                        })
+                        .collect(),
+                );
 
-                // Any profile with parents is deemed unresolved
-                if !profile.extends.is_empty() {
+                // Any profile with parents or shared variables is deemed
+                // unresolved
+                if !profile.extends.is_empty() || has_variables {
                     // All references should be made absolute during
                     // qualification, this is just a safety check
                     for parent in &profile.extends {
@@ -64,6 +176,8 @@ impl<'a> InheritanceResolver<'a> {
         Ok(Self {
             profiles,
             unresolved,
+            application_variables,
+            origins,
         })
     }
 
@@ -119,6 +233,7 @@ impl<'a> InheritanceResolver<'a> {
 
             // We know parent is resolved now, merge in their values
             trace!("Merging values from {parent} into {reference}");
+            let parent_reference = parent;
             let parent = (*self
                 .profiles
                 .get(parent)
@@ -128,16 +243,67 @@ impl<'a> InheritanceResolver<'a> {
                 .profiles
                 .get_mut(&reference)
                 .ok_or_else(|| anyhow!("Unknown profile: {}", reference))?;
+            // Variables the parent inherited keep their original definer
+            let parent_origins = self
+                .origins
+                .get(parent_reference)
+                .cloned()
+                .unwrap_or_default();
+            record_origins(
+                &mut self.origins,
+                &reference,
+                child,
+                parent.variables.keys(),
+                |variable| {
+                    parent_origins
+                        .get(variable)
+                        .cloned()
+                        .unwrap_or_else(|| {
+                            Origin::new(Definer::Profile(
+                                parent_reference.clone(),
+                            ))
+                        })
+                        .through(parent_reference)
+                },
+            );
             child.inherit_from(parent);
         }
+
+        // Application variables go in last, which puts them *underneath* all
+        // parents: anything a parent or the profile itself defines wins. This
+        // happens after parents are resolved, so a parent from another
+        // application brings along that application's variables.
+        if let Some((application, variables)) =
+            reference.application.as_ref().and_then(|application| {
+                Some((
+                    application,
+                    self.application_variables.get(application)?,
+                ))
+            })
+        {
+            trace!("Merging application variables into {reference}");
+            let child = self
+                .profiles
+                .get_mut(&reference)
+                .ok_or_else(|| anyhow!("Unknown profile: {}", reference))?;
+            record_origins(
+                &mut self.origins,
+                &reference,
+                child,
+                variables.keys(),
+                |_| Origin::new(Definer::Application(application.clone())),
+            );
+            child.variables.inherit_from(variables.clone());
+        }
         Ok(())
     }
 }
 
-trait Inherit {
+pub(super) trait Inherit {
     /// Merge a parent into this child. For map-like fields, the child's entries
     /// will take precedence. For list-like fields, the parent will be appended
-    /// to the *beginning* of the child.
+    /// to the *beginning* of the child. For optional fields, the parent is only
+    /// used if the child has no value.
     fn inherit_from(&mut self, parent: Self);
 }
 
@@ -146,6 +312,27 @@ impl Inherit for Profile {
         self.variables.inherit_from(parent.variables);
         self.pre_export.inherit_from(parent.pre_export);
         self.post_export.inherit_from(parent.post_export);
+        self.kubernetes_context
+            .inherit_from(parent.kubernetes_context);
+        self.kubernetes_namespace
+            .inherit_from(parent.kubernetes_namespace);
+        self.cwd.inherit_from(parent.cwd);
+    }
+}
+
+impl Inherit for Defaults {
+    fn inherit_from(&mut self, parent: Self) {
+        self.variables.inherit_from(parent.variables);
+        self.pre_export.inherit_from(parent.pre_export);
+        self.post_export.inherit_from(parent.post_export);
+    }
+}
+
+impl<T> Inherit for Option<T> {
+    fn inherit_from(&mut self, parent: Self) {
+        if self.is_none() {
+            *self = parent;
+        }
     }
 }
 
@@ -166,8 +353,54 @@ impl<T> Inherit for Vec<T> {
     }
 }
 
+/// Combine several profiles into one, as if each one extended the one before
+/// it. Later profiles take precedence on conflicts, and side effects run in
+/// the order the profiles are given. Fields that aren't inherited are left
+/// empty, except that the result is deprecated if any of the profiles is.
+pub(super) fn compose<'a>(
+    profiles: impl IntoIterator<Item = &'a Profile>,
+) -> Profile {
+    let mut composed = Profile::default();
+    let mut deprecated = None;
+    for profile in profiles {
+        if deprecated.is_none() && profile.is_deprecated() {
+            deprecated = profile.deprecated.clone();
+        }
+        let mut child = profile.clone();
+        child.inherit_from(composed);
+        composed = child;
+    }
+    Profile {
+        aliases: IndexSet::new(),
+        description: None,
+        hidden: false,
+        deprecated,
+        extends: IndexSet::new(),
+        ..composed
+    }
+}
+
+/// Record the origin of each variable that a profile is about to inherit.
+/// Variables the profile already has keep their existing origin
+fn record_origins<'a>(
+    origins: &mut Origins,
+    reference: &ProfileReference,
+    profile: &Profile,
+    variables: impl IntoIterator<Item = &'a String>,
+    origin: impl Fn(&str) -> Origin,
+) {
+    for variable in variables {
+        if !profile.variables.contains_key(variable) {
+            origins
+                .entry(reference.clone())
+                .or_default()
+                .insert(variable.clone(), origin(variable));
+        }
+    }
+}
+
 /// Pretty print a cycle chain
-fn display_cycle<T: Display>(nodes: &IndexSet<T>) -> String {
+pub(super) fn display_cycle<T: fmt::Display>(nodes: &IndexSet<T>) -> String {
     let mut output = String::new();
     for node in nodes {
         output.push_str(&node.to_string());
@@ -181,9 +414,13 @@ fn display_cycle<T: Display>(nodes: &IndexSet<T>) -> String {
 #[cfg(test)]
 mod tests {
     use crate::{
-        config::Profile,
-        test_util::{config, literal, map, set, side_effect},
+        config::{
+            inherit::{Definer, Origin, Origins},
+            Deprecated, Profile, ProfileReference,
+        },
+        test_util::{config, literal, map, set, side_effect, unset},
     };
+    use indexmap::IndexMap;
     use pretty_assertions::assert_eq;
 
     #[test]
@@ -203,7 +440,15 @@ mod tests {
                         variables: map([
                             ("VAR1", literal("base")),
                             ("VAR2", literal("base")),
+                            ("VAR4", literal("base")),
                         ]),
+                        kubernetes_context: Some("base".into()),
+                        kubernetes_namespace: Some("base".into()),
+                        cwd: Some("/base".into()),
+                        description: Some("base".into()),
+                        hidden: true,
+                        deprecated: Some(Deprecated::Flag(true)),
+                        ..Default::default()
                     },
                 ),
                 (
@@ -220,7 +465,14 @@ mod tests {
                             ("VAR1", literal("child")),
                             // VAR2 comes from base
                             ("VAR3", literal("child")),
+                            // Child can remove a variable from the parent
+                            ("VAR4", unset()),
                         ]),
+                        // Context comes from base
+                        kubernetes_context: None,
+                        kubernetes_namespace: Some("child".into()),
+                        cwd: Some("/child".into()),
+                        ..Default::default()
                     },
                 ),
             ],
@@ -245,7 +497,15 @@ mod tests {
                             variables: map([
                                 ("VAR1", literal("base")),
                                 ("VAR2", literal("base")),
+                                ("VAR4", literal("base")),
                             ]),
+                            kubernetes_context: Some("base".into()),
+                            kubernetes_namespace: Some("base".into()),
+                            cwd: Some("/base".into()),
+                            description: Some("base".into()),
+                            hidden: true,
+                            deprecated: Some(Deprecated::Flag(true)),
+                            ..Default::default()
                         },
                     ),
                     (
@@ -264,7 +524,13 @@ mod tests {
                                 ("VAR1", literal("child")),
                                 ("VAR3", literal("child")),
                                 ("VAR2", literal("base")),
+                                ("VAR4", unset()),
                             ]),
+                            kubernetes_context: Some("base".into()),
+                            kubernetes_namespace: Some("child".into()),
+                            cwd: Some("/child".into()),
+                            // Description, hidden, and deprecated aren't inherited
+                            ..Default::default()
                         },
                     ),
                 ]
@@ -272,6 +538,193 @@ mod tests {
         );
     }
 
+    /// Application variables are shared by every profile in the application,
+    /// underneath anything defined by the profile or its parents
+    #[test]
+    fn test_inherit_application_variables() {
+        let application_variables = map([
+            ("VAR1", literal("app1")),
+            ("VAR2", literal("app1")),
+            ("VAR3", literal("app1")),
+        ]);
+        let mut cfg = config(vec![
+            (
+                "app1",
+                vec![
+                    (
+                        "base",
+                        Profile {
+                            variables: map([("VAR2", literal("base"))]),
+                            ..Default::default()
+                        },
+                    ),
+                    (
+                        "child",
+                        Profile {
+                            extends: set(["app1/base"]),
+                            variables: map([("VAR3", literal("child"))]),
+                            ..Default::default()
+                        },
+                    ),
+                ],
+            ),
+            (
+                "app2",
+                vec![(
+                    "other",
+                    Profile {
+                        extends: set(["app1/base"]),
+                        ..Default::default()
+                    },
+                )],
+            ),
+        ]);
+        cfg.applications[0].variables = application_variables.clone();
+        cfg.inherit().expect("Error resolving valid inheritance");
+
+        let mut expected = config(vec![
+            (
+                "app1",
+                vec![
+                    (
+                        "base",
+                        Profile {
+                            variables: map([
+                                ("VAR1", literal("app1")),
+                                ("VAR2", literal("base")),
+                                ("VAR3", literal("app1")),
+                            ]),
+                            ..Default::default()
+                        },
+                    ),
+                    (
+                        "child",
+                        Profile {
+                            extends: set(["app1/base"]),
+                            variables: map([
+                                ("VAR1", literal("app1")),
+                                ("VAR2", literal("base")),
+                                ("VAR3", literal("child")),
+                            ]),
+                            ..Default::default()
+                        },
+                    ),
+                ],
+            ),
+            (
+                "app2",
+                vec![(
+                    "other",
+                    Profile {
+                        extends: set(["app1/base"]),
+                        // A parent from another application brings its
+                        // application's variables along
+                        variables: map([
+                            ("VAR1", literal("app1")),
+                            ("VAR2", literal("base")),
+                            ("VAR3", literal("app1")),
+                        ]),
+                        ..Default::default()
+                    },
+                )],
+            ),
+        ]);
+        expected.applications[0].variables = application_variables;
+        assert_eq!(cfg, expected);
+    }
+
+    /// Each variable is traced back to where it was defined, even through
+    /// multiple levels of inheritance
+    #[test]
+    fn test_inherit_origins() {
+        let mut cfg = config(vec![
+            (
+                "app1",
+                vec![
+                    (
+                        "base",
+                        Profile {
+                            variables: map([("BASE", literal("base"))]),
+                            ..Default::default()
+                        },
+                    ),
+                    (
+                        "child1",
+                        Profile {
+                            extends: set(["app1/base"]),
+                            variables: map([
+                                ("CHILD1", literal("child1")),
+                                // Overriding replaces the origin
+                                ("SHARED", literal("child1")),
+                            ]),
+                            ..Default::default()
+                        },
+                    ),
+                ],
+            ),
+            (
+                "app2",
+                vec![(
+                    "child2",
+                    Profile {
+                        extends: set(["app1/child1"]),
+                        variables: map([("CHILD2", literal("child2"))]),
+                        ..Default::default()
+                    },
+                )],
+            ),
+        ]);
+        cfg.applications[0].variables = map([("SHARED", literal("app1"))]);
+        cfg.defaults.variables = map([("DEFAULT", literal("default"))]);
+        let origins = cfg.inherit().expect("Error resolving valid inheritance");
+
+        let reference = |path: &str| path.parse::<ProfileReference>().unwrap();
+        let origin = |definer: Definer, via: &[&str]| Origin {
+            definer,
+            via: via.iter().map(|path| reference(path)).collect(),
+        };
+        let profile = |path: &str| Definer::Profile(reference(path));
+        assert_eq!(
+            origins,
+            Origins::from([
+                (
+                    reference("app1/base"),
+                    IndexMap::from([
+                        ("BASE".into(), origin(profile("app1/base"), &[])),
+                        (
+                            "SHARED".into(),
+                            origin(Definer::Application("app1".into()), &[]),
+                        ),
+                        ("DEFAULT".into(), origin(Definer::Defaults, &[])),
+                    ]),
+                ),
+                (
+                    reference("app1/child1"),
+                    IndexMap::from([
+                        ("CHILD1".into(), origin(profile("app1/child1"), &[])),
+                        ("SHARED".into(), origin(profile("app1/child1"), &[])),
+                        ("BASE".into(), origin(profile("app1/base"), &[])),
+                        ("DEFAULT".into(), origin(Definer::Defaults, &[])),
+                    ]),
+                ),
+                (
+                    reference("app2/child2"),
+                    IndexMap::from([
+                        ("CHILD2".into(), origin(profile("app2/child2"), &[])),
+                        // Inherited through an intermediate profile
+                        (
+                            "BASE".into(),
+                            origin(profile("app1/base"), &["app1/child1"]),
+                        ),
+                        ("CHILD1".into(), origin(profile("app1/child1"), &[])),
+                        ("SHARED".into(), origin(profile("app1/child1"), &[])),
+                        ("DEFAULT".into(), origin(Definer::Defaults, &[])),
+                    ]),
+                ),
+            ])
+        );
+    }
+
     #[test]
     fn test_inherit_linear() {
         let mut cfg = config(vec![
@@ -293,6 +746,7 @@ mod tests {
                                 ("VAR1", literal("base")),
                                 ("VAR2", literal("base")),
                             ]),
+                            ..Default::default()
                         },
                     ),
                     (
@@ -312,6 +766,7 @@ mod tests {
                                 // VAR2 comes from base
                                 ("VAR3", literal("child1")),
                             ]),
+                            ..Default::default()
                         },
                     ),
                 ],
@@ -336,6 +791,7 @@ mod tests {
                             // VAR3 comes from child1
                             ("VAR4", literal("child2")),
                         ]),
+                        ..Default::default()
                     },
                 )],
             ),
@@ -362,6 +818,7 @@ mod tests {
                                     ("VAR1", literal("base")),
                                     ("VAR2", literal("base")),
                                 ]),
+                                ..Default::default()
                             },
                         ),
                         (
@@ -381,6 +838,7 @@ mod tests {
                                     ("VAR1", literal("child1")),
                                     ("VAR3", literal("child1")),
                                 ]),
+                                ..Default::default()
                             },
                         ),
                     ],
@@ -407,6 +865,7 @@ mod tests {
                                 ("VAR4", literal("child2")),
                                 ("VAR3", literal("child1")),
                             ]),
+                            ..Default::default()
                         },
                     )],
                 ),
@@ -441,6 +900,7 @@ mod tests {
                                 ("BASE_VAR1", literal("base1")),
                                 ("BASE_VAR2", literal("base1")),
                             ]),
+                            ..Default::default()
                         },
                     ),
                     (
@@ -459,6 +919,7 @@ mod tests {
                                 ("BASE_VAR2", literal("prof2")),
                                 ("CHILD_VAR1", literal("prof2")),
                             ]),
+                            ..Default::default()
                         },
                     ),
                     (
@@ -477,6 +938,7 @@ mod tests {
                                 ("BASE_VAR3", literal("base2")),
                                 ("BASE_VAR4", literal("base2")),
                             ]),
+                            ..Default::default()
                         },
                     ),
                 ],
@@ -497,6 +959,7 @@ mod tests {
                                 "prof1 post",
                             )],
                             variables: map([("BASE_VAR2", literal("prof1"))]),
+                            ..Default::default()
                         },
                     ),
                     (
@@ -515,6 +978,7 @@ mod tests {
                                 ("CHILD_VAR2", literal("prof3")),
                                 ("CHILD_VAR3", literal("prof3")),
                             ]),
+                            ..Default::default()
                         },
                     ),
                     (
@@ -533,6 +997,7 @@ mod tests {
                                 ("CHILD_VAR4", literal("prof4")),
                                 ("BASE_VAR4", literal("prof4")),
                             ]),
+                            ..Default::default()
                         },
                     ),
                     (
@@ -552,6 +1017,7 @@ mod tests {
                                 "prof5 post",
                             )],
                             variables: map([("CHILD_VAR5", literal("prof5"))]),
+                            ..Default::default()
                         },
                     ),
                 ],
@@ -571,6 +1037,7 @@ mod tests {
                                 "solo post",
                             )],
                             variables: map([("SOLO_VAR1", literal("solo1"))]),
+                            ..Default::default()
                         },
                     ),
                     (
@@ -589,6 +1056,7 @@ mod tests {
                                 ("CHILD_VAR1", literal("striker1")),
                                 ("CHILD_VAR2", literal("striker1")),
                             ]),
+                            ..Default::default()
                         },
                     ),
                     (
@@ -607,6 +1075,7 @@ mod tests {
                                 ("CHILD_VAR1", literal("striker2")),
                                 ("CHILD_VAR3", literal("striker2")),
                             ]),
+                            ..Default::default()
                         },
                     ),
                 ],
@@ -635,6 +1104,7 @@ mod tests {
                                     ("BASE_VAR1", literal("base1")),
                                     ("BASE_VAR2", literal("base1")),
                                 ]),
+                                ..Default::default()
                             },
                         ),
                         (
@@ -656,6 +1126,7 @@ mod tests {
                                     ("BASE_VAR2", literal("prof2")),
                                     ("CHILD_VAR1", literal("prof2")),
                                 ]),
+                                ..Default::default()
                             },
                         ),
                         (
@@ -674,6 +1145,7 @@ mod tests {
                                     ("BASE_VAR3", literal("base2")),
                                     ("BASE_VAR4", literal("base2")),
                                 ]),
+                                ..Default::default()
                             },
                         ),
                     ],
@@ -697,6 +1169,7 @@ mod tests {
                                     ("BASE_VAR1", literal("base1")),
                                     ("BASE_VAR2", literal("prof1")),
                                 ]),
+                                ..Default::default()
                             },
                         ),
                         (
@@ -733,6 +1206,7 @@ mod tests {
                                     ("CHILD_VAR2", literal("prof3")),
                                     ("CHILD_VAR3", literal("prof3")),
                                 ]),
+                                ..Default::default()
                             },
                         ),
                         (
@@ -761,6 +1235,7 @@ mod tests {
                                     ("CHILD_VAR4", literal("prof4")),
                                     ("BASE_VAR4", literal("prof4")),
                                 ]),
+                                ..Default::default()
                             },
                         ),
                         (
@@ -818,6 +1293,7 @@ mod tests {
                                     // me
                                     ("CHILD_VAR5", literal("prof5")),
                                 ]),
+                                ..Default::default()
                             },
                         ),
                     ],
@@ -840,6 +1316,7 @@ mod tests {
                                     "SOLO_VAR1",
                                     literal("solo1"),
                                 )]),
+                                ..Default::default()
                             },
                         ),
                         (
@@ -858,6 +1335,7 @@ mod tests {
                                     ("CHILD_VAR1", literal("striker1")),
                                     ("CHILD_VAR2", literal("striker1")),
                                 ]),
+                                ..Default::default()
                             },
                         ),
                         (
@@ -885,6 +1363,7 @@ mod tests {
                                     ("CHILD_VAR1", literal("striker2")),
                                     ("CHILD_VAR3", literal("striker2")),
                                 ]),
+                                ..Default::default()
                             },
                         ),
                     ],
@@ -904,7 +1383,8 @@ mod tests {
                     extends: set(["app1/child1"]),
                     pre_export: vec![],
                     post_export: vec![],
-                    variables: map([]),
+                    variables: IndexMap::new(),
+                    ..Default::default()
                 },
             )],
         )]);
@@ -925,7 +1405,8 @@ mod tests {
                         extends: set(["app1/child2"]),
                         pre_export: vec![],
                         post_export: vec![],
-                        variables: map([]),
+                        variables: IndexMap::new(),
+                        ..Default::default()
                     },
                 ),
                 (
@@ -934,7 +1415,8 @@ mod tests {
                         extends: set(["app1/child1"]),
                         pre_export: vec![],
                         post_export: vec![],
-                        variables: map([]),
+                        variables: IndexMap::new(),
+                        ..Default::default()
                     },
                 ),
             ],
@@ -957,7 +1439,8 @@ mod tests {
                         extends: set(["app1/child3"]),
                         pre_export: vec![],
                         post_export: vec![],
-                        variables: map([]),
+                        variables: IndexMap::new(),
+                        ..Default::default()
                     },
                 ),
                 (
@@ -966,7 +1449,8 @@ mod tests {
                         extends: set(["app1/child1"]),
                         pre_export: vec![],
                         post_export: vec![],
-                        variables: map([]),
+                        variables: IndexMap::new(),
+                        ..Default::default()
                     },
                 ),
                 (
@@ -975,7 +1459,8 @@ mod tests {
                         extends: set(["app1/child2"]),
                         pre_export: vec![],
                         post_export: vec![],
-                        variables: map([]),
+                        variables: IndexMap::new(),
+                        ..Default::default()
                     },
                 ),
             ],
@@ -999,7 +1484,8 @@ mod tests {
                     extends: set(["app1/base"]),
                     pre_export: vec![],
                     post_export: vec![],
-                    variables: map([]),
+                    variables: IndexMap::new(),
+                    ..Default::default()
                 },
             )],
         )]);
@@ -1,7 +1,7 @@
-use super::Config;
+use super::{inherit::Inherit, Config};
 use crate::config::ProfileReference;
 use indexmap::{map::Entry, IndexMap};
-use log::warn;
+use log::{log, Level};
 use std::{hash::Hash, path::Path};
 
 impl Config {
@@ -9,8 +9,27 @@ impl Config {
     /// simpler. This is used only for merging multiple config files together.
     /// We only merge down to the profile level. If the same profile is defined
     /// in both files, our version will be used and the other will be thrown
-    /// out.
-    pub(super) fn merge(&mut self, other: Self, other_path: &Path) {
+    /// out. The same goes for top-level settings. Defaults are merged like
+    /// inherited profiles. Duplicate definitions are logged at the given
+    /// level, which is usually a warning, but can be lowered when duplicates
+    /// are expected, i.e. for a local override file.
+    pub(super) fn merge(
+        &mut self,
+        other: Self,
+        other_path: &Path,
+        duplicate_level: Level,
+    ) {
+        self.strict_templates =
+            self.strict_templates.or(other.strict_templates);
+        self.strict_conflicts =
+            self.strict_conflicts.or(other.strict_conflicts);
+        self.masking = self.masking.or(other.masking);
+        self.sensitive_patterns =
+            self.sensitive_patterns.take().or(other.sensitive_patterns);
+        // Defaults from the other file are treated like a parent, so ours take
+        // precedence
+        self.defaults.inherit_from(other.defaults);
+
         // Merge applications together. It would've been nice to use the trait
         // pattern like Qualify and Inherit, but it turns out it complicates
         // this a lot because of the need for context passing.
@@ -18,6 +37,26 @@ impl Config {
             &mut self.applications,
             other.applications,
             |application_name, self_application, other_application| {
+                // An application can be aliased from either file
+                self_application.aliases.extend(other_application.aliases);
+                self_application.description = self_application
+                    .description
+                    .take()
+                    .or(other_application.description);
+                // Merge shared variables together
+                merge_map(
+                    &mut self_application.variables,
+                    other_application.variables,
+                    |variable, _, _| {
+                        log!(
+                            duplicate_level,
+                            "Duplicate definition for variable `{variable}` \
+                            in application `{application_name}`. Definition \
+                            from `{}` will not be used.",
+                            other_path.display()
+                        )
+                    },
+                );
                 // Merge profiles together
                 merge_map(
                     &mut self_application.profiles,
@@ -27,7 +66,8 @@ impl Config {
                         // ProfileReference gives us consistent formatting
                         let reference: ProfileReference =
                             (application_name.clone(), profile_name).into();
-                        warn!(
+                        log!(
+                            duplicate_level,
                             "Duplicate definition for profile `{reference}`. \
                             Definition from `{}` will not be used.",
                             other_path.display()
@@ -64,9 +104,10 @@ fn merge_map<K: Clone + Eq + Hash, V>(
 #[cfg(test)]
 mod tests {
     use crate::{
-        config::Profile,
-        test_util::{config, literal, map, set},
+        config::{Config, Defaults, Profile},
+        test_util::{config, literal, map, set, side_effect},
     };
+    use log::Level;
     use pretty_assertions::assert_eq;
     use std::path::PathBuf;
 
@@ -77,12 +118,14 @@ mod tests {
             pre_export: vec![],
             post_export: vec![],
             variables: map([("VARIABLE1", literal("alpha"))]),
+            ..Default::default()
         };
         let beta_profile = Profile {
             extends: set([]),
             pre_export: vec![],
             post_export: vec![],
             variables: map([("VARIABLE1", literal("beta"))]),
+            ..Default::default()
         };
         let mut alpha_config = config(vec![(
             "app1",
@@ -96,7 +139,7 @@ mod tests {
             // Different app - no conflict
             ("app2", vec![("no_conflict", beta_profile.clone())]),
         ]);
-        alpha_config.merge(beta_config, &PathBuf::new());
+        alpha_config.merge(beta_config, &PathBuf::new(), Level::Warn);
         assert_eq!(
             alpha_config,
             config(vec![
@@ -111,4 +154,79 @@ mod tests {
             ])
         );
     }
+
+    /// Settings from the first file take precedence, if they're set
+    #[test]
+    fn test_merge_settings() {
+        let mut alpha_config = Config::default();
+        alpha_config.merge(
+            Config {
+                strict_templates: Some(false),
+                strict_conflicts: Some(true),
+                ..Default::default()
+            },
+            &PathBuf::new(),
+            Level::Warn,
+        );
+        assert_eq!(alpha_config.strict_templates, Some(false));
+        assert_eq!(alpha_config.strict_conflicts, Some(true));
+        alpha_config.merge(
+            Config {
+                strict_templates: Some(true),
+                strict_conflicts: Some(false),
+                ..Default::default()
+            },
+            &PathBuf::new(),
+            Level::Warn,
+        );
+        assert_eq!(alpha_config.strict_templates, Some(false));
+        assert_eq!(alpha_config.strict_conflicts, Some(true));
+    }
+
+    /// Defaults from both files are combined, with the first file's taking
+    /// precedence
+    #[test]
+    fn test_merge_defaults() {
+        let mut alpha_config = Config {
+            defaults: Defaults {
+                variables: map([
+                    ("VARIABLE1", literal("alpha")),
+                    ("VARIABLE2", literal("alpha")),
+                ]),
+                pre_export: vec![side_effect("alpha", "alpha")],
+                post_export: vec![],
+            },
+            ..Default::default()
+        };
+        alpha_config.merge(
+            Config {
+                defaults: Defaults {
+                    variables: map([
+                        ("VARIABLE2", literal("beta")),
+                        ("VARIABLE3", literal("beta")),
+                    ]),
+                    pre_export: vec![side_effect("beta", "beta")],
+                    post_export: vec![side_effect("beta", "beta")],
+                },
+                ..Default::default()
+            },
+            &PathBuf::new(),
+            Level::Warn,
+        );
+        assert_eq!(
+            alpha_config.defaults,
+            Defaults {
+                variables: map([
+                    ("VARIABLE1", literal("alpha")),
+                    ("VARIABLE2", literal("alpha")),
+                    ("VARIABLE3", literal("beta")),
+                ]),
+                pre_export: vec![
+                    side_effect("beta", "beta"),
+                    side_effect("alpha", "alpha"),
+                ],
+                post_export: vec![side_effect("beta", "beta")],
+            }
+        );
+    }
 }
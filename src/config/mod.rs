@@ -1,23 +1,50 @@
 mod cereal;
+mod diagnostic;
+mod import;
 mod inherit;
 mod merge;
 mod qualify;
+mod schema;
 #[cfg(test)]
 mod tests;
 
-use anyhow::{anyhow, bail, Context};
+pub use inherit::{Definer, Origin, Origins};
+pub use schema::schema;
+
+use crate::{
+    active::active_variable, config::import::Imports, runtime_dir::runtime_dir,
+    shell::ShellKind,
+};
+use anyhow::{anyhow, bail};
 use derive_more::{Deref, Display, From};
 use indexmap::{IndexMap, IndexSet};
-use log::{debug, error, info, trace};
+use log::{debug, info, trace};
 use serde::{Deserialize, Serialize};
 use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
     env, fs,
     hash::Hash,
+    mem,
     path::{Path, PathBuf},
     str::FromStr,
+    time::Duration,
 };
 
-const FILE_NAME: &str = ".env-select.toml";
+pub const FILE_NAME: &str = ".env-select.toml";
+/// Optional per-developer file that overrides [FILE_NAME] in the same
+/// directory. Intended to be gitignored
+const LOCAL_FILE_NAME: &str = ".env-select.local.toml";
+/// Name of the user's global config file, within the env-select directory of
+/// the user's config directory
+const GLOBAL_FILE_NAME: &str = "config.toml";
+/// Variable to load a specific config file, instead of scanning for files.
+/// The `--config` argument takes precedence over this
+const CONFIG_FILE_VARIABLE: &str = "ES_CONFIG_FILE";
+/// https://en.wikipedia.org/wiki/PATH_(variable)
+const PATH_VARIABLE: &str = "PATH";
+/// Joins profile names to select several at once, e.g. `dev+debug`
+pub const COMPOSE_SEPARATOR: &str = "+";
 
 /// Add configuration, as loaded from one or more config files. We use
 /// [indexmap::IndexMap] in here to preserve ordering from the input files.
@@ -30,6 +57,87 @@ pub struct Config {
     /// each variable may multiple values to select between. Each value set
     /// is known as a "profile".
     pub applications: IndexMap<Name, Application>,
+    /// Variables and side effects for every profile in every application
+    #[serde(skip_serializing_if = "Defaults::is_empty")]
+    pub defaults: Defaults,
+    /// Other config files to load, relative to this one. Their applications
+    /// and profiles can be selected and extended as if they were defined
+    /// here, but definitions in this file take precedence
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub imports: Vec<PathBuf>,
+    /// Glob patterns of other config files to load, relative to this one.
+    /// Matching files are loaded in order of path, and merged like cascading
+    /// config files. Definitions in this file take precedence
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub includes: Vec<PathBuf>,
+    /// Should templates fail when they reference an unset environment
+    /// variable? If disabled, the reference renders as an empty string.
+    /// Defaults to enabled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub strict_templates: Option<bool>,
+    /// Should it be an error when two variables in a profile set the same
+    /// variable, e.g. a literal and a `multiple` source? If disabled, the
+    /// later one wins and a warning is logged. Defaults to disabled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub strict_conflicts: Option<bool>,
+    /// How sensitive values are shown in `es show env` and the summary
+    /// printed by `es set`. Defaults to full masking
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub masking: Option<Masking>,
+    /// Glob patterns of variable names that are always sensitive, e.g.
+    /// `*_TOKEN`. A source with an explicit `sensitive = false` isn't masked
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sensitive_patterns: Option<Vec<SensitivePattern>>,
+    /// Stop searching parent directories for config files after this one
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub root: bool,
+    /// Stop searching parent directories for config files after the user's
+    /// home directory. Only used from the global config file
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub stop_at_home: bool,
+    /// Every file this config was loaded from, in the order they were loaded
+    #[serde(skip)]
+    pub sources: Vec<Source>,
+    /// Which file defined each profile and shared variable
+    #[serde(skip)]
+    pub definitions: Definitions,
+}
+
+/// A config file that was loaded, and what it contributed to the config
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Source {
+    pub path: PathBuf,
+    /// Number of applications defined in the file. An application defined
+    /// in multiple files is counted in each of them
+    pub applications: usize,
+    /// Number of profiles defined in the file, across all applications
+    pub profiles: usize,
+    /// The file failed to parse, and was skipped
+    pub invalid: bool,
+}
+
+/// The file that each profile and shared variable was taken from. When
+/// multiple files define the same thing, the one that took precedence is
+/// recorded.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Definitions {
+    pub profiles: HashMap<ProfileReference, PathBuf>,
+    /// Keyed by application and variable name
+    pub application_variables: HashMap<(Name, String), PathBuf>,
+    pub defaults: HashMap<String, PathBuf>,
+}
+
+/// Variables and side effects that apply to every profile in every
+/// application. These have the lowest precedence of anything: profiles,
+/// their parents, and their applications can all override them.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Defaults {
+    pub variables: IndexMap<String, VariableDefinition>,
+    /// Run before the pre-export side effects of each profile
+    pub pre_export: Vec<SideEffect>,
+    /// Run before the post-export side effects of each profile
+    pub post_export: Vec<SideEffect>,
 }
 
 /// An application is a grouping of profiles. Each profile should be different
@@ -38,6 +146,16 @@ pub struct Config {
 #[derive(Clone, Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
 #[serde(default, deny_unknown_fields)]
 pub struct Application {
+    /// Alternate names that can be used to select this application
+    #[serde(skip_serializing_if = "IndexSet::is_empty")]
+    pub aliases: IndexSet<Name>,
+    /// Human-readable explanation of the application, shown when selecting
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Variables shared by every profile in the application. Profiles can
+    /// override them, and so can the profiles they extend
+    #[serde(skip_serializing_if = "IndexMap::is_empty")]
+    pub variables: IndexMap<String, VariableDefinition>,
     pub profiles: IndexMap<Name, Profile>,
 }
 
@@ -51,14 +169,50 @@ pub struct Name(pub String);
 #[derive(Clone, Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
 #[serde(default, deny_unknown_fields)]
 pub struct Profile {
+    /// Alternate names that can be used to select this profile. These are
+    /// *not* inherited
+    #[serde(skip_serializing_if = "IndexSet::is_empty")]
+    pub aliases: IndexSet<Name>,
+    /// Human-readable explanation of when to use the profile, shown when
+    /// selecting. This is *not* inherited
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Leave this profile out of prompts and completions. It can still be
+    /// selected by name and extended. This is *not* inherited, so children
+    /// of a hidden base profile are visible
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub hidden: bool,
+    /// Warn whenever this profile is selected. This is *not* inherited
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deprecated: Option<Deprecated>,
     /// List of profiles that we'll inherit from. Last has precedence
     pub extends: IndexSet<ProfileReference>,
     /// The meat
-    pub variables: IndexMap<String, ValueSource>,
+    pub variables: IndexMap<String, VariableDefinition>,
     /// Imperative commands to run *before* resolving an environment
     pub pre_export: Vec<SideEffect>,
     /// Imperative commands to run *after* resolving an environment
     pub post_export: Vec<SideEffect>,
+    /// kubectl context to switch to before resolving an environment
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kubernetes_context: Option<String>,
+    /// kubectl namespace to switch to before resolving an environment. This
+    /// is set on the current context, *after* switching contexts
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kubernetes_namespace: Option<String>,
+    /// Directory to switch to when the profile is activated, relative to the
+    /// config file that this was defined in
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<PathBuf>,
+}
+
+/// Deprecation status of a profile. Either a flag, or a message explaining
+/// what to use instead
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(untagged)]
+pub enum Deprecated {
+    Flag(bool),
+    Message(String),
 }
 
 /// Pointer to a profile, relative to some "self" profile. (De)serializes as
@@ -72,6 +226,35 @@ pub struct ProfileReference {
     profile: Name,
 }
 
+/// Definition of a single variable in a profile. Usually this is a single
+/// value source, but it can also be a list of candidates, in which case the
+/// first source that matches the current platform is used, or a table of
+/// sources keyed by shell kind.
+#[derive(Clone, Debug, Serialize, Eq, PartialEq)]
+#[serde(untagged)]
+pub enum VariableDefinition {
+    Single(ValueSource),
+    Candidates(Vec<ValueSource>),
+    PerShell(ShellValueSources),
+}
+
+/// Value sources for a single variable, keyed by shell kind. `default` is used
+/// for any shell that isn't listed.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ShellValueSources {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bash: Option<ValueSource>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub zsh: Option<ValueSource>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fish: Option<ValueSource>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sh: Option<ValueSource>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<ValueSource>,
+}
+
 /// The source of an exported value. Can be a literal value or an embedded
 /// command, which will be evaluated into a value lazily. A "value source" is
 /// actually composed of 3 types:
@@ -97,9 +280,84 @@ pub struct ValueSourceInner {
     #[serde(default)]
     pub multiple: MultiVariable,
 
-    /// Value(s) should be masked in display output
+    /// Syntax of the mapping loaded by `multiple`
     #[serde(default)]
-    pub sensitive: bool,
+    pub multiple_format: MultiVariableFormat,
+
+    /// Prefix to add to every variable name loaded by `multiple`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<String>,
+
+    /// Value(s) should be masked in display output. If omitted, the default
+    /// depends on the source kind. See [ValueSourceKind::default_sensitive]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sensitive: Option<bool>,
+
+    /// Remove trailing whitespace from the resolved value
+    #[serde(default = "ValueSourceInner::default_trim")]
+    pub trim: bool,
+
+    /// Value to use if the source fails to resolve. This does *not* apply if
+    /// a multi-variable mapping fails to parse
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+
+    /// If the source fails to resolve, leave the variable out of the
+    /// environment instead of failing. If `default` is also given, the
+    /// default takes precedence
+    #[serde(default)]
+    pub optional: bool,
+
+    /// Cache the resolved value on disk, and reuse it for this long, e.g.
+    /// `"5m"` or `"1h"`. If omitted, the value isn't cached
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache: Option<ConfigDuration>,
+
+    /// Maximum time to wait for the value to resolve, e.g. `"10s"`. If
+    /// omitted, the source's default applies; most sources have no limit
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<ConfigDuration>,
+
+    /// Number of times to retry resolution if it fails, before giving up.
+    /// Timeouts count as failures
+    #[serde(default)]
+    pub retries: u32,
+
+    /// Time to wait between retries, e.g. `"5s"`. If omitted, wait one second
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_delay: Option<ConfigDuration>,
+
+    /// Only use this source on the given platform. On other platforms, it's
+    /// skipped. If omitted, the source is used on all platforms
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub platform: Option<Platform>,
+
+    /// Transform the resolved value before using it. This is applied before
+    /// parsing multi-variable mappings
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<Encoding>,
+
+    /// Transformations to apply to the resolved value, in order. These are
+    /// applied after encoding, and before parsing multi-variable mappings
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub transform: Vec<Transform>,
+
+    /// How to combine the resolved value with the variable's current value
+    /// from the parent environment. If omitted, `PATH` is prepended to and
+    /// all other variables are replaced
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<ValueMode>,
+
+    /// Separator between the resolved value and the current value, for the
+    /// `prepend` and `append` modes. If omitted, use `:`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub separator: Option<String>,
+
+    /// Remove repeated entries after prepending or appending. If omitted,
+    /// only PATH-like variables (e.g. `PATH` or `PYTHONPATH`) are deduped,
+    /// since other lists can repeat entries on purpose
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dedupe: Option<bool>,
 }
 
 /// The various kinds of supported value sources. This will only hold data
@@ -111,6 +369,18 @@ pub enum ValueSourceKind {
     #[serde(rename = "literal")]
     Literal { value: String },
 
+    /// Pass through a variable from env-select's own environment
+    #[serde(rename = "env")]
+    Env {
+        /// Name of the variable to read
+        variable: String,
+    },
+
+    /// Remove the variable from the environment, e.g. to clear a variable
+    /// set by a parent profile or by the user's shell
+    #[serde(rename = "unset")]
+    Unset,
+
     /// Load value from a file
     #[serde(rename = "file")]
     File {
@@ -118,24 +388,448 @@ pub enum ValueSourceKind {
         path: PathBuf,
     },
 
+    /// Load values from every file matching a glob pattern. Files are
+    /// concatenated in lexical order, which is mainly useful with `multiple`
+    #[serde(rename = "file-glob")]
+    FileGlob {
+        /// Glob pattern, relative to the config file that this was defined in
+        path: PathBuf,
+        /// If no files match, resolve to an empty value instead of failing
+        #[serde(default)]
+        allow_empty: bool,
+    },
+
+    /// Load all variables from a .env file. Multi-variable loading is always
+    /// enabled, so the variable name in the profile is ignored
+    #[serde(rename = "dotenv")]
+    Dotenv {
+        /// File path, relative to the config file that this was defined in
+        path: PathBuf,
+        /// Only load these variables. If omitted, load all variables
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        only: Option<Vec<String>>,
+        /// Don't load these variables
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        except: Vec<String>,
+    },
+
+    /// Load a value (or values) from within a JSON file
+    #[serde(rename = "json-file")]
+    JsonFile {
+        /// File path, relative to the config file that this was defined in
+        path: PathBuf,
+        /// JSON pointer (RFC 6901) to the value within the file, e.g.
+        /// `/database/password`. If omitted, use the entire document
+        #[serde(default)]
+        pointer: String,
+    },
+
+    /// Load a value (or values) from within a YAML file
+    #[serde(rename = "yaml-file")]
+    YamlFile {
+        /// File path, relative to the config file that this was defined in
+        path: PathBuf,
+        /// Dot-separated path to the value within the file, e.g.
+        /// `database.password`. If omitted, use the entire document
+        key: Option<String>,
+    },
+
+    /// Load a file encrypted with sops, decrypted via the `sops` CLI
+    #[serde(rename = "sops")]
+    Sops {
+        /// File path, relative to the config file that this was defined in
+        path: PathBuf,
+        /// Format of the decrypted file. If omitted, infer from the file
+        /// extension
+        format: Option<SopsFormat>,
+    },
+
+    /// Generate a new value locally. Each variable gets its own value, even
+    /// if multiple variables use the same generator
+    #[serde(rename = "generate")]
+    Generate {
+        #[serde(flatten)]
+        generator: Generator,
+    },
+
+    /// Ask the user to enter the value interactively. If the value source is
+    /// sensitive, input is hidden
+    #[serde(rename = "prompt")]
+    Prompt {
+        /// Message to show. If omitted, show the variable name
+        message: Option<String>,
+        /// Ask the user to enter the value twice, and make sure they match
+        #[serde(default)]
+        confirm: bool,
+    },
+
     /// A command that will be executed via the shell
     #[serde(rename = "command")]
     Command {
         command: ShellCommand,
         /// If omitted, use inherited cwd. Relative to config file
         cwd: Option<PathBuf>,
+        /// Text to pipe to the command's stdin. If omitted, the command gets
+        /// no input
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        stdin: Option<String>,
+        /// Export every variable declared earlier in the profile into the
+        /// command's environment, not just the ones it references
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        use_profile_env: bool,
+    },
+
+    /// A program that will be executed directly, without a shell. This avoids
+    /// shell startup output and quoting issues
+    #[serde(rename = "native-command")]
+    NativeCommand {
+        /// Program to execute. Looked up in `PATH` if not a path
+        program: String,
+        /// Arguments to pass to the program. These are passed as-is, with no
+        /// shell expansion
+        #[serde(default)]
+        args: Vec<String>,
+        /// If omitted, use inherited cwd. Relative to config file
+        cwd: Option<PathBuf>,
+        /// Export every variable declared earlier in the profile into the
+        /// program's environment
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        use_profile_env: bool,
+    },
+
+    /// A command that will be executed inside a Kubernetes pod, via
+    /// `kubectl exec`
+    #[serde(rename = "kubernetes")]
+    Kubernetes {
+        /// Program and arguments to execute in the pod
+        command: Vec<String>,
+        /// Label selector to find the pod. Must match exactly one pod
+        pod_selector: String,
+        /// If omitted, use the namespace of the current kubectl context
+        namespace: Option<String>,
+        /// If omitted, use the pod's default container
+        container: Option<String>,
+    },
+
+    /// Read a git config value, via `git config --get`
+    #[serde(rename = "git-config")]
+    GitConfig {
+        /// Config key, e.g. `user.email`
+        key: String,
+        /// Directory to run git in, which determines the repository whose
+        /// local config is used. If omitted, use inherited cwd. Relative to
+        /// config file
+        cwd: Option<PathBuf>,
+    },
+
+    /// A command that will be executed on a remote host, via `ssh`
+    #[serde(rename = "ssh")]
+    Ssh {
+        /// Host to connect to. Can be anything `ssh` accepts, including
+        /// `user@host` and aliases from your SSH config
+        host: String,
+        /// Command to execute. This is parsed by the remote user's shell
+        command: String,
+    },
+
+    /// A command that will be executed inside a Docker container, via
+    /// `docker exec`
+    #[serde(rename = "docker")]
+    Docker {
+        /// Program and arguments to execute in the container
+        command: Vec<String>,
+        /// Name or ID of the container. Exactly one of this and
+        /// `compose_service` must be given
+        container: Option<String>,
+        /// Docker Compose service to find the container from, via
+        /// `docker compose ps`
+        compose_service: Option<String>,
+    },
+
+    /// Read a secret from HashiCorp Vault, via the `vault` CLI
+    #[serde(rename = "vault")]
+    Vault {
+        /// Path to the secret, within the secrets engine
+        path: String,
+        /// Field to extract from the secret. Required unless loading
+        /// multiple values, in which case all fields are loaded
+        field: Option<String>,
+        /// Mount path of the secrets engine. If omitted, the mount must be
+        /// included in `path`
+        mount: Option<String>,
+    },
+
+    /// Read a parameter from AWS SSM Parameter Store, via the `aws` CLI
+    #[serde(rename = "aws-ssm")]
+    AwsSsm {
+        /// Parameter name. When loading multiple values, this is a path
+        /// prefix, and all parameters under it are loaded
+        parameter: String,
+        /// If omitted, use the AWS CLI's default region
+        region: Option<String>,
+        /// AWS CLI profile. If omitted, use the AWS CLI's default profile
+        profile: Option<String>,
+    },
+
+    /// Read a secret from AWS Secrets Manager, via the `aws` CLI
+    #[serde(rename = "aws-secrets-manager")]
+    AwsSecretsManager {
+        /// Name or ARN of the secret
+        secret_id: String,
+        /// Parse the secret as a JSON object and extract this key. If
+        /// omitted, use the entire secret string
+        key: Option<String>,
+        /// If omitted, use the AWS CLI's default region
+        region: Option<String>,
+        /// AWS CLI profile. If omitted, use the AWS CLI's default profile
+        profile: Option<String>,
+    },
+
+    /// Read a secret from Azure Key Vault, via the `az` CLI
+    #[serde(rename = "azure-key-vault")]
+    AzureKeyVault {
+        /// Name of the key vault
+        vault: String,
+        /// Name of the secret. When loading multiple values, this is a name
+        /// prefix, and all secrets starting with it are loaded
+        secret: String,
+    },
+
+    /// Read a secret from GCP Secret Manager, via the `gcloud` CLI
+    #[serde(rename = "gcp-secret")]
+    GcpSecret {
+        /// Name of the secret
+        secret: String,
+        /// Version of the secret. If omitted, use the latest version
+        version: Option<String>,
+        /// If omitted, use the gcloud CLI's default project
+        project: Option<String>,
+    },
+
+    /// Read a secret from 1Password, via the `op` CLI
+    #[serde(rename = "1password")]
+    OnePassword {
+        /// Secret reference, in the format `op://vault/item/field`. When
+        /// loading multiple values, reference the item (`op://vault/item`)
+        /// and all of its fields will be loaded
+        reference: String,
+    },
+
+    /// Read secrets from Doppler, via the `doppler` CLI
+    #[serde(rename = "doppler")]
+    Doppler {
+        /// Name of the secret to read. Required unless loading multiple
+        /// values, in which case the entire config is loaded
+        secret: Option<String>,
+        /// If omitted, use the project configured for the current directory
+        project: Option<String>,
+        /// If omitted, use the config configured for the current directory
+        config: Option<String>,
+    },
+
+    /// Read a generic password from the macOS Keychain, via the `security`
+    /// CLI. Only supported on macOS
+    #[serde(rename = "keychain")]
+    Keychain {
+        /// Service name of the keychain item
+        service: String,
+        /// Account name of the keychain item. If omitted, use the first item
+        /// matching the service
+        account: Option<String>,
+    },
+
+    /// Read an entry from `pass`, the standard unix password manager
+    #[serde(rename = "pass")]
+    Pass {
+        /// Name of the entry in the password store, e.g. `dev/db`
+        entry: String,
+        /// Line of the entry to use (1-indexed). If neither this nor `field`
+        /// is given, use the first line
+        line: Option<usize>,
+        /// Use the value of a `key: value` line in the entry
+        field: Option<String>,
+    },
+
+    /// Fetch a value with an HTTP GET request, via `curl`. The response body
+    /// is the value
+    #[serde(rename = "http")]
+    Http {
+        url: String,
+        /// Request headers. Values can reference variables from env-select's
+        /// environment with `${env:VARIABLE}`, e.g. for auth tokens
+        #[serde(default)]
+        headers: BTreeMap<String, String>,
     },
 }
 
 /// Value for the `multiple` field of a value source
 #[derive(Clone, Debug, Serialize, Deserialize, Eq, From, Hash, PartialEq)]
-#[serde(untagged)]
+#[serde(untagged, deny_unknown_fields)]
 pub enum MultiVariable {
     /// Multi-variable loading is enabled or disabled
     Bool(bool),
     /// Multi-variable load is enabled, but only a fixed set of variables will
     /// be loaded
     List(Vec<String>),
+    /// Same as [MultiVariable::List], but with extra options
+    Filtered {
+        keys: Vec<String>,
+        /// If a listed variable is missing from the mapping, fail instead of
+        /// skipping it
+        #[serde(default = "MultiVariable::default_required")]
+        required: bool,
+    },
+    /// Multi-variable load is enabled, and each listed variable will be
+    /// loaded under a new name. Unlisted variables are dropped
+    Map(BTreeMap<String, String>),
+}
+
+/// Syntax of a multi-variable mapping
+#[derive(
+    Copy, Clone, Debug, Default, Serialize, Deserialize, Eq, Hash, PartialEq,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum MultiVariableFormat {
+    /// `VARIABLE=value` lines, with optional quoting
+    #[default]
+    Dotenv,
+    /// A flat JSON object
+    Json,
+    /// A flat YAML mapping
+    Yaml,
+    /// `VARIABLE=value` lines, with no quoting. Everything after the first
+    /// `=` is the value
+    KeyValue,
+}
+
+/// A duration in config, e.g. a cache TTL or timeout. (De)serializes as a
+/// number with a unit suffix: `s`, `m`, `h`, or `d`, e.g. `"5m"`
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub struct ConfigDuration(pub Duration);
+
+/// An operating system that a value source can be restricted to
+#[derive(
+    Copy, Clone, Debug, Display, Serialize, Deserialize, Eq, Hash, PartialEq,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Platform {
+    #[display("linux")]
+    Linux,
+    #[display("macos")]
+    Macos,
+    #[display("windows")]
+    Windows,
+}
+
+/// Transformation to apply to a resolved value
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, Eq, Hash, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Encoding {
+    /// Decode the value from base64
+    Base64,
+    /// Encode the value as base64
+    Base64Encode,
+}
+
+/// A glob pattern for variable names that should be treated as sensitive,
+/// e.g. `*_TOKEN`. Matching is case-insensitive. (De)serializes as a string
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct SensitivePattern(glob::Pattern);
+
+/// How sensitive values are shown to the user. Values exported to the shell
+/// or passed to a command are never masked
+#[derive(
+    Copy, Clone, Debug, Default, Serialize, Deserialize, Eq, PartialEq,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum Masking {
+    /// Replace the whole value with `<REDACTED>`
+    #[default]
+    Full,
+    /// Show only the last 4 characters, e.g. `****abcd`, to tell values
+    /// apart. Values of 8 characters or fewer are masked entirely
+    Partial,
+    /// Show the whole value. This can only be enabled per-command, with
+    /// `--reveal`
+    #[serde(skip)]
+    Reveal,
+}
+
+/// How a resolved value is combined with the variable's current value from
+/// the parent environment
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, Eq, Hash, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ValueMode {
+    /// Add the value to the beginning of the current value
+    Prepend,
+    /// Add the value to the end of the current value
+    Append,
+    /// Overwrite the current value
+    Replace,
+}
+
+/// A single step in a chain of transformations applied to a resolved value
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, Hash, PartialEq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub enum Transform {
+    /// Convert to uppercase
+    Uppercase,
+    /// Convert to lowercase
+    Lowercase,
+    /// Remove leading and trailing whitespace
+    Trim,
+    /// Replace all occurrences of a substring
+    Replace { from: String, to: String },
+    /// Extract a capture group from the first match of a regex. Group 0 is
+    /// the entire match
+    Regex {
+        pattern: String,
+        #[serde(default)]
+        group: usize,
+    },
+}
+
+/// Kind of value to generate, for the `generate` value source
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, Hash, PartialEq)]
+#[serde(tag = "generator", rename_all = "kebab-case")]
+pub enum Generator {
+    /// Random (version 4) UUID
+    Uuid4,
+    /// Random hexadecimal string
+    RandomHex {
+        /// Number of hex characters
+        #[serde(default = "Generator::default_hex_length")]
+        length: usize,
+    },
+    /// Current time
+    Timestamp {
+        #[serde(default)]
+        format: TimestampFormat,
+    },
+}
+
+/// Output format for a generated timestamp
+#[derive(
+    Copy, Clone, Debug, Default, Serialize, Deserialize, Eq, Hash, PartialEq,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum TimestampFormat {
+    /// Seconds since the Unix epoch
+    #[default]
+    Unix,
+    /// Milliseconds since the Unix epoch
+    UnixMillis,
+    /// RFC 3339 datetime in UTC, e.g. `2024-01-01T00:00:00Z`
+    Rfc3339,
+}
+
+/// Plaintext format of a sops-encrypted file
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, Eq, Hash, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SopsFormat {
+    Dotenv,
+    Yaml,
+    Json,
 }
 
 /// A pair of imperative commands to run. The setup command is run during
@@ -144,13 +838,63 @@ pub enum MultiVariable {
 /// position of the setup. E.g. if the setup is run *pre*-export, the teardown
 /// will be run *after* clearing the environment.
 ///
-/// Each field is optional to support side effects that don't require teardown
-/// (or more rarely, don't require setup). Generally though, you should specify
-/// both.
+/// Side effects are either a pair of shell commands, or a built-in type that
+/// expands to pre-defined commands.
+#[derive(Clone, Debug, Serialize, Eq, Hash, PartialEq)]
+#[serde(untagged)]
+pub enum SideEffect {
+    /// Arbitrary shell commands. Each field is optional to support side
+    /// effects that don't require teardown (or more rarely, don't require
+    /// setup). Generally though, you should specify both.
+    Shell {
+        setup: Option<ShellCommand>,
+        teardown: Option<ShellCommand>,
+        /// Leave the setup command running in the background, instead of
+        /// waiting for it to exit. The process is killed during teardown,
+        /// before the teardown command runs
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        background: bool,
+    },
+    Builtin(BuiltinSideEffect),
+}
+
+/// Side effects for common tasks, with pre-defined setup and teardown
+/// commands
 #[derive(Clone, Debug, Serialize, Deserialize, Eq, Hash, PartialEq)]
-pub struct SideEffect {
-    pub setup: Option<ShellCommand>,
-    pub teardown: Option<ShellCommand>,
+#[serde(tag = "type", deny_unknown_fields)]
+pub enum BuiltinSideEffect {
+    /// Forward local port(s) to a Kubernetes resource with `kubectl
+    /// port-forward`. The forward runs in the background until teardown
+    #[serde(rename = "kubectl-port-forward")]
+    KubectlPortForward {
+        /// Resource to forward to, e.g. `svc/api` or `pod/api-123`
+        target: String,
+        /// Port mapping(s), e.g. `8080:80`. Separate multiple mappings with
+        /// spaces
+        ports: String,
+        /// If omitted, use the namespace of the current kubectl context
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        namespace: Option<String>,
+    },
+
+    /// Start services with Docker Compose, and stop them on teardown
+    #[serde(rename = "docker-compose")]
+    DockerCompose {
+        /// Compose file, relative to the config file that this was defined in
+        file: PathBuf,
+    },
+}
+
+/// A single command from a side effect, ready to be executed
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SideEffectCommand<'a> {
+    /// Executed via the user's shell
+    Shell(&'a ShellCommand),
+    /// Executed directly, without a shell
+    Native {
+        program: &'static str,
+        arguments: Vec<String>,
+    },
 }
 
 /// A shell command is just a string, which will be parsed by the shell
@@ -169,64 +913,277 @@ pub struct SideEffect {
 #[display("`{}`", _0)]
 pub struct ShellCommand(String);
 
+impl Defaults {
+    pub fn is_empty(&self) -> bool {
+        self.variables.is_empty()
+            && self.pre_export.is_empty()
+            && self.post_export.is_empty()
+    }
+}
+
 impl Config {
-    /// Load config from the current directory and all parents. Any config
-    /// file in any directory in the hierarchy will be loaded and merged into
-    /// the config, with lower files take precedence.
-    pub fn load() -> anyhow::Result<Self> {
+    /// Load config from the current directory and all parents, and resolve
+    /// inheritance. See [Self::load_unresolved] for how files are found.
+    pub fn load(
+        config_file: Option<&Path>,
+        ignore_invalid: bool,
+    ) -> anyhow::Result<Self> {
+        let mut config = Self::load_unresolved(config_file, ignore_invalid)?;
+        config.resolve()?;
+        info!("Loaded and resolved config: {config:#?}");
+        Ok(config)
+    }
+
+    /// Load config from the current directory and all parents, *without*
+    /// resolving inheritance. Any config file in any directory in the
+    /// hierarchy will be loaded and merged into the config, with lower files
+    /// take precedence. The user's global config file is loaded last, with
+    /// the lowest precedence. If a config file is given, either here or via
+    /// [CONFIG_FILE_VARIABLE], load only that file (plus its imports)
+    /// instead. If a file fails to parse, return an error, unless
+    /// `ignore_invalid` is set, in which case the file is skipped.
+    pub fn load_unresolved(
+        config_file: Option<&Path>,
+        ignore_invalid: bool,
+    ) -> anyhow::Result<Self> {
         let mut config = Config::default();
+        let mut imports = Imports::new(ignore_invalid);
 
-        // Iterate bottom-up, so the first file has priority
-        for path in Self::get_all_files()?.iter() {
-            debug!("Loading config from file {path:?}");
-            let content = fs::read_to_string(path)
-                .with_context(|| format!("Error reading file {path:?}"))?;
-            match toml::from_str::<Config>(&content) {
-                Ok(mut parsed) => {
-                    debug!("Loaded from file {path:?}: {parsed:?}");
-                    // Qualify relative paths to be absolute
-                    parsed.qualify(path);
-                    config.merge(parsed, path);
-                }
-                Err(error) => {
-                    error!("{path:?} will be ignored due to error: {error}")
+        let files = match Self::override_file(config_file)? {
+            Some(path) => {
+                debug!("Using config file override {path:?}");
+                vec![path]
+            }
+            None => {
+                let mut files = Self::get_all_files(
+                    &env::current_dir()?,
+                    Self::search_home().as_deref(),
+                );
+                if let Some(global_file) =
+                    Self::global_file().filter(|path| path.exists())
+                {
+                    debug!("Found global config file at {global_file:?}");
+                    files.push(global_file);
                 }
+                files
             }
+        };
+        // Iterate bottom-up, so the first file has priority
+        for path in files.iter() {
+            config.load_file(path, &mut imports)?;
         }
+        config.definitions = mem::take(&mut imports.definitions);
+        config.sources = imports.into_loaded();
 
         trace!("Loaded config (pre-inheritance): {config:#?}");
-        // Resolve all `extends` fields
-        config.inherit()?;
-
-        info!("Loaded and resolved config: {config:#?}");
         Ok(config)
     }
 
-    /// Starting at the current directory, walk up the tree and collect the
-    /// list of all config files. Return the list of files from
-    /// **top-to-bottom**, so that the highest priority file comes last.
-    fn get_all_files() -> anyhow::Result<Vec<PathBuf>> {
-        let cwd = env::current_dir()?;
+    /// Get the config file to load instead of scanning for config files: the
+    /// given file, or else [CONFIG_FILE_VARIABLE]. Relative paths are
+    /// relative to the cwd
+    pub fn override_file(
+        config_file: Option<&Path>,
+    ) -> anyhow::Result<Option<PathBuf>> {
+        config_file
+            .map(PathBuf::from)
+            .or_else(|| env::var_os(CONFIG_FILE_VARIABLE).map(PathBuf::from))
+            .map(|path| Ok(env::current_dir()?.join(path)))
+            .transpose()
+    }
+
+    /// Resolve all `extends` fields, and check for conflicting aliases.
+    /// Return where each variable came from
+    pub fn resolve(&mut self) -> anyhow::Result<Origins> {
+        let origins = self.inherit()?;
+        self.check_aliases()?;
+        self.check_application_variables()?;
+        Ok(origins)
+    }
+
+    /// Make sure no two applications share a state variable, e.g.
+    /// [active_variable]. Names are sanitized to build the variables, so
+    /// `my-app` and `my_app` would otherwise overwrite each other's state
+    fn check_application_variables(&self) -> anyhow::Result<()> {
+        // Variable -> name of the application that owns it
+        let mut owners: HashMap<String, &Name> = HashMap::new();
+        for name in self.applications.keys() {
+            let variable = active_variable(name);
+            if let Some(owner) = owners.insert(variable.clone(), name) {
+                bail!(
+                    "Applications `{owner}` and `{name}` would share the \
+                    variable `{variable}`; rename one of them"
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Make sure every alias refers to exactly one application or profile.
+    /// Application aliases can't collide with other applications, and profile
+    /// aliases can't collide with other profiles in the same application.
+    fn check_aliases(&self) -> anyhow::Result<()> {
+        check_aliases(&self.applications, |name| {
+            format!("application `{name}`")
+        })?;
+        for (application_name, application) in &self.applications {
+            check_aliases(&application.profiles, |name| {
+                let reference: ProfileReference =
+                    (application_name.clone(), name.clone()).into();
+                format!("profile `{reference}`")
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Get a profile by its qualified reference. Return `None` if it doesn't
+    /// exist, or if the reference doesn't include an application
+    pub fn get_profile(
+        &self,
+        reference: &ProfileReference,
+    ) -> Option<Cow<'_, Profile>> {
+        let (_, profile) = self
+            .applications
+            .get(reference.application.as_ref()?)?
+            .get_profile(&reference.profile)
+            .ok()?;
+        Some(profile)
+    }
+
+    /// Should templates fail when they reference an unset environment
+    /// variable?
+    pub fn strict_templates(&self) -> bool {
+        self.strict_templates.unwrap_or(true)
+    }
+
+    /// Should conflicting assignments to the same variable be an error?
+    pub fn strict_conflicts(&self) -> bool {
+        self.strict_conflicts.unwrap_or(false)
+    }
+
+    /// How should sensitive values be shown to the user?
+    pub fn masking(&self) -> Masking {
+        self.masking.unwrap_or_default()
+    }
+
+    /// Variable name patterns that should be masked, regardless of source
+    pub fn sensitive_patterns(&self) -> &[SensitivePattern] {
+        self.sensitive_patterns.as_deref().unwrap_or_default()
+    }
 
+    /// Path to the user's global config file, which holds personal profiles
+    /// that don't belong in any project's config. `None` if we can't find a
+    /// suitable location. The file may not exist.
+    pub fn global_file() -> Option<PathBuf> {
+        let base = env::var_os("XDG_CONFIG_HOME").map(PathBuf::from).or_else(
+            || {
+                #[cfg(windows)]
+                let base = env::var_os("APPDATA").map(PathBuf::from);
+                #[cfg(not(windows))]
+                let base = home_directory().map(|home| home.join(".config"));
+                base
+            },
+        )?;
+        Some(base.join("env-select").join(GLOBAL_FILE_NAME))
+    }
+
+    /// Get the config file closest to the current directory, i.e. the one
+    /// with the highest precedence. Local override files are skipped. `None`
+    /// if there are no config files in the current directory or its ancestors
+    pub fn nearest_file() -> anyhow::Result<Option<PathBuf>> {
+        let files = Self::get_all_files(
+            &env::current_dir()?,
+            Self::search_home().as_deref(),
+        );
+        Ok(files
+            .into_iter()
+            .find(|path| path.file_name() == Some(FILE_NAME.as_ref())))
+    }
+
+    /// Get the home directory, if the global config file says the search for
+    /// config files should stop there
+    fn search_home() -> Option<PathBuf> {
+        let global_file = Self::global_file().filter(|path| path.exists())?;
+        if SearchSettings::load(&global_file).stop_at_home {
+            home_directory()
+        } else {
+            None
+        }
+    }
+
+    /// Starting at the given directory, walk up the tree and collect the
+    /// list of all config files. Each directory can have a local override
+    /// file, which comes before the main file. The walk stops early at a
+    /// directory with a file that sets `root = true`, or after searching the
+    /// home directory, if given. Return the list of files from
+    /// **bottom-to-top**, so that the highest priority file comes first.
+    fn get_all_files(start: &Path, home: Option<&Path>) -> Vec<PathBuf> {
         let mut config_files: Vec<PathBuf> = Vec::new();
-        let mut search_dir: Option<&Path> = Some(&cwd);
+        let mut search_dir: Option<&Path> = Some(start);
         // Walk *up* the tree until we've hit the root
         while let Some(dir) = search_dir {
             trace!("Scanning for config file in {dir:?}");
-            let path = dir.join(FILE_NAME);
-            if path.exists() {
-                trace!("Found config file at {path:?}");
-                config_files.push(path);
+            let mut root = false;
+            for file_name in [LOCAL_FILE_NAME, FILE_NAME] {
+                let path = dir.join(file_name);
+                if path.exists() {
+                    trace!("Found config file at {path:?}");
+                    root |= SearchSettings::load(&path).root;
+                    config_files.push(path);
+                }
+            }
+            if root {
+                debug!("Stopping config search at root {dir:?}");
+                break;
+            }
+            if Some(dir) == home {
+                debug!("Stopping config search at home directory {dir:?}");
+                break;
             }
             search_dir = dir.parent();
         }
+        config_files
+    }
+}
 
-        // Return top->bottom results
-        config_files.reverse();
-        Ok(config_files)
+/// Settings that control which config files are loaded. These have to be read
+/// before the full config is loaded, so they're parsed separately.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct SearchSettings {
+    root: bool,
+    stop_at_home: bool,
+}
+
+impl SearchSettings {
+    /// Read settings from a config file. If the file can't be read or
+    /// parsed, use the defaults. Loading the full config will report the
+    /// error.
+    fn load(path: &Path) -> Self {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|content| toml::from_str(&content).ok())
+            .unwrap_or_default()
     }
 }
 
+/// Get the current user's home directory
+fn home_directory() -> Option<PathBuf> {
+    #[cfg(windows)]
+    let home = env::var_os("USERPROFILE");
+    #[cfg(not(windows))]
+    let home = env::var_os("HOME");
+    home.map(PathBuf::from)
+}
+
+/// 64-bit FNV-1a hash. Unlike [std::hash::DefaultHasher], the output is
+/// guaranteed not to change between Rust versions
+fn stable_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
 impl Name {
     pub fn as_str(&self) -> &str {
         self.0.as_str()
@@ -262,11 +1219,98 @@ impl FromStr for Name {
     }
 }
 
+impl Source {
+    fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            applications: 0,
+            profiles: 0,
+            invalid: false,
+        }
+    }
+}
+
+impl Application {
+    /// Get a profile, along with its real name, by its name or one of its
+    /// aliases. If nothing matches and the name joins several profiles with
+    /// `+`, e.g. `dev+debug`, those profiles are composed into one. See
+    /// [inherit::compose]
+    pub fn get_profile(
+        &self,
+        name: &Name,
+    ) -> anyhow::Result<(Name, Cow<'_, Profile>)> {
+        let error = match get_aliased(&self.profiles, name) {
+            Ok((name, profile)) => {
+                return Ok((name.clone(), Cow::Borrowed(profile)))
+            }
+            Err(error) => error,
+        };
+        if !name.as_str().contains(COMPOSE_SEPARATOR) {
+            return Err(error);
+        }
+
+        let parts = name
+            .as_str()
+            .split(COMPOSE_SEPARATOR)
+            .map(|part| get_aliased(&self.profiles, &part.parse()?))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let name = Name(
+            parts
+                .iter()
+                .map(|(name, _)| name.as_str())
+                .collect::<Vec<_>>()
+                .join(COMPOSE_SEPARATOR),
+        );
+        let profile =
+            inherit::compose(parts.into_iter().map(|(_, profile)| profile));
+        Ok((name, Cow::Owned(profile)))
+    }
+}
+
+impl Profile {
+    /// Combine profiles from any applications into one. Later profiles take
+    /// precedence. See [inherit::compose]
+    pub fn compose<'a>(profiles: impl IntoIterator<Item = &'a Self>) -> Self {
+        inherit::compose(profiles)
+    }
+
+    /// Is this profile marked as deprecated?
+    pub fn is_deprecated(&self) -> bool {
+        !matches!(self.deprecated, None | Some(Deprecated::Flag(false)))
+    }
+
+    /// If the profile is deprecated, get a warning to show when it's selected
+    pub fn deprecation_warning(
+        &self,
+        reference: &ProfileReference,
+    ) -> Option<String> {
+        match self.deprecated.as_ref()? {
+            Deprecated::Flag(false) => None,
+            Deprecated::Flag(true) => {
+                Some(format!("Profile `{reference}` is deprecated"))
+            }
+            Deprecated::Message(message) => {
+                Some(format!("Profile `{reference}` is deprecated: {message}"))
+            }
+        }
+    }
+}
+
 impl ProfileReference {
     /// Is this an absolute reference, i.e. does it include an application name?
     pub fn is_qualified(&self) -> bool {
         self.application.is_some()
     }
+
+    /// Name of the referenced profile's application, if included
+    pub fn application(&self) -> Option<&Name> {
+        self.application.as_ref()
+    }
+
+    /// Name of the referenced profile
+    pub fn profile(&self) -> &Name {
+        &self.profile
+    }
 }
 
 impl FromStr for ProfileReference {
@@ -307,18 +1351,319 @@ impl Display for ProfileReference {
     }
 }
 
+/// Units supported by [ConfigDuration], largest first
+const DURATION_UNITS: &[(char, u64)] =
+    &[('d', 86_400), ('h', 3_600), ('m', 60), ('s', 1)];
+
+impl FromStr for ConfigDuration {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let error = || {
+            anyhow!(
+                "Invalid duration `{value}`; expected a number followed by \
+                one of s, m, h, d (e.g. `5m`)"
+            )
+        };
+        let unit = value.chars().last().ok_or_else(error)?;
+        let (_, seconds_per_unit) = DURATION_UNITS
+            .iter()
+            .find(|(suffix, _)| *suffix == unit)
+            .ok_or_else(error)?;
+        let quantity: u64 = value[..value.len() - unit.len_utf8()]
+            .parse()
+            .map_err(|_| error())?;
+        let seconds =
+            quantity.checked_mul(*seconds_per_unit).ok_or_else(error)?;
+        Ok(Self(Duration::from_secs(seconds)))
+    }
+}
+
+impl Display for ConfigDuration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let seconds = self.0.as_secs();
+        // Use the largest unit that represents the duration exactly
+        let (unit, seconds_per_unit) = DURATION_UNITS
+            .iter()
+            .find(|(_, seconds_per_unit)| seconds % seconds_per_unit == 0)
+            .unwrap_or(&('s', 1));
+        write!(f, "{}{unit}", seconds / seconds_per_unit)
+    }
+}
+
+impl SensitivePattern {
+    /// Does the variable name match this pattern? Case is ignored, since
+    /// names from multi-variable sources aren't always upper case
+    pub fn matches(&self, variable: &str) -> bool {
+        self.0.matches_with(
+            variable,
+            glob::MatchOptions {
+                case_sensitive: false,
+                ..Default::default()
+            },
+        )
+    }
+}
+
+impl FromStr for SensitivePattern {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let pattern = glob::Pattern::new(value).map_err(|error| {
+            anyhow!("Invalid sensitive pattern `{value}`: {error}")
+        })?;
+        Ok(Self(pattern))
+    }
+}
+
+impl Display for SensitivePattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
 impl ValueSource {
     /// Build a [ValueSource] from a simple string value. All extra fields
     /// are populated with defaults.
     pub fn from_literal(value: impl ToString) -> Self {
+        Self::from(ValueSourceKind::Literal {
+            value: value.to_string(),
+        })
+    }
+}
+
+impl From<ValueSourceKind> for ValueSource {
+    /// Build a [ValueSource] of the given kind. All extra fields are
+    /// populated with defaults.
+    fn from(kind: ValueSourceKind) -> Self {
         Self(ValueSourceInner {
-            kind: ValueSourceKind::Literal {
-                value: value.to_string(),
-            },
+            kind,
             multiple: false.into(),
-            sensitive: false,
+            multiple_format: MultiVariableFormat::default(),
+            prefix: None,
+            sensitive: None,
+            trim: true,
+            default: None,
+            optional: false,
+            cache: None,
+            timeout: None,
+            retries: 0,
+            retry_delay: None,
+            platform: None,
+            encoding: None,
+            transform: Vec::new(),
+            mode: None,
+            separator: None,
+            dedupe: None,
+        })
+    }
+}
+
+impl ValueSourceInner {
+    fn default_trim() -> bool {
+        true
+    }
+
+    /// Should the resolved value(s) be masked in display output?
+    pub fn is_sensitive(&self) -> bool {
+        self.sensitive
+            .unwrap_or_else(|| self.kind.default_sensitive())
+    }
+
+    /// Should the given variable resolved from this source be masked in
+    /// display output? If the source doesn't say either way, the variable's
+    /// name is checked against the patterns. The name is the one from the
+    /// mapping for multi-variable sources, not the one in the config
+    pub fn is_sensitive_variable(
+        &self,
+        variable: &str,
+        patterns: &[SensitivePattern],
+    ) -> bool {
+        self.sensitive.unwrap_or_else(|| {
+            self.kind.default_sensitive()
+                || patterns.iter().any(|pattern| pattern.matches(variable))
+        })
+    }
+
+    /// Should this source load a multi-variable mapping? `dotenv` sources
+    /// always do
+    pub fn is_multiple(&self) -> bool {
+        self.multiple.enabled()
+            || matches!(self.kind, ValueSourceKind::Dotenv { .. })
+    }
+
+    /// Should the given variable from a multi-variable mapping be loaded?
+    pub fn includes(&self, variable: &String) -> bool {
+        match &self.kind {
+            ValueSourceKind::Dotenv { only, except, .. } => {
+                (!self.multiple.enabled() || self.multiple.includes(variable))
+                    && only
+                        .as_ref()
+                        .map_or(true, |only| only.contains(variable))
+                    && !except.contains(variable)
+            }
+            _ => self.multiple.includes(variable),
+        }
+    }
+
+    /// How to combine a resolved value for the given variable with its
+    /// current value. `PATH` is added to by default, since replacing it is
+    /// rarely what anyone wants
+    pub fn mode_for(&self, variable: &str) -> ValueMode {
+        self.mode.unwrap_or(if variable == PATH_VARIABLE {
+            ValueMode::Prepend
+        } else {
+            ValueMode::Replace
+        })
+    }
+
+    /// Should repeated entries be removed when prepending or appending to the
+    /// given variable? By default, only lists of paths are deduped, where
+    /// later duplicates have no effect. Other lists, e.g. `LDFLAGS`, can
+    /// repeat entries on purpose
+    pub fn dedupe_for(&self, variable: &str) -> bool {
+        self.dedupe
+            .unwrap_or_else(|| variable.ends_with(PATH_VARIABLE))
+    }
+
+    /// Separator to use when prepending or appending. If the source doesn't
+    /// specify, use the PATH separator for the current platform
+    pub fn separator(&self) -> &str {
+        self.separator.as_deref().unwrap_or_else(|| {
+            Platform::current().map_or(":", Platform::list_separator)
         })
     }
+
+    /// Should this source be used on the current platform?
+    pub fn matches_platform(&self) -> bool {
+        self.platform
+            .map_or(true, |platform| Some(platform) == Platform::current())
+    }
+}
+
+impl VariableDefinition {
+    /// Get the value source to use for the given shell on the current
+    /// platform. If no source matches, return `None`.
+    pub fn select(&self, shell_kind: ShellKind) -> Option<&ValueSource> {
+        match self {
+            Self::Single(value_source) => {
+                Some(value_source).filter(|source| source.0.matches_platform())
+            }
+            Self::Candidates(value_sources) => value_sources
+                .iter()
+                .find(|source| source.0.matches_platform()),
+            Self::PerShell(value_sources) => value_sources
+                .get(shell_kind)
+                .filter(|source| source.0.matches_platform()),
+        }
+    }
+
+    /// Get all value sources in this definition, including those that don't
+    /// match the current platform or shell
+    pub fn value_sources_mut(
+        &mut self,
+    ) -> Box<dyn Iterator<Item = &mut ValueSource> + '_> {
+        match self {
+            Self::Single(value_source) => {
+                Box::new(std::iter::once(value_source))
+            }
+            Self::Candidates(value_sources) => {
+                Box::new(value_sources.iter_mut())
+            }
+            Self::PerShell(ShellValueSources {
+                bash,
+                zsh,
+                fish,
+                sh,
+                default,
+            }) => {
+                Box::new([bash, zsh, fish, sh, default].into_iter().flatten())
+            }
+        }
+    }
+}
+
+impl ShellValueSources {
+    /// Keys of the table, as they appear in config
+    pub const KEYS: &'static [&'static str] =
+        &["bash", "zsh", "fish", "sh", "default"];
+
+    /// Get the value source for a shell, falling back to the default
+    pub fn get(&self, shell_kind: ShellKind) -> Option<&ValueSource> {
+        let value_source = match shell_kind {
+            ShellKind::Bash => &self.bash,
+            ShellKind::Zsh => &self.zsh,
+            ShellKind::Fish => &self.fish,
+            ShellKind::Sh => &self.sh,
+        };
+        value_source.as_ref().or(self.default.as_ref())
+    }
+}
+
+impl From<ValueSource> for VariableDefinition {
+    fn from(value_source: ValueSource) -> Self {
+        Self::Single(value_source)
+    }
+}
+
+impl Platform {
+    /// Get the platform that env-select is running on. `None` if it's not
+    /// one of the supported platforms
+    pub fn current() -> Option<Self> {
+        if cfg!(target_os = "linux") {
+            Some(Self::Linux)
+        } else if cfg!(target_os = "macos") {
+            Some(Self::Macos)
+        } else if cfg!(target_os = "windows") {
+            Some(Self::Windows)
+        } else {
+            None
+        }
+    }
+
+    /// Separator between entries of list variables like PATH
+    pub fn list_separator(self) -> &'static str {
+        match self {
+            Self::Linux | Self::Macos => ":",
+            Self::Windows => ";",
+        }
+    }
+}
+
+impl ValueSourceKind {
+    /// Should values from this kind of source be masked by default? Sources
+    /// that exist solely to load secrets are sensitive unless the user says
+    /// otherwise.
+    pub fn default_sensitive(&self) -> bool {
+        matches!(
+            self,
+            Self::Vault { .. }
+                | Self::AwsSecretsManager { .. }
+                | Self::AzureKeyVault { .. }
+                | Self::GcpSecret { .. }
+                | Self::OnePassword { .. }
+                | Self::Doppler { .. }
+                | Self::Keychain { .. }
+                | Self::Pass { .. }
+                | Self::Sops { .. }
+        )
+    }
+
+    /// Maximum time to wait for this kind of source, if the user doesn't
+    /// specify a timeout. Remote commands can hang on an unresponsive host, so
+    /// they're bounded by default.
+    pub fn default_timeout(&self) -> Option<Duration> {
+        match self {
+            Self::Ssh { .. } => Some(Duration::from_secs(30)),
+            _ => None,
+        }
+    }
+}
+
+impl Generator {
+    fn default_hex_length() -> usize {
+        32
+    }
 }
 
 impl MultiVariable {
@@ -333,7 +1678,46 @@ impl MultiVariable {
         match self {
             // This shouldn't be called if multi is disabled
             MultiVariable::Bool(enabled) => *enabled,
-            MultiVariable::List(variables) => variables.contains(variable),
+            MultiVariable::List(variables)
+            | MultiVariable::Filtered {
+                keys: variables, ..
+            } => variables.contains(variable),
+            MultiVariable::Map(variables) => variables.contains_key(variable),
+        }
+    }
+
+    /// Get the variables that must be present in the mapping
+    pub fn required(&self) -> Vec<&str> {
+        match self {
+            MultiVariable::Bool(_)
+            | MultiVariable::Filtered {
+                required: false, ..
+            } => vec![],
+            MultiVariable::List(variables)
+            | MultiVariable::Filtered {
+                keys: variables,
+                required: true,
+            } => variables.iter().map(String::as_str).collect(),
+            MultiVariable::Map(variables) => {
+                variables.keys().map(String::as_str).collect()
+            }
+        }
+    }
+
+    fn default_required() -> bool {
+        true
+    }
+
+    /// Get the name to load a variable from the mapping under. Only
+    /// [MultiVariable::Map] changes names
+    pub fn rename(&self, variable: String) -> String {
+        match self {
+            MultiVariable::Map(variables) => {
+                variables.get(&variable).cloned().unwrap_or(variable)
+            }
+            MultiVariable::Bool(_)
+            | MultiVariable::List(_)
+            | MultiVariable::Filtered { .. } => variable,
         }
     }
 }
@@ -345,12 +1729,137 @@ impl Default for MultiVariable {
 }
 
 impl SideEffect {
-    pub fn setup(&self) -> Option<&ShellCommand> {
-        self.setup.as_ref()
+    pub fn setup(&self) -> anyhow::Result<Option<SideEffectCommand>> {
+        match self {
+            Self::Shell { setup, .. } => {
+                Ok(setup.as_ref().map(SideEffectCommand::Shell))
+            }
+            Self::Builtin(builtin) => builtin.setup().map(Some),
+        }
+    }
+
+    pub fn teardown(&self) -> anyhow::Result<Option<SideEffectCommand>> {
+        match self {
+            Self::Shell { teardown, .. } => {
+                Ok(teardown.as_ref().map(SideEffectCommand::Shell))
+            }
+            Self::Builtin(builtin) => builtin.teardown().map(Some),
+        }
+    }
+
+    /// Should the setup command be left running in the background?
+    pub fn is_background(&self) -> bool {
+        matches!(
+            self,
+            Self::Shell {
+                background: true,
+                ..
+            }
+        )
+    }
+}
+
+impl BuiltinSideEffect {
+    /// Script to start a port forward in the background. It waits until the
+    /// forward is ready, so whatever runs next can use it immediately. If it
+    /// isn't ready after 30 seconds, the forward is killed and setup fails.
+    /// The PID and output files are passed as the first two arguments,
+    /// followed by the arguments for `kubectl port-forward`
+    const PORT_FORWARD_SETUP: &'static str = r#"pidfile="$1"; log="$2"; shift 2
+kubectl port-forward "$@" > "$log" 2>&1 &
+echo $! > "$pidfile"
+tries=0
+until grep -q "Forwarding from" "$log"; do
+    kill -0 $! 2> /dev/null || { cat "$log" >&2; exit 1; }
+    tries=$((tries + 1))
+    if [ "$tries" -ge 300 ]; then
+        kill $!; rm -f "$pidfile"
+        echo "Timed out waiting for port forward to be ready" >&2
+        cat "$log" >&2; exit 1
+    fi
+    sleep 0.1
+done"#;
+
+    /// Script to stop a port forward started by [Self::PORT_FORWARD_SETUP]
+    const PORT_FORWARD_TEARDOWN: &'static str =
+        r#"kill "$(cat "$1")"; rm -f "$1" "$2""#;
+
+    fn setup(&self) -> anyhow::Result<SideEffectCommand<'static>> {
+        let command = match self {
+            Self::KubectlPortForward {
+                target,
+                ports,
+                namespace,
+            } => {
+                let mut arguments =
+                    self.port_forward_arguments(Self::PORT_FORWARD_SETUP)?;
+                if let Some(namespace) = namespace {
+                    arguments.extend(["--namespace".into(), namespace.clone()]);
+                }
+                arguments.push(target.clone());
+                arguments.extend(ports.split_whitespace().map(String::from));
+                SideEffectCommand::Native {
+                    program: "sh",
+                    arguments,
+                }
+            }
+            Self::DockerCompose { file } => {
+                Self::docker_compose_command(file, &["up", "--detach"])
+            }
+        };
+        Ok(command)
+    }
+
+    fn teardown(&self) -> anyhow::Result<SideEffectCommand<'static>> {
+        let command = match self {
+            Self::KubectlPortForward { .. } => SideEffectCommand::Native {
+                program: "sh",
+                arguments: self
+                    .port_forward_arguments(Self::PORT_FORWARD_TEARDOWN)?,
+            },
+            Self::DockerCompose { file } => {
+                Self::docker_compose_command(file, &["down"])
+            }
+        };
+        Ok(command)
+    }
+
+    /// Arguments to run a port forward script with `sh`. The PID and output
+    /// files are in the runtime directory, named by a hash of the side
+    /// effect, so setup and teardown agree on them without any extra state.
+    /// The hash has to be the same across builds, since setup and teardown
+    /// can be run by different versions of env-select
+    fn port_forward_arguments(
+        &self,
+        script: &str,
+    ) -> anyhow::Result<Vec<String>> {
+        let key = serde_json::to_string(self)?;
+        let base = runtime_dir()?
+            .join(format!("port-forward-{:016x}", stable_hash(key.as_bytes())));
+        Ok(vec![
+            "-c".into(),
+            script.into(),
+            // $0 for the script
+            "sh".into(),
+            base.with_extension("pid").to_string_lossy().into_owned(),
+            base.with_extension("log").to_string_lossy().into_owned(),
+        ])
     }
 
-    pub fn teardown(&self) -> Option<&ShellCommand> {
-        self.teardown.as_ref()
+    fn docker_compose_command(
+        file: &Path,
+        subcommand: &[&str],
+    ) -> SideEffectCommand<'static> {
+        let mut arguments = vec![
+            "compose".to_owned(),
+            "--file".to_owned(),
+            file.to_string_lossy().into_owned(),
+        ];
+        arguments.extend(subcommand.iter().copied().map(String::from));
+        SideEffectCommand::Native {
+            program: "docker",
+            arguments,
+        }
     }
 }
 
@@ -364,8 +1873,43 @@ impl Display for ValueSourceInner {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match &self.kind {
             ValueSourceKind::Literal { value } => write!(f, "\"{value}\""),
+            ValueSourceKind::Env { variable } => write!(f, "${variable}"),
+            ValueSourceKind::Unset => write!(f, "(unset)"),
             ValueSourceKind::File { path } => write!(f, "{}", path.display()),
-            ValueSourceKind::Command { command, cwd } => {
+            ValueSourceKind::FileGlob { path, .. } => {
+                write!(f, "{}", path.display())
+            }
+            ValueSourceKind::Dotenv { path, .. } => {
+                write!(f, "load .env ({})", path.display())
+            }
+            ValueSourceKind::JsonFile { path, pointer } => {
+                write!(f, "{}#{pointer}", path.display())
+            }
+            ValueSourceKind::YamlFile { path, key } => {
+                write!(f, "{}", path.display())?;
+                if let Some(key) = key {
+                    write!(f, "#{key}")?;
+                }
+                Ok(())
+            }
+            ValueSourceKind::Sops { path, .. } => {
+                write!(f, "sops:{}", path.display())
+            }
+            ValueSourceKind::Generate { generator } => match generator {
+                Generator::Uuid4 => write!(f, "<uuid4>"),
+                Generator::RandomHex { length } => {
+                    write!(f, "<random-hex({length})>")
+                }
+                Generator::Timestamp { .. } => write!(f, "<timestamp>"),
+            },
+            ValueSourceKind::Prompt { message, .. } => {
+                write!(f, "<prompt")?;
+                if let Some(message) = message {
+                    write!(f, " \"{message}\"")?;
+                }
+                write!(f, ">")
+            }
+            ValueSourceKind::Command { command, cwd, .. } => {
                 write!(f, "{command}")?;
                 match cwd {
                     Some(cwd) => {
@@ -374,19 +1918,255 @@ impl Display for ValueSourceInner {
                     None => write!(f, " (current directory)"),
                 }
             }
+            ValueSourceKind::NativeCommand {
+                program, args, cwd, ..
+            } => {
+                write!(f, "{program}")?;
+                for arg in args {
+                    write!(f, " {arg}")?;
+                }
+                match cwd {
+                    Some(cwd) => {
+                        write!(f, " ({})", cwd.display())
+                    }
+                    None => write!(f, " (current directory)"),
+                }
+            }
+            ValueSourceKind::Kubernetes {
+                command,
+                pod_selector,
+                namespace,
+                container,
+            } => {
+                write!(f, "`{}` in pod `{pod_selector}`", command.join(" "))?;
+                if let Some(container) = container {
+                    write!(f, ", container `{container}`")?;
+                }
+                if let Some(namespace) = namespace {
+                    write!(f, " (namespace `{namespace}`)")?;
+                }
+                Ok(())
+            }
+            ValueSourceKind::GitConfig { key, cwd } => {
+                write!(f, "git:{key}")?;
+                if let Some(cwd) = cwd {
+                    write!(f, " ({})", cwd.display())?;
+                }
+                Ok(())
+            }
+            ValueSourceKind::Ssh { host, command } => {
+                write!(f, "`{command}` on `{host}`")
+            }
+            ValueSourceKind::Docker {
+                command,
+                container,
+                compose_service,
+            } => {
+                write!(f, "`{}` in ", command.join(" "))?;
+                match (container, compose_service) {
+                    (Some(container), _) => {
+                        write!(f, "container `{container}`")
+                    }
+                    (None, Some(service)) => {
+                        write!(f, "compose service `{service}`")
+                    }
+                    (None, None) => write!(f, "<no container>"),
+                }
+            }
+            ValueSourceKind::Vault { path, field, mount } => {
+                write!(f, "vault:")?;
+                if let Some(mount) = mount {
+                    write!(f, "{mount}/")?;
+                }
+                write!(f, "{path}")?;
+                if let Some(field) = field {
+                    write!(f, "#{field}")?;
+                }
+                Ok(())
+            }
+            ValueSourceKind::AwsSsm { parameter, .. } => {
+                write!(f, "ssm:{parameter}")
+            }
+            ValueSourceKind::AwsSecretsManager { secret_id, key, .. } => {
+                write!(f, "secretsmanager:{secret_id}")?;
+                if let Some(key) = key {
+                    write!(f, "#{key}")?;
+                }
+                Ok(())
+            }
+            ValueSourceKind::AzureKeyVault { vault, secret } => {
+                write!(f, "azure:{vault}/{secret}")
+            }
+            ValueSourceKind::GcpSecret {
+                secret,
+                version,
+                project,
+            } => {
+                write!(f, "gcp:")?;
+                if let Some(project) = project {
+                    write!(f, "{project}/")?;
+                }
+                write!(f, "{secret}")?;
+                if let Some(version) = version {
+                    write!(f, "@{version}")?;
+                }
+                Ok(())
+            }
+            ValueSourceKind::OnePassword { reference } => {
+                write!(f, "{reference}")
+            }
+            ValueSourceKind::Doppler {
+                secret,
+                project,
+                config,
+            } => {
+                write!(f, "doppler:")?;
+                if let Some(project) = project {
+                    write!(f, "{project}/")?;
+                }
+                if let Some(config) = config {
+                    write!(f, "{config}/")?;
+                }
+                write!(f, "{}", secret.as_deref().unwrap_or("*"))
+            }
+            ValueSourceKind::Keychain { service, account } => {
+                write!(f, "keychain:{service}")?;
+                if let Some(account) = account {
+                    write!(f, " ({account})")?;
+                }
+                Ok(())
+            }
+            ValueSourceKind::Pass { entry, line, field } => {
+                write!(f, "pass:{entry}")?;
+                if let Some(field) = field {
+                    write!(f, "#{field}")?;
+                } else if let Some(line) = line {
+                    write!(f, ":{line}")?;
+                }
+                Ok(())
+            }
+            ValueSourceKind::Http { url, .. } => write!(f, "GET {url}"),
+        }?;
+        if let Some(default) = &self.default {
+            write!(f, " (default \"{default}\")")?;
+        }
+        if let Some(platform) = self.platform {
+            write!(f, " ({platform} only)")?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for VariableDefinition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Single(value_source) => write!(f, "{value_source}"),
+            Self::Candidates(value_sources) => {
+                for (i, value_source) in value_sources.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " | ")?;
+                    }
+                    write!(f, "{value_source}")?;
+                }
+                Ok(())
+            }
+            Self::PerShell(value_sources) => {
+                let ShellValueSources {
+                    bash,
+                    zsh,
+                    fish,
+                    sh,
+                    default,
+                } = value_sources;
+                let entries = ShellValueSources::KEYS
+                    .iter()
+                    .zip([bash, zsh, fish, sh, default])
+                    .filter_map(|(key, value_source)| {
+                        Some((key, value_source.as_ref()?))
+                    });
+                for (i, (key, value_source)) in entries.enumerate() {
+                    if i > 0 {
+                        write!(f, " | ")?;
+                    }
+                    write!(f, "{key}: {value_source}")?;
+                }
+                Ok(())
+            }
         }
     }
 }
 
+/// An application or profile, which can be selected by its name or any of its
+/// aliases
+pub trait Aliased {
+    fn aliases(&self) -> &IndexSet<Name>;
+}
+
+impl Aliased for Application {
+    fn aliases(&self) -> &IndexSet<Name> {
+        &self.aliases
+    }
+}
+
+impl Aliased for Profile {
+    fn aliases(&self) -> &IndexSet<Name> {
+        &self.aliases
+    }
+}
+
+/// Get an application or profile, along with its real name, by its name or
+/// one of its aliases. If nothing matches, return an error listing the
+/// options.
+pub fn get_aliased<'a, T: Aliased>(
+    options: &'a IndexMap<Name, T>,
+    name: &Name,
+) -> anyhow::Result<(&'a Name, &'a T)> {
+    options
+        .iter()
+        .find(|(_, option)| option.aliases().contains(name))
+        .or_else(|| options.get_key_value(name))
+        .ok_or_else(|| {
+            anyhow!(
+                "Unknown key {name}, options are: {}",
+                options.display_keys()
+            )
+        })
+}
+
+/// Check that no alias in a group collides with a name or another alias.
+/// `describe` formats a name for error messages.
+fn check_aliases<T: Aliased>(
+    options: &IndexMap<Name, T>,
+    describe: impl Fn(&Name) -> String,
+) -> anyhow::Result<()> {
+    // Alias -> name of the option that owns it
+    let mut owners: HashMap<&Name, &Name> = HashMap::new();
+    for (name, option) in options {
+        for alias in option.aliases() {
+            if options.contains_key(alias) {
+                bail!(
+                    "Alias `{alias}` of {} conflicts with {}",
+                    describe(name),
+                    describe(alias)
+                );
+            }
+            if let Some(owner) = owners.insert(alias, name) {
+                bail!(
+                    "Alias `{alias}` is used by both {} and {}",
+                    describe(owner),
+                    describe(name)
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
 /// Nice little extension trait for IndexMap
 pub trait MapExt {
     type Key;
     type Value;
 
-    /// Get a reference to a value by key. If the key isn't in the map, return
-    /// an error with a helpful message.
-    fn try_get(&self, key: &Self::Key) -> anyhow::Result<&Self::Value>;
-
     /// Print the keys of this map, comma-delimited
     fn display_keys(&self) -> String {
         self.display_keys_delimited(", ")
@@ -400,12 +2180,6 @@ impl<K: Display + Eq + Hash + PartialEq, V> MapExt for IndexMap<K, V> {
     type Key = K;
     type Value = V;
 
-    fn try_get(&self, key: &Self::Key) -> anyhow::Result<&Self::Value> {
-        self.get(key).ok_or_else(|| {
-            anyhow!("Unknown key {}, options are: {}", key, self.display_keys())
-        })
-    }
-
     fn display_keys_delimited(&self, separator: &str) -> String {
         self.keys()
             .map(|key| key.to_string())
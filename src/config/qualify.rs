@@ -1,6 +1,7 @@
 use crate::config::{
-    Application, Config, Name, Profile, ProfileReference, ValueSource,
-    ValueSourceKind,
+    Application, BuiltinSideEffect, Config, Defaults, Name, Profile,
+    ProfileReference, SideEffect, ValueSource, ValueSourceKind,
+    VariableDefinition,
 };
 use log::trace;
 use std::path::{Path, PathBuf};
@@ -33,6 +34,10 @@ impl<'a> Qualify<'a> for Config {
 
     fn qualify(&mut self, config_path: &Self::Context) {
         trace!("Qualifying config `{config_path:?}`");
+        self.defaults.qualify(config_path);
+        for import in self.imports.iter_mut().chain(&mut self.includes) {
+            import.qualify(config_path);
+        }
         for (name, application) in &mut self.applications {
             trace!("Qualifying application `{name}`");
             application.qualify(&ApplicationContext {
@@ -43,10 +48,40 @@ impl<'a> Qualify<'a> for Config {
     }
 }
 
+impl<'a> Qualify<'a> for Defaults {
+    type Context = Path;
+
+    fn qualify(&mut self, config_path: &Self::Context) {
+        for value_source in self
+            .variables
+            .values_mut()
+            .flat_map(VariableDefinition::value_sources_mut)
+        {
+            value_source.qualify(config_path);
+        }
+
+        for side_effect in self
+            .pre_export
+            .iter_mut()
+            .chain(self.post_export.iter_mut())
+        {
+            side_effect.qualify(config_path);
+        }
+    }
+}
+
 impl<'a> Qualify<'a> for Application {
     type Context = ApplicationContext<'a>;
 
     fn qualify(&mut self, context: &Self::Context) {
+        for value_source in self
+            .variables
+            .values_mut()
+            .flat_map(VariableDefinition::value_sources_mut)
+        {
+            value_source.qualify(context.config_path);
+        }
+
         for (name, profile) in &mut self.profiles {
             trace!(
                 "Qualifying profile `{}/{}`",
@@ -71,8 +106,36 @@ impl<'a> Qualify<'a> for Profile {
             })
             .collect();
 
-        for value_source in self.variables.values_mut() {
-            value_source.qualify(context);
+        for value_source in self
+            .variables
+            .values_mut()
+            .flat_map(VariableDefinition::value_sources_mut)
+        {
+            value_source.qualify(context.config_path);
+        }
+
+        for side_effect in self
+            .pre_export
+            .iter_mut()
+            .chain(self.post_export.iter_mut())
+        {
+            side_effect.qualify(context.config_path);
+        }
+
+        if let Some(cwd) = &mut self.cwd {
+            cwd.qualify(context.config_path);
+        }
+    }
+}
+
+impl<'a> Qualify<'a> for SideEffect {
+    type Context = Path;
+
+    fn qualify(&mut self, config_path: &Self::Context) {
+        if let SideEffect::Builtin(BuiltinSideEffect::DockerCompose { file }) =
+            self
+        {
+            file.qualify(config_path);
         }
     }
 }
@@ -93,15 +156,22 @@ impl<'a> Qualify<'a> for ProfileReference {
 }
 
 impl<'a> Qualify<'a> for ValueSource {
-    type Context = ApplicationContext<'a>;
+    type Context = Path;
 
-    fn qualify(&mut self, context: &Self::Context) {
+    fn qualify(&mut self, config_path: &Self::Context) {
         match &mut self.0.kind {
-            ValueSourceKind::File { path } => {
-                path.qualify(context.config_path);
+            ValueSourceKind::File { path }
+            | ValueSourceKind::FileGlob { path, .. }
+            | ValueSourceKind::Dotenv { path, .. }
+            | ValueSourceKind::JsonFile { path, .. }
+            | ValueSourceKind::YamlFile { path, .. }
+            | ValueSourceKind::Sops { path, .. } => {
+                path.qualify(config_path);
             }
-            ValueSourceKind::Command { cwd: Some(cwd), .. } => {
-                cwd.qualify(context.config_path);
+            ValueSourceKind::Command { cwd: Some(cwd), .. }
+            | ValueSourceKind::NativeCommand { cwd: Some(cwd), .. }
+            | ValueSourceKind::GitConfig { cwd: Some(cwd), .. } => {
+                cwd.qualify(config_path);
             }
             _ => {}
         }
@@ -113,8 +183,14 @@ impl<'a> Qualify<'a> for PathBuf {
 
     /// Qualify file references based on the given config *file*. The
     /// parent directory of the context path will be the root of the absolute
-    /// path (if this path is relative).
+    /// path (if this path is relative). Paths that start with a template, e.g.
+    /// `${env:HOME}`, are left alone, because they're rendered at resolution
+    /// time and are typically absolute.
     fn qualify(&mut self, config_path: &Self::Context) {
+        if self.to_str().is_some_and(|path| path.starts_with("${")) {
+            trace!("Skipping qualification of template path {self:?}");
+            return;
+        }
         let new_path = match config_path.parent() {
             Some(directory) => directory.join(self.as_path()),
             None => panic!(
@@ -132,7 +208,7 @@ mod tests {
     use super::*;
     use crate::{
         config::Profile,
-        test_util::{command, config, file, map, set},
+        test_util::{command, config, dotenv, file, map, native_command, set},
     };
     use pretty_assertions::assert_eq;
     use rstest::rstest;
@@ -209,7 +285,9 @@ mod tests {
                     variables: map([
                         ("VAR1", command("echo")),
                         ("VAR2", command("echo").cwd(".venv/bin")),
+                        ("VAR3", native_command("echo", &[]).cwd(".venv/bin")),
                     ]),
+                    cwd: Some("packages/web".into()),
                     ..Default::default()
                 },
             )],
@@ -225,7 +303,13 @@ mod tests {
                         variables: map([
                             ("VAR1", command("echo")),
                             ("VAR2", command("echo").cwd("/root/.venv/bin")),
+                            (
+                                "VAR3",
+                                native_command("echo", &[])
+                                    .cwd("/root/.venv/bin"),
+                            ),
                         ]),
+                        cwd: Some("/root/packages/web".into()),
                         ..Default::default()
                     },
                 )],
@@ -240,7 +324,27 @@ mod tests {
             vec![(
                 "prof",
                 Profile {
-                    variables: map([("VAR1", file("var.txt"))]),
+                    variables: map([
+                        ("VAR1", file("var.txt")),
+                        ("VAR4", file("${env:HOME}/var.txt")),
+                        (
+                            "VAR2",
+                            ValueSourceKind::JsonFile {
+                                path: "var.json".into(),
+                                pointer: "/var".into(),
+                            }
+                            .into(),
+                        ),
+                        (
+                            "VAR3",
+                            ValueSourceKind::Sops {
+                                path: "var.enc.env".into(),
+                                format: None,
+                            }
+                            .into(),
+                        ),
+                        ("VAR5", dotenv("var.env", None, &[])),
+                    ]),
                     ..Default::default()
                 },
             )],
@@ -253,7 +357,27 @@ mod tests {
                 vec![(
                     "prof",
                     Profile {
-                        variables: map([("VAR1", file("/root/var.txt"))]),
+                        variables: map([
+                            ("VAR1", file("/root/var.txt")),
+                            ("VAR4", file("${env:HOME}/var.txt")),
+                            (
+                                "VAR2",
+                                ValueSourceKind::JsonFile {
+                                    path: "/root/var.json".into(),
+                                    pointer: "/var".into(),
+                                }
+                                .into(),
+                            ),
+                            (
+                                "VAR3",
+                                ValueSourceKind::Sops {
+                                    path: "/root/var.enc.env".into(),
+                                    format: None,
+                                }
+                                .into(),
+                            ),
+                            ("VAR5", dotenv("/root/var.env", None, &[])),
+                        ]),
                         ..Default::default()
                     },
                 )],
@@ -0,0 +1,709 @@
+//! JSON Schema for the config file format, for editor validation and
+//! completion. This is written by hand, so it has to be kept in sync with the
+//! serde attributes (and custom deserialization in [super::cereal]) of the
+//! config types.
+
+use serde_json::{json, Map, Value};
+
+/// Valid application/profile names. See [super::Name]
+const NAME_PATTERN: &str = r"[^\s\\/*?!]([^\\/*?!]*[^\s\\/*?!])?";
+
+/// Generate a JSON Schema for [super::Config]
+pub fn schema() -> Value {
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "env-select config",
+        "$ref": "#/$defs/Config",
+        "$defs": {
+            "Config": {
+                "type": "object",
+                "additionalProperties": false,
+                "properties": {
+                    "applications": {
+                        "description": "Named applications, each with a set \
+                            of profiles to select between",
+                        "type": "object",
+                        "additionalProperties": reference("Application"),
+                    },
+                    "defaults": reference("Defaults"),
+                    "imports": {
+                        "description": "Other config files to load, relative \
+                            to this one",
+                        "type": "array",
+                        "items": {"type": "string"},
+                    },
+                    "includes": {
+                        "description": "Glob patterns of other config files \
+                            to load, relative to this one",
+                        "type": "array",
+                        "items": {"type": "string"},
+                    },
+                    "strict_templates": {"type": "boolean"},
+                    "strict_conflicts": {"type": "boolean"},
+                    "masking": {
+                        "description": "How sensitive values are shown in \
+                            `es show env` and `es set`",
+                        "enum": ["full", "partial"],
+                    },
+                    "sensitive_patterns": {
+                        "description": "Glob patterns of variable names that \
+                            are always masked, e.g. `*_TOKEN`",
+                        "type": "array",
+                        "items": {"type": "string"},
+                    },
+                    "root": {
+                        "description": "Stop searching parent directories for \
+                            config files after this one",
+                        "type": "boolean",
+                    },
+                    "stop_at_home": {
+                        "description": "Stop searching parent directories \
+                            after the home directory. Only used from the \
+                            global config file",
+                        "type": "boolean",
+                    },
+                },
+            },
+            "Defaults": {
+                "description": "Variables and side effects for every profile \
+                    in every application",
+                "type": "object",
+                "additionalProperties": false,
+                "properties": {
+                    "variables": reference("Variables"),
+                    "pre_export": side_effects(),
+                    "post_export": side_effects(),
+                },
+            },
+            "Application": {
+                "type": "object",
+                "additionalProperties": false,
+                "properties": {
+                    "aliases": names(),
+                    "description": {"type": "string"},
+                    "variables": reference("Variables"),
+                    "profiles": {
+                        "type": "object",
+                        "additionalProperties": reference("Profile"),
+                    },
+                },
+            },
+            "Profile": {
+                "type": "object",
+                "additionalProperties": false,
+                "properties": {
+                    "aliases": names(),
+                    "description": {"type": "string"},
+                    "hidden": {"type": "boolean"},
+                    "deprecated": {
+                        "description": "Warn whenever this profile is \
+                            selected. Either a flag or a message",
+                        "type": ["boolean", "string"],
+                    },
+                    "extends": {
+                        "type": "array",
+                        "uniqueItems": true,
+                        "items": reference("ProfileReference"),
+                    },
+                    "variables": reference("Variables"),
+                    "pre_export": side_effects(),
+                    "post_export": side_effects(),
+                    "kubernetes_context": {"type": "string"},
+                    "kubernetes_namespace": {"type": "string"},
+                    "cwd": {"type": "string"},
+                },
+            },
+            "Name": {
+                "type": "string",
+                "pattern": format!("^{NAME_PATTERN}$"),
+            },
+            "ProfileReference": {
+                "description": "Profile name, optionally qualified as \
+                    `application/profile`",
+                "type": "string",
+                "pattern": format!("^({NAME_PATTERN}/)?{NAME_PATTERN}$"),
+            },
+            "Variables": {
+                "type": "object",
+                "additionalProperties": reference("VariableDefinition"),
+            },
+            "VariableDefinition": {
+                "anyOf": [
+                    reference("ValueSource"),
+                    {
+                        "description": "Candidate sources. The first that \
+                            matches the current platform is used",
+                        "type": "array",
+                        "minItems": 1,
+                        "items": reference("ValueSource"),
+                    },
+                    reference("ShellValueSources"),
+                ],
+            },
+            "ShellValueSources": {
+                "description": "Value sources keyed by shell kind",
+                "type": "object",
+                "additionalProperties": false,
+                "minProperties": 1,
+                "properties": {
+                    "bash": reference("ValueSource"),
+                    "zsh": reference("ValueSource"),
+                    "fish": reference("ValueSource"),
+                    "sh": reference("ValueSource"),
+                    "default": reference("ValueSource"),
+                },
+            },
+            "ValueSource": {
+                "anyOf": [
+                    {
+                        "description": "Literal value. null unsets the \
+                            variable",
+                        "type": ["string", "boolean", "number", "null"],
+                    },
+                    reference("ValueSourceTable"),
+                ],
+            },
+            // Kind-specific fields are flattened into the same table as the
+            // common fields, so unknown fields are accepted
+            "ValueSourceTable": {
+                "type": "object",
+                "required": ["type"],
+                "properties": value_source_properties(),
+                "oneOf": value_source_kinds(),
+            },
+            "MultiVariable": {
+                "anyOf": [
+                    {"type": "boolean"},
+                    {"type": "array", "items": {"type": "string"}},
+                    {
+                        "type": "object",
+                        "additionalProperties": false,
+                        "required": ["keys"],
+                        "properties": {
+                            "keys": {
+                                "type": "array",
+                                "items": {"type": "string"},
+                            },
+                            "required": {"type": "boolean"},
+                        },
+                    },
+                    {
+                        "type": "object",
+                        "additionalProperties": {"type": "string"},
+                    },
+                ],
+            },
+            "Transform": {
+                "anyOf": [
+                    {"enum": ["uppercase", "lowercase", "trim"]},
+                    single_key("replace", object(
+                        json!({
+                            "from": {"type": "string"},
+                            "to": {"type": "string"},
+                        }),
+                        &["from", "to"],
+                    )),
+                    single_key("regex", object(
+                        json!({
+                            "pattern": {"type": "string"},
+                            "group": {"type": "integer", "minimum": 0},
+                        }),
+                        &["pattern"],
+                    )),
+                ],
+            },
+            "Duration": {
+                "description": "Number with a unit suffix: s, m, h, or d, \
+                    e.g. `5m`",
+                "type": "string",
+                "pattern": "^[0-9]+[smhd]$",
+            },
+            "SideEffect": {
+                "anyOf": [
+                    object(
+                        json!({
+                            "setup": {"type": "string"},
+                            "teardown": {"type": "string"},
+                            "background": {"type": "boolean"},
+                        }),
+                        &[],
+                    ),
+                    reference("BuiltinSideEffect"),
+                ],
+            },
+            "BuiltinSideEffect": {
+                "oneOf": [
+                    object(
+                        json!({
+                            "type": {"const": "kubectl-port-forward"},
+                            "target": {"type": "string"},
+                            "ports": {"type": "string"},
+                            "namespace": {"type": "string"},
+                        }),
+                        &["type", "target", "ports"],
+                    ),
+                    object(
+                        json!({
+                            "type": {"const": "docker-compose"},
+                            "file": {"type": "string"},
+                        }),
+                        &["type", "file"],
+                    ),
+                ],
+            },
+        },
+    })
+}
+
+/// Fields common to all value source kinds. See [super::ValueSourceInner]
+fn value_source_properties() -> Value {
+    json!({
+        "type": {"type": "string"},
+        "multiple": reference("MultiVariable"),
+        "multiple_format": {"enum": ["dotenv", "json", "yaml", "key-value"]},
+        "prefix": {"type": "string"},
+        "sensitive": {"type": "boolean"},
+        "trim": {"type": "boolean"},
+        "default": {"type": "string"},
+        "optional": {"type": "boolean"},
+        "cache": reference("Duration"),
+        "timeout": reference("Duration"),
+        "retries": {"type": "integer", "minimum": 0},
+        "retry_delay": reference("Duration"),
+        "platform": {"enum": ["linux", "macos", "windows"]},
+        "encoding": {"enum": ["base64", "base64-encode"]},
+        "transform": {"type": "array", "items": reference("Transform")},
+        "mode": {"enum": ["prepend", "append", "replace"]},
+        "separator": {"type": "string"},
+    })
+}
+
+/// Kind-specific fields of a value source, one schema per kind. See
+/// [super::ValueSourceKind]
+fn value_source_kinds() -> Vec<Value> {
+    let string = || json!({"type": "string"});
+    let boolean = || json!({"type": "boolean"});
+    let strings = || json!({"type": "array", "items": {"type": "string"}});
+    let kinds: &[(&str, Value, &[&str])] = &[
+        ("literal", json!({"value": string()}), &["value"]),
+        ("env", json!({"variable": string()}), &["variable"]),
+        ("unset", json!({}), &[]),
+        ("file", json!({"path": string()}), &["path"]),
+        (
+            "file-glob",
+            json!({"path": string(), "allow_empty": boolean()}),
+            &["path"],
+        ),
+        (
+            "dotenv",
+            json!({"path": string(), "only": strings(), "except": strings()}),
+            &["path"],
+        ),
+        (
+            "json-file",
+            json!({"path": string(), "pointer": string()}),
+            &["path"],
+        ),
+        (
+            "yaml-file",
+            json!({"path": string(), "key": string()}),
+            &["path"],
+        ),
+        (
+            "sops",
+            json!({
+                "path": string(),
+                "format": {"enum": ["dotenv", "yaml", "json"]},
+            }),
+            &["path"],
+        ),
+        (
+            "generate",
+            json!({
+                "generator": {"enum": ["uuid4", "random-hex", "timestamp"]},
+                "length": {"type": "integer", "minimum": 0},
+                "format": {"enum": ["unix", "unix-millis", "rfc3339"]},
+            }),
+            &["generator"],
+        ),
+        (
+            "prompt",
+            json!({"message": string(), "confirm": boolean()}),
+            &[],
+        ),
+        (
+            "command",
+            json!({
+                "command": string(),
+                "cwd": string(),
+                "stdin": string(),
+                "use_profile_env": boolean(),
+            }),
+            &["command"],
+        ),
+        (
+            "native-command",
+            json!({
+                "program": string(),
+                "args": strings(),
+                "cwd": string(),
+                "use_profile_env": boolean(),
+            }),
+            &["program"],
+        ),
+        (
+            "kubernetes",
+            json!({
+                "command": strings(),
+                "pod_selector": string(),
+                "namespace": string(),
+                "container": string(),
+            }),
+            &["command", "pod_selector"],
+        ),
+        (
+            "git-config",
+            json!({"key": string(), "cwd": string()}),
+            &["key"],
+        ),
+        (
+            "ssh",
+            json!({"host": string(), "command": string()}),
+            &["host", "command"],
+        ),
+        (
+            "docker",
+            json!({
+                "command": strings(),
+                "container": string(),
+                "compose_service": string(),
+            }),
+            &["command"],
+        ),
+        (
+            "vault",
+            json!({"path": string(), "field": string(), "mount": string()}),
+            &["path"],
+        ),
+        (
+            "aws-ssm",
+            json!({
+                "parameter": string(),
+                "region": string(),
+                "profile": string(),
+            }),
+            &["parameter"],
+        ),
+        (
+            "aws-secrets-manager",
+            json!({
+                "secret_id": string(),
+                "key": string(),
+                "region": string(),
+                "profile": string(),
+            }),
+            &["secret_id"],
+        ),
+        (
+            "azure-key-vault",
+            json!({"vault": string(), "secret": string()}),
+            &["vault", "secret"],
+        ),
+        (
+            "gcp-secret",
+            json!({
+                "secret": string(),
+                "version": string(),
+                "project": string(),
+            }),
+            &["secret"],
+        ),
+        ("1password", json!({"reference": string()}), &["reference"]),
+        (
+            "doppler",
+            json!({
+                "secret": string(),
+                "project": string(),
+                "config": string(),
+            }),
+            &[],
+        ),
+        (
+            "keychain",
+            json!({"service": string(), "account": string()}),
+            &["service"],
+        ),
+        (
+            "pass",
+            json!({
+                "entry": string(),
+                "line": {"type": "integer", "minimum": 0},
+                "field": string(),
+            }),
+            &["entry"],
+        ),
+        (
+            "http",
+            json!({
+                "url": string(),
+                "headers": {
+                    "type": "object",
+                    "additionalProperties": {"type": "string"},
+                },
+            }),
+            &["url"],
+        ),
+    ];
+
+    kinds
+        .iter()
+        .map(|(kind, properties, required)| {
+            let mut properties = properties.clone();
+            properties
+                .as_object_mut()
+                .unwrap()
+                .insert("type".into(), json!({"const": kind}));
+            json!({"properties": properties, "required": required})
+        })
+        .collect()
+}
+
+/// Pointer to a definition in `$defs`
+fn reference(name: &str) -> Value {
+    json!({"$ref": format!("#/$defs/{name}")})
+}
+
+/// Object with a fixed set of fields
+fn object(properties: Value, required: &[&str]) -> Value {
+    let mut schema = Map::new();
+    schema.insert("type".into(), "object".into());
+    schema.insert("additionalProperties".into(), false.into());
+    schema.insert("properties".into(), properties);
+    if !required.is_empty() {
+        schema.insert("required".into(), json!(required));
+    }
+    schema.into()
+}
+
+/// Object with exactly one field, e.g. an externally tagged enum variant
+fn single_key(key: &str, value: Value) -> Value {
+    object(json!({key: value}), &[key])
+}
+
+/// List of side effects
+fn side_effects() -> Value {
+    json!({"type": "array", "items": reference("SideEffect")})
+}
+
+/// List of aliases
+fn names() -> Value {
+    json!({
+        "type": "array",
+        "uniqueItems": true,
+        "items": reference("Name"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{tests::CONFIG, Config};
+    use regex::Regex;
+    use rstest::rstest;
+
+    /// Minimal JSON Schema validator, covering only the keywords used in
+    /// [schema]. Return the path of the first invalid value
+    fn validate(
+        root: &Value,
+        schema: &Value,
+        value: &Value,
+        path: &str,
+    ) -> Result<(), String> {
+        let error = || Err(path.to_owned());
+        let schema = schema.as_object().unwrap();
+        if let Some(reference) = schema.get("$ref") {
+            let name = reference
+                .as_str()
+                .unwrap()
+                .strip_prefix("#/$defs/")
+                .unwrap();
+            validate(root, &root["$defs"][name], value, path)?;
+        }
+        if let Some(types) = schema.get("type") {
+            let types = match types {
+                Value::Array(types) => types.iter().collect(),
+                _ => vec![types],
+            };
+            let type_matches = |ty: &str| match ty {
+                "object" => value.is_object(),
+                "array" => value.is_array(),
+                "string" => value.is_string(),
+                "boolean" => value.is_boolean(),
+                "number" => value.is_number(),
+                "integer" => value.is_u64() || value.is_i64(),
+                "null" => value.is_null(),
+                _ => panic!("Unknown type {ty}"),
+            };
+            if !types.iter().any(|ty| type_matches(ty.as_str().unwrap())) {
+                return error();
+            }
+        }
+        if let Some(expected) = schema.get("const") {
+            if value != expected {
+                return error();
+            }
+        }
+        if let Some(Value::Array(options)) = schema.get("enum") {
+            if !options.contains(value) {
+                return error();
+            }
+        }
+        if let (Some(pattern), Value::String(value)) =
+            (schema.get("pattern"), value)
+        {
+            if !Regex::new(pattern.as_str().unwrap())
+                .unwrap()
+                .is_match(value)
+            {
+                return error();
+            }
+        }
+        if let Some(minimum) = schema.get("minimum") {
+            if value.as_f64() < minimum.as_f64() {
+                return error();
+            }
+        }
+        if let Value::Array(items) = value {
+            if let Some(min) = schema.get("minItems") {
+                if (items.len() as u64) < min.as_u64().unwrap() {
+                    return error();
+                }
+            }
+            if let Some(item_schema) = schema.get("items") {
+                for (i, item) in items.iter().enumerate() {
+                    validate(root, item_schema, item, &format!("{path}/{i}"))?;
+                }
+            }
+        }
+        if let Value::Object(map) = value {
+            if let Some(min) = schema.get("minProperties") {
+                if (map.len() as u64) < min.as_u64().unwrap() {
+                    return error();
+                }
+            }
+            if let Some(Value::Array(required)) = schema.get("required") {
+                if !required
+                    .iter()
+                    .all(|key| map.contains_key(key.as_str().unwrap()))
+                {
+                    return error();
+                }
+            }
+            let properties = schema.get("properties");
+            for (key, field) in map {
+                let field_path = format!("{path}/{key}");
+                match (
+                    properties.and_then(|properties| properties.get(key)),
+                    schema.get("additionalProperties"),
+                ) {
+                    (Some(field_schema), _) => {
+                        validate(root, field_schema, field, &field_path)?
+                    }
+                    (None, Some(Value::Bool(false))) => return Err(field_path),
+                    (None, Some(additional @ Value::Object(_))) => {
+                        validate(root, additional, field, &field_path)?
+                    }
+                    (None, _) => {}
+                }
+            }
+        }
+        let matches = |options: &Value| {
+            options
+                .as_array()
+                .unwrap()
+                .iter()
+                .filter(|option| validate(root, option, value, path).is_ok())
+                .count()
+        };
+        if let Some(options) = schema.get("anyOf") {
+            if matches(options) == 0 {
+                return error();
+            }
+        }
+        if let Some(options) = schema.get("oneOf") {
+            if matches(options) != 1 {
+                return error();
+            }
+        }
+        Ok(())
+    }
+
+    /// The sample config is valid, and still valid after a round trip
+    /// through serialization
+    #[test]
+    fn test_schema_sample_config() {
+        let schema = schema();
+        let value: Value = toml::from_str(CONFIG).unwrap();
+        assert_eq!(validate(&schema, &schema, &value, ""), Ok(()));
+
+        let config: Config = toml::from_str(CONFIG).unwrap();
+        let value: Value =
+            toml::from_str(&toml::to_string(&config).unwrap()).unwrap();
+        assert_eq!(validate(&schema, &schema, &value, ""), Ok(()));
+    }
+
+    #[rstest]
+    #[case::per_shell(
+        "[applications.a.profiles.p.variables]
+        V = {bash = 'a', default = {type = 'env', variable = 'V'}}",
+        Ok(())
+    )]
+    #[case::candidates(
+        "[applications.a.profiles.p.variables]
+        V = [{type = 'file', path = 'a', platform = 'linux'}, 'b']",
+        Ok(())
+    )]
+    #[case::side_effects(
+        "[defaults]
+        pre_export = [
+            {type = 'docker-compose', file = 'compose.yml'},
+            {setup = 'a', background = true},
+        ]",
+        Ok(())
+    )]
+    #[case::unknown_field(
+        "[applications.a.profiles.p]
+        varaibles = {}",
+        Err("/applications/a/profiles/p/varaibles")
+    )]
+    #[case::unknown_kind(
+        "[applications.a.profiles.p.variables]
+        V = {type = 'magic'}",
+        Err("/applications/a/profiles/p/variables/V")
+    )]
+    #[case::missing_field(
+        "[applications.a.profiles.p.variables]
+        V = {type = 'command'}",
+        Err("/applications/a/profiles/p/variables/V")
+    )]
+    #[case::invalid_duration(
+        "[applications.a.profiles.p.variables]
+        V = {type = 'command', command = 'a', cache = '5 minutes'}",
+        Err("/applications/a/profiles/p/variables/V")
+    )]
+    #[case::invalid_reference(
+        "[applications.a.profiles.p]
+        extends = ['a/b/c']",
+        Err("/applications/a/profiles/p/extends/0")
+    )]
+    fn test_schema(#[case] config: &str, #[case] expected: Result<(), &str>) {
+        let schema = schema();
+        let value: Value = toml::from_str(config).unwrap();
+        // Make sure the schema agrees with serde
+        assert_eq!(toml::from_str::<Config>(config).is_ok(), expected.is_ok());
+        assert_eq!(
+            validate(&schema, &schema, &value, ""),
+            expected.map_err(String::from)
+        );
+    }
+}
@@ -4,16 +4,21 @@
 use super::*;
 use crate::{
     config::{Config, Profile},
-    test_util::{command, config, literal, map, set, side_effect},
+    test_util::{
+        assert_result, command, config, kubernetes, literal, map, set,
+        side_effect, unset, vault,
+    },
 };
 use pretty_assertions::assert_eq;
+use rstest::rstest;
 use serde_test::{
-    assert_de_tokens, assert_de_tokens_error, assert_tokens, Token,
+    assert_de_tokens, assert_de_tokens_error, assert_ser_tokens, assert_tokens,
+    Token,
 };
 
 /// A general config to test parsing. This doesn't include all edge cases, but
 /// it's got a good variety
-const CONFIG: &str = r#"
+pub const CONFIG: &str = r#"
 [applications.base.profiles.base]
 pre_export = [
     {setup = "echo pre setup", teardown = "echo pre teardown"},
@@ -35,11 +40,16 @@ variables = {SERVICE1 = "dev", SERVICE2 = "also-dev"}
 
 [applications.server.profiles.prd]
 extends = ["base"]
+description = "Production. Be careful!"
+kubernetes_context = "prd-cluster"
+kubernetes_namespace = "server"
+cwd = "packages/server"
 [applications.server.profiles.prd.variables]
 SERVICE1 = "prd"
 SERVICE2 = "also-prd"
 multiple = {type = "literal", value = "MULTI1=multi1\nMULTI2=multi2", multiple = true}
 multiple_filtered = {type = "literal", value = "MULTI1=multi1\nMULTI2=multi2", multiple = ["MULTI1"]}
+multiple_renamed = {type = "literal", value = "MULTI1=multi1\nMULTI2=multi2", multiple = {MULTI2 = "RENAMED"}}
 
 [applications.server.profiles.secret]
 extends = ["base"]
@@ -70,6 +80,7 @@ fn test_parse_config() {
                         "echo post teardown",
                     )],
                     variables: map([("I_AM_HERE", literal("true"))]),
+                    ..Default::default()
                 },
             )],
         ),
@@ -83,6 +94,7 @@ fn test_parse_config() {
                         pre_export: vec![],
                         post_export: vec![],
                         variables: map([("USERNAME", literal("user"))]),
+                        ..Default::default()
                     },
                 ),
                 (
@@ -95,6 +107,7 @@ fn test_parse_config() {
                             ("SERVICE1", literal("dev")),
                             ("SERVICE2", literal("also-dev")),
                         ]),
+                        ..Default::default()
                     },
                 ),
                 (
@@ -116,7 +129,17 @@ fn test_parse_config() {
                                 literal("MULTI1=multi1\nMULTI2=multi2")
                                     .multiple_filtered(&["MULTI1"]),
                             ),
+                            (
+                                "multiple_renamed",
+                                literal("MULTI1=multi1\nMULTI2=multi2")
+                                    .multiple_renamed(&[("MULTI2", "RENAMED")]),
+                            ),
                         ]),
+                        kubernetes_context: Some("prd-cluster".into()),
+                        kubernetes_namespace: Some("server".into()),
+                        cwd: Some("packages/server".into()),
+                        description: Some("Production. Be careful!".into()),
+                        ..Default::default()
                     },
                 ),
                 (
@@ -133,6 +156,7 @@ fn test_parse_config() {
                                     .sensitive(),
                             ),
                         ]),
+                        ..Default::default()
                     },
                 ),
             ],
@@ -142,6 +166,420 @@ fn test_parse_config() {
     assert_eq!(toml::from_str::<Config>(CONFIG).unwrap(), expected);
 }
 
+#[test]
+fn test_parse_strict_templates() {
+    let parse = |content: &str| {
+        toml::from_str::<Config>(content)
+            .unwrap()
+            .strict_templates()
+    };
+    assert!(parse(""));
+    assert!(parse("strict_templates = true"));
+    assert!(!parse("strict_templates = false"));
+}
+
+#[test]
+fn test_parse_strict_conflicts() {
+    let parse = |content: &str| {
+        toml::from_str::<Config>(content)
+            .unwrap()
+            .strict_conflicts()
+    };
+    assert!(!parse(""));
+    assert!(parse("strict_conflicts = true"));
+    assert!(!parse("strict_conflicts = false"));
+}
+
+#[test]
+fn test_parse_masking() {
+    let parse = |content: &str| toml::from_str::<Config>(content);
+    assert_eq!(parse("").unwrap().masking(), Masking::Full);
+    assert_eq!(
+        parse("masking = 'partial'").unwrap().masking(),
+        Masking::Partial
+    );
+    // Revealing can only be done per-command
+    assert!(parse("masking = 'reveal'").is_err());
+}
+
+#[test]
+fn test_parse_sensitive_patterns() {
+    let config: Config =
+        toml::from_str("sensitive_patterns = ['*_TOKEN', '*PASSWORD*']")
+            .unwrap();
+    let patterns = config.sensitive_patterns();
+    assert_eq!(patterns.len(), 2);
+    assert!(patterns[0].matches("API_TOKEN"));
+    assert!(patterns[0].matches("api_token"));
+    assert!(!patterns[0].matches("TOKEN_FILE"));
+    assert!(patterns[1].matches("DB_PASSWORD_FILE"));
+
+    assert_eq!(
+        toml::from_str::<Config>("sensitive_patterns = ['[']")
+            .unwrap_err()
+            .message(),
+        "Invalid sensitive pattern `[`: Pattern syntax error near position \
+        0: invalid range pattern"
+    );
+}
+
+#[test]
+fn test_parse_application_variables() {
+    let config: Config = toml::from_str(
+        r#"
+[applications.server.variables]
+HOST = "localhost"
+PORT = {type = "literal", value = "3000"}
+[applications.server.profiles.dev]
+variables = {MODE = "dev"}
+"#,
+    )
+    .unwrap();
+    let application = &config.applications[0];
+    assert_eq!(
+        application.variables,
+        map([("HOST", literal("localhost")), ("PORT", literal("3000"))])
+    );
+    assert_eq!(
+        application.profiles[0].variables,
+        map([("MODE", literal("dev"))])
+    );
+}
+
+/// Applications and profiles can be looked up by name or alias
+#[test]
+fn test_aliases() {
+    let config: Config = toml::from_str(
+        r#"
+[applications.server]
+aliases = ["srv", "s"]
+[applications.server.profiles.production]
+aliases = ["prd"]
+[applications.server.profiles.development]
+"#,
+    )
+    .unwrap();
+    config.check_aliases().unwrap();
+    let (name, application) =
+        get_aliased(&config.applications, &"srv".into()).unwrap();
+    assert_eq!(name, &"server".into());
+    assert_eq!(application.aliases, set(["srv", "s"]));
+    let (name, profile) =
+        get_aliased(&application.profiles, &"prd".into()).unwrap();
+    assert_eq!(name, &"production".into());
+    assert_eq!(profile, &application.profiles[0]);
+    // Names still work
+    assert_eq!(
+        get_aliased(&application.profiles, &"development".into())
+            .unwrap()
+            .1,
+        &application.profiles[1]
+    );
+    assert_eq!(
+        get_aliased(&application.profiles, &"dev".into())
+            .unwrap_err()
+            .to_string(),
+        "Unknown key dev, options are: production, development"
+    );
+}
+
+/// Profiles joined with `+` are composed into one, with later profiles taking
+/// precedence. A profile whose real name includes `+` is still selected as-is
+#[test]
+fn test_compose_profiles() {
+    let config: Config = toml::from_str(
+        r#"
+[applications.server.profiles.base]
+aliases = ["b"]
+description = "Base profile"
+kubernetes_context = "base"
+variables = {REGION = "us", LOG = "info"}
+pre_export = [{setup = "echo base", teardown = "echo base"}]
+[applications.server.profiles.debug]
+deprecated = "use trace"
+variables = {LOG = "debug"}
+pre_export = [{setup = "echo debug", teardown = "echo debug"}]
+[applications.server.profiles."odd+name"]
+variables = {ODD = "true"}
+"#,
+    )
+    .unwrap();
+    let application = &config.applications[0];
+
+    let (name, profile) = application.get_profile(&"b+debug".into()).unwrap();
+    assert_eq!(name, "base+debug".into());
+    assert_eq!(
+        profile.into_owned(),
+        Profile {
+            deprecated: Some(Deprecated::Message("use trace".into())),
+            variables: map([
+                ("REGION", literal("us")),
+                ("LOG", literal("debug"))
+            ]),
+            pre_export: vec![
+                side_effect("echo base", "echo base"),
+                side_effect("echo debug", "echo debug"),
+            ],
+            kubernetes_context: Some("base".into()),
+            ..Default::default()
+        }
+    );
+
+    let (name, profile) = application.get_profile(&"odd+name".into()).unwrap();
+    assert_eq!(name, "odd+name".into());
+    assert_eq!(profile.as_ref(), &application.profiles[2]);
+
+    assert_eq!(
+        application
+            .get_profile(&"base+prd".into())
+            .unwrap_err()
+            .to_string(),
+        "Unknown key prd, options are: base, debug, odd+name"
+    );
+}
+
+#[rstest]
+#[case::not_deprecated("", None)]
+#[case::flag_false("deprecated = false", None)]
+#[case::flag("deprecated = true", Some("Profile `server/dev` is deprecated"))]
+#[case::message(
+    r#"deprecated = "use server/dev2 instead""#,
+    Some("Profile `server/dev` is deprecated: use server/dev2 instead")
+)]
+fn test_deprecation_warning(
+    #[case] content: &str,
+    #[case] expected: Option<&str>,
+) {
+    let profile: Profile = toml::from_str(content).unwrap();
+    let reference: ProfileReference = "server/dev".parse().unwrap();
+    assert_eq!(profile.is_deprecated(), expected.is_some());
+    assert_eq!(profile.deprecation_warning(&reference).as_deref(), expected);
+}
+
+#[rstest]
+#[case::valid(
+    r#"
+[applications.a]
+aliases = ["x"]
+[applications.b]
+aliases = ["y"]
+"#,
+    Ok(())
+)]
+#[case::alias_is_name(
+    r#"
+[applications.a]
+aliases = ["b"]
+[applications.b]
+"#,
+    Err("Alias `b` of application `a` conflicts with application `b`")
+)]
+#[case::duplicate_alias(
+    r#"
+[applications.a]
+aliases = ["x"]
+[applications.b]
+aliases = ["x"]
+"#,
+    Err("Alias `x` is used by both application `a` and application `b`")
+)]
+#[case::duplicate_profile_alias(
+    r#"
+[applications.app.profiles.a]
+aliases = ["x"]
+[applications.app.profiles.b]
+aliases = ["x"]
+"#,
+    Err("Alias `x` is used by both profile `app/a` and profile `app/b`")
+)]
+// Profiles in different applications can share aliases
+#[case::different_applications(
+    r#"
+[applications.app1.profiles.a]
+aliases = ["x"]
+[applications.app2.profiles.a]
+aliases = ["x"]
+"#,
+    Ok(())
+)]
+fn test_check_aliases(
+    #[case] content: &str,
+    #[case] expected: Result<(), &str>,
+) {
+    let config: Config = toml::from_str(content).unwrap();
+    assert_result(config.check_aliases(), expected);
+}
+
+/// Application names that sanitize to the same state variable are rejected
+#[rstest]
+#[case::distinct("[applications.my-app]\n[applications.my-app2]", Ok(()))]
+#[case::punctuation(
+    "[applications.my-app]\n[applications.my_app]",
+    Err(
+        "Applications `my-app` and `my_app` would share the variable \
+        `ENV_SELECT_ACTIVE_MY_APP`; rename one of them"
+    )
+)]
+#[case::case(
+    "[applications.app]\n[applications.APP]",
+    Err(
+        "Applications `app` and `APP` would share the variable \
+    `ENV_SELECT_ACTIVE_APP`; rename one of them"
+    )
+)]
+fn test_check_application_variables(
+    #[case] content: &str,
+    #[case] expected: Result<(), &str>,
+) {
+    let config: Config = toml::from_str(content).unwrap();
+    assert_result(config.check_application_variables(), expected);
+}
+
+/// Config files are collected bottom-to-top, with each directory's local file
+/// before its main file. The search stops at a root file, or at the home
+/// directory. Files are given as (path, content) relative to home
+#[rstest]
+#[case::home(
+    "home",
+    &[("../.env-select.toml", ""), (".env-select.toml", ""), ("a/b/.env-select.toml", "")],
+    "a/b/c",
+    &["a/b/.env-select.toml", ".env-select.toml"],
+)]
+#[case::local(
+    "local",
+    &[
+        (".env-select.toml", ""),
+        (".env-select.local.toml", ""),
+        ("a/.env-select.local.toml", ""),
+    ],
+    "a",
+    &["a/.env-select.local.toml", ".env-select.local.toml", ".env-select.toml"],
+)]
+#[case::root(
+    "root",
+    &[(".env-select.toml", ""), ("a/.env-select.toml", "root = true"), ("a/b/.env-select.toml", "")],
+    "a/b/c",
+    &["a/b/.env-select.toml", "a/.env-select.toml"],
+)]
+// Either file in a directory can mark it as the root
+#[case::local_root(
+    "local_root",
+    &[
+        (".env-select.toml", ""),
+        ("a/.env-select.toml", ""),
+        ("a/.env-select.local.toml", "root = true"),
+    ],
+    "a",
+    &["a/.env-select.local.toml", "a/.env-select.toml"],
+)]
+// Invalid files are treated as non-root
+#[case::invalid(
+    "invalid",
+    &[(".env-select.toml", ""), ("a/.env-select.toml", "root = ")],
+    "a",
+    &["a/.env-select.toml", ".env-select.toml"],
+)]
+fn test_get_all_files(
+    #[case] name: &str,
+    #[case] files: &[(&str, &str)],
+    #[case] start: &str,
+    #[case] expected: &[&str],
+) {
+    let base = env::temp_dir().join(format!("es-test-search-{name}"));
+    let home = base.join("home");
+    let _ = fs::remove_dir_all(&base);
+    fs::create_dir_all(home.join(start)).unwrap();
+    for (path, content) in files {
+        fs::write(home.join(path), content).unwrap();
+    }
+    let expected: Vec<PathBuf> =
+        expected.iter().map(|path| home.join(path)).collect();
+    assert_eq!(
+        Config::get_all_files(&home.join(start), Some(&home)),
+        expected
+    );
+}
+
+/// Every layer of config stacks, from lowest to highest precedence: defaults
+/// from a parent directory's config, defaults from the child directory's
+/// config, application variables, parent profiles, then the profile itself
+#[test]
+fn test_precedence() {
+    let parent_directory: Config = toml::from_str(
+        r#"
+[defaults]
+pre_export = [{setup = "parent directory"}]
+[defaults.variables]
+VAR1 = "parent directory"
+VAR2 = "parent directory"
+VAR3 = "parent directory"
+VAR4 = "parent directory"
+VAR5 = "parent directory"
+"#,
+    )
+    .unwrap();
+    let mut config: Config = toml::from_str(
+        r#"
+[defaults]
+pre_export = [{setup = "child directory"}]
+[defaults.variables]
+VAR2 = "child directory"
+VAR3 = "child directory"
+VAR4 = "child directory"
+VAR5 = "child directory"
+
+[applications.app.variables]
+VAR3 = "application"
+VAR4 = "application"
+VAR5 = "application"
+
+[applications.app.profiles.base]
+pre_export = [{setup = "base"}]
+variables = {VAR4 = "base", VAR5 = "base"}
+
+[applications.app.profiles.child]
+extends = ["base"]
+pre_export = [{setup = "child"}]
+variables = {VAR5 = "child"}
+"#,
+    )
+    .unwrap();
+    // Mimic Config::load: the child directory's file is loaded first
+    config.qualify("/root/project/.env-select.toml");
+    config.merge(
+        parent_directory,
+        Path::new("/root/.env-select.toml"),
+        log::Level::Warn,
+    );
+    config.inherit().unwrap();
+
+    let setup = |command: &str| SideEffect::Shell {
+        setup: Some(command.to_owned().into()),
+        teardown: None,
+        background: false,
+    };
+    let profile = &config.applications[0].profiles[1];
+    assert_eq!(
+        profile.variables,
+        map([
+            ("VAR1", literal("parent directory")),
+            ("VAR2", literal("child directory")),
+            ("VAR3", literal("application")),
+            ("VAR4", literal("base")),
+            ("VAR5", literal("child")),
+        ])
+    );
+    assert_eq!(
+        profile.pre_export,
+        vec![
+            setup("parent directory"),
+            setup("child directory"),
+            setup("base"),
+            setup("child"),
+        ]
+    );
+}
+
 #[test]
 fn test_parse_name() {
     assert_tokens(
@@ -225,7 +663,11 @@ fn test_parse_profile_reference() {
 #[test]
 fn test_parse_value_source() {
     assert_tokens(
-        &literal("abc").multiple().sensitive().0,
+        &literal("abc")
+            .multiple()
+            .multiple_format(MultiVariableFormat::Json)
+            .sensitive()
+            .0,
         &[
             Token::Map { len: None },
             Token::Str("type"),
@@ -234,13 +676,104 @@ fn test_parse_value_source() {
             Token::Str("abc"),
             Token::Str("multiple"),
             Token::Bool(true),
+            Token::Str("multiple_format"),
+            Token::UnitVariant {
+                name: "MultiVariableFormat",
+                variant: "json",
+            },
             Token::Str("sensitive"),
+            Token::Some,
+            Token::Bool(true),
+            Token::Str("trim"),
+            Token::Bool(true),
+            Token::Str("optional"),
+            Token::Bool(false),
+            Token::Str("retries"),
+            Token::U32(0),
+            Token::MapEnd,
+        ],
+    );
+    assert_tokens(
+        &literal("YWJj").encoding(Encoding::Base64).0,
+        &[
+            Token::Map { len: None },
+            Token::Str("type"),
+            Token::Str("literal"),
+            Token::Str("value"),
+            Token::Str("YWJj"),
+            Token::Str("multiple"),
+            Token::Bool(false),
+            Token::Str("multiple_format"),
+            Token::UnitVariant {
+                name: "MultiVariableFormat",
+                variant: "dotenv",
+            },
+            Token::Str("trim"),
             Token::Bool(true),
+            Token::Str("optional"),
+            Token::Bool(false),
+            Token::Str("retries"),
+            Token::U32(0),
+            Token::Str("encoding"),
+            Token::Some,
+            Token::UnitVariant {
+                name: "Encoding",
+                variant: "base64",
+            },
             Token::MapEnd,
         ],
     );
 }
 
+/// Some source kinds are sensitive by default, but that can be overridden
+#[test]
+fn test_parse_sensitive_default() {
+    let parse = |content: &str| -> bool {
+        toml::from_str::<ValueSource>(content)
+            .unwrap()
+            .0
+            .is_sensitive()
+    };
+    assert!(!parse(
+        r#"type = "literal"
+value = "abc""#
+    ));
+    assert!(parse(
+        r#"type = "vault"
+path = "secret/db""#
+    ));
+    assert!(!parse(
+        r#"type = "vault"
+path = "secret/db"
+sensitive = false"#
+    ));
+    assert!(parse(
+        r#"type = "azure-key-vault"
+vault = "dev"
+secret = "db-password""#
+    ));
+    assert!(parse(
+        r#"type = "gcp-secret"
+secret = "db-password""#
+    ));
+    assert!(parse(
+        r#"type = "doppler"
+secret = "DB_PASSWORD""#
+    ));
+    assert!(parse(
+        r#"type = "keychain"
+service = "github""#
+    ));
+    assert!(parse(
+        r#"type = "pass"
+entry = "dev/db""#
+    ));
+    assert!(parse(
+        r#"type = "sops"
+path = "secrets.enc.env""#
+    ));
+}
+
 #[test]
 fn test_parse_literal() {
     // Flat syntax
@@ -274,9 +807,34 @@ fn test_parse_literal() {
     );
 }
 
+/// A variable can be unset with a source type, or a null in formats that
+/// support it
 #[test]
-fn test_parse_side_effects() {
+fn test_parse_unset() {
     assert_tokens(
+        &unset().0.kind,
+        &[
+            Token::Struct {
+                name: "ValueSourceKind",
+                len: 1,
+            },
+            Token::Str("type"),
+            Token::Str("unset"),
+            Token::StructEnd,
+        ],
+    );
+    assert_eq!(
+        toml::from_str::<ValueSource>(r#"type = "unset""#).unwrap(),
+        unset()
+    );
+    assert_de_tokens(&unset(), &[Token::Unit]);
+    assert_de_tokens(&VariableDefinition::from(unset()), &[Token::Unit]);
+    assert_de_tokens(&VariableDefinition::from(unset()), &[Token::None]);
+}
+
+#[test]
+fn test_parse_side_effects() {
+    assert_ser_tokens(
         &side_effect("echo setup", "echo teardown"),
         &[
             Token::Struct {
@@ -301,6 +859,150 @@ fn test_parse_side_effects() {
             Token::StructEnd,
         ],
     );
+    assert_de_tokens(
+        &side_effect("echo setup", "echo teardown"),
+        &[
+            Token::Map { len: Some(2) },
+            Token::Str("setup"),
+            Token::Str("echo setup"),
+            Token::Str("teardown"),
+            Token::Str("echo teardown"),
+            Token::MapEnd,
+        ],
+    );
+}
+
+#[rstest]
+#[case::shell(
+    "setup = \"echo setup\"",
+    Ok(SideEffect::Shell {
+        setup: Some("echo setup".to_owned().into()),
+        teardown: None,
+        background: false,
+    }),
+)]
+#[case::background(
+    "setup = \"ssh -N tunnel\"\nbackground = true",
+    Ok(SideEffect::Shell {
+        setup: Some("ssh -N tunnel".to_owned().into()),
+        teardown: None,
+        background: true,
+    }),
+)]
+#[case::port_forward(
+    "type = \"kubectl-port-forward\"\ntarget = \"svc/api\"\nports = \"8080:80\"",
+    Ok(SideEffect::Builtin(BuiltinSideEffect::KubectlPortForward {
+        target: "svc/api".into(),
+        ports: "8080:80".into(),
+        namespace: None,
+    })),
+)]
+#[case::docker_compose(
+    "type = \"docker-compose\"\nfile = \"docker-compose.yml\"",
+    Ok(SideEffect::Builtin(BuiltinSideEffect::DockerCompose {
+        file: "docker-compose.yml".into(),
+    })),
+)]
+#[case::unknown_type(
+    "type = \"ssh-tunnel\"",
+    Err(
+        "unknown variant `ssh-tunnel`, expected `kubectl-port-forward` or \
+    `docker-compose`"
+    )
+)]
+#[case::missing_field("type = \"docker-compose\"", Err("missing field `file`"))]
+#[case::unknown_field(
+    "setup = \"echo setup\"\nfile = \"docker-compose.yml\"",
+    Err(
+        "unknown field `file`, expected one of `setup`, `teardown`, \
+        `background`"
+    )
+)]
+fn test_parse_side_effect_type(
+    #[case] input: &str,
+    #[case] expected: Result<SideEffect, &str>,
+) {
+    // Compare just the message, without the location in the input
+    let result = toml::from_str::<SideEffect>(input)
+        .map_err(|error| error.message().to_owned());
+    assert_result(result, expected);
+}
+
+/// Built-in side effects expand to native commands
+#[test]
+fn test_builtin_side_effect_commands() {
+    let docker = SideEffect::Builtin(BuiltinSideEffect::DockerCompose {
+        file: "/root/docker-compose.yml".into(),
+    });
+    assert_eq!(
+        docker.setup().unwrap(),
+        Some(SideEffectCommand::Native {
+            program: "docker",
+            arguments: vec![
+                "compose".into(),
+                "--file".into(),
+                "/root/docker-compose.yml".into(),
+                "up".into(),
+                "--detach".into(),
+            ],
+        })
+    );
+    assert_eq!(
+        docker.teardown().unwrap(),
+        Some(SideEffectCommand::Native {
+            program: "docker",
+            arguments: vec![
+                "compose".into(),
+                "--file".into(),
+                "/root/docker-compose.yml".into(),
+                "down".into(),
+            ],
+        })
+    );
+
+    let port_forward =
+        SideEffect::Builtin(BuiltinSideEffect::KubectlPortForward {
+            target: "svc/api".into(),
+            ports: "8080:80 9090".into(),
+            namespace: Some("dev".into()),
+        });
+    let Some(SideEffectCommand::Native { program, arguments }) =
+        port_forward.setup().unwrap()
+    else {
+        panic!("Expected native command")
+    };
+    assert_eq!(program, "sh");
+    // Script and file arguments are internal details; check what's passed to
+    // kubectl
+    assert_eq!(
+        &arguments[5..],
+        &["--namespace", "dev", "svc/api", "8080:80", "9090"]
+    );
+    // Setup and teardown share the same PID and log files
+    let Some(SideEffectCommand::Native {
+        arguments: teardown_arguments,
+        ..
+    }) = port_forward.teardown().unwrap()
+    else {
+        panic!("Expected native command")
+    };
+    assert_eq!(&teardown_arguments[2..], &arguments[2..5]);
+    // Files go in the private runtime directory, with a name that's stable
+    // across builds
+    let runtime_dir = runtime_dir().unwrap();
+    assert_eq!(
+        PathBuf::from(&arguments[3]),
+        runtime_dir.join("port-forward-7a0965cedee3bc25.pid")
+    );
+}
+
+/// The hash used to name files must not change, or teardown after an upgrade
+/// wouldn't find the files from setup
+#[rstest]
+#[case::empty("", 0xcbf29ce484222325)]
+#[case::text("env-select", 0xe883580f4a74fe9b)]
+fn test_stable_hash(#[case] input: &str, #[case] expected: u64) {
+    assert_eq!(stable_hash(input.as_bytes()), expected);
 }
 
 #[test]
@@ -349,6 +1051,342 @@ fn test_parse_shell_command() {
     );
 }
 
+#[test]
+fn test_parse_kubernetes() {
+    assert_tokens(
+        &kubernetes(&["printenv", "DB_PASSWORD"], "app=api").0.kind,
+        &[
+            Token::Struct {
+                name: "ValueSourceKind",
+                len: 5,
+            },
+            Token::Str("type"),
+            Token::Str("kubernetes"),
+            Token::Str("command"),
+            Token::Seq { len: Some(2) },
+            Token::Str("printenv"),
+            Token::Str("DB_PASSWORD"),
+            Token::SeqEnd,
+            Token::Str("pod_selector"),
+            Token::Str("app=api"),
+            Token::Str("namespace"),
+            Token::None,
+            Token::Str("container"),
+            Token::None,
+            Token::StructEnd,
+        ],
+    );
+}
+
+#[test]
+fn test_parse_vault() {
+    assert_tokens(
+        &vault("db/creds", Some("password")).0.kind,
+        &[
+            Token::Struct {
+                name: "ValueSourceKind",
+                len: 4,
+            },
+            Token::Str("type"),
+            Token::Str("vault"),
+            Token::Str("path"),
+            Token::Str("db/creds"),
+            Token::Str("field"),
+            Token::Some,
+            Token::Str("password"),
+            Token::Str("mount"),
+            Token::None,
+            Token::StructEnd,
+        ],
+    );
+}
+
+#[test]
+fn test_parse_aws_ssm() {
+    assert_de_tokens(
+        &ValueSource::from(ValueSourceKind::AwsSsm {
+            parameter: "/app/db".into(),
+            region: Some("us-east-1".into()),
+            profile: None,
+        }),
+        &[
+            Token::Map { len: None },
+            Token::Str("type"),
+            Token::Str("aws-ssm"),
+            Token::Str("parameter"),
+            Token::Str("/app/db"),
+            Token::Str("region"),
+            Token::Str("us-east-1"),
+            Token::MapEnd,
+        ],
+    );
+}
+
+#[test]
+fn test_parse_http() {
+    assert_de_tokens(
+        &ValueSource::from(ValueSourceKind::Http {
+            url: "http://localhost/token".into(),
+            headers: [("Authorization".into(), "Bearer ${env:TOKEN}".into())]
+                .into(),
+        }),
+        &[
+            Token::Map { len: None },
+            Token::Str("type"),
+            Token::Str("http"),
+            Token::Str("url"),
+            Token::Str("http://localhost/token"),
+            Token::Str("headers"),
+            Token::Map { len: None },
+            Token::Str("Authorization"),
+            Token::Str("Bearer ${env:TOKEN}"),
+            Token::MapEnd,
+            Token::MapEnd,
+        ],
+    );
+}
+
+#[test]
+fn test_parse_generate() {
+    let parse = |content: &str| -> ValueSourceKind {
+        toml::from_str::<ValueSource>(content).unwrap().0.kind
+    };
+    assert_eq!(
+        parse(
+            r#"type = "generate"
+generator = "uuid4""#
+        ),
+        ValueSourceKind::Generate {
+            generator: Generator::Uuid4
+        }
+    );
+    assert_eq!(
+        parse(
+            r#"type = "generate"
+generator = "random-hex""#
+        ),
+        ValueSourceKind::Generate {
+            generator: Generator::RandomHex { length: 32 }
+        }
+    );
+    assert_eq!(
+        parse(
+            r#"type = "generate"
+generator = "timestamp"
+format = "rfc3339""#
+        ),
+        ValueSourceKind::Generate {
+            generator: Generator::Timestamp {
+                format: TimestampFormat::Rfc3339
+            }
+        }
+    );
+}
+
+#[test]
+fn test_parse_cache_duration() {
+    assert_tokens(
+        &ConfigDuration(Duration::from_secs(300)),
+        &[Token::Str("5m")],
+    );
+    assert_tokens(
+        &ConfigDuration(Duration::from_secs(90)),
+        &[Token::Str("90s")],
+    );
+    assert_tokens(
+        &ConfigDuration(Duration::from_secs(7_200)),
+        &[Token::Str("2h")],
+    );
+    assert_tokens(
+        &ConfigDuration(Duration::from_secs(86_400)),
+        &[Token::Str("1d")],
+    );
+
+    // Invalid durations
+    for value in ["", "5", "m", "5x", "-5m", "5.5m"] {
+        assert_de_tokens_error::<ConfigDuration>(
+            &[Token::Str(value)],
+            &format!(
+                "Invalid duration `{value}`; expected a number followed by \
+                one of s, m, h, d (e.g. `5m`)"
+            ),
+        );
+    }
+}
+
+/// `multiple` can be a bool, list, table of options, or rename table
+#[rstest]
+#[case::bool("true", true.into())]
+#[case::list(r#"["A"]"#, MultiVariable::List(vec!["A".into()]))]
+#[case::filtered(
+    r#"{keys = ["A"], required = false}"#,
+    MultiVariable::Filtered { keys: vec!["A".into()], required: false },
+)]
+#[case::filtered_default(
+    r#"{keys = ["A"]}"#,
+    MultiVariable::Filtered { keys: vec!["A".into()], required: true },
+)]
+#[case::map(
+    r#"{A = "B", keys = "C"}"#,
+    MultiVariable::Map(
+        [("A".into(), "B".into()), ("keys".into(), "C".into())].into()
+    ),
+)]
+fn test_parse_multiple(#[case] content: &str, #[case] expected: MultiVariable) {
+    #[derive(Debug, Deserialize)]
+    struct Wrapper {
+        multiple: MultiVariable,
+    }
+    let parsed =
+        toml::from_str::<Wrapper>(&format!("multiple = {content}")).unwrap();
+    assert_eq!(parsed.multiple, expected);
+}
+
+#[test]
+fn test_parse_transform() {
+    let content = r#"
+type = "command"
+command = "login --verbose"
+transform = [
+    "trim",
+    {replace = {from = "-", to = "_"}},
+    {regex = {pattern = 'token: (\w+)', group = 1}},
+    {regex = {pattern = '\w+'}},
+]
+"#;
+    let expected = command("login --verbose")
+        .transform(Transform::Trim)
+        .transform(Transform::Replace {
+            from: "-".into(),
+            to: "_".into(),
+        })
+        .transform(Transform::Regex {
+            pattern: r"token: (\w+)".into(),
+            group: 1,
+        })
+        .transform(Transform::Regex {
+            pattern: r"\w+".into(),
+            group: 0,
+        });
+    let parsed = toml::from_str::<ValueSource>(content).unwrap();
+    assert_eq!(parsed, expected);
+
+    // Make sure it round trips
+    let serialized = toml::to_string(&parsed).unwrap();
+    assert_eq!(
+        toml::from_str::<ValueSource>(&serialized).unwrap(),
+        expected
+    );
+}
+
+/// A variable can be defined as a list of platform-specific candidates
+#[test]
+fn test_parse_candidates() {
+    let content = r#"
+[[candidates]]
+type = "literal"
+value = "/var/run/docker.sock"
+platform = "linux"
+
+[[candidates]]
+type = "literal"
+value = "/Users/me/.docker/run/docker.sock"
+platform = "macos"
+"#;
+    #[derive(Debug, Deserialize, Serialize)]
+    struct Wrapper {
+        candidates: VariableDefinition,
+    }
+    let expected = VariableDefinition::Candidates(vec![
+        literal("/var/run/docker.sock").platform(Platform::Linux),
+        literal("/Users/me/.docker/run/docker.sock").platform(Platform::Macos),
+    ]);
+    let parsed = toml::from_str::<Wrapper>(content).unwrap();
+    assert_eq!(parsed.candidates, expected);
+
+    // Make sure it round trips
+    let serialized = toml::to_string(&parsed).unwrap();
+    assert_eq!(
+        toml::from_str::<Wrapper>(&serialized).unwrap().candidates,
+        expected
+    );
+
+    // Single sources are still supported
+    assert_eq!(
+        toml::from_str::<Wrapper>("candidates = \"abc\"")
+            .unwrap()
+            .candidates,
+        literal("abc").into()
+    );
+
+    // Empty list is invalid
+    assert_de_tokens_error::<VariableDefinition>(
+        &[Token::Seq { len: Some(0) }, Token::SeqEnd],
+        "List of value sources cannot be empty",
+    );
+}
+
+#[test]
+fn test_parse_mode() {
+    assert_eq!(
+        toml::from_str::<ValueSource>(
+            r#"type = "literal"
+value = "/opt/lib"
+mode = "append"
+separator = ";""#
+        )
+        .unwrap(),
+        literal("/opt/lib").mode(ValueMode::Append).separator(";")
+    );
+}
+
+/// A variable can be defined as a table of shell-specific sources
+#[test]
+fn test_parse_per_shell() {
+    let content = r#"
+[per_shell]
+fish = "fish value"
+default = {type = "command", command = "echo default"}
+"#;
+    #[derive(Debug, Deserialize, Serialize)]
+    struct Wrapper {
+        per_shell: VariableDefinition,
+    }
+    let expected = VariableDefinition::PerShell(ShellValueSources {
+        fish: Some(literal("fish value")),
+        default: Some(command("echo default")),
+        ..Default::default()
+    });
+    let parsed = toml::from_str::<Wrapper>(content).unwrap();
+    assert_eq!(parsed.per_shell, expected);
+
+    // Make sure it round trips
+    let serialized = toml::to_string(&parsed).unwrap();
+    assert_eq!(
+        toml::from_str::<Wrapper>(&serialized).unwrap().per_shell,
+        expected
+    );
+
+    // A map with any other keys is a single value source
+    assert_eq!(
+        toml::from_str::<Wrapper>(
+            "per_shell = {type = \"literal\", value = \"abc\"}"
+        )
+        .unwrap()
+        .per_shell,
+        literal("abc").into()
+    );
+    assert_de_tokens_error::<VariableDefinition>(
+        &[
+            Token::Map { len: None },
+            Token::Str("value"),
+            Token::Str("abc"),
+            Token::MapEnd,
+        ],
+        "missing field `type`",
+    );
+}
+
 #[test]
 fn test_parse_unknown_type() {
     assert_de_tokens_error::<ValueSource>(
@@ -359,6 +1397,10 @@ fn test_parse_unknown_type() {
             Token::MapEnd,
         ],
         "unknown variant `unknown`, expected one of \
-            `literal`, `file`, `command`",
+            `literal`, `env`, `unset`, `file`, `file-glob`, `dotenv`, \
+            `json-file`, `yaml-file`, `sops`, `generate`, `prompt`, `command`, \
+            `native-command`, `kubernetes`, `git-config`, `ssh`, `docker`, \
+            `vault`, `aws-ssm`, `aws-secrets-manager`, `azure-key-vault`, \
+            `gcp-secret`, `1password`, `doppler`, `keychain`, `pass`, `http`",
     )
 }
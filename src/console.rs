@@ -1,49 +1,348 @@
-use crate::config::{Application, MapExt, Name, Profile};
-use anyhow::bail;
-use dialoguer::{theme::ColorfulTheme, Select};
-use indexmap::IndexMap;
-use std::fmt::Write;
+use crate::config::{
+    get_aliased, Aliased, Application, MapExt, Name, Profile, ValueSource,
+    ValueSourceInner, ValueSourceKind, VariableDefinition,
+};
+use anyhow::{anyhow, bail};
+use clap::ValueEnum;
+use dialoguer::{theme::ColorfulTheme, FuzzySelect, MultiSelect};
+use indexmap::{IndexMap, IndexSet};
+use std::{
+    env,
+    fmt::Write,
+    io::{self, BufRead, IsTerminal},
+    mem,
+};
 use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
 
-/// Prompt the user to select one option from a list.
-pub fn prompt_options<'a, T: Prompt>(
+/// How to ask the user to select from a list of options
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PromptMode {
+    /// Interactive list that can be filtered by typing
+    Fancy,
+    /// Numbered list, answered by typing a number. For terminals that can't
+    /// move the cursor, where the fancy prompt renders garbage
+    Plain,
+    /// Fail instead of prompting
+    Disabled,
+}
+
+impl PromptMode {
+    /// Pick how to prompt, based on flags and the terminal. Prompts read from
+    /// stdin and draw on stderr, so they need both to be a terminal. stdout may
+    /// be piped to `source` while prompting.
+    pub fn detect(non_interactive: bool, plain: bool) -> Self {
+        if non_interactive
+            || !io::stdin().is_terminal()
+            || !io::stderr().is_terminal()
+        {
+            Self::Disabled
+        } else if plain || env::var("TERM").is_ok_and(|term| term == "dumb") {
+            Self::Plain
+        } else {
+            Self::Fancy
+        }
+    }
+}
+
+/// Prompt the user to select one option from a list. If a name is given, the
+/// matching option (by name or alias) is selected without prompting. Return
+/// the selected option along with its name. If `highlighted` names an option,
+/// the prompt starts on it instead of the first option. If prompts are
+/// disabled, fail with a list of the options instead of prompting.
+pub fn prompt_options<'a, T: Prompt + Aliased>(
     options: &'a IndexMap<Name, T>,
-    default_name: Option<&'a Name>,
-) -> anyhow::Result<&'a T> {
+    default_name: Option<&Name>,
+    highlighted: Option<&Name>,
+    mode: PromptMode,
+) -> anyhow::Result<(&'a Name, &'a T)> {
     match default_name {
-        Some(default_name) => options.try_get(default_name),
+        Some(default_name) => get_aliased(options, default_name),
+
+        None if mode == PromptMode::Disabled => {
+            Err(non_interactive_error(options))
+        }
 
         // Show a prompt to ask the user which profile to use
         None => {
-            let theme = ColorfulTheme::default();
-            let options_vec = options.iter().collect::<Vec<_>>();
-
-            if options_vec.is_empty() {
-                bail!("No {}s to choose from", T::SELF_NAME);
-            }
-
-            // Show a prompt to ask the user which value to use
-            let chosen_index = Select::with_theme(&theme)
-                .with_prompt(format!("Select {}", T::SELF_NAME))
-                .items(
+            let options_vec = visible_options(options)?;
+            let default_index = highlighted
+                .and_then(|highlighted| {
                     options_vec
                         .iter()
-                        .map(|(name, option)| option.format_option(name))
-                        .collect::<Vec<_>>()
-                        .as_slice(),
-                )
-                .default(0)
-                .interact()?;
+                        .position(|(name, _)| *name == highlighted)
+                })
+                .unwrap_or(0);
+
+            let chosen_index = if mode == PromptMode::Plain {
+                plain_select(
+                    &mut io::stdin().lock(),
+                    &mut io::stderr(),
+                    &format!("Select {}", T::SELF_NAME),
+                    &compact_items(&options_vec),
+                    default_index,
+                )?
+            } else {
+                // Typing filters the options, matching against everything
+                // shown for each one, including aliases and descriptions
+                FuzzySelect::with_theme(&ColorfulTheme::default())
+                    .with_prompt(format!(
+                        "Select {} (type to filter)",
+                        T::SELF_NAME
+                    ))
+                    .items(
+                        options_vec
+                            .iter()
+                            .map(|(name, option)| option.format_option(name))
+                            .collect::<Vec<_>>()
+                            .as_slice(),
+                    )
+                    .default(default_index)
+                    .interact()?
+            };
 
             // This index is safe because it came from the value array above
-            Ok(options_vec[chosen_index].1)
+            Ok(options_vec[chosen_index])
+        }
+    }
+}
+
+/// Prompt the user to select any number of options from a list, in the order
+/// they're listed. Options named in `checked` start out selected. At least one
+/// option must be selected. If prompts are disabled, fail with a list of the
+/// options instead of prompting.
+pub fn prompt_multiple<'a, T: Prompt>(
+    options: &'a IndexMap<Name, T>,
+    checked: &[&Name],
+    mode: PromptMode,
+) -> anyhow::Result<Vec<&'a Name>> {
+    if mode == PromptMode::Disabled {
+        return Err(non_interactive_error(options));
+    }
+    let options_vec = visible_options(options)?;
+    let checked = options_vec
+        .iter()
+        .map(|(name, _)| checked.contains(name))
+        .collect::<Vec<_>>();
+
+    let chosen_indexes = if mode == PromptMode::Plain {
+        plain_select_multiple(
+            &mut io::stdin().lock(),
+            &mut io::stderr(),
+            &format!("Select {}s", T::SELF_NAME),
+            &compact_items(&options_vec),
+            &checked,
+        )?
+    } else {
+        MultiSelect::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!(
+                "Select {}s (space to toggle, enter to confirm)",
+                T::SELF_NAME
+            ))
+            .items_checked(
+                &options_vec
+                    .iter()
+                    .zip(checked)
+                    .map(|((name, option), checked)| {
+                        (option.format_option(name), checked)
+                    })
+                    .collect::<Vec<_>>(),
+            )
+            .interact()?
+    };
+    if chosen_indexes.is_empty() {
+        bail!("No {}s selected", T::SELF_NAME);
+    }
+
+    // These indexes are safe because they came from the value array above
+    Ok(chosen_indexes
+        .into_iter()
+        .map(|index| options_vec[index].0)
+        .collect())
+}
+
+/// Get the options that can be shown in a prompt. Fail if there are none
+fn visible_options<T: Prompt>(
+    options: &IndexMap<Name, T>,
+) -> anyhow::Result<Vec<(&Name, &T)>> {
+    let options_vec = options
+        .iter()
+        .filter(|(_, option)| !option.is_hidden())
+        .collect::<Vec<_>>();
+    if options_vec.is_empty() {
+        bail!("No {}s to choose from", T::SELF_NAME);
+    }
+    Ok(options_vec)
+}
+
+/// Error for when we need to prompt but can't. List the options, so the user
+/// can pass one instead
+fn non_interactive_error<T: Prompt>(
+    options: &IndexMap<Name, T>,
+) -> anyhow::Error {
+    anyhow!(
+        "No {name} given, and can't prompt in non-interactive mode. \
+        Pass one of: {}",
+        options.display_keys(),
+        name = T::SELF_NAME,
+    )
+}
+
+/// Format options as single lines, for the plain prompt
+fn compact_items<T: Prompt>(options: &[(&Name, &T)]) -> Vec<String> {
+    options
+        .iter()
+        .map(|(name, option)| option.format_compact(name))
+        .collect()
+}
+
+/// Ask the user to pick an option by its number in a list. An empty answer
+/// picks the default. Invalid answers are explained, and asked again.
+fn plain_select(
+    input: &mut impl BufRead,
+    output: &mut impl io::Write,
+    prompt: &str,
+    items: &[String],
+    default: usize,
+) -> anyhow::Result<usize> {
+    write_numbered(output, prompt, items)?;
+    loop {
+        write!(output, "Enter a number [{}]: ", default + 1)?;
+        let answer = read_answer(input, output)?;
+        if answer.is_empty() {
+            return Ok(default);
+        }
+        match parse_choice(&answer, items.len()) {
+            Ok(index) => return Ok(index),
+            Err(error) => writeln!(output, "{error}")?,
+        }
+    }
+}
+
+/// Ask the user to pick any number of options by their numbers in a list,
+/// separated by spaces or commas. An empty answer picks the checked options.
+/// Invalid answers are explained, and asked again. The chosen indexes are
+/// returned in list order.
+fn plain_select_multiple(
+    input: &mut impl BufRead,
+    output: &mut impl io::Write,
+    prompt: &str,
+    items: &[String],
+    checked: &[bool],
+) -> anyhow::Result<Vec<usize>> {
+    let defaults = checked
+        .iter()
+        .enumerate()
+        .filter(|(_, checked)| **checked)
+        .map(|(index, _)| index)
+        .collect::<Vec<_>>();
+    write_numbered(output, prompt, items)?;
+    loop {
+        write!(output, "Enter numbers separated by spaces")?;
+        if !defaults.is_empty() {
+            let defaults = defaults
+                .iter()
+                .map(|index| (index + 1).to_string())
+                .collect::<Vec<_>>();
+            write!(output, " [{}]", defaults.join(" "))?;
+        }
+        write!(output, ": ")?;
+        let answer = read_answer(input, output)?;
+        if answer.is_empty() {
+            return Ok(defaults);
+        }
+        let chosen = answer
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|choice| !choice.is_empty())
+            .map(|choice| parse_choice(choice, items.len()))
+            .collect::<Result<Vec<_>, _>>();
+        match chosen {
+            Ok(mut chosen) => {
+                chosen.sort_unstable();
+                chosen.dedup();
+                return Ok(chosen);
+            }
+            Err(error) => writeln!(output, "{error}")?,
         }
     }
 }
 
+/// Write a numbered list of items, starting at 1
+fn write_numbered(
+    output: &mut impl io::Write,
+    prompt: &str,
+    items: &[String],
+) -> anyhow::Result<()> {
+    writeln!(output, "{prompt}:")?;
+    for (i, item) in items.iter().enumerate() {
+        writeln!(output, "{:>3}) {item}", i + 1)?;
+    }
+    Ok(())
+}
+
+/// Read a line of input, after the question has been written. Fail if input
+/// has ended, since the question can never be answered
+fn read_answer(
+    input: &mut impl BufRead,
+    output: &mut impl io::Write,
+) -> anyhow::Result<String> {
+    output.flush()?;
+    let mut answer = String::new();
+    if input.read_line(&mut answer)? == 0 {
+        bail!("Input ended before an option was selected");
+    }
+    Ok(answer.trim().to_owned())
+}
+
+/// Parse a 1-based option number into an index
+fn parse_choice(choice: &str, len: usize) -> Result<usize, String> {
+    match choice.parse::<usize>() {
+        Ok(number) if (1..=len).contains(&number) => Ok(number - 1),
+        _ => Err(format!("Invalid choice `{choice}`; enter 1 to {len}")),
+    }
+}
+
+/// When to color output
+#[derive(Copy, Clone, Debug, Default, ValueEnum)]
+pub enum ColorMode {
+    /// Color if stderr is a terminal, unless NO_COLOR is set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorMode {
+    /// Apply this setting to all console output: hints, prompts, and logs
+    /// (via the returned flag). Call this once, before printing anything.
+    /// Return whether color is enabled.
+    pub fn init(self) -> bool {
+        let enabled = match self {
+            // https://no-color.org
+            Self::Auto => {
+                io::stderr().is_terminal()
+                    && !env::var_os("NO_COLOR")
+                        .is_some_and(|value| !value.is_empty())
+            }
+            Self::Always => true,
+            Self::Never => false,
+        };
+        // Prompt themes check this, and so do hints, so there's only one
+        // source of truth
+        dialoguer::console::set_colors_enabled(enabled);
+        dialoguer::console::set_colors_enabled_stderr(enabled);
+        enabled
+    }
+}
+
 /// Print the given message to stderr, with warning styling
 pub fn print_hint(message: &str) -> anyhow::Result<()> {
-    let mut stderr = StandardStream::stderr(ColorChoice::Always);
+    let mut stderr = StandardStream::stderr(
+        if dialoguer::console::colors_enabled_stderr() {
+            ColorChoice::Always
+        } else {
+            ColorChoice::Never
+        },
+    );
     stderr.set_color(
         ColorSpec::new().set_fg(Some(Color::Yellow)).set_bold(true),
     )?;
@@ -52,11 +351,25 @@ pub fn print_hint(message: &str) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Max width of a description in a prompt, in characters
+const DESCRIPTION_WIDTH: usize = 72;
+/// Max number of lines of a description in a prompt. Anything beyond this is
+/// cut off, to keep the list of options readable
+const DESCRIPTION_LINES: usize = 3;
+
 /// Little helper to define how a type should be rendered in a TUI prompt
 pub trait Prompt: Sized {
     const SELF_NAME: &'static str;
 
     fn format_option(&self, name: &Name) -> String;
+
+    /// Format as a single line, for the plain prompt
+    fn format_compact(&self, name: &Name) -> String;
+
+    /// Should this be left out of prompts and completions?
+    fn is_hidden(&self) -> bool {
+        false
+    }
 }
 
 impl Prompt for Application {
@@ -67,11 +380,19 @@ impl Prompt for Application {
         // we'll show all the variable mappings
         let mut buffer = String::new();
         writeln!(buffer, "=== {name} ===").unwrap();
-        for profile_name in self.profiles.keys() {
+        write_aliases(&mut buffer, &self.aliases);
+        write_description(&mut buffer, self.description.as_deref());
+        for (profile_name, _) in
+            self.profiles.iter().filter(|(_, profile)| !profile.hidden)
+        {
             writeln!(buffer, "{profile_name}").unwrap();
         }
         buffer
     }
+
+    fn format_compact(&self, name: &Name) -> String {
+        format_compact(name, &self.aliases, self.description.as_deref(), false)
+    }
 }
 
 impl Prompt for Profile {
@@ -81,10 +402,316 @@ impl Prompt for Profile {
         // First line of the output will be the profile name, then
         // we'll show all the variable mappings
         let mut buffer = String::new();
-        writeln!(buffer, "=== {name} ===").unwrap();
+        if self.is_deprecated() {
+            writeln!(buffer, "=== {name} (deprecated) ===").unwrap();
+        } else {
+            writeln!(buffer, "=== {name} ===").unwrap();
+        }
+        write_aliases(&mut buffer, &self.aliases);
+        write_description(&mut buffer, self.description.as_deref());
         for (variable, value) in &self.variables {
-            writeln!(buffer, "{variable} = {value}").unwrap();
+            match value {
+                // The variable name for .env sources is a meaningless
+                // placeholder, so don't show it
+                VariableDefinition::Single(ValueSource(ValueSourceInner {
+                    kind: ValueSourceKind::Dotenv { .. },
+                    ..
+                })) => writeln!(buffer, "{value}").unwrap(),
+                VariableDefinition::Single(ValueSource(ValueSourceInner {
+                    kind: ValueSourceKind::Unset,
+                    ..
+                })) => writeln!(buffer, "{variable} (unset)").unwrap(),
+                _ => writeln!(buffer, "{variable} = {value}").unwrap(),
+            }
         }
         buffer
     }
+
+    fn format_compact(&self, name: &Name) -> String {
+        format_compact(
+            name,
+            &self.aliases,
+            self.description.as_deref(),
+            self.is_deprecated(),
+        )
+    }
+
+    fn is_hidden(&self) -> bool {
+        self.hidden
+    }
+}
+
+/// Format an option as a single line, e.g.
+/// `dev [local] (deprecated) - Local development`. Only the first line of the
+/// description is included
+fn format_compact(
+    name: &Name,
+    aliases: &IndexSet<Name>,
+    description: Option<&str>,
+    deprecated: bool,
+) -> String {
+    let mut buffer = name.to_string();
+    if !aliases.is_empty() {
+        let aliases = aliases.iter().map(Name::as_str).collect::<Vec<_>>();
+        write!(buffer, " [{}]", aliases.join(", ")).unwrap();
+    }
+    if deprecated {
+        buffer.push_str(" (deprecated)");
+    }
+    let lines = description.map(wrap_description).unwrap_or_default();
+    if let Some(line) = lines.first() {
+        write!(buffer, " - {line}").unwrap();
+        if lines.len() > 1 && !line.ends_with('…') {
+            buffer.push('…');
+        }
+    }
+    buffer
+}
+
+/// Write aliases into a prompt option, if there are any, so they can be
+/// searched for
+fn write_aliases(buffer: &mut String, aliases: &IndexSet<Name>) {
+    if !aliases.is_empty() {
+        let aliases = aliases.iter().map(Name::as_str).collect::<Vec<_>>();
+        writeln!(buffer, "Aliases: {}", aliases.join(", ")).unwrap();
+    }
+}
+
+/// Write a description into a prompt option, if there is one. The description
+/// is wrapped, and truncated if it's too long
+fn write_description(buffer: &mut String, description: Option<&str>) {
+    if let Some(description) = description {
+        for line in wrap_description(description) {
+            writeln!(buffer, "{line}").unwrap();
+        }
+    }
+}
+
+/// Wrap a description to [DESCRIPTION_WIDTH], collapsing whitespace. If it
+/// takes more than [DESCRIPTION_LINES], the last line ends with an ellipsis.
+/// Words too long for a single line are cut off the same way.
+fn wrap_description(description: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+    for word in description.split_whitespace() {
+        if !line.is_empty() {
+            if line.chars().count() + 1 + word.chars().count()
+                > DESCRIPTION_WIDTH
+            {
+                lines.push(mem::take(&mut line));
+            } else {
+                line.push(' ');
+            }
+        }
+        line.push_str(word);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+
+    let truncated = lines.len() > DESCRIPTION_LINES;
+    lines.truncate(DESCRIPTION_LINES);
+    let last_index = lines.len().saturating_sub(1);
+    for (i, line) in lines.iter_mut().enumerate() {
+        if (truncated && i == last_index)
+            || line.chars().count() > DESCRIPTION_WIDTH
+        {
+            *line = line.chars().take(DESCRIPTION_WIDTH - 1).collect();
+            line.push('…');
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::literal;
+    use indexmap::IndexMap;
+    use rstest::rstest;
+
+    /// Hidden profiles are left out of the application summary
+    #[test]
+    fn test_format_application() {
+        let application = Application {
+            description: Some("The server".into()),
+            profiles: IndexMap::from([
+                (
+                    "base".into(),
+                    Profile {
+                        hidden: true,
+                        ..Default::default()
+                    },
+                ),
+                ("dev".into(), Profile::default()),
+            ]),
+            ..Default::default()
+        };
+        assert_eq!(
+            application.format_option(&"server".into()),
+            "=== server ===\nThe server\ndev\n"
+        );
+    }
+
+    #[test]
+    fn test_format_profile() {
+        let profile = Profile {
+            aliases: IndexSet::from(["local".into(), "d".into()]),
+            description: Some("Local development".into()),
+            variables: IndexMap::from([(
+                "HOST".into(),
+                VariableDefinition::Single(literal("localhost")),
+            )]),
+            ..Default::default()
+        };
+        assert_eq!(
+            profile.format_option(&"dev".into()),
+            "=== dev ===\nAliases: local, d\nLocal development\n\
+            HOST = \"localhost\"\n"
+        );
+    }
+
+    #[rstest]
+    #[case::plain(&[], None, false, "dev")]
+    #[case::full(
+        &["local", "d"],
+        Some("Local development"),
+        true,
+        "dev [local, d] (deprecated) - Local development"
+    )]
+    #[case::long_description(
+        &[],
+        Some(
+            "word word word word word word word word word word word word word word \
+            word word word word word word"
+        ),
+        false,
+        "dev - word word word word word word word word word word word word \
+        word word…"
+    )]
+    fn test_format_compact(
+        #[case] aliases: &[&str],
+        #[case] description: Option<&str>,
+        #[case] deprecated: bool,
+        #[case] expected: &str,
+    ) {
+        let aliases = aliases.iter().map(|alias| (*alias).into()).collect();
+        assert_eq!(
+            format_compact(&"dev".into(), &aliases, description, deprecated),
+            expected
+        );
+    }
+
+    /// Invalid answers are asked again, and an empty answer picks the default
+    #[rstest]
+    #[case::number("2\n", 0, 1, "")]
+    #[case::default("\n", 2, 2, "")]
+    #[case::retry(
+        "0\nfour\n3\n",
+        0,
+        2,
+        "Invalid choice `0`; enter 1 to 3\n\
+        Enter a number [1]: Invalid choice `four`; enter 1 to 3\n\
+        Enter a number [1]: "
+    )]
+    fn test_plain_select(
+        #[case] input: &str,
+        #[case] default: usize,
+        #[case] expected: usize,
+        #[case] retries: &str,
+    ) {
+        let mut output = Vec::new();
+        let items = ["dev".to_owned(), "stg".into(), "prd".into()];
+        let chosen = plain_select(
+            &mut input.as_bytes(),
+            &mut output,
+            "Pick",
+            &items,
+            default,
+        )
+        .unwrap();
+        assert_eq!(chosen, expected);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            format!(
+                "Pick:\n  1) dev\n  2) stg\n  3) prd\nEnter a number [{}]: \
+                {retries}",
+                default + 1
+            )
+        );
+    }
+
+    #[rstest]
+    #[case::numbers("3, 1 3\n", &[false, false, false], &[0, 2])]
+    #[case::default("\n", &[false, true, true], &[1, 2])]
+    #[case::retry("1 x\n2\n", &[false, false, false], &[1])]
+    fn test_plain_select_multiple(
+        #[case] input: &str,
+        #[case] checked: &[bool],
+        #[case] expected: &[usize],
+    ) {
+        let items = ["dev".to_owned(), "stg".into(), "prd".into()];
+        let chosen = plain_select_multiple(
+            &mut input.as_bytes(),
+            &mut Vec::new(),
+            "Pick",
+            &items,
+            checked,
+        )
+        .unwrap();
+        assert_eq!(chosen, expected);
+    }
+
+    /// Running out of input is an error, rather than looping forever
+    #[test]
+    fn test_plain_select_end_of_input() {
+        let items = ["dev".to_owned()];
+        assert_eq!(
+            plain_select(
+                &mut "7\n".as_bytes(),
+                &mut Vec::new(),
+                "Pick",
+                &items,
+                0
+            )
+            .unwrap_err()
+            .to_string(),
+            "Input ended before an option was selected"
+        );
+    }
+
+    #[rstest]
+    #[case::empty("  ", &[])]
+    #[case::short("Use for   local\ndev", &["Use for local dev"])]
+    #[case::wrapped(
+        &"word ".repeat(30),
+        &[
+            "word word word word word word word word word word word word word word",
+            "word word word word word word word word word word word word word word",
+            "word word",
+        ],
+    )]
+    #[case::truncated(
+        &"word ".repeat(60),
+        &[
+            "word word word word word word word word word word word word word word",
+            "word word word word word word word word word word word word word word",
+            "word word word word word word word word word word word word word word…",
+        ],
+    )]
+    fn test_wrap_description(
+        #[case] description: &str,
+        #[case] expected: &[&str],
+    ) {
+        assert_eq!(wrap_description(description), expected);
+    }
+
+    /// Words that don't fit on a line are cut off
+    #[test]
+    fn test_wrap_description_long_word() {
+        assert_eq!(
+            wrap_description(&"a".repeat(100)),
+            [format!("{}…", "a".repeat(71))]
+        );
+    }
 }
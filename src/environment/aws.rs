@@ -0,0 +1,290 @@
+//! Value resolution for AWS value sources
+
+use crate::{
+    environment::{
+        format_dotenv, format_dotenv_json, json_to_string, Environment,
+    },
+    execute::IntoExecutable,
+};
+use anyhow::{anyhow, Context};
+use serde::Deserialize;
+use serde_json::Value;
+
+const AWS: &str = "aws";
+
+impl Environment {
+    /// Read a parameter from SSM Parameter Store. In single-value mode, this
+    /// returns the parameter's value. In multi-value mode, the parameter is
+    /// treated as a path prefix, and all parameters under that path are
+    /// returned as a dotenv mapping, keyed by the basename of each parameter.
+    pub(super) async fn resolve_aws_ssm(
+        parameter: &str,
+        region: Option<&str>,
+        profile: Option<&str>,
+        multiple: bool,
+    ) -> anyhow::Result<String> {
+        let on_error = |stderr: &str| ssm_error(parameter, stderr);
+        if multiple {
+            let output = execute_aws(
+                &[
+                    "ssm",
+                    "get-parameters-by-path",
+                    "--path",
+                    parameter,
+                    "--recursive",
+                    "--with-decryption",
+                    "--output",
+                    "json",
+                ],
+                region,
+                profile,
+                on_error,
+            )
+            .await?;
+            parse_parameters(&output, parameter)
+        } else {
+            execute_aws(
+                &[
+                    "ssm",
+                    "get-parameter",
+                    "--name",
+                    parameter,
+                    "--with-decryption",
+                    "--query",
+                    "Parameter.Value",
+                    "--output",
+                    "text",
+                ],
+                region,
+                profile,
+                on_error,
+            )
+            .await
+        }
+    }
+
+    /// Read a secret from Secrets Manager. If a key is given, the secret is
+    /// parsed as a JSON object and that key is extracted. In multi-value mode,
+    /// the secret (or the object under `key`) must be a JSON object, and its
+    /// top-level fields are returned as a dotenv mapping.
+    pub(super) async fn resolve_aws_secrets_manager(
+        secret_id: &str,
+        key: Option<&str>,
+        region: Option<&str>,
+        profile: Option<&str>,
+        multiple: bool,
+    ) -> anyhow::Result<String> {
+        let secret = execute_aws(
+            &[
+                "secretsmanager",
+                "get-secret-value",
+                "--secret-id",
+                secret_id,
+                "--query",
+                "SecretString",
+                "--output",
+                "text",
+            ],
+            region,
+            profile,
+            |stderr| secrets_manager_error(secret_id, stderr),
+        )
+        .await?;
+        parse_secret(&secret, secret_id, key, multiple)
+    }
+}
+
+/// Execute an AWS CLI command and return its stdout. If the command fails,
+/// its stderr is passed to `on_error` to generate a descriptive error.
+async fn execute_aws(
+    arguments: &[&str],
+    region: Option<&str>,
+    profile: Option<&str>,
+    on_error: impl FnOnce(&str) -> anyhow::Error,
+) -> anyhow::Result<String> {
+    let mut arguments = arguments.to_vec();
+    if let Some(region) = region {
+        arguments.extend(["--region", region]);
+    }
+    if let Some(profile) = profile {
+        arguments.extend(["--profile", profile]);
+    }
+
+    let mut executable = (AWS, arguments).executable();
+    let output = executable.output().await?;
+    if output.status.success() {
+        Ok(String::from_utf8(output.stdout)
+            .with_context(|| format!("Error decoding output for {executable}"))?
+            .trim_end()
+            .to_owned())
+    } else {
+        Err(on_error(&String::from_utf8_lossy(&output.stderr)))
+    }
+}
+
+/// Generate an error for a failed SSM request, based on the CLI's stderr
+fn ssm_error(parameter: &str, stderr: &str) -> anyhow::Error {
+    if stderr.contains("ParameterNotFound") {
+        anyhow!("SSM parameter `{parameter}` not found")
+    } else if stderr.to_lowercase().contains("kms")
+        || stderr.contains("InvalidKeyId")
+    {
+        anyhow!(
+            "Error decrypting SSM parameter `{parameter}`; make sure you have \
+            access to its KMS key"
+        )
+    } else {
+        anyhow!(
+            "Error reading SSM parameter `{parameter}`: {}",
+            stderr.trim()
+        )
+    }
+}
+
+/// Generate an error for a failed Secrets Manager request, based on the CLI's
+/// stderr
+fn secrets_manager_error(secret_id: &str, stderr: &str) -> anyhow::Error {
+    if stderr.contains("ResourceNotFoundException") {
+        anyhow!("Secret `{secret_id}` not found")
+    } else {
+        anyhow!("Error reading secret `{secret_id}`: {}", stderr.trim())
+    }
+}
+
+/// Extract a value (or values) from a Secrets Manager secret string
+fn parse_secret(
+    secret: &str,
+    secret_id: &str,
+    key: Option<&str>,
+    multiple: bool,
+) -> anyhow::Result<String> {
+    // Only parse as JSON if we actually need to look inside the secret
+    if key.is_none() && !multiple {
+        return Ok(secret.to_owned());
+    }
+
+    let json: Value = serde_json::from_str(secret).with_context(|| {
+        format!("Error parsing secret `{secret_id}` as JSON")
+    })?;
+    let value = match key {
+        Some(key) => json.get(key).ok_or_else(|| {
+            anyhow!("Key `{key}` not found in secret `{secret_id}`")
+        })?,
+        None => &json,
+    };
+
+    if multiple {
+        let object = value.as_object().ok_or_else(|| {
+            anyhow!(
+                "Secret `{secret_id}` must be a JSON object to load \
+                multiple values"
+            )
+        })?;
+        format_dotenv_json(object)
+    } else {
+        Ok(json_to_string(value))
+    }
+}
+
+/// Parse the JSON output of `get-parameters-by-path` into a dotenv mapping
+fn parse_parameters(output: &str, path: &str) -> anyhow::Result<String> {
+    #[derive(Deserialize)]
+    #[serde(rename_all = "PascalCase")]
+    struct Response {
+        parameters: Vec<Parameter>,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "PascalCase")]
+    struct Parameter {
+        name: String,
+        value: String,
+    }
+
+    let response: Response =
+        serde_json::from_str(output).with_context(|| {
+            format!("Error parsing SSM parameters under path `{path}`")
+        })?;
+    if response.parameters.is_empty() {
+        return Err(anyhow!("No SSM parameters found under path `{path}`"));
+    }
+    format_dotenv(response.parameters.iter().map(|parameter| {
+        let basename = parameter
+            .name
+            .rsplit_once('/')
+            .map_or(parameter.name.as_str(), |(_, basename)| basename);
+        (basename, parameter.value.as_str())
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::assert_result;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::not_found(
+        "An error occurred (ParameterNotFound) when calling the GetParameter \
+        operation:",
+        "SSM parameter `/app/db` not found"
+    )]
+    #[case::decryption(
+        "An error occurred (AccessDeniedException) when calling the \
+        GetParameter operation: User is not authorized to perform: \
+        kms:Decrypt",
+        "Error decrypting SSM parameter `/app/db`; make sure you have access \
+        to its KMS key"
+    )]
+    #[case::other(
+        "Unable to locate credentials\n",
+        "Error reading SSM parameter `/app/db`: Unable to locate credentials"
+    )]
+    fn test_ssm_error(#[case] stderr: &str, #[case] expected: &str) {
+        assert_eq!(ssm_error("/app/db", stderr).to_string(), expected);
+    }
+
+    #[rstest]
+    #[case::raw(None, false, Ok(r#"{"USER": "root", "PORT": 5432}"#))]
+    #[case::key(Some("USER"), false, Ok("root"))]
+    #[case::key_number(Some("PORT"), false, Ok("5432"))]
+    #[case::multiple(None, true, Ok("PORT='5432'\nUSER='root'\n"))]
+    #[case::unknown_key(
+        Some("PASSWORD"),
+        false,
+        Err("Key `PASSWORD` not found in secret `db`")
+    )]
+    #[case::multiple_not_object(
+        Some("USER"),
+        true,
+        Err("Secret `db` must be a JSON object to load multiple values")
+    )]
+    fn test_parse_secret(
+        #[case] key: Option<&str>,
+        #[case] multiple: bool,
+        #[case] expected: Result<&str, &str>,
+    ) {
+        let secret = r#"{"USER": "root", "PORT": 5432}"#;
+        let result = parse_secret(secret, "db", key, multiple);
+        assert_result(result, expected);
+    }
+
+    #[test]
+    fn test_parse_parameters() {
+        let output = r#"{"Parameters": [
+            {"Name": "/app/DB_USER", "Value": "root", "Type": "String"},
+            {"Name": "/app/nested/DB_PASSWORD", "Value": "hunter2"}
+        ]}"#;
+        assert_eq!(
+            parse_parameters(output, "/app").unwrap(),
+            "DB_USER='root'\nDB_PASSWORD='hunter2'\n"
+        );
+
+        assert_eq!(
+            parse_parameters(r#"{"Parameters": []}"#, "/app")
+                .unwrap_err()
+                .to_string(),
+            "No SSM parameters found under path `/app`"
+        );
+    }
+}
@@ -0,0 +1,176 @@
+//! Value resolution for the `azure-key-vault` value source
+
+use crate::{
+    environment::{format_dotenv, Environment},
+    execute::IntoExecutable,
+};
+use anyhow::{anyhow, Context};
+use futures::future;
+
+const AZ: &str = "az";
+
+impl Environment {
+    /// Read a secret from Azure Key Vault. In multi-value mode, the secret
+    /// name is treated as a prefix, and all secrets starting with it are
+    /// returned as a dotenv mapping. Variable names are the secret names with
+    /// the prefix removed and `-` replaced by `_`, because Key Vault doesn't
+    /// allow underscores in names.
+    pub(super) async fn resolve_azure_key_vault(
+        vault: &str,
+        secret: &str,
+        multiple: bool,
+    ) -> anyhow::Result<String> {
+        if multiple {
+            let names = execute_az(
+                &[
+                    "keyvault",
+                    "secret",
+                    "list",
+                    "--vault-name",
+                    vault,
+                    "--query",
+                    "[].name",
+                    "--output",
+                    "tsv",
+                ],
+                vault,
+                None,
+            )
+            .await?;
+            let names: Vec<&str> = names
+                .lines()
+                .filter(|name| name.starts_with(secret))
+                .collect();
+            if names.is_empty() {
+                return Err(anyhow!(
+                    "No secrets found with prefix `{secret}` in key vault \
+                    `{vault}`"
+                ));
+            }
+            // Fetch all the secrets in parallel
+            let values = future::try_join_all(
+                names.iter().map(|name| get_secret(vault, name)),
+            )
+            .await?;
+            format_dotenv(names.iter().zip(&values).map(|(name, value)| {
+                (variable_name(name, secret), value.as_str())
+            }))
+        } else {
+            get_secret(vault, secret).await
+        }
+    }
+}
+
+/// Get the value of a single secret
+async fn get_secret(vault: &str, secret: &str) -> anyhow::Result<String> {
+    execute_az(
+        &[
+            "keyvault",
+            "secret",
+            "show",
+            "--vault-name",
+            vault,
+            "--name",
+            secret,
+            "--query",
+            "value",
+            "--output",
+            "tsv",
+        ],
+        vault,
+        Some(secret),
+    )
+    .await
+}
+
+/// Execute an `az` command and return its stdout
+async fn execute_az(
+    arguments: &[&str],
+    vault: &str,
+    secret: Option<&str>,
+) -> anyhow::Result<String> {
+    let mut executable = (AZ, arguments.iter().copied()).executable();
+    let output = executable.output().await?;
+    if output.status.success() {
+        Ok(String::from_utf8(output.stdout)
+            .with_context(|| format!("Error decoding output for {executable}"))?
+            .trim_end()
+            .to_owned())
+    } else {
+        Err(az_error(
+            vault,
+            secret,
+            &String::from_utf8_lossy(&output.stderr),
+        ))
+    }
+}
+
+/// Generate an error for a failed `az` command, based on its stderr
+fn az_error(vault: &str, secret: Option<&str>, stderr: &str) -> anyhow::Error {
+    if stderr.contains("az login") {
+        anyhow!("Not logged in to Azure; run `az login` and try again")
+    } else if stderr.contains("SecretNotFound") {
+        anyhow!(
+            "Secret `{}` not found in key vault `{vault}`",
+            secret.unwrap_or_default()
+        )
+    } else if stderr.contains("VaultNotFound")
+        || stderr.contains("Failed to resolve")
+    {
+        anyhow!("Key vault `{vault}` not found")
+    } else {
+        anyhow!("Error reading from key vault `{vault}`: {}", stderr.trim())
+    }
+}
+
+/// Convert a Key Vault secret name to a variable name
+fn variable_name(name: &str, prefix: &str) -> String {
+    name.strip_prefix(prefix).unwrap_or(name).replace('-', "_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::not_logged_in(
+        None,
+        "ERROR: Please run 'az login' to setup account.\n",
+        "Not logged in to Azure; run `az login` and try again"
+    )]
+    #[case::secret_not_found(
+        Some("db-password"),
+        "ERROR: (SecretNotFound) A secret with (name/id) db-password was not \
+        found in this key vault.\n",
+        "Secret `db-password` not found in key vault `dev`"
+    )]
+    #[case::vault_not_found(
+        Some("db-password"),
+        "ERROR: Failed to resolve 'dev.vault.azure.net'\n",
+        "Key vault `dev` not found"
+    )]
+    #[case::other(
+        None,
+        "ERROR: (Forbidden) Access denied\n",
+        "Error reading from key vault `dev`: ERROR: (Forbidden) Access denied"
+    )]
+    fn test_az_error(
+        #[case] secret: Option<&str>,
+        #[case] stderr: &str,
+        #[case] expected: &str,
+    ) {
+        assert_eq!(az_error("dev", secret, stderr).to_string(), expected);
+    }
+
+    #[rstest]
+    #[case("app-DB-USER", "app-", "DB_USER")]
+    #[case("DB-USER", "", "DB_USER")]
+    fn test_variable_name(
+        #[case] name: &str,
+        #[case] prefix: &str,
+        #[case] expected: &str,
+    ) {
+        assert_eq!(variable_name(name, prefix), expected);
+    }
+}
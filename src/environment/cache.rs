@@ -0,0 +1,218 @@
+//! On-disk cache of resolved values, for value sources with `cache` set.
+//! Each cached value is stored in its own file, named by a hash of the value
+//! source definition and everything it's rendered with. The file's
+//! modification time determines its age.
+
+use crate::{
+    config::ValueSource,
+    environment::template::{self, Chunk, TemplateContext},
+    source_file,
+};
+use anyhow::Context;
+use log::{debug, warn};
+use std::{
+    env, fs,
+    hash::{DefaultHasher, Hash, Hasher},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+/// Get a cached value for a value source, if present and not expired. Any
+/// errors are treated as a cache miss.
+pub async fn load(
+    value_source: &ValueSource,
+    context: &TemplateContext,
+    ttl: Duration,
+) -> Option<String> {
+    let path = cache_directory()?.join(cache_key(value_source, context));
+    smol::unblock(move || match read(&path, ttl) {
+        Ok(value) => value,
+        Err(error) => {
+            debug!("Error reading cache file {path:?}: {error:#}");
+            None
+        }
+    })
+    .await
+}
+
+/// Cache a resolved value. Failures are logged, but otherwise ignored,
+/// because the value was resolved successfully
+pub async fn store(
+    value_source: &ValueSource,
+    context: &TemplateContext,
+    value: &str,
+) {
+    let Some(directory) = cache_directory() else {
+        return;
+    };
+    let key = cache_key(value_source, context);
+    let value = value.to_owned();
+    smol::unblock(move || {
+        if let Err(error) = write(&directory, &key, &value) {
+            warn!("Error writing to cache in {directory:?}: {error:#}");
+        }
+    })
+    .await
+}
+
+/// Directory to store cache files in. `None` if we can't find a suitable
+/// location
+fn cache_directory() -> Option<PathBuf> {
+    let base =
+        env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| {
+                env::var_os("HOME").map(|home| Path::new(&home).join(".cache"))
+            })?;
+    Some(base.join("env-select"))
+}
+
+/// Generate a unique cache key for a value source. The same definition can
+/// resolve differently depending on where it's run and what its templates and
+/// commands render to, so the key includes the current directory, the values
+/// of referenced profile variables, and any `${env:VARIABLE}` values
+fn cache_key(value_source: &ValueSource, context: &TemplateContext) -> String {
+    let mut hasher = DefaultHasher::new();
+    value_source.hash(&mut hasher);
+    env::current_dir().ok().hash(&mut hasher);
+    for (variable, value) in context.references.iter_unmasked() {
+        (variable, value).hash(&mut hasher);
+    }
+    for variable in context.references.iter_unset() {
+        variable.hash(&mut hasher);
+    }
+    for variable in env_references(value_source) {
+        (&variable, env::var_os(&variable)).hash(&mut hasher);
+    }
+    format!("{:016x}", hasher.finish())
+}
+
+/// Get the names of all parent environment variables referenced by templates
+/// anywhere in a value source
+fn env_references(value_source: &ValueSource) -> Vec<String> {
+    fn walk(value: &serde_json::Value, references: &mut Vec<String>) {
+        match value {
+            serde_json::Value::String(string) => {
+                // Strings that aren't valid templates will fail to render
+                // anyway, so there's nothing to key on
+                for chunk in template::parse(string).unwrap_or_default() {
+                    if let Chunk::Env(variable) = chunk {
+                        references.push(variable.to_owned());
+                    }
+                }
+            }
+            serde_json::Value::Array(values) => {
+                values.iter().for_each(|value| walk(value, references))
+            }
+            serde_json::Value::Object(map) => {
+                map.values().for_each(|value| walk(value, references))
+            }
+            _ => {}
+        }
+    }
+
+    let mut references = Vec::new();
+    if let Ok(value) = serde_json::to_value(value_source) {
+        walk(&value, &mut references);
+    }
+    references
+}
+
+/// Read a value from a cache file. Return `None` if the file doesn't exist or
+/// is older than the TTL
+fn read(path: &Path, ttl: Duration) -> anyhow::Result<Option<String>> {
+    let modified = match fs::metadata(path) {
+        Ok(metadata) => metadata.modified()?,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(None)
+        }
+        Err(error) => return Err(error.into()),
+    };
+    // If the clock went backwards, treat the entry as expired
+    let age = SystemTime::now()
+        .duration_since(modified)
+        .unwrap_or(Duration::MAX);
+    if age >= ttl {
+        debug!("Cache file {path:?} expired");
+        return Ok(None);
+    }
+    Ok(Some(fs::read_to_string(path)?))
+}
+
+/// Write a value to a cache file. Cached values may be sensitive, so the file
+/// is only readable by the current user
+fn write(directory: &Path, key: &str, value: &str) -> anyhow::Result<()> {
+    fs::create_dir_all(directory)
+        .with_context(|| format!("Error creating directory {directory:?}"))?;
+    // Concurrent runs may read this entry while it's replaced, so they have
+    // to see either the old value or the new one, never a partial write
+    source_file::write(&directory.join(key), value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        environment::Environment,
+        test_util::{command, literal},
+    };
+
+    #[test]
+    fn test_cache_key() {
+        let context = TemplateContext::default();
+        assert_eq!(
+            cache_key(&literal("a"), &context),
+            cache_key(&literal("a"), &context)
+        );
+        assert_ne!(
+            cache_key(&literal("a"), &context),
+            cache_key(&literal("b"), &context)
+        );
+    }
+
+    /// The same definition gets a different key when the values it renders
+    /// with change
+    #[test]
+    fn test_cache_key_rendered() {
+        let value_source = command("curl $HOST/${env:ES_TEST_CACHE_KEY}");
+        let context = |host: &str| {
+            let mut references = Environment::default();
+            references.insert_internal("HOST", host.into());
+            TemplateContext {
+                references,
+                strict: false,
+            }
+        };
+
+        env::set_var("ES_TEST_CACHE_KEY", "a");
+        let key = cache_key(&value_source, &context("localhost"));
+        assert_eq!(cache_key(&value_source, &context("localhost")), key);
+        assert_ne!(cache_key(&value_source, &context("remote")), key);
+        env::set_var("ES_TEST_CACHE_KEY", "b");
+        assert_ne!(cache_key(&value_source, &context("localhost")), key);
+    }
+
+    #[test]
+    fn test_read_write() {
+        let directory = env::temp_dir().join("es-test-cache");
+        let path = directory.join("key");
+        let _ = fs::remove_file(&path);
+        let ttl = Duration::from_secs(60);
+
+        assert_eq!(read(&path, ttl).unwrap(), None);
+        write(&directory, "key", "value1").unwrap();
+        assert_eq!(read(&path, ttl).unwrap().as_deref(), Some("value1"));
+        // Overwrite an existing entry
+        write(&directory, "key", "value2").unwrap();
+        assert_eq!(read(&path, ttl).unwrap().as_deref(), Some("value2"));
+        // Expired
+        assert_eq!(read(&path, Duration::ZERO).unwrap(), None);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+}
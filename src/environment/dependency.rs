@@ -0,0 +1,378 @@
+//! Ordering of variable resolution, based on references between variables in
+//! a profile. Variables are resolved in waves: each wave contains every
+//! variable whose references were all resolved in previous waves.
+
+use crate::{
+    config::{MultiVariable, ValueSource, ValueSourceKind},
+    environment::template,
+};
+use anyhow::{anyhow, Context};
+use indexmap::{IndexMap, IndexSet};
+use std::collections::HashMap;
+
+/// Mapping of each variable in a profile to the other variables it references
+pub type Dependencies<'a> = IndexMap<&'a str, IndexSet<&'a str>>;
+
+/// Find references between the variables of a profile, given the value source
+/// selected for each variable. Literals reference variables with
+/// `${VARIABLE}`, and every name must exist in the profile. Commands may use
+/// `$VARIABLE` in the shell; only names that exist in the profile count, since
+/// others could come from the parent environment. Commands with
+/// `use_profile_env` also depend on every variable declared before them, so
+/// they can't reference a variable declared after them.
+///
+/// Names loaded by multi-variable sources count as variables of the profile,
+/// and a reference to one depends on the source that loads it. If a source
+/// loads names that can't be known until it's resolved, a literal's reference
+/// to a name that isn't otherwise known depends on that source.
+pub fn dependencies<'a>(
+    value_sources: &IndexMap<&'a str, &'a ValueSource>,
+) -> anyhow::Result<Dependencies<'a>> {
+    // Which profile variable loads each name that's known ahead of time. If
+    // multiple do, the last one wins, the same as when the profile is applied
+    let mut providers: HashMap<String, &str> = HashMap::new();
+    // Multi-variable sources whose names aren't known until they're resolved
+    let mut unknown_providers: Vec<&str> = Vec::new();
+    for (variable, value_source) in value_sources {
+        match provided_variables(variable, value_source) {
+            Some(names) => {
+                for name in names {
+                    providers.insert(name, variable);
+                }
+            }
+            None => unknown_providers.push(variable),
+        }
+    }
+
+    value_sources
+        .iter()
+        .enumerate()
+        .map(|(index, (variable, value_source))| {
+            let mut references = match &value_source.0.kind {
+                ValueSourceKind::Literal { value } => {
+                    let mut references = IndexSet::new();
+                    for reference in
+                        template::references(value).with_context(|| {
+                            format!("Error in value for {variable}")
+                        })?
+                    {
+                        match providers.get(reference) {
+                            Some(provider) => {
+                                references.insert(*provider);
+                            }
+                            // The name may be loaded by one of these
+                            None if !unknown_providers.is_empty() => {
+                                references.extend(
+                                    unknown_providers.iter().filter(
+                                        |provider| *provider != variable,
+                                    ),
+                                );
+                            }
+                            None => {
+                                return Err(anyhow!(
+                                    "{variable} references unknown variable \
+                                    `{reference}`"
+                                ))
+                            }
+                        }
+                    }
+                    references
+                }
+                // A command referencing its own variable is reading the value
+                // from the parent environment, e.g. `PATH=$PATH:...`
+                ValueSourceKind::Command {
+                    command,
+                    use_profile_env,
+                    ..
+                } => {
+                    let mut references = IndexSet::new();
+                    for reference in template::shell_references(command) {
+                        let Some(provider) = providers.get(reference) else {
+                            continue;
+                        };
+                        if provider == variable {
+                            continue;
+                        }
+                        if *use_profile_env
+                            && value_sources.get_index_of(*provider)
+                                > Some(index)
+                        {
+                            return Err(anyhow!(
+                                "{variable} references `{reference}`, which \
+                                is declared after it. Commands with \
+                                `use_profile_env` can only reference \
+                                variables declared before them"
+                            ));
+                        }
+                        references.insert(*provider);
+                    }
+                    references
+                }
+                _ => IndexSet::new(),
+            };
+            if let ValueSourceKind::Command {
+                use_profile_env: true,
+                ..
+            }
+            | ValueSourceKind::NativeCommand {
+                use_profile_env: true,
+                ..
+            } = value_source.0.kind
+            {
+                references.extend(value_sources.keys().take(index).copied());
+            }
+            Ok((*variable, references))
+        })
+        .collect()
+}
+
+/// Get the names that a value source will set, if they can be known before
+/// it's resolved. A multi-variable source only knows its names if they're
+/// restricted to a fixed list
+fn provided_variables(
+    variable: &str,
+    ValueSource(value_source): &ValueSource,
+) -> Option<Vec<String>> {
+    if !value_source.is_multiple() {
+        return Some(vec![variable.to_owned()]);
+    }
+    let names: Vec<String> = match (&value_source.multiple, &value_source.kind)
+    {
+        (MultiVariable::List(names), _)
+        | (MultiVariable::Filtered { keys: names, .. }, _) => names.clone(),
+        // Names are loaded under their new names
+        (MultiVariable::Map(names), _) => names.values().cloned().collect(),
+        (
+            MultiVariable::Bool(_),
+            ValueSourceKind::Dotenv {
+                only: Some(only),
+                except,
+                ..
+            },
+        ) => only
+            .iter()
+            .filter(|name| !except.contains(name))
+            .cloned()
+            .collect(),
+        (MultiVariable::Bool(_), _) => return None,
+    };
+    Some(
+        names
+            .into_iter()
+            .map(|name| match &value_source.prefix {
+                Some(prefix) => format!("{prefix}{name}"),
+                None => name,
+            })
+            .collect(),
+    )
+}
+
+/// Group variables into waves that can be resolved in parallel. Each wave only
+/// references variables from previous waves. If there is a reference cycle,
+/// return an error naming the variables involved.
+pub fn resolution_order<'a>(
+    dependencies: &Dependencies<'a>,
+) -> anyhow::Result<Vec<Vec<&'a str>>> {
+    let mut waves: Vec<Vec<&str>> = Vec::new();
+    let mut remaining: IndexSet<&str> = dependencies.keys().copied().collect();
+    while !remaining.is_empty() {
+        let wave: Vec<&str> = remaining
+            .iter()
+            .copied()
+            .filter(|variable| {
+                dependencies[variable]
+                    .iter()
+                    .all(|reference| !remaining.contains(reference))
+            })
+            .collect();
+        if wave.is_empty() {
+            return Err(cycle_error(dependencies, &remaining));
+        }
+        for variable in &wave {
+            remaining.shift_remove(variable);
+        }
+        waves.push(wave);
+    }
+    Ok(waves)
+}
+
+/// Build an error for a reference cycle. Every remaining variable has at least
+/// one unresolved reference, so following references from any of them must
+/// eventually revisit a variable.
+fn cycle_error(
+    dependencies: &Dependencies,
+    remaining: &IndexSet<&str>,
+) -> anyhow::Error {
+    let mut path: IndexSet<&str> = IndexSet::new();
+    let mut current = remaining[0];
+    while path.insert(current) {
+        current = dependencies[current]
+            .iter()
+            .copied()
+            .find(|reference| remaining.contains(reference))
+            .expect("Unresolved variable has no unresolved references");
+    }
+    // Trim any lead-in to the cycle, so the path starts and ends with the same
+    // variable
+    let start = path.get_index_of(current).unwrap();
+    let cycle: Vec<&str> =
+        path.iter().skip(start).copied().chain([current]).collect();
+    anyhow!("Reference cycle between variables: {}", cycle.join(" -> "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{
+        assert_result, command, dotenv, file, literal, native_command,
+    };
+    use rstest::rstest;
+
+    /// Build a list of literal value sources
+    fn literals(variables: &[(&str, &str)]) -> Vec<(String, ValueSource)> {
+        variables
+            .iter()
+            .map(|(variable, value)| ((*variable).to_owned(), literal(value)))
+            .collect()
+    }
+
+    /// Borrow a list of value sources as a map
+    fn value_sources(
+        variables: &[(String, ValueSource)],
+    ) -> IndexMap<&str, &ValueSource> {
+        variables
+            .iter()
+            .map(|(variable, value_source)| (variable.as_str(), value_source))
+            .collect()
+    }
+
+    #[test]
+    fn test_dependencies() {
+        let variables = [
+            ("HOST".to_owned(), literal("localhost")),
+            ("URL".to_owned(), literal("http://${HOST}/${PATH}")),
+            ("PATH".to_owned(), command("echo $PATH:$HOST:$HOME")),
+            ("FILE".to_owned(), file("${HOST}")),
+        ];
+        let dependencies = dependencies(&value_sources(&variables)).unwrap();
+        assert_eq!(
+            dependencies,
+            IndexMap::from([
+                ("HOST", IndexSet::new()),
+                ("URL", IndexSet::from(["HOST", "PATH"])),
+                ("PATH", IndexSet::from(["HOST"])),
+                ("FILE", IndexSet::new()),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_dependencies_use_profile_env() {
+        let variables = [
+            ("HOST".to_owned(), literal("localhost")),
+            ("TOKEN".to_owned(), command("get-token").use_profile_env()),
+            ("PORT".to_owned(), literal("3000")),
+            (
+                "URL".to_owned(),
+                native_command("build-url", &[]).use_profile_env(),
+            ),
+        ];
+        let dependencies = dependencies(&value_sources(&variables)).unwrap();
+        assert_eq!(
+            dependencies,
+            IndexMap::from([
+                ("HOST", IndexSet::new()),
+                ("TOKEN", IndexSet::from(["HOST"])),
+                ("PORT", IndexSet::new()),
+                ("URL", IndexSet::from(["HOST", "TOKEN", "PORT"])),
+            ])
+        );
+    }
+
+    /// References to names loaded by multi-variable sources depend on the
+    /// source that loads them. If the names can't be known ahead of time,
+    /// otherwise unknown names depend on the source
+    #[test]
+    fn test_dependencies_multiple() {
+        let variables = [
+            ("URL".to_owned(), literal("http://${API_HOST}:${DB_PORT}")),
+            (
+                "api".to_owned(),
+                literal("HOST=localhost")
+                    .multiple_filtered(&["HOST"])
+                    .prefix("API_"),
+            ),
+            ("TOKEN".to_owned(), command("get-token $API_HOST $HOME")),
+            ("db".to_owned(), dotenv("db.env", None, &[])),
+        ];
+        let dependencies = dependencies(&value_sources(&variables)).unwrap();
+        assert_eq!(
+            dependencies,
+            IndexMap::from([
+                ("URL", IndexSet::from(["api", "db"])),
+                ("api", IndexSet::new()),
+                ("TOKEN", IndexSet::from(["api"])),
+                ("db", IndexSet::new()),
+            ])
+        );
+    }
+
+    /// Commands that use the profile environment can't reference variables
+    /// declared after them
+    #[test]
+    fn test_dependencies_forward_reference() {
+        let variables = [
+            (
+                "TOKEN".to_owned(),
+                command("get-token $HOST").use_profile_env(),
+            ),
+            ("HOST".to_owned(), literal("localhost")),
+        ];
+        assert_eq!(
+            dependencies(&value_sources(&variables))
+                .unwrap_err()
+                .to_string(),
+            "TOKEN references `HOST`, which is declared after it. Commands \
+            with `use_profile_env` can only reference variables declared \
+            before them"
+        );
+    }
+
+    #[test]
+    fn test_dependencies_unknown() {
+        let variables = literals(&[("URL", "http://${HOST}")]);
+        assert_eq!(
+            dependencies(&value_sources(&variables))
+                .unwrap_err()
+                .to_string(),
+            "URL references unknown variable `HOST`"
+        );
+    }
+
+    #[rstest]
+    #[case::independent(
+        &[("A", "a"), ("B", "b")],
+        Ok(vec![vec!["A", "B"]]),
+    )]
+    #[case::chain(
+        &[("C", "${B}"), ("B", "${A}"), ("A", "a")],
+        Ok(vec![vec!["A"], vec!["B"], vec!["C"]]),
+    )]
+    #[case::self_cycle(
+        &[("A", "${A}")],
+        Err("Reference cycle between variables: A -> A"),
+    )]
+    #[case::cycle(
+        &[("A", "a"), ("B", "${A}${D}"), ("C", "${B}"), ("D", "${C}")],
+        Err("Reference cycle between variables: B -> D -> C -> B"),
+    )]
+    fn test_resolution_order(
+        #[case] variables: &[(&str, &str)],
+        #[case] expected: Result<Vec<Vec<&str>>, &str>,
+    ) {
+        let variables = literals(variables);
+        let dependencies = dependencies(&value_sources(&variables)).unwrap();
+        let result = resolution_order(&dependencies);
+        assert_result(result, expected);
+    }
+}
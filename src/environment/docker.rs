@@ -0,0 +1,130 @@
+//! Value resolution for the `docker` value source
+
+use crate::{environment::Environment, execute::IntoExecutable};
+use anyhow::{anyhow, Context};
+
+const DOCKER: &str = "docker";
+
+impl Environment {
+    /// Execute a command inside a running container, and return its output.
+    /// The container is given either directly, or as a Compose service.
+    pub(super) async fn resolve_docker(
+        command: &[String],
+        container: Option<&str>,
+        compose_service: Option<&str>,
+    ) -> anyhow::Result<String> {
+        let container = match (container, compose_service) {
+            (Some(container), None) => container.to_owned(),
+            (None, Some(service)) => find_compose_container(service).await?,
+            _ => {
+                return Err(anyhow!(
+                    "Exactly one of `container` and `compose_service` must be \
+                    given"
+                ))
+            }
+        };
+
+        let mut arguments: Vec<&str> = vec!["exec", &container];
+        arguments.extend(command.iter().map(String::as_str));
+        let mut executable = (DOCKER, arguments).executable();
+        let output = executable.output().await?;
+        if output.status.success() {
+            String::from_utf8(output.stdout).with_context(|| {
+                format!("Error decoding output for {executable}")
+            })
+        } else {
+            Err(exec_error(
+                &container,
+                &String::from_utf8_lossy(&output.stderr),
+            ))
+        }
+    }
+}
+
+/// Get the ID of the running container for a Compose service. If there isn't
+/// exactly one, return an error
+async fn find_compose_container(service: &str) -> anyhow::Result<String> {
+    let output = (DOCKER, ["compose", "ps", "--quiet", service])
+        .executable()
+        .check_output()
+        .await
+        .with_context(|| {
+            format!("Error finding container for compose service `{service}`")
+        })?;
+    parse_container(&output, service)
+}
+
+/// Parse `docker compose ps --quiet` output, and make sure it contains
+/// exactly one container
+fn parse_container(output: &str, service: &str) -> anyhow::Result<String> {
+    let containers: Vec<&str> = output
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect();
+    match containers.as_slice() {
+        [] => Err(anyhow!("Compose service `{service}` is not running")),
+        [container] => Ok((*container).to_owned()),
+        containers => Err(anyhow!(
+            "Multiple containers running for compose service `{service}`: {}",
+            containers.join(", ")
+        )),
+    }
+}
+
+/// Generate an error for a failed `docker exec`, based on its stderr
+fn exec_error(container: &str, stderr: &str) -> anyhow::Error {
+    if stderr.contains("is not running") {
+        anyhow!("Container `{container}` is not running")
+    } else if stderr.contains("No such container") {
+        anyhow!("Container `{container}` not found")
+    } else {
+        anyhow!(
+            "Error executing command in container `{container}`: {}",
+            stderr.trim()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::assert_result;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("abc123\n", Ok("abc123"))]
+    #[case("", Err("Compose service `api` is not running"))]
+    #[case(
+        "abc123\ndef456\n",
+        Err(
+            "Multiple containers running for compose service `api`: abc123, \
+            def456"
+        )
+    )]
+    fn test_parse_container(
+        #[case] output: &str,
+        #[case] expected: Result<&str, &str>,
+    ) {
+        let result = parse_container(output, "api");
+        assert_result(result, expected);
+    }
+
+    #[rstest]
+    #[case::not_running(
+        "Error response from daemon: container abc123 is not running\n",
+        "Container `api` is not running"
+    )]
+    #[case::not_found(
+        "Error response from daemon: No such container: api\n",
+        "Container `api` not found"
+    )]
+    #[case::other(
+        "OCI runtime exec failed: executable file not found\n",
+        "Error executing command in container `api`: OCI runtime exec \
+        failed: executable file not found"
+    )]
+    fn test_exec_error(#[case] stderr: &str, #[case] expected: &str) {
+        assert_eq!(exec_error("api", stderr).to_string(), expected);
+    }
+}
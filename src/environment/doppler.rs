@@ -0,0 +1,119 @@
+//! Value resolution for the `doppler` value source
+
+use crate::{environment::Environment, execute::IntoExecutable};
+use anyhow::{anyhow, Context};
+
+impl Environment {
+    /// Read secrets from Doppler. In single-value mode, return the value of
+    /// one secret. In multi-value mode, download the entire config as a
+    /// dotenv mapping.
+    pub(super) async fn resolve_doppler(
+        secret: Option<&str>,
+        project: Option<&str>,
+        config: Option<&str>,
+        multiple: bool,
+    ) -> anyhow::Result<String> {
+        let arguments = doppler_arguments(secret, project, config, multiple)?;
+        let mut executable = ("doppler", arguments).executable();
+        let output = executable.output().await?;
+        if output.status.success() {
+            String::from_utf8(output.stdout).with_context(|| {
+                format!("Error decoding output for {executable}")
+            })
+        } else {
+            Err(anyhow!(
+                "Error reading {} from Doppler: {}",
+                secret.map_or("secrets".into(), |secret| format!("`{secret}`")),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ))
+        }
+    }
+}
+
+/// Build the arguments for a `doppler` invocation
+fn doppler_arguments<'a>(
+    secret: Option<&'a str>,
+    project: Option<&'a str>,
+    config: Option<&'a str>,
+    multiple: bool,
+) -> anyhow::Result<Vec<&'a str>> {
+    let mut arguments = if multiple {
+        vec!["secrets", "download", "--no-file", "--format", "env"]
+    } else {
+        let secret = secret.ok_or_else(|| {
+            anyhow!("Field `secret` is required unless `multiple` is enabled")
+        })?;
+        vec!["secrets", "get", secret, "--plain"]
+    };
+    if let Some(project) = project {
+        arguments.extend(["--project", project]);
+    }
+    if let Some(config) = config {
+        arguments.extend(["--config", config]);
+    }
+    Ok(arguments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::assert_result;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::single(
+        Some("DB_PASSWORD"),
+        None,
+        None,
+        false,
+        Ok(&["secrets", "get", "DB_PASSWORD", "--plain"][..])
+    )]
+    #[case::project_config(
+        Some("DB_PASSWORD"),
+        Some("backend"),
+        Some("dev"),
+        false,
+        Ok(&[
+            "secrets",
+            "get",
+            "DB_PASSWORD",
+            "--plain",
+            "--project",
+            "backend",
+            "--config",
+            "dev",
+        ][..])
+    )]
+    #[case::multiple(
+        None,
+        Some("backend"),
+        None,
+        true,
+        Ok(&[
+            "secrets",
+            "download",
+            "--no-file",
+            "--format",
+            "env",
+            "--project",
+            "backend",
+        ][..])
+    )]
+    #[case::missing_secret(
+        None,
+        None,
+        None,
+        false,
+        Err("Field `secret` is required unless `multiple` is enabled")
+    )]
+    fn test_doppler_arguments(
+        #[case] secret: Option<&str>,
+        #[case] project: Option<&str>,
+        #[case] config: Option<&str>,
+        #[case] multiple: bool,
+        #[case] expected: Result<&[&str], &str>,
+    ) {
+        let result = doppler_arguments(secret, project, config, multiple);
+        assert_result(result, expected);
+    }
+}
@@ -0,0 +1,68 @@
+//! Value resolution for the `file-glob` value source
+
+use crate::{environment::Environment, glob_files::glob_files};
+use anyhow::{anyhow, Context};
+use log::debug;
+use smol::fs;
+use std::path::Path;
+
+impl Environment {
+    /// Load every file matching a glob pattern, and concatenate their
+    /// contents in lexical order of path
+    pub(super) async fn resolve_file_glob(
+        pattern: &Path,
+        allow_empty: bool,
+    ) -> anyhow::Result<String> {
+        let paths = glob_files(pattern)?;
+        debug!("Files matching {pattern:?}: {paths:?}");
+        if paths.is_empty() && !allow_empty {
+            return Err(anyhow!("No files match {pattern:?}"));
+        }
+
+        let mut output = String::new();
+        for path in paths {
+            let content = fs::read_to_string(&path)
+                .await
+                .with_context(|| format!("Error loading file {path:?}"))?;
+            output.push_str(&content);
+            // Make sure the next file starts on its own line
+            if !output.is_empty() && !output.ends_with('\n') {
+                output.push('\n');
+            }
+        }
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs};
+
+    #[test]
+    fn test_resolve_file_glob() {
+        let directory = env::temp_dir().join("es-test-file-glob");
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(directory.join("b.env")).unwrap();
+        fs::write(directory.join("c.env"), "VARIABLE3=c\n").unwrap();
+        fs::write(directory.join("a.env"), "VARIABLE1=a").unwrap();
+        fs::write(directory.join("a.txt"), "VARIABLE2=a").unwrap();
+
+        let resolve = |pattern: &str, allow_empty| {
+            smol::block_on(Environment::resolve_file_glob(
+                &directory.join(pattern),
+                allow_empty,
+            ))
+        };
+        // Directories are skipped, and files are joined in order
+        assert_eq!(
+            resolve("*.env", false).unwrap(),
+            "VARIABLE1=a\nVARIABLE3=c\n"
+        );
+        assert_eq!(resolve("*.json", true).unwrap(), "");
+        assert_eq!(
+            resolve("*.json", false).unwrap_err().to_string(),
+            format!("No files match {:?}", directory.join("*.json"))
+        );
+    }
+}
@@ -0,0 +1,85 @@
+//! Value resolution for the `gcp-secret` value source
+
+use crate::{environment::Environment, execute::IntoExecutable};
+use anyhow::{anyhow, Context};
+
+const LATEST_VERSION: &str = "latest";
+
+impl Environment {
+    /// Read a secret version from GCP Secret Manager. The payload is returned
+    /// as-is, so in multi-value mode it should be a dotenv mapping.
+    pub(super) async fn resolve_gcp_secret(
+        secret: &str,
+        version: Option<&str>,
+        project: Option<&str>,
+    ) -> anyhow::Result<String> {
+        let secret_argument = format!("--secret={secret}");
+        let mut arguments = vec![
+            "secrets",
+            "versions",
+            "access",
+            version.unwrap_or(LATEST_VERSION),
+            &secret_argument,
+        ];
+        let project_argument;
+        if let Some(project) = project {
+            project_argument = format!("--project={project}");
+            arguments.push(&project_argument);
+        }
+
+        let mut executable = ("gcloud", arguments).executable();
+        let output = executable.output().await?;
+        if output.status.success() {
+            String::from_utf8(output.stdout).with_context(|| {
+                format!("Error decoding output for {executable}")
+            })
+        } else {
+            Err(gcloud_error(
+                secret,
+                &String::from_utf8_lossy(&output.stderr),
+            ))
+        }
+    }
+}
+
+/// Generate an error for a failed `gcloud` command, based on its stderr
+fn gcloud_error(secret: &str, stderr: &str) -> anyhow::Error {
+    if stderr.contains("gcloud auth login")
+        || stderr.contains("do not currently have an active account")
+    {
+        anyhow!(
+            "Not authenticated with gcloud; run `gcloud auth login` and try \
+            again"
+        )
+    } else if stderr.contains("NOT_FOUND") {
+        anyhow!("GCP secret `{secret}` not found")
+    } else {
+        anyhow!("Error reading GCP secret `{secret}`: {}", stderr.trim())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::not_authenticated(
+        "ERROR: (gcloud.secrets.versions.access) You do not currently have an \
+        active account selected.\nPlease run:\n\n  $ gcloud auth login\n",
+        "Not authenticated with gcloud; run `gcloud auth login` and try again"
+    )]
+    #[case::not_found(
+        "ERROR: (gcloud.secrets.versions.access) NOT_FOUND: Secret \
+        [projects/123/secrets/db-password] not found or has no versions.\n",
+        "GCP secret `db-password` not found"
+    )]
+    #[case::other(
+        "ERROR: (gcloud.secrets.versions.access) PERMISSION_DENIED\n",
+        "Error reading GCP secret `db-password`: ERROR: \
+        (gcloud.secrets.versions.access) PERMISSION_DENIED"
+    )]
+    fn test_gcloud_error(#[case] stderr: &str, #[case] expected: &str) {
+        assert_eq!(gcloud_error("db-password", stderr).to_string(), expected);
+    }
+}
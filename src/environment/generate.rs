@@ -0,0 +1,145 @@
+//! Value resolution for the `generate` value source
+
+use crate::{
+    config::{Generator, TimestampFormat},
+    environment::Environment,
+};
+use anyhow::Context;
+use std::{
+    fmt::Write,
+    time::{Duration, SystemTime},
+};
+
+const SECONDS_PER_DAY: u64 = 86_400;
+
+impl Environment {
+    /// Generate a new value. This is done entirely in-process
+    pub(super) fn resolve_generate(
+        generator: &Generator,
+    ) -> anyhow::Result<String> {
+        match generator {
+            Generator::Uuid4 => Ok(uuid4(fastrand::u128(..))),
+            Generator::RandomHex { length } => Ok(random_hex(*length)),
+            Generator::Timestamp { format } => {
+                let now = SystemTime::now()
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .context("System clock is set before the Unix epoch")?;
+                Ok(format_timestamp(now, *format))
+            }
+        }
+    }
+}
+
+/// Format random bits as a version 4 UUID
+fn uuid4(bits: u128) -> String {
+    // Set the version (4) and variant (RFC 4122) bits
+    let bits = (bits & !(0xf << 76)) | (0x4 << 76);
+    let bits = (bits & !(0x3 << 62)) | (0x2 << 62);
+    let hex = format!("{bits:032x}");
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+/// Generate a random hex string of the given length
+fn random_hex(length: usize) -> String {
+    (0..length).fold(String::with_capacity(length), |mut output, _| {
+        write!(output, "{:x}", fastrand::u8(..16)).unwrap();
+        output
+    })
+}
+
+/// Format a duration since the Unix epoch
+fn format_timestamp(since_epoch: Duration, format: TimestampFormat) -> String {
+    match format {
+        TimestampFormat::Unix => since_epoch.as_secs().to_string(),
+        TimestampFormat::UnixMillis => since_epoch.as_millis().to_string(),
+        TimestampFormat::Rfc3339 => {
+            let seconds = since_epoch.as_secs();
+            let (year, month, day) = civil_from_days(seconds / SECONDS_PER_DAY);
+            let time = seconds % SECONDS_PER_DAY;
+            format!(
+                "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+                time / 3600,
+                time % 3600 / 60,
+                time % 60
+            )
+        }
+    }
+}
+
+/// Convert days since the Unix epoch to a (year, month, day) date. See
+/// http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524
+        - day_of_era / 146_096)
+        / 365;
+    let day_of_year =
+        day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(0, "00000000-0000-4000-8000-000000000000")]
+    #[case(u128::MAX, "ffffffff-ffff-4fff-bfff-ffffffffffff")]
+    fn test_uuid4(#[case] bits: u128, #[case] expected: &str) {
+        assert_eq!(uuid4(bits), expected);
+    }
+
+    #[test]
+    fn test_random_hex() {
+        let value = random_hex(16);
+        assert_eq!(value.len(), 16);
+        assert!(value.chars().all(|c| c.is_ascii_hexdigit()));
+    }
+
+    #[rstest]
+    #[case::unix(1_700_000_000_123, TimestampFormat::Unix, "1700000000")]
+    #[case::unix_millis(
+        1_700_000_000_123,
+        TimestampFormat::UnixMillis,
+        "1700000000123"
+    )]
+    #[case::epoch(0, TimestampFormat::Rfc3339, "1970-01-01T00:00:00Z")]
+    #[case::rfc3339(
+        1_700_000_000_123,
+        TimestampFormat::Rfc3339,
+        "2023-11-14T22:13:20Z"
+    )]
+    #[case::leap_day(
+        1_709_164_800_000,
+        TimestampFormat::Rfc3339,
+        "2024-02-29T00:00:00Z"
+    )]
+    fn test_format_timestamp(
+        #[case] millis: u64,
+        #[case] format: TimestampFormat,
+        #[case] expected: &str,
+    ) {
+        assert_eq!(
+            format_timestamp(Duration::from_millis(millis), format),
+            expected
+        );
+    }
+}
@@ -0,0 +1,46 @@
+//! Value resolution for the `git-config` value source
+
+use crate::{environment::Environment, execute::IntoExecutable};
+use anyhow::{anyhow, Context};
+use std::{env, path::Path};
+
+/// Exit code from `git config --get` when the key isn't set
+const GIT_CONFIG_MISSING_CODE: i32 = 1;
+
+impl Environment {
+    /// Read a value from git config
+    pub(super) async fn resolve_git_config(
+        key: &str,
+        cwd: Option<&Path>,
+    ) -> anyhow::Result<String> {
+        let mut executable = ("git", ["config", "--get", key]).executable();
+        if let Some(cwd) = cwd {
+            executable.current_dir(cwd);
+        }
+        let output = executable.output().await?;
+
+        if output.status.success() {
+            Ok(String::from_utf8(output.stdout)
+                .with_context(|| {
+                    format!("Error decoding output for {executable}")
+                })?
+                .trim_end()
+                .to_owned())
+        } else {
+            // Error messages should include the directory, because that
+            // determines which repo's config we read
+            let cwd = match cwd {
+                Some(cwd) => cwd.to_owned(),
+                None => env::current_dir()?,
+            };
+            if output.status.code() == Some(GIT_CONFIG_MISSING_CODE) {
+                Err(anyhow!("Git config key `{key}` is not set in {cwd:?}"))
+            } else {
+                Err(anyhow!(
+                    "Error reading git config key `{key}` in {cwd:?}: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ))
+            }
+        }
+    }
+}
@@ -0,0 +1,139 @@
+//! Value resolution for the `http` value source
+
+use crate::{
+    environment::{template::TemplateContext, Environment},
+    execute::IntoExecutable,
+};
+use anyhow::{anyhow, Context};
+use std::collections::BTreeMap;
+
+/// Maximum time for an entire request, in seconds
+const TIMEOUT_SECONDS: u32 = 30;
+/// curl exit code for a timed out request
+const CURL_TIMEOUT_CODE: i32 = 28;
+
+impl Environment {
+    /// Make a GET request to the given URL and return the response body.
+    /// Redirects are followed. If the final response isn't 2xx, return an
+    /// error. Header values can reference the parent environment.
+    pub(super) async fn resolve_http(
+        url: &str,
+        headers: &BTreeMap<String, String>,
+        context: &TemplateContext,
+    ) -> anyhow::Result<String> {
+        let timeout = TIMEOUT_SECONDS.to_string();
+        let mut arguments: Vec<String> = [
+            "--silent",
+            "--show-error",
+            "--location",
+            "--max-time",
+            &timeout,
+            // Append the status code to the body, so we can check it
+            "--write-out",
+            "\n%{http_code}",
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect();
+        // Headers often carry credentials, so pass them on stdin instead of
+        // as arguments, where they'd be visible in `ps` and our logs
+        let mut header_lines = String::new();
+        for (header, value) in headers {
+            let value = context.render_env(value).with_context(|| {
+                format!("Error in header `{header}` for `{url}`")
+            })?;
+            if value.contains(['\n', '\r']) {
+                return Err(anyhow!(
+                    "Header `{header}` for `{url}` can't contain a newline"
+                ));
+            }
+            header_lines += &format!("{header}: {value}\n");
+        }
+        if !header_lines.is_empty() {
+            arguments.extend(["--header".into(), "@-".into()]);
+        }
+        arguments.push(url.to_owned());
+
+        let output = ("curl", arguments)
+            .executable()
+            .stdin(header_lines)
+            .output()
+            .await?;
+        if output.status.success() {
+            let output =
+                String::from_utf8(output.stdout).with_context(|| {
+                    format!("Error decoding response body from `{url}`")
+                })?;
+            parse_response(&output, url)
+        } else if output.status.code() == Some(CURL_TIMEOUT_CODE) {
+            Err(anyhow!(
+                "Request to `{url}` timed out after {TIMEOUT_SECONDS}s"
+            ))
+        } else {
+            Err(anyhow!(
+                "Error fetching `{url}`: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ))
+        }
+    }
+}
+
+/// Split curl output into body and status code, and check the status
+fn parse_response(output: &str, url: &str) -> anyhow::Result<String> {
+    let (body, status) = output
+        .rsplit_once('\n')
+        .ok_or_else(|| anyhow!("Missing status code in response"))?;
+    let status: u16 = status
+        .parse()
+        .with_context(|| format!("Invalid status code `{status}`"))?;
+    if (200..300).contains(&status) {
+        Ok(body.trim_end().to_owned())
+    } else {
+        Err(anyhow!("Request to `{url}` failed with status {status}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::assert_result;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::ok("token\n\n200", Ok("token"))]
+    #[case::empty("\n204", Ok(""))]
+    #[case::multi_line("A=1\nB=2\n200", Ok("A=1\nB=2"))]
+    #[case::not_found(
+        "not found\n404",
+        Err("Request to `http://localhost` failed with status 404")
+    )]
+    fn test_parse_response(
+        #[case] output: &str,
+        #[case] expected: Result<&str, &str>,
+    ) {
+        let result = parse_response(output, "http://localhost");
+        assert_result(result, expected);
+    }
+
+    /// Headers are passed to curl on stdin, one per line, so a newline in a
+    /// value could inject another header
+    #[test]
+    fn test_resolve_http_header_newline() {
+        let context = TemplateContext {
+            references: Environment::default(),
+            strict: true,
+        };
+        let headers =
+            BTreeMap::from([("X-Token".into(), "abc\nX-Other: 1".into())]);
+        assert_eq!(
+            smol::block_on(Environment::resolve_http(
+                "http://localhost",
+                &headers,
+                &context
+            ))
+            .unwrap_err()
+            .to_string(),
+            "Header `X-Token` for `http://localhost` can't contain a newline"
+        );
+    }
+}
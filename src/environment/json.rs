@@ -0,0 +1,102 @@
+//! Value resolution for the `json-file` value source
+
+use crate::environment::{format_dotenv_json, json_to_string, Environment};
+use anyhow::{anyhow, Context};
+use serde_json::Value;
+use smol::fs;
+use std::path::Path;
+
+impl Environment {
+    /// Load a JSON file and extract the value at the given pointer. In
+    /// multi-value mode, the value must be an object, and its fields are
+    /// returned as a dotenv mapping.
+    pub(super) async fn resolve_json_file(
+        path: &Path,
+        pointer: &str,
+        multiple: bool,
+    ) -> anyhow::Result<String> {
+        let content = fs::read_to_string(path)
+            .await
+            .with_context(|| format!("Error loading file {path:?}"))?;
+        let document: Value = serde_json::from_str(&content)
+            .with_context(|| format!("Error parsing JSON file {path:?}"))?;
+        extract_pointer(&document, pointer, multiple)
+            .with_context(|| format!("Error loading from JSON file {path:?}"))
+    }
+}
+
+/// Get the value at a JSON pointer, and convert it to a string
+pub(super) fn extract_pointer(
+    document: &Value,
+    pointer: &str,
+    multiple: bool,
+) -> anyhow::Result<String> {
+    let value = document.pointer(pointer).ok_or_else(|| {
+        let keys = match document {
+            Value::Object(object) => object
+                .keys()
+                .map(String::as_str)
+                .collect::<Vec<_>>()
+                .join(", "),
+            _ => String::new(),
+        };
+        anyhow!(
+            "Pointer `{pointer}` not found; available top-level keys are: \
+            {keys}"
+        )
+    })?;
+
+    if multiple {
+        let object = value.as_object().ok_or_else(|| {
+            anyhow!(
+                "Value at pointer `{pointer}` must be an object to load \
+                multiple values"
+            )
+        })?;
+        format_dotenv_json(object)
+    } else {
+        Ok(json_to_string(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::assert_result;
+    use rstest::rstest;
+    use serde_json::json;
+
+    #[rstest]
+    #[case::string("/database/user", false, Ok("root"))]
+    #[case::number("/database/port", false, Ok("5432"))]
+    #[case::object("/database", false, Ok(r#"{"port":5432,"user":"root"}"#))]
+    #[case::multiple("/database", true, Ok("port='5432'\nuser='root'\n"))]
+    #[case::multiple_scalar(
+        "/database/user",
+        true,
+        Err(
+            "Value at pointer `/database/user` must be an object to load \
+            multiple values"
+        )
+    )]
+    #[case::missing(
+        "/databse/user",
+        false,
+        Err(
+            "Pointer `/databse/user` not found; available top-level keys \
+            are: database, debug"
+        )
+    )]
+    fn test_extract_pointer(
+        #[case] pointer: &str,
+        #[case] multiple: bool,
+        #[case] expected: Result<&str, &str>,
+    ) {
+        let document = json!({
+            "database": {"user": "root", "port": 5432},
+            "debug": true,
+        });
+        let result = extract_pointer(&document, pointer, multiple);
+        assert_result(result, expected);
+    }
+}
@@ -0,0 +1,79 @@
+//! Value resolution for the `keychain` value source
+
+use crate::{environment::Environment, execute::IntoExecutable};
+use anyhow::{anyhow, Context};
+
+impl Environment {
+    /// Read a generic password from the macOS Keychain. On other platforms,
+    /// this always fails.
+    pub(super) async fn resolve_keychain(
+        service: &str,
+        account: Option<&str>,
+    ) -> anyhow::Result<String> {
+        if !cfg!(target_os = "macos") {
+            return Err(anyhow!(
+                "Error reading keychain item for service `{service}`: the \
+                `keychain` value source is only supported on macOS"
+            ));
+        }
+
+        let mut arguments = vec!["find-generic-password", "-s", service];
+        if let Some(account) = account {
+            arguments.extend(["-a", account]);
+        }
+        // Print only the password
+        arguments.push("-w");
+
+        let mut executable = ("security", arguments).executable();
+        let output = executable.output().await?;
+        if output.status.success() {
+            Ok(String::from_utf8(output.stdout)
+                .with_context(|| {
+                    format!("Error decoding output for {executable}")
+                })?
+                .trim_end_matches('\n')
+                .to_owned())
+        } else {
+            Err(keychain_error(
+                service,
+                &String::from_utf8_lossy(&output.stderr),
+            ))
+        }
+    }
+}
+
+/// Generate an error for a failed `security` command, based on its stderr
+fn keychain_error(service: &str, stderr: &str) -> anyhow::Error {
+    if stderr.contains("could not be found") {
+        anyhow!("No keychain item found for service `{service}`")
+    } else {
+        anyhow!(
+            "Error reading keychain item for service `{service}`: {}",
+            stderr.trim()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keychain_error() {
+        assert_eq!(
+            keychain_error(
+                "github",
+                "security: SecKeychainSearchCopyNext: The specified item \
+                could not be found in the keychain.\n"
+            )
+            .to_string(),
+            "No keychain item found for service `github`"
+        );
+        assert_eq!(
+            keychain_error("github", "User interaction is not allowed.\n")
+                .to_string(),
+            "Error reading keychain item for service `github`: User \
+            interaction is not allowed."
+        );
+    }
+}
@@ -0,0 +1,104 @@
+//! Value resolution for the `kubernetes` value source
+
+use crate::{environment::Environment, execute::IntoExecutable};
+use anyhow::{anyhow, Context};
+
+const KUBECTL: &str = "kubectl";
+
+impl Environment {
+    /// Execute a command inside the one pod that matches the given label
+    /// selector, and return its output
+    pub(super) async fn resolve_kubernetes(
+        command: &[String],
+        pod_selector: &str,
+        namespace: Option<&str>,
+        container: Option<&str>,
+    ) -> anyhow::Result<String> {
+        let pod = find_pod(pod_selector, namespace).await?;
+
+        let mut arguments: Vec<&str> = vec!["exec"];
+        if let Some(namespace) = namespace {
+            arguments.extend(["--namespace", namespace]);
+        }
+        arguments.push(&pod);
+        if let Some(container) = container {
+            arguments.extend(["--container", container]);
+        }
+        arguments.push("--");
+        arguments.extend(command.iter().map(String::as_str));
+
+        (KUBECTL, arguments)
+            .executable()
+            .check_output()
+            .await
+            .with_context(|| {
+                format!(
+                    "Error executing command in pod `{pod}` \
+                    (selector `{pod_selector}`)"
+                )
+            })
+    }
+}
+
+/// Get the name of the pod matching a label selector. If there isn't exactly
+/// one match, return an error
+async fn find_pod(
+    pod_selector: &str,
+    namespace: Option<&str>,
+) -> anyhow::Result<String> {
+    let mut arguments = vec!["get", "pods", "--selector", pod_selector];
+    if let Some(namespace) = namespace {
+        arguments.extend(["--namespace", namespace]);
+    }
+    arguments.extend(["--output", "name"]);
+
+    let output = (KUBECTL, arguments)
+        .executable()
+        .check_output()
+        .await
+        .with_context(|| {
+            format!("Error listing pods for selector `{pod_selector}`")
+        })?;
+    parse_pod(&output, pod_selector)
+}
+
+/// Parse `kubectl get pods --output name` output, and make sure it contains
+/// exactly one pod
+fn parse_pod(output: &str, pod_selector: &str) -> anyhow::Result<String> {
+    let pods: Vec<&str> = output
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect();
+    match pods.as_slice() {
+        [] => Err(anyhow!("No pods matched selector `{pod_selector}`")),
+        [pod] => Ok((*pod).to_owned()),
+        pods => Err(anyhow!(
+            "Multiple pods matched selector `{pod_selector}`: {}",
+            pods.join(", ")
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::assert_result;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("pod/api-123\n", Ok("pod/api-123"))]
+    #[case("\n  pod/api-123  \n\n", Ok("pod/api-123"))]
+    #[case("", Err("No pods matched selector `app=api`"))]
+    #[case(
+        "pod/api-123\npod/api-456",
+        Err("Multiple pods matched selector `app=api`: pod/api-123, pod/api-456")
+    )]
+    fn test_parse_pod(
+        #[case] output: &str,
+        #[case] expected: Result<&str, &str>,
+    ) {
+        let result = parse_pod(output, "app=api");
+        assert_result(result, expected);
+    }
+}
@@ -0,0 +1,161 @@
+//! Parsing of multi-variable mappings, for value sources with `multiple` set
+
+use crate::{
+    config::MultiVariableFormat,
+    environment::{json_to_string, yaml::scalar_to_string},
+};
+use anyhow::{anyhow, Context};
+
+/// Parse a multi-variable mapping into (variable, value) pairs
+pub fn parse(
+    format: MultiVariableFormat,
+    mapping: &str,
+) -> anyhow::Result<Vec<(String, String)>> {
+    match format {
+        MultiVariableFormat::Dotenv => Ok(dotenv_parser::parse_dotenv(mapping)
+            .map_err(|error| anyhow!(error))?
+            .into_iter()
+            .collect()),
+        MultiVariableFormat::Json => parse_json(mapping),
+        MultiVariableFormat::Yaml => parse_yaml(mapping),
+        MultiVariableFormat::KeyValue => parse_key_value(mapping),
+    }
+}
+
+/// Parse a flat JSON object. Non-string values are stringified, but nested
+/// objects are an error
+fn parse_json(mapping: &str) -> anyhow::Result<Vec<(String, String)>> {
+    let object: serde_json::Map<String, serde_json::Value> =
+        serde_json::from_str(mapping).context("Invalid JSON")?;
+    object
+        .into_iter()
+        .map(|(variable, value)| {
+            if value.is_object() {
+                Err(anyhow!(
+                    "Value for `{variable}` is a nested object; only strings, \
+                    numbers, booleans, null, and arrays are supported"
+                ))
+            } else {
+                Ok((variable, json_to_string(&value)))
+            }
+        })
+        .collect()
+}
+
+/// Parse a flat YAML mapping. Scalar values are stringified, but nested
+/// mappings and sequences are an error
+fn parse_yaml(mapping: &str) -> anyhow::Result<Vec<(String, String)>> {
+    let mapping: serde_yaml::Mapping =
+        serde_yaml::from_str(mapping).context("Invalid YAML")?;
+    mapping
+        .iter()
+        .map(|(variable, value)| {
+            let variable = scalar_to_string(variable)
+                .context("Invalid variable name in mapping")?;
+            let value = scalar_to_string(value)
+                .with_context(|| format!("Invalid value for `{variable}`"))?;
+            Ok((variable, value))
+        })
+        .collect()
+}
+
+/// Parse `VARIABLE=value` lines. Blank lines are skipped. Values are taken
+/// as-is, with no quote handling.
+fn parse_key_value(mapping: &str) -> anyhow::Result<Vec<(String, String)>> {
+    mapping
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            let (variable, value) = line.split_once('=').ok_or_else(|| {
+                anyhow!("Line {} is not in the format `VARIABLE=value`", i + 1)
+            })?;
+            Ok((variable.trim().to_owned(), value.to_owned()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::assert_result;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::dotenv(
+        MultiVariableFormat::Dotenv,
+        "VARIABLE1=a\nVARIABLE2='b c'\n",
+        Ok(vec![("VARIABLE1", "a"), ("VARIABLE2", "b c")]),
+    )]
+    #[case::json(
+        MultiVariableFormat::Json,
+        r#"{"VARIABLE1": "a", "VARIABLE2": 3, "VARIABLE3": [true, null]}"#,
+        Ok(vec![
+            ("VARIABLE1", "a"),
+            ("VARIABLE2", "3"),
+            ("VARIABLE3", "[true,null]"),
+        ]),
+    )]
+    #[case::json_nested(
+        MultiVariableFormat::Json,
+        r#"{"VARIABLE1": "a", "VARIABLE2": {"b": "c"}}"#,
+        Err(
+            "Value for `VARIABLE2` is a nested object; only strings, numbers, \
+            booleans, null, and arrays are supported"
+        )
+    )]
+    #[case::json_not_object(
+        MultiVariableFormat::Json,
+        r#"["a"]"#,
+        Err(
+            "Invalid JSON: invalid type: sequence, expected a map at line 1 \
+            column 0"
+        )
+    )]
+    #[case::yaml(
+        MultiVariableFormat::Yaml,
+        "VARIABLE1: a\nVARIABLE2: 3\nVARIABLE3: true\nVARIABLE4:\n",
+        Ok(vec![
+            ("VARIABLE1", "a"),
+            ("VARIABLE2", "3"),
+            ("VARIABLE3", "true"),
+            ("VARIABLE4", ""),
+        ]),
+    )]
+    #[case::yaml_nested(
+        MultiVariableFormat::Yaml,
+        "VARIABLE1: a\nVARIABLE2:\n  b: c\n",
+        Err(
+            "Invalid value for `VARIABLE2`: Expected a scalar value, but \
+            found a mapping"
+        )
+    )]
+    #[case::yaml_not_mapping(
+        MultiVariableFormat::Yaml,
+        "- a\n",
+        Err("Invalid YAML: invalid type: sequence, expected a YAML mapping")
+    )]
+    #[case::key_value(
+        MultiVariableFormat::KeyValue,
+        "VARIABLE1=a=b\n\nVARIABLE2='c'\n",
+        Ok(vec![("VARIABLE1", "a=b"), ("VARIABLE2", "'c'")]),
+    )]
+    #[case::key_value_invalid(
+        MultiVariableFormat::KeyValue,
+        "VARIABLE1=a\nVARIABLE2",
+        Err("Line 2 is not in the format `VARIABLE=value`")
+    )]
+    fn test_parse(
+        #[case] format: MultiVariableFormat,
+        #[case] mapping: &str,
+        #[case] expected: Result<Vec<(&str, &str)>, &str>,
+    ) {
+        let expected = expected.map(|expected| {
+            expected
+                .into_iter()
+                .map(|(variable, value)| (variable.into(), value.into()))
+                .collect::<Vec<(String, String)>>()
+        });
+        assert_result(parse(format, mapping), expected);
+    }
+}
@@ -0,0 +1,172 @@
+//! Value resolution for the `1password` value source
+
+use crate::{
+    environment::{format_dotenv, Environment},
+    execute::IntoExecutable,
+};
+use anyhow::{anyhow, Context};
+use serde::Deserialize;
+use std::io;
+
+const OP: &str = "op";
+const REFERENCE_PREFIX: &str = "op://";
+
+impl Environment {
+    /// Read a secret from 1Password. In single-value mode, the reference
+    /// should point to a field and this returns that field's value. In
+    /// multi-value mode, the reference should point to an item, and all
+    /// fields of the item are returned as a dotenv mapping, keyed by label.
+    pub(super) async fn resolve_one_password(
+        reference: &str,
+        multiple: bool,
+    ) -> anyhow::Result<String> {
+        if multiple {
+            let (vault, item) = parse_item_reference(reference)?;
+            let output = execute_op(
+                &["item", "get", item, "--vault", vault, "--format", "json"],
+                reference,
+            )
+            .await?;
+            parse_item(&output)
+                .with_context(|| format!("Error reading item `{reference}`"))
+        } else {
+            execute_op(&["read", "--no-newline", reference], reference).await
+        }
+    }
+}
+
+/// Execute an `op` command and return its stdout. Errors for common failure
+/// cases (CLI missing, not signed in) are made explicit.
+async fn execute_op(
+    arguments: &[&str],
+    reference: &str,
+) -> anyhow::Result<String> {
+    let mut executable = (OP, arguments.iter().copied()).executable();
+    let output = match executable.output().await {
+        Ok(output) => output,
+        Err(error)
+            if error.downcast_ref::<io::Error>().is_some_and(|error| {
+                error.kind() == io::ErrorKind::NotFound
+            }) =>
+        {
+            return Err(anyhow!(
+                "1Password CLI `{OP}` not found on PATH. Install it from \
+                https://developer.1password.com/docs/cli/get-started"
+            ))
+        }
+        Err(error) => return Err(error),
+    };
+
+    if output.status.success() {
+        String::from_utf8(output.stdout)
+            .with_context(|| format!("Error decoding output for {executable}"))
+    } else {
+        Err(op_error(
+            reference,
+            &String::from_utf8_lossy(&output.stderr),
+        ))
+    }
+}
+
+/// Generate an error for a failed `op` command, based on its stderr
+fn op_error(reference: &str, stderr: &str) -> anyhow::Error {
+    if stderr.contains("not currently signed in")
+        || stderr.contains("account is not signed in")
+    {
+        anyhow!(
+            "Not signed in to 1Password; run `eval $(op signin)` and try again"
+        )
+    } else {
+        anyhow!(
+            "Error reading `{reference}` from 1Password: {}",
+            stderr.trim()
+        )
+    }
+}
+
+/// Split an `op://vault/item` reference into its vault and item
+fn parse_item_reference(reference: &str) -> anyhow::Result<(&str, &str)> {
+    reference
+        .strip_prefix(REFERENCE_PREFIX)
+        .and_then(|path| path.split_once('/'))
+        .filter(|(vault, item)| !vault.is_empty() && !item.is_empty())
+        .ok_or_else(|| {
+            anyhow!(
+                "Invalid 1Password item reference `{reference}`; expected \
+                `{REFERENCE_PREFIX}<vault>/<item>`"
+            )
+        })
+}
+
+/// Parse JSON output from `op item get` into a dotenv mapping of all fields
+fn parse_item(output: &str) -> anyhow::Result<String> {
+    #[derive(Deserialize)]
+    struct Item {
+        fields: Vec<Field>,
+    }
+
+    #[derive(Deserialize)]
+    struct Field {
+        label: String,
+        value: Option<String>,
+    }
+
+    let item: Item = serde_json::from_str(output)
+        .context("Error parsing output from 1Password")?;
+    // Skip fields without a value, such as empty notes
+    format_dotenv(item.fields.iter().filter_map(|field| {
+        Some((field.label.as_str(), field.value.as_deref()?))
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("op://dev/db", Ok(("dev", "db")))]
+    #[case("op://dev", Err(()))]
+    #[case("op:///db", Err(()))]
+    #[case("dev/db", Err(()))]
+    fn test_parse_item_reference(
+        #[case] reference: &str,
+        #[case] expected: Result<(&str, &str), ()>,
+    ) {
+        assert_eq!(parse_item_reference(reference).map_err(|_| ()), expected);
+    }
+
+    #[test]
+    fn test_op_error() {
+        assert_eq!(
+            op_error(
+                "op://dev/db/password",
+                "[ERROR] 2024/01/01 00:00:00 You are not currently signed in."
+            )
+            .to_string(),
+            "Not signed in to 1Password; run `eval $(op signin)` and try again"
+        );
+        assert_eq!(
+            op_error("op://dev/db/password", "[ERROR] item not found\n")
+                .to_string(),
+            "Error reading `op://dev/db/password` from 1Password: \
+            [ERROR] item not found"
+        );
+    }
+
+    #[test]
+    fn test_parse_item() {
+        let output = r#"{
+            "id": "abc",
+            "fields": [
+                {"id": "username", "label": "DB_USER", "value": "root"},
+                {"id": "password", "label": "DB_PASSWORD", "value": "hunter2"},
+                {"id": "notesPlain", "label": "notesPlain"}
+            ]
+        }"#;
+        assert_eq!(
+            parse_item(output).unwrap(),
+            "DB_USER='root'\nDB_PASSWORD='hunter2'\n"
+        );
+    }
+}
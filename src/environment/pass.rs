@@ -0,0 +1,104 @@
+//! Value resolution for the `pass` value source
+
+use crate::{
+    environment::{format_dotenv, Environment},
+    execute::IntoExecutable,
+};
+use anyhow::{anyhow, Context};
+
+impl Environment {
+    /// Read an entry from the password store. By convention, the first line
+    /// of an entry is the password, and subsequent lines are `key: value`
+    /// metadata. In single-value mode, return the first line, or the selected
+    /// line/field. In multi-value mode, return all metadata lines as a dotenv
+    /// mapping.
+    pub(super) async fn resolve_pass(
+        entry: &str,
+        line: Option<usize>,
+        field: Option<&str>,
+        multiple: bool,
+    ) -> anyhow::Result<String> {
+        let mut executable = ("pass", ["show", entry]).executable();
+        let output = executable.output().await?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "Error reading `{entry}` from password store: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        let content = String::from_utf8(output.stdout).with_context(|| {
+            format!("Error decoding output for {executable}")
+        })?;
+        parse_entry(&content, line, field, multiple)
+            .with_context(|| format!("Error reading pass entry `{entry}`"))
+    }
+}
+
+/// Extract a value (or values) from the content of a pass entry
+fn parse_entry(
+    content: &str,
+    line: Option<usize>,
+    field: Option<&str>,
+    multiple: bool,
+) -> anyhow::Result<String> {
+    // Metadata is everything after the password line
+    let fields = || {
+        content
+            .lines()
+            .skip(1)
+            .filter_map(|line| line.split_once(':'))
+            .map(|(key, value)| (key.trim(), value.trim()))
+    };
+
+    if multiple {
+        format_dotenv(fields())
+    } else if let Some(field) = field {
+        fields()
+            .find(|(key, _)| *key == field)
+            .map(|(_, value)| value.to_owned())
+            .ok_or_else(|| anyhow!("Field `{field}` not found"))
+    } else {
+        let line = line.unwrap_or(1);
+        line.checked_sub(1)
+            .and_then(|index| content.lines().nth(index))
+            .map(String::from)
+            .ok_or_else(|| anyhow!("Line {line} not found"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::assert_result;
+    use rstest::rstest;
+
+    const ENTRY: &str = "hunter2\nusername: root\nurl: https://db.local:5432\n";
+
+    #[rstest]
+    #[case::first_line(None, None, false, Ok("hunter2"))]
+    #[case::line(Some(2), None, false, Ok("username: root"))]
+    #[case::field(None, Some("url"), false, Ok("https://db.local:5432"))]
+    #[case::multiple(
+        None,
+        None,
+        true,
+        Ok("username='root'\nurl='https://db.local:5432'\n")
+    )]
+    #[case::line_zero(Some(0), None, false, Err("Line 0 not found"))]
+    #[case::line_missing(Some(4), None, false, Err("Line 4 not found"))]
+    #[case::field_missing(
+        None,
+        Some("password"),
+        false,
+        Err("Field `password` not found")
+    )]
+    fn test_parse_entry(
+        #[case] line: Option<usize>,
+        #[case] field: Option<&str>,
+        #[case] multiple: bool,
+        #[case] expected: Result<&str, &str>,
+    ) {
+        let result = parse_entry(ENTRY, line, field, multiple);
+        assert_result(result, expected);
+    }
+}
@@ -0,0 +1,96 @@
+//! Value resolution for the `prompt` value source
+
+use crate::{console::PromptMode, environment::Environment};
+use anyhow::bail;
+use dialoguer::{console::Term, theme::ColorfulTheme, Input, Password};
+use smol::lock::Mutex;
+
+/// Values are resolved in parallel, so this makes sure only one prompt is
+/// shown at a time
+static PROMPT_LOCK: Mutex<()> = Mutex::new(());
+
+impl Environment {
+    /// Ask the user to enter a value. Sensitive values are entered as a
+    /// password, with hidden input. If prompts are disabled, fail instead.
+    pub(super) async fn resolve_prompt(
+        message: &str,
+        confirm: bool,
+        sensitive: bool,
+        mode: PromptMode,
+    ) -> anyhow::Result<String> {
+        if mode == PromptMode::Disabled {
+            bail!("Cannot prompt for `{message}` in non-interactive mode");
+        }
+
+        let _guard = PROMPT_LOCK.lock().await;
+        let message = message.to_owned();
+        // dialoguer is blocking, so run it off the executor
+        smol::unblock(move || {
+            if mode == PromptMode::Plain {
+                plain_prompt(&message, confirm, sensitive)
+            } else {
+                prompt(&message, confirm, sensitive)
+            }
+        })
+        .await
+    }
+}
+
+/// Show a prompt and wait for input
+fn prompt(
+    message: &str,
+    confirm: bool,
+    sensitive: bool,
+) -> anyhow::Result<String> {
+    let theme = ColorfulTheme::default();
+    if sensitive {
+        let password = Password::with_theme(&theme)
+            .with_prompt(message)
+            .allow_empty_password(true);
+        let password = if confirm {
+            password.with_confirmation("Confirm", "Values don't match")
+        } else {
+            password
+        };
+        Ok(password.interact()?)
+    } else {
+        let input = |prompt: &str| {
+            Input::<String>::with_theme(&theme)
+                .with_prompt(prompt)
+                .allow_empty(true)
+                .interact_text()
+        };
+        loop {
+            let value = input(message)?;
+            if !confirm || input("Confirm")? == value {
+                return Ok(value);
+            }
+            eprintln!("Values don't match");
+        }
+    }
+}
+
+/// Ask for a value with a plain line of text, for terminals that can't move
+/// the cursor
+fn plain_prompt(
+    message: &str,
+    confirm: bool,
+    sensitive: bool,
+) -> anyhow::Result<String> {
+    let term = Term::stderr();
+    let input = |prompt: &str| -> anyhow::Result<String> {
+        term.write_str(&format!("{prompt}: "))?;
+        Ok(if sensitive {
+            term.read_secure_line()?
+        } else {
+            term.read_line()?
+        })
+    };
+    loop {
+        let value = input(message)?;
+        if !confirm || input("Confirm")? == value {
+            return Ok(value);
+        }
+        term.write_line("Values don't match")?;
+    }
+}
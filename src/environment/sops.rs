@@ -0,0 +1,161 @@
+//! Value resolution for the `sops` value source
+
+use crate::{
+    config::SopsFormat,
+    environment::{json, yaml, Environment},
+    execute::IntoExecutable,
+};
+use anyhow::{anyhow, Context};
+use std::path::Path;
+
+impl Environment {
+    /// Decrypt a sops file. In single-value mode, the plaintext is the value.
+    /// In multi-value mode, the plaintext is converted to a dotenv mapping
+    /// based on its format.
+    pub(super) async fn resolve_sops(
+        path: &Path,
+        format: Option<SopsFormat>,
+        multiple: bool,
+    ) -> anyhow::Result<String> {
+        let format = format.unwrap_or_else(|| infer_format(path));
+        let format_name = format_name(format);
+        let mut executable = (
+            "sops",
+            [
+                "--decrypt",
+                "--input-type",
+                format_name,
+                "--output-type",
+                format_name,
+                &path.to_string_lossy(),
+            ],
+        )
+            .executable();
+        let output = executable.output().await?;
+        if !output.status.success() {
+            return Err(sops_error(
+                path,
+                &String::from_utf8_lossy(&output.stderr),
+            ));
+        }
+        let plaintext =
+            String::from_utf8(output.stdout).with_context(|| {
+                format!("Error decoding output for {executable}")
+            })?;
+        parse_plaintext(&plaintext, format, multiple)
+            .with_context(|| format!("Error loading from sops file {path:?}"))
+    }
+}
+
+/// Guess the format of a file from its extension. Anything unrecognized is
+/// assumed to be dotenv
+fn infer_format(path: &Path) -> SopsFormat {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("yaml" | "yml") => SopsFormat::Yaml,
+        Some("json") => SopsFormat::Json,
+        _ => SopsFormat::Dotenv,
+    }
+}
+
+/// Name of a format, as understood by the sops CLI
+fn format_name(format: SopsFormat) -> &'static str {
+    match format {
+        SopsFormat::Dotenv => "dotenv",
+        SopsFormat::Yaml => "yaml",
+        SopsFormat::Json => "json",
+    }
+}
+
+/// Generate an error for a failed decryption, based on the CLI's stderr
+fn sops_error(path: &Path, stderr: &str) -> anyhow::Error {
+    if stderr.contains("Failed to get the data key") {
+        anyhow!(
+            "Error decrypting sops file {path:?}; make sure you have access \
+            to one of its keys"
+        )
+    } else {
+        anyhow!("Error decrypting sops file {path:?}: {}", stderr.trim())
+    }
+}
+
+/// Convert decrypted plaintext to the value string. In multi-value mode, this
+/// is a dotenv mapping of the file's top-level fields
+fn parse_plaintext(
+    plaintext: &str,
+    format: SopsFormat,
+    multiple: bool,
+) -> anyhow::Result<String> {
+    if !multiple {
+        return Ok(plaintext.to_owned());
+    }
+    match format {
+        SopsFormat::Dotenv => Ok(plaintext.to_owned()),
+        SopsFormat::Yaml => {
+            let document = serde_yaml::from_str(plaintext)
+                .context("Error parsing decrypted YAML")?;
+            yaml::extract_key(&document, None, true)
+        }
+        SopsFormat::Json => {
+            let document = serde_json::from_str(plaintext)
+                .context("Error parsing decrypted JSON")?;
+            json::extract_pointer(&document, "", true)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("secrets.yaml", SopsFormat::Yaml)]
+    #[case("secrets.yml", SopsFormat::Yaml)]
+    #[case("secrets.json", SopsFormat::Json)]
+    #[case("secrets.env", SopsFormat::Dotenv)]
+    #[case(".env", SopsFormat::Dotenv)]
+    fn test_infer_format(#[case] path: &str, #[case] expected: SopsFormat) {
+        assert_eq!(infer_format(Path::new(path)), expected);
+    }
+
+    #[test]
+    fn test_sops_error() {
+        let path = Path::new("secrets.env");
+        assert_eq!(
+            sops_error(
+                path,
+                "Failed to get the data key required to decrypt the SOPS \
+                file.\n\nGroup 0: FAILED\n"
+            )
+            .to_string(),
+            "Error decrypting sops file \"secrets.env\"; make sure you have \
+            access to one of its keys"
+        );
+        assert_eq!(
+            sops_error(path, "Error: sops metadata not found\n").to_string(),
+            "Error decrypting sops file \"secrets.env\": Error: sops metadata \
+            not found"
+        );
+    }
+
+    #[rstest]
+    #[case::single(SopsFormat::Yaml, false, "USER: root\nPORT: 5432\n")]
+    #[case::dotenv(SopsFormat::Dotenv, true, "USER=root\nPORT=5432\n")]
+    #[case::yaml(SopsFormat::Yaml, true, "USER='root'\nPORT='5432'\n")]
+    #[case::json(SopsFormat::Json, true, "PORT='5432'\nUSER='root'\n")]
+    fn test_parse_plaintext(
+        #[case] format: SopsFormat,
+        #[case] multiple: bool,
+        #[case] expected: &str,
+    ) {
+        let plaintext = match format {
+            SopsFormat::Dotenv => "USER=root\nPORT=5432\n",
+            SopsFormat::Yaml => "USER: root\nPORT: 5432\n",
+            SopsFormat::Json => r#"{"USER": "root", "PORT": 5432}"#,
+        };
+        assert_eq!(
+            parse_plaintext(plaintext, format, multiple).unwrap(),
+            expected
+        );
+    }
+}
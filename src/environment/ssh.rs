@@ -0,0 +1,22 @@
+//! Value resolution for the `ssh` value source
+
+use crate::{environment::Environment, execute::IntoExecutable};
+use anyhow::Context;
+
+impl Environment {
+    /// Execute a command on a remote host, and return its output. SSH runs in
+    /// batch mode, so it will fail instead of prompting for a password or
+    /// host key confirmation.
+    pub(super) async fn resolve_ssh(
+        host: &str,
+        command: &str,
+    ) -> anyhow::Result<String> {
+        ("ssh", ["-o", "BatchMode=yes", host, "--", command])
+            .executable()
+            .check_output()
+            .await
+            .with_context(|| {
+                format!("Error executing command on host `{host}`")
+            })
+    }
+}
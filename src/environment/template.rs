@@ -0,0 +1,286 @@
+//! Templating for values and paths. Templates are rendered at resolution
+//! time, so the config always shows the original template.
+//!
+//! - `${VARIABLE}` is replaced by the resolved value of another variable in
+//!   the same profile. This is only supported in literal values
+//! - `${env:VARIABLE}` is replaced by a variable from the parent environment
+//! - `$${` is a literal `${`
+
+use crate::environment::Environment;
+use anyhow::anyhow;
+use std::{borrow::Cow, env, path::Path};
+
+const REFERENCE_START: &str = "${";
+const REFERENCE_END: char = '}';
+const ENV_PREFIX: &str = "env:";
+
+/// One piece of a parsed template
+#[derive(Debug, PartialEq)]
+pub enum Chunk<'a> {
+    /// Text to include as-is
+    Raw(&'a str),
+    /// Name of a profile variable to insert
+    Reference(&'a str),
+    /// Name of a variable from the parent environment to insert
+    Env(&'a str),
+}
+
+/// Everything needed to render the templates of a single value source
+#[derive(Debug, Default)]
+pub struct TemplateContext {
+    /// Already-resolved profile variables that the value source references
+    pub references: Environment,
+    /// If an environment variable isn't set, fail instead of rendering it as
+    /// an empty string
+    pub strict: bool,
+}
+
+/// Split a template into raw text and variable references
+pub fn parse(template: &str) -> anyhow::Result<Vec<Chunk<'_>>> {
+    let mut chunks = Vec::new();
+    let mut remaining = template;
+    while let Some(start) = remaining.find(REFERENCE_START) {
+        let raw = &remaining[..start];
+        if raw.ends_with('$') {
+            // Escaped with `$${`. Keep the first `$`, skip the second, and
+            // treat the `{` as raw text
+            chunks.push(Chunk::Raw(raw));
+            remaining = &remaining[start + 1..];
+            continue;
+        }
+        chunks.push(Chunk::Raw(raw));
+        let reference = &remaining[start + REFERENCE_START.len()..];
+        let end = reference.find(REFERENCE_END).ok_or_else(|| {
+            anyhow!("Unclosed `{REFERENCE_START}` in `{template}`")
+        })?;
+        let name = &reference[..end];
+        chunks.push(match name.strip_prefix(ENV_PREFIX) {
+            Some(variable) => Chunk::Env(variable),
+            None => Chunk::Reference(name),
+        });
+        remaining = &reference[end + 1..];
+    }
+    chunks.push(Chunk::Raw(remaining));
+    chunks.retain(|chunk| *chunk != Chunk::Raw(""));
+    Ok(chunks)
+}
+
+/// Get the names of all profile variables referenced by a template
+pub fn references(template: &str) -> anyhow::Result<Vec<&str>> {
+    Ok(parse(template)?
+        .into_iter()
+        .filter_map(|chunk| match chunk {
+            Chunk::Reference(reference) => Some(reference),
+            Chunk::Raw(_) | Chunk::Env(_) => None,
+        })
+        .collect())
+}
+
+impl TemplateContext {
+    /// Render a template that can reference both profile variables and the
+    /// parent environment
+    pub fn render(&self, template: &str) -> anyhow::Result<String> {
+        self.render_chunks(template, true)
+    }
+
+    /// Are any of the referenced profile variables sensitive? If so, anything
+    /// rendered from them must be treated as sensitive too
+    pub fn is_sensitive(&self) -> bool {
+        self.references.is_sensitive()
+    }
+
+    /// Render a template that can only reference the parent environment
+    pub fn render_env(&self, template: &str) -> anyhow::Result<String> {
+        self.render_chunks(template, false)
+    }
+
+    /// Render a path as a template that can only reference the parent
+    /// environment. Paths that aren't valid UTF-8 are returned as-is
+    pub fn render_path<'a>(
+        &self,
+        path: &'a Path,
+    ) -> anyhow::Result<Cow<'a, Path>> {
+        match path.to_str() {
+            Some(template) if template.contains(REFERENCE_START) => {
+                Ok(Cow::Owned(self.render_env(template)?.into()))
+            }
+            _ => Ok(Cow::Borrowed(path)),
+        }
+    }
+
+    fn render_chunks(
+        &self,
+        template: &str,
+        allow_references: bool,
+    ) -> anyhow::Result<String> {
+        parse(template)?
+            .into_iter()
+            .map(|chunk| match chunk {
+                Chunk::Raw(raw) => Ok(Cow::Borrowed(raw)),
+                Chunk::Reference(reference) if allow_references => self
+                    .references
+                    .get(reference)
+                    .map(Cow::Borrowed)
+                    .ok_or_else(|| {
+                        anyhow!("Variable `{reference}` has no value")
+                    }),
+                Chunk::Reference(reference) => Err(anyhow!(
+                    "Reference `${{{reference}}}` is not supported here; only \
+                    `${{env:VARIABLE}}` is allowed"
+                )),
+                Chunk::Env(variable) => match env::var(variable) {
+                    Ok(value) => Ok(Cow::Owned(value)),
+                    Err(_) if !self.strict => Ok(Cow::Borrowed("")),
+                    Err(_) => Err(anyhow!(
+                        "Environment variable `{variable}` is not set"
+                    )),
+                },
+            })
+            .collect()
+    }
+}
+
+/// Get the names of all variables that a shell command *might* reference,
+/// either as `$VARIABLE` or `${VARIABLE}`. This doesn't understand shell
+/// quoting, so it may find names that the shell wouldn't actually expand.
+pub fn shell_references(command: &str) -> Vec<&str> {
+    let is_name_char = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let mut references = Vec::new();
+    let mut remaining = command;
+    while let Some(start) = remaining.find('$') {
+        remaining = &remaining[start + 1..];
+        if let Some(braced) = remaining.strip_prefix('{') {
+            if let Some(end) = braced.find(REFERENCE_END) {
+                references.push(&braced[..end]);
+                remaining = &braced[end + 1..];
+            }
+        } else {
+            let end = remaining
+                .find(|c| !is_name_char(c))
+                .unwrap_or(remaining.len());
+            if end > 0 {
+                references.push(&remaining[..end]);
+            }
+            remaining = &remaining[end..];
+        }
+    }
+    references
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::assert_result;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::empty("", Ok(vec![]))]
+    #[case::raw("plain", Ok(vec![Chunk::Raw("plain")]))]
+    #[case::references(
+        "postgres://${USER}@${HOST}/app",
+        Ok(vec![
+            Chunk::Raw("postgres://"),
+            Chunk::Reference("USER"),
+            Chunk::Raw("@"),
+            Chunk::Reference("HOST"),
+            Chunk::Raw("/app"),
+        ])
+    )]
+    #[case::escaped(
+        "$${USER} ${USER}",
+        Ok(vec![
+            Chunk::Raw("$"),
+            Chunk::Raw("{USER} "),
+            Chunk::Reference("USER"),
+        ])
+    )]
+    #[case::env(
+        "${env:HOME}/.env",
+        Ok(vec![Chunk::Env("HOME"), Chunk::Raw("/.env")])
+    )]
+    #[case::lone_dollar("$5 $USER", Ok(vec![Chunk::Raw("$5 $USER")]))]
+    #[case::unclosed("${USER", Err("Unclosed `${` in `${USER`"))]
+    fn test_parse(
+        #[case] template: &str,
+        #[case] expected: Result<Vec<Chunk>, &str>,
+    ) {
+        let result = parse(template);
+        assert_result(result, expected);
+    }
+
+    /// Build a template context with a single profile variable, `USER`
+    fn context(strict: bool) -> TemplateContext {
+        let mut references = Environment::default();
+        references.insert_internal("USER", "root".into());
+        TemplateContext { references, strict }
+    }
+
+    #[rstest]
+    #[case::reference("${USER}@$${HOST}", Ok("root@${HOST}"))]
+    #[case::env("${env:ES_TEST_TEMPLATE}/${USER}", Ok("/home/root"))]
+    #[case::escaped_env(
+        "$${env:ES_TEST_TEMPLATE}",
+        Ok("${env:ES_TEST_TEMPLATE}")
+    )]
+    #[case::unknown_reference("${HOST}", Err("Variable `HOST` has no value"))]
+    #[case::unknown_env(
+        "${env:ES_TEST_TEMPLATE_UNKNOWN}",
+        Err("Environment variable `ES_TEST_TEMPLATE_UNKNOWN` is not set")
+    )]
+    fn test_render(
+        #[case] template: &str,
+        #[case] expected: Result<&str, &str>,
+    ) {
+        env::set_var("ES_TEST_TEMPLATE", "/home");
+        let result = context(true).render(template);
+        assert_result(result, expected);
+    }
+
+    /// Non-strict mode renders unknown environment variables as empty, but
+    /// unknown profile variables are still an error
+    #[test]
+    fn test_render_not_strict() {
+        let context = context(false);
+        assert_eq!(
+            context.render("a${env:ES_TEST_TEMPLATE_UNKNOWN}b").unwrap(),
+            "ab"
+        );
+        assert_eq!(
+            context.render("${HOST}").unwrap_err().to_string(),
+            "Variable `HOST` has no value"
+        );
+    }
+
+    #[test]
+    fn test_render_path() {
+        env::set_var("ES_TEST_TEMPLATE_PATH", "/home/root");
+        let context = context(true);
+        assert_eq!(
+            context
+                .render_path(Path::new("${env:ES_TEST_TEMPLATE_PATH}/.env"))
+                .unwrap(),
+            Path::new("/home/root/.env")
+        );
+        assert_eq!(
+            context.render_path(Path::new("/plain/.env")).unwrap(),
+            Path::new("/plain/.env")
+        );
+        assert_eq!(
+            context
+                .render_path(Path::new("${USER}/.env"))
+                .unwrap_err()
+                .to_string(),
+            "Reference `${USER}` is not supported here; only \
+            `${env:VARIABLE}` is allowed"
+        );
+    }
+
+    #[rstest]
+    #[case::none("echo test", &[])]
+    #[case::plain("curl $HOST/token", &["HOST"])]
+    #[case::braced("echo ${HOST}_${PORT}", &["HOST", "PORT"])]
+    #[case::special("echo $$ $? $", &[])]
+    fn test_shell_references(#[case] command: &str, #[case] expected: &[&str]) {
+        assert_eq!(shell_references(command), expected);
+    }
+}
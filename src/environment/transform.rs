@@ -0,0 +1,89 @@
+//! Transformations of resolved values, for the `transform` field
+
+use crate::config::Transform;
+use anyhow::{anyhow, Context};
+use regex::Regex;
+
+/// Apply a single transformation to a resolved value
+pub fn apply(transform: &Transform, value: String) -> anyhow::Result<String> {
+    match transform {
+        Transform::Uppercase => Ok(value.to_uppercase()),
+        Transform::Lowercase => Ok(value.to_lowercase()),
+        Transform::Trim => Ok(value.trim().to_owned()),
+        Transform::Replace { from, to } => Ok(value.replace(from, to)),
+        Transform::Regex { pattern, group } => {
+            let regex = Regex::new(pattern)
+                .with_context(|| format!("Invalid regex `{pattern}`"))?;
+            let captures = regex.captures(&value).ok_or_else(|| {
+                anyhow!("Regex `{pattern}` did not match the value")
+            })?;
+            if *group >= captures.len() {
+                return Err(anyhow!(
+                    "Regex `{pattern}` has no capture group {group}"
+                ));
+            }
+            // The group exists, but may not have participated in the match,
+            // e.g. one side of an alternation
+            let capture = captures.get(*group).ok_or_else(|| {
+                anyhow!("Regex `{pattern}` group {group} did not match")
+            })?;
+            Ok(capture.as_str().to_owned())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::assert_result;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::uppercase(Transform::Uppercase, "Hello", Ok("HELLO"))]
+    #[case::lowercase(Transform::Lowercase, "Hello", Ok("hello"))]
+    #[case::trim(Transform::Trim, " \thello\n", Ok("hello"))]
+    #[case::replace(
+        Transform::Replace { from: "-".into(), to: "_".into() },
+        "a-b-c",
+        Ok("a_b_c"),
+    )]
+    #[case::regex_match(
+        Transform::Regex { pattern: r"token=\w+".into(), group: 0 },
+        "status=ok token=abc123 expires=60",
+        Ok("token=abc123"),
+    )]
+    #[case::regex_group(
+        Transform::Regex { pattern: r"token=(\w+)".into(), group: 1 },
+        "status=ok token=abc123 expires=60",
+        Ok("abc123"),
+    )]
+    #[case::regex_invalid(
+        Transform::Regex { pattern: "(".into(), group: 0 },
+        "",
+        Err("Invalid regex `(`: regex parse error:\n    (\n    ^\n\
+            error: unclosed group"),
+    )]
+    #[case::regex_no_match(
+        Transform::Regex { pattern: "token".into(), group: 0 },
+        "status=ok",
+        Err("Regex `token` did not match the value"),
+    )]
+    #[case::regex_unknown_group(
+        Transform::Regex { pattern: "token".into(), group: 1 },
+        "token",
+        Err("Regex `token` has no capture group 1"),
+    )]
+    #[case::regex_unmatched_group(
+        Transform::Regex { pattern: "a|(b)".into(), group: 1 },
+        "a",
+        Err("Regex `a|(b)` group 1 did not match"),
+    )]
+    fn test_apply(
+        #[case] transform: Transform,
+        #[case] value: &str,
+        #[case] expected: Result<&str, &str>,
+    ) {
+        let result = apply(&transform, value.to_owned());
+        assert_result(result, expected);
+    }
+}
@@ -0,0 +1,99 @@
+//! Value resolution for the `vault` value source
+
+use crate::{
+    environment::{format_dotenv_json, Environment},
+    execute::IntoExecutable,
+};
+use anyhow::{anyhow, Context};
+use serde_json::Value;
+
+impl Environment {
+    /// Read a secret from Vault. In single-value mode, this returns the
+    /// requested field. In multi-value mode, *all* fields in the secret are
+    /// returned as a dotenv mapping.
+    pub(super) async fn resolve_vault(
+        path: &str,
+        field: Option<&str>,
+        mount: Option<&str>,
+        multiple: bool,
+    ) -> anyhow::Result<String> {
+        let mount_arg = mount.map(|mount| format!("-mount={mount}"));
+        let mut arguments = vec!["kv", "get"];
+        arguments.extend(mount_arg.as_deref());
+
+        if multiple {
+            arguments.extend(["-format=json", path]);
+            let output = ("vault", arguments)
+                .executable()
+                .check_output()
+                .await
+                .with_context(|| format!("Error reading secret `{path}`"))?;
+            parse_secret(&output)
+                .with_context(|| format!("Error reading secret `{path}`"))
+        } else {
+            let field = field.ok_or_else(|| {
+                anyhow!(
+                    "Vault secret `{path}` requires a `field`, unless \
+                    `multiple` is enabled"
+                )
+            })?;
+            let field_arg = format!("-field={field}");
+            arguments.extend([field_arg.as_str(), path]);
+            ("vault", arguments)
+                .executable()
+                .check_output()
+                .await
+                .with_context(|| {
+                    format!("Error reading field `{field}` of secret `{path}`")
+                })
+        }
+    }
+}
+
+/// Parse JSON output from `vault kv get`, and convert all fields of the secret
+/// into a dotenv mapping. Supports both v1 and v2 of the KV secrets engine.
+fn parse_secret(output: &str) -> anyhow::Result<String> {
+    let value: Value = serde_json::from_str(output)
+        .context("Error parsing output from vault")?;
+    let data = &value["data"];
+    // KV v2 nests the secret under data.data, alongside metadata
+    let fields = if data["metadata"].is_object() {
+        &data["data"]
+    } else {
+        data
+    };
+    let fields = fields
+        .as_object()
+        .ok_or_else(|| anyhow!("Secret data is not a JSON object"))?;
+
+    format_dotenv_json(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::kv_v1(r#"{"data": {"USERNAME": "user", "PORT": 5432}}"#)]
+    #[case::kv_v2(
+        r#"{"data": {
+            "data": {"USERNAME": "user", "PORT": 5432},
+            "metadata": {"version": 3}
+        }}"#
+    )]
+    fn test_parse_secret(#[case] output: &str) {
+        assert_eq!(
+            parse_secret(output).unwrap(),
+            "PORT='5432'\nUSERNAME='user'\n"
+        );
+    }
+
+    #[test]
+    fn test_parse_secret_invalid() {
+        assert_eq!(
+            parse_secret(r#"{"data": "oops"}"#).unwrap_err().to_string(),
+            "Secret data is not a JSON object"
+        );
+    }
+}
@@ -0,0 +1,157 @@
+//! Value resolution for the `yaml-file` value source
+
+use crate::environment::{format_dotenv, Environment};
+use anyhow::{anyhow, Context};
+use serde_yaml::Value;
+use smol::fs;
+use std::path::Path;
+
+impl Environment {
+    /// Load a YAML file and extract the value at the given dotted key. In
+    /// single-value mode, the value must be a scalar. In multi-value mode, it
+    /// must be a mapping of scalars, which is returned as a dotenv mapping.
+    pub(super) async fn resolve_yaml_file(
+        path: &Path,
+        key: Option<&str>,
+        multiple: bool,
+    ) -> anyhow::Result<String> {
+        let content = fs::read_to_string(path)
+            .await
+            .with_context(|| format!("Error loading file {path:?}"))?;
+        let document: Value = serde_yaml::from_str(&content)
+            .with_context(|| format!("Error parsing YAML file {path:?}"))?;
+        extract_key(&document, key, multiple)
+            .with_context(|| format!("Error loading from YAML file {path:?}"))
+    }
+}
+
+/// Get the value at a dotted key, and convert it to a string
+pub(super) fn extract_key(
+    document: &Value,
+    key: Option<&str>,
+    multiple: bool,
+) -> anyhow::Result<String> {
+    let mut value = document;
+    for segment in key.into_iter().flat_map(|key| key.split('.')) {
+        value = value.get(segment).ok_or_else(|| {
+            anyhow!("Key `{}` not found", key.unwrap_or_default())
+        })?;
+    }
+    let key = key.unwrap_or("<root>");
+
+    if multiple {
+        let mapping = value.as_mapping().ok_or_else(|| {
+            anyhow!(
+                "Value at key `{key}` must be a mapping to load multiple values"
+            )
+        })?;
+        let mapping = mapping
+            .iter()
+            .map(|(variable, value)| {
+                let variable =
+                    scalar_to_string(variable).with_context(|| {
+                        format!("Invalid variable name in mapping at `{key}`")
+                    })?;
+                let value = scalar_to_string(value).with_context(|| {
+                    format!("Invalid value for `{key}.{variable}`")
+                })?;
+                Ok((variable, value))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        format_dotenv(mapping)
+    } else {
+        scalar_to_string(value)
+            .with_context(|| format!("Invalid value for `{key}`"))
+    }
+}
+
+/// Stringify a scalar YAML value. Mappings and sequences are an error, because
+/// there's no obvious string representation for them
+pub(super) fn scalar_to_string(value: &Value) -> anyhow::Result<String> {
+    match value {
+        Value::Null => Ok(String::new()),
+        Value::Bool(value) => Ok(value.to_string()),
+        Value::Number(value) => Ok(value.to_string()),
+        Value::String(value) => Ok(value.clone()),
+        Value::Sequence(_) => {
+            Err(anyhow!("Expected a scalar value, but found a sequence"))
+        }
+        Value::Mapping(_) => {
+            Err(anyhow!("Expected a scalar value, but found a mapping"))
+        }
+        Value::Tagged(tagged) => scalar_to_string(&tagged.value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::assert_result;
+    use rstest::rstest;
+
+    const DOCUMENT: &str = "
+database:
+  user: root
+  port: 5432
+  hosts: [primary, replica]
+debug: true
+";
+
+    #[rstest]
+    #[case::string(Some("database.user"), false, Ok("root"))]
+    #[case::number(Some("database.port"), false, Ok("5432"))]
+    #[case::bool(Some("debug"), false, Ok("true"))]
+    #[case::mapping(
+        Some("database"),
+        false,
+        Err(
+            "Invalid value for `database`: Expected a scalar value, but \
+            found a mapping"
+        )
+    )]
+    #[case::sequence(
+        Some("database.hosts"),
+        false,
+        Err(
+            "Invalid value for `database.hosts`: Expected a scalar value, \
+            but found a sequence"
+        )
+    )]
+    #[case::multiple_nested(
+        Some("database"),
+        true,
+        Err(
+            "Invalid value for `database.hosts`: Expected a scalar value, \
+            but found a sequence"
+        )
+    )]
+    #[case::multiple_scalar(
+        Some("debug"),
+        true,
+        Err("Value at key `debug` must be a mapping to load multiple values")
+    )]
+    #[case::missing(
+        Some("database.password"),
+        false,
+        Err("Key `database.password` not found")
+    )]
+    fn test_extract_key(
+        #[case] key: Option<&str>,
+        #[case] multiple: bool,
+        #[case] expected: Result<&str, &str>,
+    ) {
+        let document: Value = serde_yaml::from_str(DOCUMENT).unwrap();
+        let result = extract_key(&document, key, multiple);
+        assert_result(result, expected);
+    }
+
+    #[test]
+    fn test_extract_key_multiple() {
+        let document: Value =
+            serde_yaml::from_str("user: root\nport: 5432").unwrap();
+        assert_eq!(
+            extract_key(&document, None, true).unwrap(),
+            "user='root'\nport='5432'\n"
+        );
+    }
+}
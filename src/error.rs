@@ -7,15 +7,68 @@ use std::{
 /// An error representing a subprocess failure. **This should only be used when
 /// we want to propagate the exit code**. Not all subprocesses warrant this
 /// behavior!
-#[derive(Copy, Clone, Debug)]
-pub struct ExitCodeError(Option<i32>);
+#[derive(Clone, Debug)]
+pub enum ExitCodeError {
+    /// A single process failed
+    Process {
+        code: Option<i32>,
+        /// Signal that killed the process. Only available on Unix
+        signal: Option<i32>,
+    },
+    /// Some of several runs of a command failed. Each failure should already
+    /// be reported to the user, so this just carries the exit code
+    Aggregate {
+        /// Exit code of each failed run. `None` if the run couldn't report
+        /// an exit code, e.g. because it failed to start
+        codes: Vec<Option<i32>>,
+        /// Total number of runs, including successful ones
+        total: usize,
+    },
+}
+
+impl ExitCodeError {
+    /// Get the exit code to pass on. If the process was killed by a signal,
+    /// follow the shell convention of 128 + the signal number. For an
+    /// aggregate, the code is passed on if every failure had the same one.
+    pub fn exit_code(&self) -> Option<i32> {
+        match self {
+            Self::Process {
+                signal: Some(signal),
+                ..
+            } => Some(128 + signal),
+            Self::Process { code, .. } => *code,
+            Self::Aggregate { codes, .. } => match codes.as_slice() {
+                [first, rest @ ..] if rest.iter().all(|code| code == first) => {
+                    *first
+                }
+                _ => None,
+            },
+        }
+    }
+}
 
 impl Display for ExitCodeError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "process failed with exit code: ")?;
-        match self.0 {
-            Some(code) => write!(f, "{code}"),
-            None => write!(f, "none"),
+        match self {
+            Self::Process {
+                signal: Some(signal),
+                ..
+            } => match signal_name(*signal) {
+                Some(name) => {
+                    write!(f, "process was killed by signal {name} ({signal})")
+                }
+                None => write!(f, "process was killed by signal {signal}"),
+            },
+            Self::Process { code, .. } => {
+                write!(f, "process failed with exit code: ")?;
+                match code {
+                    Some(code) => write!(f, "{code}"),
+                    None => write!(f, "none"),
+                }
+            }
+            Self::Aggregate { codes, total } => {
+                write!(f, "{} of {total} runs failed", codes.len())
+            }
         }
     }
 }
@@ -24,13 +77,20 @@ impl Error for ExitCodeError {}
 
 impl From<&ExitStatus> for ExitCodeError {
     fn from(value: &ExitStatus) -> Self {
-        Self(value.code())
+        #[cfg(unix)]
+        let signal = std::os::unix::process::ExitStatusExt::signal(value);
+        #[cfg(not(unix))]
+        let signal = None;
+        Self::Process {
+            code: value.code(),
+            signal,
+        }
     }
 }
 
 impl From<ExitCodeError> for ExitCode {
     fn from(value: ExitCodeError) -> Self {
-        match value.0 {
+        match value.exit_code() {
             Some(code) => {
                 // ExitStatus uses i32 but ExitCode uses u8, so we have to toss
                 // out the value if it doesn't fit in u8
@@ -43,3 +103,65 @@ impl From<ExitCodeError> for ExitCode {
         }
     }
 }
+
+/// Get the name of a signal. Only signals with the same number on every Unix
+/// platform are named
+fn signal_name(signal: i32) -> Option<&'static str> {
+    let name = match signal {
+        1 => "SIGHUP",
+        2 => "SIGINT",
+        3 => "SIGQUIT",
+        4 => "SIGILL",
+        5 => "SIGTRAP",
+        6 => "SIGABRT",
+        8 => "SIGFPE",
+        9 => "SIGKILL",
+        11 => "SIGSEGV",
+        13 => "SIGPIPE",
+        14 => "SIGALRM",
+        15 => "SIGTERM",
+        _ => return None,
+    };
+    Some(name)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use std::os::unix::process::ExitStatusExt;
+
+    /// Raw wait statuses hold the exit code in the second byte, or the signal
+    /// in the first
+    #[rstest]
+    #[case::success(0, Some(0), "process failed with exit code: 0")]
+    #[case::failure(3 << 8, Some(3), "process failed with exit code: 3")]
+    #[case::sigint(2, Some(130), "process was killed by signal SIGINT (2)")]
+    #[case::sigkill(9, Some(137), "process was killed by signal SIGKILL (9)")]
+    #[case::sigsegv(11, Some(139), "process was killed by signal SIGSEGV (11)")]
+    #[case::unnamed(40, Some(168), "process was killed by signal 40")]
+    fn test_exit_code(
+        #[case] raw: i32,
+        #[case] expected_code: Option<i32>,
+        #[case] expected_message: &str,
+    ) {
+        let error = ExitCodeError::from(&ExitStatus::from_raw(raw));
+        assert_eq!(error.exit_code(), expected_code);
+        assert_eq!(error.to_string(), expected_message);
+    }
+
+    /// An aggregate passes on the exit code only if all failures agree
+    #[rstest]
+    #[case::same(vec![Some(3), Some(3)], Some(3), "2 of 3 runs failed")]
+    #[case::different(vec![Some(3), Some(4)], None, "2 of 3 runs failed")]
+    #[case::no_code(vec![Some(3), None], None, "2 of 3 runs failed")]
+    fn test_exit_code_aggregate(
+        #[case] codes: Vec<Option<i32>>,
+        #[case] expected_code: Option<i32>,
+        #[case] expected_message: &str,
+    ) {
+        let error = ExitCodeError::Aggregate { codes, total: 3 };
+        assert_eq!(error.exit_code(), expected_code);
+        assert_eq!(error.to_string(), expected_message);
+    }
+}
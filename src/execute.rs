@@ -1,60 +1,88 @@
 use crate::{
-    config::{ShellCommand, SideEffect},
+    background::BackgroundProcesses,
+    config::{Masking, SideEffect, SideEffectCommand},
     environment::Environment,
     shell::Shell,
 };
 use anyhow::{anyhow, Context};
-use log::{debug, info};
-use smol::process::{Command, ExitStatus, Stdio};
+use log::{debug, info, warn};
+use smol::{
+    future,
+    io::AsyncWriteExt,
+    process::{Child, Command, ExitStatus, Output, Stdio},
+};
 use std::{
+    env,
     fmt::{Display, Formatter},
+    io, mem,
     path::Path,
+    process,
 };
 
-/// Execute the *setup* stage of a list of side effects
+/// Execute the *setup* stage of a list of side effects. Return the processes
+/// started by background side effects, which need to be passed back to
+/// [revert_side_effects] to be killed.
 pub fn apply_side_effects(
     side_effects: &[SideEffect],
     shell: &Shell,
     environment: &Environment,
-) -> anyhow::Result<()> {
-    execute_side_effects(
-        side_effects.iter().filter_map(SideEffect::setup),
-        shell,
-        environment,
-    )
+) -> anyhow::Result<BackgroundProcesses> {
+    // If anything fails, dropping this kills what we've started so far
+    let mut background = BackgroundProcesses::default();
+    // Execute side-effects sequentially
+    smol::block_on(async {
+        for (index, side_effect) in side_effects.iter().enumerate() {
+            let Some(command) = side_effect.setup()? else {
+                continue;
+            };
+            let mut executable = side_effect_executable(command, shell);
+            executable.environment(environment);
+            if side_effect.is_background() {
+                background.insert(index, executable.spawn()?);
+            } else {
+                executable.status().await?;
+            }
+        }
+        Ok(background)
+    })
 }
 
-/// Execute the *teardown* stage of a list of side effects
+/// Execute the *teardown* stage of a list of side effects. Background
+/// processes are killed before their side effect's teardown command runs.
 pub fn revert_side_effects(
     side_effects: &[SideEffect],
+    mut background: BackgroundProcesses,
     shell: &Shell,
     environment: &Environment,
 ) -> anyhow::Result<()> {
-    execute_side_effects(
+    smol::block_on(async {
         // Revert in *reverse* order
-        side_effects.iter().filter_map(SideEffect::teardown).rev(),
-        shell,
-        environment,
-    )
+        for (index, side_effect) in side_effects.iter().enumerate().rev() {
+            if let Some(process) = background.remove(index) {
+                process.kill().await?;
+            }
+            if let Some(command) = side_effect.teardown()? {
+                side_effect_executable(command, shell)
+                    .environment(environment)
+                    .status()
+                    .await?;
+            }
+        }
+        Ok(())
+    })
 }
 
-/// Helper for executing a list of side effect commands
-fn execute_side_effects<'a>(
-    commands: impl Iterator<Item = &'a ShellCommand>,
+/// Build an executable for a single side effect command
+fn side_effect_executable(
+    command: SideEffectCommand,
     shell: &Shell,
-    environment: &Environment,
-) -> anyhow::Result<()> {
-    // Execute side-effects sequentially
-    smol::block_on(async {
-        for command in commands {
-            shell
-                .executable(command)
-                .environment(environment)
-                .status()
-                .await?;
+) -> Executable {
+    match command {
+        SideEffectCommand::Shell(command) => shell.executable(command),
+        SideEffectCommand::Native { program, arguments } => {
+            (program, arguments).executable()
         }
-        Ok(())
-    })
+    }
 }
 
 /// A wrapper around the std Command type, which provides some more ergnomics.
@@ -63,17 +91,22 @@ fn execute_side_effects<'a>(
 pub struct Executable {
     program: String,
     arguments: Vec<String>,
-    command: Command,
+    /// Built with the std type, so platform-specific options are available.
+    /// It's converted to an async command when executed
+    command: process::Command,
+    /// Bytes to write to the command's stdin. If `None`, stdin is closed
+    stdin: Option<Vec<u8>>,
 }
 
 impl Executable {
     fn new(program: String, arguments: Vec<String>) -> Self {
-        let mut command = Command::new(&program);
+        let mut command = process::Command::new(&program);
         command.args(&arguments);
         let executable = Self {
             program,
             arguments,
             command,
+            stdin: None,
         };
         debug!("Initializing command {executable}");
         executable
@@ -97,10 +130,38 @@ impl Executable {
     }
 
     /// Pass an environment that the command will be run with. This will
-    /// *extend* the parent environment, not replace it.
+    /// *extend* the parent environment, not replace it. Unset variables are
+    /// removed from the inherited environment.
     pub fn environment(&mut self, environment: &Environment) -> &mut Self {
-        debug!("Setting environment for {self}: {environment}");
+        debug!(
+            "Setting environment for {self}: {}",
+            environment.masked(Masking::Full)
+        );
         self.command.envs(environment.iter_unmasked());
+        for variable in environment.iter_unset() {
+            self.command.env_remove(variable);
+        }
+        self
+    }
+
+    /// Start the command with an empty environment instead of inheriting
+    /// this process's, except for the listed variables. Anything passed to
+    /// [Self::environment] afterward is added on top.
+    pub fn env_clear(&mut self, keep: &[String]) -> &mut Self {
+        debug!("Clearing environment for {self}, keeping {keep:?}");
+        self.command.env_clear();
+        for variable in keep {
+            if let Some(value) = env::var_os(variable) {
+                self.command.env(variable, value);
+            }
+        }
+        self
+    }
+
+    /// Pipe input to the command's stdin. This only applies to
+    /// [Self::check_output] and [Self::output]
+    pub fn stdin(&mut self, input: impl Into<Vec<u8>>) -> &mut Self {
+        self.stdin = Some(input.into());
         self
     }
 
@@ -108,30 +169,131 @@ impl Executable {
     /// inherited from the parent.
     pub async fn status(&mut self) -> anyhow::Result<ExitStatus> {
         info!("Executing {self}");
-        self.command
+        self.take_command()
             .status()
             .await
             .with_context(|| format!("Error executing command {self}"))
     }
 
+    /// Execute and return success/failure status, like [Self::status]. If
+    /// env-select receives SIGTERM or SIGINT while the command is running, the
+    /// signal is forwarded to the command, and we keep waiting for it to exit.
+    /// This gives the caller a chance to clean up afterward.
+    ///
+    /// On Unix, the command runs in its own process group, so signals reach
+    /// anything it starts too (e.g. a shell running a pipeline). If
+    /// env-select is in the foreground of a terminal, the command's group is
+    /// given the terminal while it runs, so it can read input and gets ctrl-c
+    /// directly. Only one command can have the terminal at a time, so this
+    /// must not run concurrently with itself. See
+    /// [Self::status_forwarding_signals_background] for that.
+    pub async fn status_forwarding_signals(
+        &mut self,
+    ) -> anyhow::Result<ExitStatus> {
+        self.status_in_group(true).await
+    }
+
+    /// Like [Self::status_forwarding_signals], but the command never gets the
+    /// terminal, and its stdin is closed so it can't be stopped trying to
+    /// read from it. This is safe to run concurrently, e.g. to run a command
+    /// in several profiles at once.
+    pub async fn status_forwarding_signals_background(
+        &mut self,
+    ) -> anyhow::Result<ExitStatus> {
+        self.status_in_group(false).await
+    }
+
+    /// Shared implementation of [Self::status_forwarding_signals] and
+    /// [Self::status_forwarding_signals_background]
+    async fn status_in_group(
+        &mut self,
+        foreground: bool,
+    ) -> anyhow::Result<ExitStatus> {
+        info!("Executing {self}");
+        #[cfg(unix)]
+        let status = {
+            use async_signal::{Signal, Signals};
+            use std::os::unix::process::CommandExt;
+
+            // Listen before the command starts, so no signal is missed
+            let signals = Signals::new([Signal::Term, Signal::Int])?;
+            self.command.process_group(0);
+            let _terminal = if foreground {
+                ForegroundTerminal::hand_over(&mut self.command)
+            } else {
+                None
+            };
+            let mut command = self.take_command();
+            if !foreground {
+                command.stdin(Stdio::null());
+            }
+            let mut child = command
+                .spawn()
+                .with_context(|| format!("Error executing command {self}"))?;
+            let pgid = child.id();
+            let status =
+                async { child.status().await.map_err(anyhow::Error::from) };
+            future::or(status, forward_signals(signals, pgid)).await
+        };
+        #[cfg(not(unix))]
+        let status = {
+            let mut command = self.take_command();
+            if !foreground {
+                command.stdin(Stdio::null());
+            }
+            command.status().await.map_err(anyhow::Error::from)
+        };
+        status.with_context(|| format!("Error executing command {self}"))
+    }
+
+    /// Replace this process with the command, like `execvp`. This only
+    /// returns if the command couldn't be started. Nothing else in this
+    /// process runs afterward, including destructors.
+    #[cfg(unix)]
+    pub fn exec(&mut self) -> anyhow::Error {
+        use std::os::unix::process::CommandExt;
+        info!("Executing {self} in place of env-select");
+        let error = self.command.exec();
+        anyhow::Error::from(error)
+            .context(format!("Error executing command {self}"))
+    }
+
+    /// Start the command without waiting for it to exit. Stdin and stdout are
+    /// closed so the process can't hold onto the terminal or a pipe that
+    /// someone is waiting on. Stderr is inherited. Unlike other execution
+    /// methods, the process is *not* killed when the handle is dropped.
+    ///
+    /// On Unix, the process leads its own process group, so it can be killed
+    /// along with any children it starts (e.g. a shell running a command).
+    pub fn spawn(&mut self) -> anyhow::Result<Child> {
+        info!("Spawning {self}");
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt;
+            self.command.process_group(0);
+        }
+        self.take_command()
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .kill_on_drop(false)
+            .spawn()
+            .with_context(|| format!("Error spawning command {self}"))
+    }
+
     /// Execute and return captured stdout. If the command fails (status >0),
     /// return an error. Stderr will be inherited from the parent.
     pub async fn check_output(&mut self) -> anyhow::Result<String> {
         info!("Executing {self}");
+        // Forward stderr to the user, in case something goes wrong
         let output = self
-            .command
-            // Forward stderr to the user, in case something goes wrong
-            .stderr(Stdio::inherit())
-            .output()
+            .run_output(Stdio::inherit())
             .await
             .with_context(|| format!("Error executing command {self}"))?;
         // TODO Replace with ExitStatus::exit_ok
         // https://github.com/rust-lang/rust/issues/84908
         if output.status.success() {
-            Ok(String::from_utf8(output.stdout)
-                .with_context(|| format!("Error decoding output for {self}"))?
-                .trim_end()
-                .to_string())
+            String::from_utf8(output.stdout)
+                .with_context(|| format!("Error decoding output for {self}"))
         } else {
             Err(anyhow!(
                 "{self} failed with exit code {}",
@@ -143,6 +305,62 @@ impl Executable {
             ))
         }
     }
+
+    /// Execute and return the full output, with both stdout and stderr
+    /// captured. Unlike [Self::check_output], a failure status is *not* an
+    /// error. This is useful when the caller wants to inspect stderr to
+    /// generate a more helpful error message.
+    pub async fn output(&mut self) -> anyhow::Result<Output> {
+        info!("Executing {self}");
+        self.run_output(Stdio::piped())
+            .await
+            .with_context(|| format!("Error executing command {self}"))
+    }
+
+    /// Run the command to completion and collect its output. If there's
+    /// input, write it to stdin *while* collecting output, so a command that
+    /// fills its stdout pipe before reading all its input can't deadlock.
+    /// Stdout is always captured; stderr is handled as given
+    async fn run_output(&mut self, stderr: Stdio) -> io::Result<Output> {
+        let mut command = self.take_command();
+        command.stderr(stderr);
+        let Some(input) = self.stdin.take() else {
+            return command.output().await;
+        };
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        let write = async move {
+            // Dropping stdin closes it, so the command sees EOF. If the
+            // command exits without reading everything, that's its business
+            match stdin.write_all(&input).await {
+                Err(error) if error.kind() != io::ErrorKind::BrokenPipe => {
+                    Err(error)
+                }
+                _ => Ok(()),
+            }
+        };
+        let ((), output) = future::try_zip(write, child.output()).await?;
+        Ok(output)
+    }
+
+    /// Convert to an async command for execution. An executable can only be
+    /// executed once, so this leaves an empty command in its place. Stdio
+    /// settings don't survive the conversion, so they have to be set on the
+    /// returned command
+    fn take_command(&mut self) -> Command {
+        let command = mem::replace(
+            &mut self.command,
+            process::Command::new(&self.program),
+        );
+        let mut command = Command::from(command);
+        // If the future is dropped (e.g. resolution timed out), don't leave
+        // the process running in the background
+        command.kill_on_drop(true);
+        command
+    }
 }
 
 impl Display for Executable {
@@ -151,6 +369,96 @@ impl Display for Executable {
     }
 }
 
+/// Forward signals to a process group, until the future is dropped. Errors are
+/// logged but never end the future, so the caller keeps waiting for the
+/// command either way.
+#[cfg(unix)]
+async fn forward_signals(
+    mut signals: async_signal::Signals,
+    pgid: u32,
+) -> anyhow::Result<ExitStatus> {
+    use smol::stream::StreamExt;
+
+    while let Some(signal) = signals.next().await {
+        let signal = match signal {
+            Ok(signal) => signal,
+            Err(error) => {
+                warn!("Error receiving signal: {error}");
+                continue;
+            }
+        };
+        info!("Forwarding {signal:?} to process group {pgid}");
+        // A negative PID signals the whole group
+        // SAFETY: kill has no memory safety requirements
+        if unsafe { libc::kill(-(pgid as libc::pid_t), signal as i32) } != 0 {
+            warn!(
+                "Error forwarding {signal:?} to process group {pgid}: {}",
+                io::Error::last_os_error()
+            );
+        }
+    }
+    future::pending().await
+}
+
+/// The terminal on stdin, while it's handed over to a command in another
+/// process group. Only the foreground group of a terminal can read from it,
+/// and ctrl-c is sent to that group. When dropped, env-select's group takes
+/// the terminal back.
+#[cfg(unix)]
+struct ForegroundTerminal;
+
+#[cfg(unix)]
+impl ForegroundTerminal {
+    /// Set up a command to take the terminal when it starts, if env-select
+    /// has it. The command has to take it itself, before it runs anything
+    /// that might read input. Return `None` if stdin isn't a terminal, or
+    /// env-select is running in the background
+    fn hand_over(command: &mut process::Command) -> Option<Self> {
+        use std::os::unix::process::CommandExt;
+
+        // SAFETY: These calls have no memory safety requirements
+        let foreground =
+            unsafe { libc::tcgetpgrp(libc::STDIN_FILENO) == libc::getpgrp() };
+        if !foreground {
+            return None;
+        }
+        // SAFETY: The closure runs between fork and exec, and only makes
+        // async-signal-safe calls. The process group is normally created
+        // before the closure runs, but setting it again is harmless
+        unsafe {
+            command.pre_exec(|| {
+                libc::setpgid(0, 0);
+                take_terminal();
+                Ok(())
+            });
+        }
+        Some(Self)
+    }
+}
+
+#[cfg(unix)]
+impl Drop for ForegroundTerminal {
+    fn drop(&mut self) {
+        // SAFETY: See take_terminal
+        unsafe { take_terminal() }
+    }
+}
+
+/// Make the calling process's group the foreground group of the terminal on
+/// stdin. A background group that does this is sent SIGTTOU, which would stop
+/// it, so the signal is ignored in the meantime.
+///
+/// # Safety
+///
+/// This changes the process's signal disposition, so it must not run
+/// concurrently with anything else that does
+#[cfg(unix)]
+unsafe fn take_terminal() {
+    let previous = libc::signal(libc::SIGTTOU, libc::SIG_IGN);
+    libc::tcsetpgrp(libc::STDIN_FILENO, libc::getpgrp());
+    libc::signal(libc::SIGTTOU, previous);
+}
+
 /// Helper trait that makes it more ergonomic to convert to [Executable].
 /// Usually a .into() in the middle of a call chain can't infer correctly, so
 /// this makes the conversion unambiguous.
@@ -0,0 +1,20 @@
+//! Expanding glob patterns into lists of files, for config `includes` and the
+//! `file-glob` value source
+
+use anyhow::{anyhow, Context};
+use std::path::{Path, PathBuf};
+
+/// Get all files matching a glob pattern, sorted by path. Directories that
+/// match are skipped
+pub fn glob_files(pattern: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let pattern_str = pattern.to_str().ok_or_else(|| {
+        anyhow!("Glob pattern {pattern:?} is not valid UTF-8")
+    })?;
+    let mut paths = glob::glob(pattern_str)
+        .with_context(|| format!("Invalid glob pattern {pattern:?}"))?
+        .filter(|entry| entry.as_ref().map_or(true, |path| path.is_file()))
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("Error expanding glob pattern {pattern:?}"))?;
+    paths.sort();
+    Ok(paths)
+}
@@ -0,0 +1,137 @@
+//! The profile last selected for each application, per directory. Only
+//! selections made in a prompt or with `--last` are recorded. This lets
+//! `--last` reuse a selection without prompting, and lets the prompt start on
+//! the previous choice. History is a convenience, so errors reading or
+//! writing it are logged and otherwise ignored.
+
+use crate::config::Name;
+use anyhow::Context;
+use log::{debug, warn};
+use std::{
+    collections::HashMap,
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+const FILE_NAME: &str = "history.json";
+
+/// Selected profiles, keyed by directory, then by application
+type History = HashMap<PathBuf, HashMap<Name, Name>>;
+
+/// Get the profile last selected for an application in the current
+/// directory, if any
+pub fn last_profile(application: &Name) -> Option<Name> {
+    let path = history_file()?;
+    let directory = env::current_dir().ok()?;
+    read(&path).remove(&directory)?.remove(application)
+}
+
+/// Remember the profile selected for an application in the current directory
+pub fn record(application: &Name, profile: &Name) {
+    let Some(path) = history_file() else {
+        return;
+    };
+    let result = env::current_dir()
+        .context("Error getting current directory")
+        .and_then(|directory| write(&path, directory, application, profile));
+    if let Err(error) = result {
+        warn!("Error writing selection history to {path:?}: {error:#}");
+    }
+}
+
+/// Forget every remembered selection, in all directories
+pub fn clear() -> anyhow::Result<()> {
+    let Some(path) = history_file() else {
+        return Ok(());
+    };
+    match fs::remove_file(&path) {
+        Ok(()) => Ok(()),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(error) => Err(error)
+            .with_context(|| format!("Error deleting history file {path:?}")),
+    }
+}
+
+/// Path to the history file. `None` if we can't find a suitable location
+fn history_file() -> Option<PathBuf> {
+    let base =
+        env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| {
+                env::var_os("HOME")
+                    .map(|home| Path::new(&home).join(".local").join("share"))
+            })?;
+    Some(base.join("env-select").join(FILE_NAME))
+}
+
+/// Load history from a file. A missing or invalid file is treated as empty
+fn read(path: &Path) -> History {
+    let result = fs::read_to_string(path)
+        .map_err(anyhow::Error::from)
+        .and_then(|content| Ok(serde_json::from_str(&content)?));
+    match result {
+        Ok(history) => history,
+        Err(error) => {
+            debug!("Error reading history file {path:?}: {error:#}");
+            History::default()
+        }
+    }
+}
+
+/// Add a selection to the history file, creating it if necessary
+fn write(
+    path: &Path,
+    directory: PathBuf,
+    application: &Name,
+    profile: &Name,
+) -> anyhow::Result<()> {
+    let mut history = read(path);
+    history
+        .entry(directory)
+        .or_default()
+        .insert(application.clone(), profile.clone());
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Error creating directory {parent:?}"))?;
+    }
+    fs::write(path, serde_json::to_string(&history)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_read_write() {
+        let path = env::temp_dir().join("es-test-history/history.json");
+        let _ = fs::remove_file(&path);
+        let application: Name = "app".into();
+
+        assert_eq!(read(&path), History::default());
+        write(&path, "/project".into(), &application, &"dev".into()).unwrap();
+        write(&path, "/other".into(), &application, &"prd".into()).unwrap();
+        // Overwrite an existing entry
+        write(&path, "/project".into(), &application, &"stg".into()).unwrap();
+        assert_eq!(
+            read(&path),
+            History::from([
+                (
+                    "/project".into(),
+                    HashMap::from([(application.clone(), "stg".into())])
+                ),
+                (
+                    "/other".into(),
+                    HashMap::from([(application.clone(), "prd".into())])
+                ),
+            ])
+        );
+
+        // Invalid files are ignored, and overwritten on the next write
+        fs::write(&path, "not json").unwrap();
+        assert_eq!(read(&path), History::default());
+        write(&path, "/project".into(), &application, &"dev".into()).unwrap();
+        assert_eq!(read(&path).len(), 1);
+    }
+}
@@ -0,0 +1,216 @@
+//! Switching the kubectl context and namespace, for profiles that set
+//! `kubernetes_context` or `kubernetes_namespace`
+
+use crate::{config::Profile, execute::IntoExecutable};
+use anyhow::Context;
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use std::env;
+
+const KUBECTL: &str = "kubectl";
+
+/// Variable that `es set` exports to remember how to undo a switch, so the
+/// previous settings can be restored when switching to another profile
+pub const RESTORE_VARIABLE: &str = "ENV_SELECT_KUBERNETES_RESTORE";
+
+/// kubectl settings from before a profile switched them. Restoring this puts
+/// kubectl back the way it was.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
+pub struct KubernetesRestore {
+    /// Context that was active before the switch. `None` if the profile
+    /// didn't switch contexts
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    context: Option<String>,
+    /// Namespace of the profile's context before the switch. Empty if the
+    /// context had no namespace. `None` if the profile didn't switch
+    /// namespaces
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    namespace: Option<String>,
+}
+
+impl KubernetesRestore {
+    /// Switch kubectl to the profile's context and namespace. Return what's
+    /// needed to undo the switch, or `None` if the profile doesn't set either
+    pub fn switch(profile: &Profile) -> anyhow::Result<Option<Self>> {
+        if profile.kubernetes_context.is_none()
+            && profile.kubernetes_namespace.is_none()
+        {
+            return Ok(None);
+        }
+        smol::block_on(async {
+            let mut restore = Self::default();
+            if let Some(context) = &profile.kubernetes_context {
+                restore.context = Some(
+                    kubectl(&["config", "current-context"])
+                        .await
+                        .context("Error getting current kubectl context")?,
+                );
+                info!("Switching kubectl context to {context}");
+                kubectl(&["config", "use-context", context]).await?;
+            }
+            // Namespace is set on the context we just switched to
+            if let Some(namespace) = &profile.kubernetes_namespace {
+                let switched = async {
+                    let previous = kubectl(&[
+                        "config",
+                        "view",
+                        "--minify",
+                        "--output",
+                        "jsonpath={..namespace}",
+                    ])
+                    .await
+                    .context("Error getting current kubectl namespace")?;
+                    info!("Switching kubectl namespace to {namespace}");
+                    kubectl(&[
+                        "config",
+                        "set-context",
+                        "--current",
+                        &format!("--namespace={namespace}"),
+                    ])
+                    .await?;
+                    Ok::<_, anyhow::Error>(previous)
+                }
+                .await;
+                match switched {
+                    Ok(previous) => restore.namespace = Some(previous),
+                    // Don't leave the context switched if we're failing
+                    Err(error) => {
+                        Self::log_restore_error(restore.restore_async().await);
+                        return Err(error);
+                    }
+                }
+            }
+            Ok(Some(restore))
+        })
+    }
+
+    /// Switch kubectl to the profile's context and namespace, call a
+    /// function, then undo the switch, whether or not the function succeeded.
+    /// If both fail, the function's error is returned and the restore error
+    /// is logged.
+    pub fn switched<T>(
+        profile: &Profile,
+        function: impl FnOnce() -> anyhow::Result<T>,
+    ) -> anyhow::Result<T> {
+        let Some(restore) = Self::switch(profile)? else {
+            return function();
+        };
+        let result = function();
+        let restored = restore.restore();
+        match result {
+            Ok(value) => restored.map(|()| value),
+            Err(error) => {
+                Self::log_restore_error(restored);
+                Err(error)
+            }
+        }
+    }
+
+    /// Report a restore that failed while we're already failing for another
+    /// reason
+    fn log_restore_error(result: anyhow::Result<()>) {
+        if let Err(error) = result {
+            error!("Error restoring kubectl settings: {error:#}");
+        }
+    }
+
+    /// Undo a switch. The namespace is restored first, because it was set on
+    /// the profile's context, which is still active
+    pub fn restore(&self) -> anyhow::Result<()> {
+        smol::block_on(self.restore_async())
+    }
+
+    async fn restore_async(&self) -> anyhow::Result<()> {
+        if let Some(namespace) = &self.namespace {
+            info!("Restoring kubectl namespace to {namespace:?}");
+            kubectl(&[
+                "config",
+                "set-context",
+                "--current",
+                &format!("--namespace={namespace}"),
+            ])
+            .await?;
+        }
+        if let Some(context) = &self.context {
+            info!("Restoring kubectl context to {context}");
+            kubectl(&["config", "use-context", context]).await?;
+        }
+        Ok(())
+    }
+
+    /// Load the settings exported by a previous `es set`, if any
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        env::var(RESTORE_VARIABLE)
+            .ok()
+            .map(|value| Self::parse(&value))
+            .transpose()
+    }
+
+    /// Parse settings from the value of [RESTORE_VARIABLE]
+    fn parse(value: &str) -> anyhow::Result<Self> {
+        serde_json::from_str(value)
+            .with_context(|| format!("Invalid value for {RESTORE_VARIABLE}"))
+    }
+
+    /// Serialize settings to be stored in [RESTORE_VARIABLE]
+    pub fn to_variable(&self) -> String {
+        serde_json::to_string(self).expect("Serialization is infallible")
+    }
+}
+
+/// Run a kubectl command and return its trimmed output
+async fn kubectl(arguments: &[&str]) -> anyhow::Result<String> {
+    let output = (KUBECTL, arguments.iter().copied())
+        .executable()
+        .check_output()
+        .await?;
+    Ok(output.trim().to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::empty(KubernetesRestore::default(), "{}")]
+    #[case::context(
+        KubernetesRestore {
+            context: Some("prd".into()),
+            namespace: None,
+        },
+        r#"{"context":"prd"}"#,
+    )]
+    #[case::both(
+        KubernetesRestore {
+            context: Some("prd".into()),
+            namespace: Some("".into()),
+        },
+        r#"{"context":"prd","namespace":""}"#,
+    )]
+    fn test_variable(
+        #[case] restore: KubernetesRestore,
+        #[case] expected: &str,
+    ) {
+        let variable = restore.to_variable();
+        assert_eq!(variable, expected);
+        assert_eq!(KubernetesRestore::parse(&variable).unwrap(), restore);
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert_eq!(
+            KubernetesRestore::parse("prd").unwrap_err().to_string(),
+            "Invalid value for ENV_SELECT_KUBERNETES_RESTORE"
+        );
+    }
+
+    /// Profiles without kubernetes settings don't touch kubectl at all
+    #[test]
+    fn test_switch_none() {
+        assert_eq!(
+            KubernetesRestore::switch(&Profile::default()).unwrap(),
+            None
+        );
+    }
+}
@@ -0,0 +1,368 @@
+//! Recording what `es set` changed in the shell, so `es unset` can revert it
+
+use crate::{
+    config::{ProfileReference, SensitivePattern},
+    environment::Environment,
+};
+use anyhow::Context;
+use indexmap::{IndexMap, IndexSet};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashSet,
+    env,
+    path::{Path, PathBuf},
+};
+
+/// Variable that `es set` exports to record what it changed, so `es unset`
+/// can put everything back
+pub const LAST_EXPORT_VARIABLE: &str = "ENV_SELECT_LAST_EXPORT";
+
+/// What `es set` changed in the shell. When multiple profiles are set in a
+/// row, this covers all of them, so reverting it restores the shell to how it
+/// was before the first one.
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct LastExport {
+    /// Most recently set profile. Its side effects are torn down on revert
+    pub profile: ProfileReference,
+    /// Profiles from other applications that were combined with the profile
+    /// via `--with`. Their side effects are torn down too
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub with: Vec<ProfileReference>,
+    /// Did the profile's side effects run? If not, there's nothing to tear
+    /// down
+    #[serde(default = "LastExport::default_side_effects")]
+    pub side_effects: bool,
+    /// Value of each modified variable before env-select first touched it.
+    /// `None` if the variable wasn't set
+    previous: IndexMap<String, Option<String>>,
+    /// Modified variables that were sensitive in any profile that set them.
+    /// Their previous values are masked too when they're restored
+    #[serde(default, skip_serializing_if = "IndexSet::is_empty")]
+    sensitive: IndexSet<String>,
+    /// Working directory from before env-select first changed it. `None` if
+    /// no profile changed it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cwd: Option<PathBuf>,
+    /// Variables exported by the most recent profile set for each
+    /// application, so switching profiles can clear out variables that the
+    /// new profile doesn't set
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    exported: IndexMap<String, Vec<String>>,
+}
+
+impl LastExport {
+    /// Record the changes that exporting an environment will make, on top of
+    /// whatever a previous `es set` changed. Values from before the previous
+    /// set take precedence, because they're the shell's original values. The
+    /// same goes for the working directory, if `cwd` is given
+    pub fn new(
+        profile: ProfileReference,
+        with: Vec<ProfileReference>,
+        side_effects: bool,
+        environment: &Environment,
+        cwd: Option<&Path>,
+        previous: Option<Self>,
+    ) -> Self {
+        let (mut previous, mut sensitive, previous_cwd, mut exported) =
+            match previous {
+                Some(Self {
+                    previous,
+                    sensitive,
+                    cwd,
+                    exported,
+                    ..
+                }) => (previous, sensitive, cwd, exported),
+                None => Default::default(),
+            };
+        for variable in environment.variables() {
+            previous
+                .entry(variable.to_owned())
+                .or_insert_with(|| env::var(variable).ok());
+        }
+        sensitive.extend(environment.sensitive_variables().map(String::from));
+        let cwd =
+            previous_cwd.or_else(|| cwd.and_then(|_| env::current_dir().ok()));
+        if let Some(application) = profile.application() {
+            exported.insert(
+                application.to_string(),
+                environment.variables().map(String::from).collect(),
+            );
+        }
+        Self {
+            profile,
+            with,
+            side_effects,
+            previous,
+            sensitive,
+            cwd,
+            exported,
+        }
+    }
+
+    /// Get an environment that clears out variables from the application's
+    /// previous profile that the new environment doesn't set. Each one is put
+    /// back to its value from before env-select first touched it, or removed
+    /// if it wasn't set. Variables still set by another application's
+    /// profile are left alone. See [Self::revert] for masking.
+    pub fn stale(
+        &self,
+        profile: &ProfileReference,
+        environment: &Environment,
+        sensitive_patterns: &[SensitivePattern],
+    ) -> Environment {
+        let mut stale = Environment::default();
+        let Some(application) = profile.application() else {
+            return stale;
+        };
+        let Some(exported) = self.exported.get(application.as_str()) else {
+            return stale;
+        };
+        let covered: HashSet<&str> = environment
+            .variables()
+            .chain(
+                self.exported
+                    .iter()
+                    .filter(|(other, _)| *other != application.as_str())
+                    .flat_map(|(_, variables)| variables.iter())
+                    .map(String::as_str),
+            )
+            .collect();
+        for variable in exported {
+            if covered.contains(variable.as_str()) {
+                continue;
+            }
+            self.restore(&mut stale, variable, sensitive_patterns);
+        }
+        stale
+    }
+
+    fn default_side_effects() -> bool {
+        true
+    }
+
+    /// Get an environment that puts every modified variable back the way it
+    /// was. Variables that weren't set before are removed. A restored value
+    /// is sensitive if a profile marked the variable sensitive, or its name
+    /// matches one of the sensitive patterns
+    pub fn revert(
+        &self,
+        sensitive_patterns: &[SensitivePattern],
+    ) -> Environment {
+        let mut environment = Environment::default();
+        for variable in self.previous.keys() {
+            self.restore(&mut environment, variable, sensitive_patterns);
+        }
+        environment
+    }
+
+    /// Working directory to return to on revert, if a profile changed it
+    pub fn cwd(&self) -> Option<&Path> {
+        self.cwd.as_deref()
+    }
+
+    /// Add a variable's previous value to an environment, or unset it if it
+    /// wasn't set before
+    fn restore(
+        &self,
+        environment: &mut Environment,
+        variable: &str,
+        sensitive_patterns: &[SensitivePattern],
+    ) {
+        match self.previous.get(variable) {
+            Some(Some(value)) => {
+                let sensitive = self.sensitive.contains(variable)
+                    || sensitive_patterns
+                        .iter()
+                        .any(|pattern| pattern.matches(variable));
+                environment.insert_restored(variable, value.clone(), sensitive)
+            }
+            _ => environment.unset(variable.to_owned()),
+        }
+    }
+
+    /// Load the record exported by a previous `es set`, if any
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        env::var(LAST_EXPORT_VARIABLE)
+            .ok()
+            .map(|value| Self::parse(&value))
+            .transpose()
+    }
+
+    /// Parse a record from the value of [LAST_EXPORT_VARIABLE]
+    fn parse(value: &str) -> anyhow::Result<Self> {
+        serde_json::from_str(value).with_context(|| {
+            format!("Invalid value for {LAST_EXPORT_VARIABLE}")
+        })
+    }
+
+    /// Serialize the record to be stored in [LAST_EXPORT_VARIABLE]
+    pub fn to_variable(&self) -> String {
+        serde_json::to_string(self).expect("Serialization is infallible")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    /// Each variable's value from before the *first* set is kept, so chained
+    /// sets revert all the way back to the original shell. The same goes for
+    /// the working directory. Restored values stay masked if any profile that
+    /// set them marked them sensitive
+    #[test]
+    fn test_new() {
+        env::set_var("ES_TEST_LAST_EXPORT_SET", "original");
+        env::remove_var("ES_TEST_LAST_EXPORT_UNSET");
+        env::set_var("ES_TEST_LAST_EXPORT_CHAINED", "from first set");
+        env::set_var("ES_TEST_LAST_EXPORT_SECRET", "hunter2");
+
+        let mut environment = Environment::default();
+        environment.insert_internal("ES_TEST_LAST_EXPORT_SET", "new".into());
+        environment.insert_internal("ES_TEST_LAST_EXPORT_UNSET", "new".into());
+        environment.unset("ES_TEST_LAST_EXPORT_CHAINED".into());
+        environment.insert_restored(
+            "ES_TEST_LAST_EXPORT_SECRET",
+            "hunter3".into(),
+            true,
+        );
+        let previous = LastExport {
+            profile: "app/first".parse().unwrap(),
+            with: vec![],
+            side_effects: true,
+            previous: IndexMap::from([(
+                "ES_TEST_LAST_EXPORT_CHAINED".into(),
+                None,
+            )]),
+            sensitive: IndexSet::new(),
+            cwd: Some("/first".into()),
+            exported: IndexMap::from([(
+                "app".into(),
+                vec!["ES_TEST_LAST_EXPORT_CHAINED".into()],
+            )]),
+        };
+        let last_export = LastExport::new(
+            "app/second".parse().unwrap(),
+            vec!["other/profile".parse().unwrap()],
+            false,
+            &environment,
+            Some(Path::new("/second")),
+            Some(previous),
+        );
+        assert_eq!(
+            last_export,
+            LastExport {
+                profile: "app/second".parse().unwrap(),
+                with: vec!["other/profile".parse().unwrap()],
+                side_effects: false,
+                previous: IndexMap::from([
+                    ("ES_TEST_LAST_EXPORT_CHAINED".into(), None),
+                    ("ES_TEST_LAST_EXPORT_SET".into(), Some("original".into())),
+                    ("ES_TEST_LAST_EXPORT_UNSET".into(), None),
+                    (
+                        "ES_TEST_LAST_EXPORT_SECRET".into(),
+                        Some("hunter2".into())
+                    ),
+                ]),
+                sensitive: IndexSet::from(
+                    ["ES_TEST_LAST_EXPORT_SECRET".into()]
+                ),
+                cwd: Some("/first".into()),
+                exported: IndexMap::from([(
+                    "app".into(),
+                    vec![
+                        "ES_TEST_LAST_EXPORT_SET".into(),
+                        "ES_TEST_LAST_EXPORT_UNSET".into(),
+                        "ES_TEST_LAST_EXPORT_CHAINED".into(),
+                        "ES_TEST_LAST_EXPORT_SECRET".into(),
+                    ]
+                )]),
+            }
+        );
+
+        let mut expected = Environment::default();
+        expected.unset("ES_TEST_LAST_EXPORT_CHAINED".into());
+        expected.insert_internal("ES_TEST_LAST_EXPORT_SET", "original".into());
+        expected.unset("ES_TEST_LAST_EXPORT_UNSET".into());
+        expected.insert_restored(
+            "ES_TEST_LAST_EXPORT_SECRET",
+            "hunter2".into(),
+            true,
+        );
+        assert_eq!(last_export.revert(&[]), expected);
+        assert_eq!(last_export.cwd(), Some(Path::new("/first")));
+
+        // Round trip through the variable
+        assert_eq!(
+            LastExport::parse(&last_export.to_variable()).unwrap(),
+            last_export
+        );
+    }
+
+    /// Switching profiles clears variables that the application's previous
+    /// profile set, but the new one doesn't. Restored values matching the
+    /// sensitive patterns are masked
+    #[test]
+    fn test_stale() {
+        let last_export = LastExport {
+            profile: "app/first".parse().unwrap(),
+            with: vec![],
+            side_effects: true,
+            previous: IndexMap::from([
+                ("COVERED".into(), None),
+                ("RESTORED".into(), Some("original".into())),
+                ("REMOVED".into(), None),
+                ("SHARED".into(), None),
+                ("API_TOKEN".into(), Some("hunter2".into())),
+            ]),
+            sensitive: IndexSet::new(),
+            cwd: None,
+            exported: IndexMap::from([
+                (
+                    "app".into(),
+                    vec![
+                        "COVERED".into(),
+                        "RESTORED".into(),
+                        "REMOVED".into(),
+                        "SHARED".into(),
+                        "API_TOKEN".into(),
+                    ],
+                ),
+                ("other".into(), vec!["SHARED".into()]),
+            ]),
+        };
+        let mut environment = Environment::default();
+        environment.insert_internal("COVERED", "new".into());
+
+        let mut expected = Environment::default();
+        expected.insert_internal("RESTORED", "original".into());
+        expected.unset("REMOVED".into());
+        expected.insert_restored("API_TOKEN", "hunter2".into(), true);
+        let patterns = ["*_TOKEN".parse().unwrap()];
+        assert_eq!(
+            last_export.stale(
+                &"app/second".parse().unwrap(),
+                &environment,
+                &patterns
+            ),
+            expected
+        );
+        // Nothing is stale for an application that hasn't been set
+        assert_eq!(
+            last_export.stale(
+                &"third/profile".parse().unwrap(),
+                &environment,
+                &patterns
+            ),
+            Environment::default()
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert_eq!(
+            LastExport::parse("app/profile").unwrap_err().to_string(),
+            "Invalid value for ENV_SELECT_LAST_EXPORT"
+        );
+    }
+}
@@ -1,3 +1,5 @@
+mod active;
+mod background;
 mod commands;
 mod completions;
 mod config;
@@ -5,14 +7,24 @@ mod console;
 mod environment;
 mod error;
 mod execute;
+mod glob_files;
+mod history;
+mod kubernetes;
+mod last_export;
+mod runtime_dir;
+mod source_file;
 #[cfg(test)]
 mod test_util;
 
 mod shell;
 
-use crate::{commands::Commands, error::ExitCodeError, shell::ShellKind};
+use crate::{
+    commands::Commands, console::ColorMode, error::ExitCodeError,
+    shell::ShellArg,
+};
 use clap::{CommandFactory, Parser};
-use log::{error, LevelFilter};
+use env_logger::WriteStyle;
+use log::{error, info, LevelFilter};
 // https://github.com/la10736/rstest/tree/master/rstest_reuse#cavelets
 use clap_complete::CompleteEnv;
 use std::{path::PathBuf, process::ExitCode};
@@ -41,10 +53,45 @@ pub struct GlobalArgs {
     #[clap(long, hide = true)]
     source_file: Option<PathBuf>,
 
+    /// Load configuration from this file only, instead of scanning the
+    /// current directory and its ancestors for config files. Can also be set
+    /// with the ES_CONFIG_FILE environment variable.
+    #[clap(long)]
+    config: Option<PathBuf>,
+
+    /// Skip config files that fail to parse, instead of failing. Skipped
+    /// files are logged as errors.
+    #[clap(long)]
+    ignore_invalid_config: bool,
+
     /// Type of the shell binary in use. If omitted, it will be auto-detected
-    /// from the $SHELL variable.
+    /// from the $SHELL variable, or the parent process if $SHELL isn't a
+    /// supported shell. `auto-strict` prefers the parent process, for when
+    /// you've launched a different shell from your login shell
     #[clap(short, long)]
-    shell: Option<ShellKind>,
+    shell: Option<ShellArg>,
+
+    /// Ignore cached values, and resolve every value source from scratch.
+    /// Freshly resolved values will not be written to the cache either.
+    #[clap(long)]
+    no_cache: bool,
+
+    /// Fail instead of prompting to select an application or profile. This
+    /// is automatic when stdin or stderr isn't a terminal, e.g. in scripts
+    /// and CI
+    #[clap(long)]
+    non_interactive: bool,
+
+    /// Prompt with a numbered list, answered by typing a number, instead of
+    /// an interactive list. This is automatic when TERM is `dumb`, e.g. in an
+    /// Emacs shell
+    #[clap(long)]
+    plain_prompt: bool,
+
+    /// When to color output. `auto` colors when writing to a terminal,
+    /// unless the NO_COLOR environment variable is set
+    #[clap(long, value_enum, default_value_t)]
+    color: ColorMode,
 
     /// Increase output verbosity, for debugging. Supports up to -vvv
     #[clap(short, long, action = clap::ArgAction::Count)]
@@ -55,10 +102,16 @@ fn main() -> ExitCode {
     // If COMPLETE var is enabled, process will stop after completions
     CompleteEnv::with_factory(Args::command).complete();
     let args = Args::parse();
+    let color = args.global.color.init();
     env_logger::Builder::new()
         .format_timestamp(None)
         .format_module_path(false)
         .format_target(false)
+        .write_style(if color {
+            WriteStyle::Always
+        } else {
+            WriteStyle::Never
+        })
         .filter_level(match args.global.verbose {
             0 => LevelFilter::Warn,
             1 => LevelFilter::Info,
@@ -76,8 +129,11 @@ fn main() -> ExitCode {
                 // If we're propagating the exit code, we don't want to print
                 // the error. This is for `es run`, which means
                 // stdout/stderr have been forwarded and we don't want to tack
-                // on any more logging.
-                Ok(error) => error.into(),
+                // on any more logging, except in verbose mode.
+                Ok(error) => {
+                    info!("{error}");
+                    error.into()
+                }
                 // For most errors, print it. Most of the time this is a user
                 // error, but this will also handle system errors or application
                 // bugs. The user should pass -v to get a stack trace for
@@ -0,0 +1,113 @@
+//! A private directory for files that track state between invocations, such
+//! as the PIDs of background processes. Paths in it are predictable, so it
+//! must not be writable by anyone else.
+
+use anyhow::{anyhow, Context};
+use std::{env, fs, io::ErrorKind, path::PathBuf};
+
+/// Get the runtime directory, creating it if needed. This is
+/// `$XDG_RUNTIME_DIR/env-select` if that's set, otherwise a directory in the
+/// temp dir named after the current user. If the directory already exists but
+/// isn't private to the current user, fail rather than trust its contents.
+pub fn runtime_dir() -> anyhow::Result<PathBuf> {
+    let path = path();
+    let mut builder = fs::DirBuilder::new();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+    match builder.create(&path) {
+        Ok(()) => {}
+        Err(error) if error.kind() == ErrorKind::AlreadyExists => {}
+        Err(error) => {
+            return Err(error).with_context(|| {
+                format!("Error creating runtime directory {path:?}")
+            })
+        }
+    }
+    check(&path)?;
+    Ok(path)
+}
+
+#[cfg(unix)]
+fn path() -> PathBuf {
+    match env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir).join("env-select"),
+        // SAFETY: getuid can't fail
+        _ => env::temp_dir()
+            .join(format!("env-select-{}", unsafe { libc::getuid() })),
+    }
+}
+
+/// The temp dir is already private to the user on Windows
+#[cfg(not(unix))]
+fn path() -> PathBuf {
+    env::temp_dir().join("env-select")
+}
+
+/// Make sure the directory is a real directory, owned by the current user,
+/// that nobody else can access
+#[cfg(unix)]
+fn check(path: &std::path::Path) -> anyhow::Result<()> {
+    use std::os::unix::fs::MetadataExt;
+
+    let metadata = fs::symlink_metadata(path)
+        .with_context(|| format!("Error reading runtime directory {path:?}"))?;
+    // SAFETY: getuid can't fail
+    let uid = unsafe { libc::getuid() };
+    if !metadata.is_dir() {
+        Err(anyhow!("Runtime directory {path:?} is not a directory"))
+    } else if metadata.uid() != uid {
+        Err(anyhow!(
+            "Runtime directory {path:?} is owned by another user"
+        ))
+    } else if metadata.mode() & 0o077 != 0 {
+        Err(anyhow!(
+            "Runtime directory {path:?} is accessible to other users; \
+            its permissions should be 0700"
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(not(unix))]
+fn check(_: &std::path::Path) -> anyhow::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The directory is created private, and reused after that
+    #[cfg(unix)]
+    #[test]
+    fn test_runtime_dir() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = runtime_dir().unwrap();
+        let mode = fs::metadata(&dir).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o700);
+        assert_eq!(runtime_dir().unwrap(), dir);
+    }
+
+    /// A directory that other users can write to is rejected
+    #[cfg(unix)]
+    #[test]
+    fn test_check_shared() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = env::temp_dir().join("es-test-runtime-dir-shared");
+        fs::create_dir_all(&dir).unwrap();
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o777)).unwrap();
+        assert_eq!(
+            check(&dir).unwrap_err().to_string(),
+            format!(
+                "Runtime directory {dir:?} is accessible to other users; \
+                its permissions should be 0700"
+            )
+        );
+    }
+}
@@ -3,25 +3,24 @@ use crate::{
     environment::Environment,
     execute::{Executable, IntoExecutable},
 };
-use anyhow::anyhow;
-use clap::ValueEnum;
+use anyhow::{anyhow, Context};
+use clap::{builder::PossibleValue, ValueEnum};
 use derive_more::Display;
-use log::{debug, info};
+use log::{debug, info, trace};
 use std::{
     env,
     ffi::OsStr,
     fmt::{Debug, Formatter, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
 
-/// https://en.wikipedia.org/wiki/PATH_(variable)
-const PATH_VARIABLE: &str = "PATH";
-
 /// In each wrapper, this key will be replaced by the path to env-select
 const BINARY_REPLACEMENT_KEY: &str = "ENV_SELECT_BINARY";
 const BASH_WRAPPER: &str = include_str!("../shells/es.sh");
 const ZSH_WRAPPER: &str = include_str!("../shells/es.sh");
 const FISH_WRAPPER: &str = include_str!("../shells/es.fish");
+const SH_WRAPPER: &str = include_str!("../shells/es.posix.sh");
+const DIRENV_SHIM: &str = include_str!("../shells/direnvrc.sh");
 
 /// A pointer to a specific type of shell
 #[derive(Clone, Debug)]
@@ -36,7 +35,7 @@ pub struct Shell {
 
 /// A supported kind of shell. The display implementation here defines the
 /// binary name that we'll use to invoke it
-#[derive(Copy, Clone, Debug, Display, ValueEnum)]
+#[derive(Copy, Clone, Debug, Display, Eq, PartialEq, ValueEnum)]
 pub enum ShellKind {
     #[display("bash")]
     Bash,
@@ -44,12 +43,86 @@ pub enum ShellKind {
     Zsh,
     #[display("fish")]
     Fish,
+    /// POSIX sh, e.g. dash
+    #[display("sh")]
+    #[value(alias = "dash")]
+    Sh,
+}
+
+/// Value of the `--shell` argument: either a specific shell, or a detection
+/// mode
+#[derive(Copy, Clone, Debug)]
+pub enum ShellArg {
+    Kind(ShellKind),
+    /// Detect the shell from the parent process chain, preferring it over
+    /// $SHELL when they disagree
+    AutoStrict,
 }
 
+impl ValueEnum for ShellArg {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[
+            Self::Kind(ShellKind::Bash),
+            Self::Kind(ShellKind::Zsh),
+            Self::Kind(ShellKind::Fish),
+            Self::Kind(ShellKind::Sh),
+            Self::AutoStrict,
+        ]
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        match self {
+            Self::Kind(kind) => kind.to_possible_value(),
+            Self::AutoStrict => Some(
+                PossibleValue::new("auto-strict")
+                    .help("Detect from the parent process, then $SHELL"),
+            ),
+        }
+    }
+}
+
+/// Maximum number of ancestor processes to check when looking for a shell
+const MAX_PARENT_DEPTH: usize = 16;
+
 impl Shell {
-    /// Detect the current shell from the $SHELL variable.
-    pub fn detect() -> anyhow::Result<Self> {
-        let path = env::var("SHELL")?;
+    /// Detect the current shell. $SHELL is the login shell, which isn't
+    /// necessarily the one running, e.g. if fish was launched from bash. In
+    /// strict mode, the nearest shell in the parent process chain is used
+    /// instead, and $SHELL is only a fallback. Otherwise, $SHELL is used, and
+    /// the parent process chain is only checked if $SHELL isn't usable.
+    pub fn detect(strict: bool) -> anyhow::Result<Self> {
+        let from_env = Self::from_env();
+        if !strict {
+            return from_env.or_else(|error| {
+                debug!("{error}; checking parent processes instead");
+                Self::from_parent_process().ok_or(error)
+            });
+        }
+
+        match (Self::from_parent_process(), from_env) {
+            (Some(parent), Ok(from_env)) => {
+                if parent.kind != from_env.kind {
+                    debug!(
+                        "Parent process shell ({parent}) disagrees with \
+                        $SHELL ({from_env}); using the parent process"
+                    );
+                }
+                Ok(parent)
+            }
+            (Some(parent), Err(_)) => Ok(parent),
+            (None, from_env) => {
+                debug!(
+                    "No known shell in parent processes; falling back to \
+                    $SHELL"
+                );
+                from_env
+            }
+        }
+    }
+
+    /// Detect the current shell from the $SHELL variable
+    fn from_env() -> anyhow::Result<Self> {
+        let path = env::var("SHELL").context("$SHELL is not set")?;
         debug!("Detected shell path from $SHELL: {path}");
         let path_buf = PathBuf::from(&path);
         let shell_name = path_buf
@@ -65,28 +138,39 @@ impl Shell {
         })
     }
 
+    /// Walk up the parent process chain to find the nearest process that's a
+    /// supported shell
+    fn from_parent_process() -> Option<Self> {
+        let mut pid = parent_pid(std::process::id())?;
+        for _ in 0..MAX_PARENT_DEPTH {
+            // Stop at init, which is never a shell
+            if pid <= 1 {
+                break;
+            }
+            let name = process_name(pid)?;
+            // Login shells are prefixed with `-`, e.g. `-bash`
+            let name = name.trim_start_matches('-');
+            if let Ok(kind) = ShellKind::from_str(name, true) {
+                let path = process_path(pid);
+                info!(
+                    "Detected shell type from parent process {pid} \
+                    ({name}): {kind}"
+                );
+                return Some(Self { kind, path });
+            }
+            trace!("Parent process {pid} ({name}) is not a known shell");
+            pid = parent_pid(pid)?;
+        }
+        debug!("No known shell found in parent processes");
+        None
+    }
+
     /// Create a shell of the given kind. Since no path is given, we'll just
     /// hope it's in PATH if we ever need to execute it.
     pub fn from_kind(kind: ShellKind) -> Self {
         Self { path: None, kind }
     }
 
-    /// Is the given variable the PATH variable? PATH gets special functionality
-    /// to prepend instead of replacing
-    pub fn is_path_variable(variable: &str) -> bool {
-        variable == PATH_VARIABLE
-    }
-
-    /// Add a new directory to the *beginning* of the PATH variable. This will
-    /// give the new directory priority over all others. It stands to reason
-    /// that if a user is adding a directory specifically for one environment,
-    /// they would want it to override any potential duplicates.
-    pub fn prepend_path(new_path: String) -> String {
-        env::var(PATH_VARIABLE)
-            .map(|full_path| format!("{new_path}:{full_path}"))
-            .unwrap_or(new_path)
-    }
-
     /// Get a valid shell script that will initialize the `es` wrapper as well
     /// as whatever other initialization is needed. The script should be piped
     /// to `source`.
@@ -95,21 +179,38 @@ impl Shell {
             ShellKind::Bash => BASH_WRAPPER,
             ShellKind::Zsh => ZSH_WRAPPER,
             ShellKind::Fish => FISH_WRAPPER,
+            ShellKind::Sh => SH_WRAPPER,
         };
 
-        // Inject the path of the current binary into the script. This prevents
-        // any need to modify PATH
-        Ok(wrapper_template.replace(
-            BINARY_REPLACEMENT_KEY,
-            &env::current_exe()?.display().to_string(),
-        ))
+        inject_binary(wrapper_template)
+    }
+
+    /// Get a script for direnv's `direnvrc` that defines `use env_select`, so
+    /// `.envrc` files can load profiles. direnv always runs bash, so this
+    /// doesn't depend on the user's shell.
+    pub fn direnv_script() -> anyhow::Result<String> {
+        inject_binary(DIRENV_SHIM)
     }
 
     /// Get the shell command(s) that will configure the environment to a
     /// particular set of key=value pairs for this shell type. This command
-    /// can later be piped to the source command to apply it.
+    /// can later be piped to the source command to apply it. Unset variables
+    /// are removed.
     pub fn export(&self, environment: &Environment) -> String {
         let mut output = String::new();
+        for variable in environment.iter_unset() {
+            let variable = self.escape(variable);
+            match self.kind {
+                ShellKind::Bash | ShellKind::Zsh | ShellKind::Sh => {
+                    writeln!(output, "unset {variable}")
+                        .expect("string writing is infallible");
+                }
+                ShellKind::Fish => {
+                    writeln!(output, "set -e {variable}")
+                        .expect("string writing is infallible");
+                }
+            }
+        }
         for (variable, value) in environment.iter_unmasked() {
             // Escape single quotes to prevent injection vulnerabilities
             let variable = self.escape(variable);
@@ -117,7 +218,7 @@ impl Shell {
 
             // Generate a shell command to export the variable
             match self.kind {
-                ShellKind::Bash | ShellKind::Zsh => {
+                ShellKind::Bash | ShellKind::Zsh | ShellKind::Sh => {
                     writeln!(output, "export {variable}={value}")
                         .expect("string writing is infallible");
                 }
@@ -130,6 +231,18 @@ impl Shell {
         output
     }
 
+    /// Get a sourceable command that changes the working directory. The
+    /// syntax is the same in all supported shells, besides escaping
+    pub fn change_directory(&self, directory: &Path) -> String {
+        format!("cd {}\n", self.escape(&directory.to_string_lossy()))
+    }
+
+    /// Get a sourceable command that empties a file. The syntax is the same
+    /// in all supported shells, besides escaping
+    pub fn truncate_file(&self, path: &Path) -> String {
+        format!("true > {}\n", self.escape(&path.to_string_lossy()))
+    }
+
     /// Get an [Executable] command to run in this shell, from a shell command
     pub fn executable(&self, command: &ShellCommand) -> Executable {
         // Use the full shell path if we have it. Otherwise, just pass
@@ -173,19 +286,102 @@ impl Shell {
     /// Wrap the given string in single quotes, escaping inner single quotes.
     /// This makes it safe to pass as a shell argument without possibility of
     /// injection.
-    fn escape(&self, value: &str) -> String {
+    pub fn escape(&self, value: &str) -> String {
         let escaped = match self.kind {
-            // Bash and zsh don't support escaping ' with just a backslash.
+            // Bash, zsh, and sh don't support escaping ' with just a backslash.
             // You have to terminate the current string, add a single quote,
             // then open a new string
             // https://stackoverflow.com/a/1250279/1907353
-            ShellKind::Bash | ShellKind::Zsh => value.replace('\'', "'\\''"),
+            ShellKind::Bash | ShellKind::Zsh | ShellKind::Sh => {
+                value.replace('\'', "'\\''")
+            }
             ShellKind::Fish => value.replace('\'', "\\'"),
         };
         format!("'{escaped}'")
     }
 }
 
+/// Inject the path of the current binary into a script. This prevents any need
+/// to modify PATH
+fn inject_binary(template: &str) -> anyhow::Result<String> {
+    Ok(template.replace(
+        BINARY_REPLACEMENT_KEY,
+        &env::current_exe()?.display().to_string(),
+    ))
+}
+
+/// Get the ID of a process's parent
+#[cfg(target_os = "linux")]
+fn parent_pid(pid: u32) -> Option<u32> {
+    let stat = std::fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+    parse_stat_parent(&stat)
+}
+
+/// Get the ID of a process's parent
+#[cfg(all(unix, not(target_os = "linux")))]
+fn parent_pid(pid: u32) -> Option<u32> {
+    ps(pid, "ppid=")?.parse().ok()
+}
+
+#[cfg(not(unix))]
+fn parent_pid(_: u32) -> Option<u32> {
+    None
+}
+
+/// Get the name of a process's binary
+#[cfg(target_os = "linux")]
+fn process_name(pid: u32) -> Option<String> {
+    let comm = std::fs::read_to_string(format!("/proc/{pid}/comm")).ok()?;
+    Some(comm.trim_end().to_owned())
+}
+
+/// Get the name of a process's binary. `ps` may give the full path
+#[cfg(all(unix, not(target_os = "linux")))]
+fn process_name(pid: u32) -> Option<String> {
+    let comm = ps(pid, "comm=")?;
+    let name = Path::new(&comm).file_name()?.to_str()?;
+    Some(name.to_owned())
+}
+
+#[cfg(not(unix))]
+fn process_name(_: u32) -> Option<String> {
+    None
+}
+
+/// Get the full path of a process's binary, if we're allowed to see it
+#[cfg(target_os = "linux")]
+fn process_path(pid: u32) -> Option<String> {
+    let path = std::fs::read_link(format!("/proc/{pid}/exe")).ok()?;
+    Some(path.to_str()?.to_owned())
+}
+
+/// Get the full path of a process's binary, if `ps` gives one
+#[cfg(not(target_os = "linux"))]
+fn process_path(_: u32) -> Option<String> {
+    None
+}
+
+/// Get a single field for a process from `ps`
+#[cfg(all(unix, not(target_os = "linux")))]
+fn ps(pid: u32, field: &str) -> Option<String> {
+    let output = std::process::Command::new("ps")
+        .args(["-o", field, "-p", &pid.to_string()])
+        .output()
+        .ok()?;
+    let output = String::from_utf8(output.stdout).ok()?;
+    Some(output.trim().to_owned())
+}
+
+/// Get the parent process ID from the contents of `/proc/<pid>/stat`. The
+/// process name is in parentheses, and can contain spaces and parentheses
+/// itself, so find the fields after the *last* closing parenthesis
+#[cfg(any(target_os = "linux", test))]
+fn parse_stat_parent(stat: &str) -> Option<u32> {
+    let (_, fields) = stat.rsplit_once(')')?;
+    // Fields are: state, ppid, ...
+    fields.split_whitespace().nth(1)?.parse().ok()
+}
+
 impl Display for Shell {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match &self.path {
@@ -206,22 +402,27 @@ mod tests {
     use super::*;
     use crate::{
         config::Profile,
-        test_util::{all_shells, literal, map},
+        console::PromptMode,
+        environment::{Base, ResolveOptions},
+        test_util::{all_shells, literal, map, unset},
     };
     use assert_cmd::Command;
     use rstest::rstest;
     use rstest_reuse::apply;
 
-    /// Bash and Zsh use the same export format so we can test them together
+    /// Bash, Zsh, and sh use the same export format so we can test them
+    /// together
     #[rstest]
     fn test_bash_zsh_export(
-        #[values(ShellKind::Bash, ShellKind::Zsh)] shell_kind: ShellKind,
+        #[values(ShellKind::Bash, ShellKind::Zsh, ShellKind::Sh)]
+        shell_kind: ShellKind,
     ) {
         let shell = Shell::from_kind(shell_kind);
         let environment = environment(&shell);
         assert_eq!(
             shell.export(&environment).as_str(),
             "\
+unset 'REMOVED'
 export 'SIMPLE'='simple'
 export 'ESCAPED'\\''oops'\\'''=''\\''; echo bobby tables '\\'''
 "
@@ -236,12 +437,54 @@ export 'ESCAPED'\\''oops'\\'''=''\\''; echo bobby tables '\\'''
         assert_eq!(
             shell.export(&environment).as_str(),
             "\
+set -e 'REMOVED'
 set -gx 'SIMPLE' 'simple'
 set -gx 'ESCAPED\\'oops\\'' '\\'; echo bobby tables \\''
 "
         );
     }
 
+    #[rstest]
+    #[case::bash(ShellKind::Bash, "cd '/root/it'\\''s here'\n")]
+    #[case::zsh(ShellKind::Zsh, "cd '/root/it'\\''s here'\n")]
+    #[case::fish(ShellKind::Fish, "cd '/root/it\\'s here'\n")]
+    #[case::sh(ShellKind::Sh, "cd '/root/it'\\''s here'\n")]
+    fn test_change_directory(
+        #[case] shell_kind: ShellKind,
+        #[case] expected: &str,
+    ) {
+        let shell = Shell::from_kind(shell_kind);
+        assert_eq!(
+            shell.change_directory(Path::new("/root/it's here")),
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case::simple("123 (bash) S 100 123 123", Some(100))]
+    #[case::spaces("123 (my shell) S 100 123 123", Some(100))]
+    #[case::parens("123 (a) b) S 100 123 123", Some(100))]
+    #[case::invalid("123 bash S 100", None)]
+    fn test_parse_stat_parent(
+        #[case] stat: &str,
+        #[case] expected: Option<u32>,
+    ) {
+        assert_eq!(parse_stat_parent(stat), expected);
+    }
+
+    #[rstest]
+    #[case::bash(ShellKind::Bash, "true > '/tmp/it'\\''s here'\n")]
+    #[case::zsh(ShellKind::Zsh, "true > '/tmp/it'\\''s here'\n")]
+    #[case::fish(ShellKind::Fish, "true > '/tmp/it\\'s here'\n")]
+    #[case::sh(ShellKind::Sh, "true > '/tmp/it'\\''s here'\n")]
+    fn test_truncate_file(
+        #[case] shell_kind: ShellKind,
+        #[case] expected: &str,
+    ) {
+        let shell = Shell::from_kind(shell_kind);
+        assert_eq!(shell.truncate_file(Path::new("/tmp/it's here")), expected);
+    }
+
     fn environment(shell: &Shell) -> Environment {
         Environment::from_profile(
             shell,
@@ -249,9 +492,18 @@ set -gx 'ESCAPED\\'oops\\'' '\\'; echo bobby tables \\''
                 variables: map([
                     ("SIMPLE", literal("simple")),
                     ("ESCAPED'oops'", literal("'; echo bobby tables '")),
+                    ("REMOVED", unset()),
                 ]),
                 ..Default::default()
             },
+            &ResolveOptions {
+                use_cache: false,
+                strict_templates: true,
+                strict_conflicts: false,
+                sensitive_patterns: Vec::new(),
+                base: Base::Inherit,
+                prompt_mode: PromptMode::Disabled,
+            },
         )
         .unwrap()
     }
@@ -0,0 +1,101 @@
+//! Writing sourceable output for the shell wrapper, via `--source-file`. The
+//! output can contain secrets, so it's kept away from other users, and the
+//! wrapper should never see a partially written file. `es export --output`
+//! writes unmasked values the same way, as does the value cache.
+
+use anyhow::Context;
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    process,
+};
+
+/// Write sensitive content to the given file. The content is written to a
+/// temporary file in the same directory, then renamed over the target, so if
+/// we die partway through, the target is left as it was. The file is only
+/// readable by the current user.
+pub fn write(path: &Path, content: &str) -> anyhow::Result<()> {
+    let temp_path = temp_path(path);
+    write_new(&temp_path, content)
+        .and_then(|()| {
+            fs::rename(&temp_path, path).with_context(|| {
+                format!("Error renaming {temp_path:?} to {path:?}")
+            })
+        })
+        .inspect_err(|_| {
+            // Don't leave a half-written file lying around
+            let _ = fs::remove_file(&temp_path);
+        })
+}
+
+/// Path of the temporary file to write before renaming into place. It's in
+/// the same directory, because renames can't cross file systems
+fn temp_path(path: &Path) -> PathBuf {
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{file_name}.{}.tmp", process::id()))
+}
+
+/// Write content to a new file that only the current user can read. The
+/// permissions are set on creation, so there's no window where the content
+/// is readable by others
+fn write_new(path: &Path, content: &str) -> anyhow::Result<()> {
+    // Clear out a leftover from a previous run with the same PID
+    let _ = fs::remove_file(path);
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options
+        .open(path)
+        .with_context(|| format!("Error opening {path:?}"))?;
+    file.write_all(content.as_bytes())
+        .with_context(|| format!("Error writing to {path:?}"))?;
+    file.sync_all()
+        .with_context(|| format!("Error writing to {path:?}"))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_write() {
+        let path = env::temp_dir().join("es-test-source-file");
+        fs::write(&path, "old").unwrap();
+
+        write(&path, "export 'A'='1'\n").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "export 'A'='1'\n");
+        assert!(!temp_path(&path).exists());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+
+    /// If writing fails partway, the target keeps its old content instead of
+    /// partial shell syntax
+    #[test]
+    fn test_write_failure() {
+        let path = env::temp_dir().join("es-test-source-file-failure");
+        fs::write(&path, "").unwrap();
+        // A directory in the way of the temp file makes the write fail
+        let temp_path = temp_path(&path);
+        let _ = fs::remove_dir(&temp_path);
+        fs::create_dir(&temp_path).unwrap();
+
+        assert!(write(&path, "export 'A'='1'\n").is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "");
+        fs::remove_dir(&temp_path).unwrap();
+    }
+}
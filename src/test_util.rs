@@ -1,12 +1,18 @@
 //! Utilities for tests!
 
 use crate::config::{
-    Application, Config, MultiVariable, Name, Profile, ProfileReference,
-    SideEffect, ValueSource, ValueSourceInner, ValueSourceKind,
+    Application, Config, ConfigDuration, Defaults, Encoding, MultiVariable,
+    MultiVariableFormat, Name, Platform, Profile, ProfileReference, SideEffect,
+    Transform, ValueMode, ValueSource, ValueSourceKind,
 };
 use indexmap::{IndexMap, IndexSet};
 use rstest_reuse::{self, *};
-use std::{hash::Hash, path::Path};
+use std::{
+    fmt::{Debug, Display},
+    hash::Hash,
+    path::Path,
+    time::Duration,
+};
 
 impl From<&str> for Name {
     fn from(value: &str) -> Self {
@@ -20,20 +26,57 @@ impl From<&str> for ProfileReference {
     }
 }
 
-impl From<ValueSourceKind> for ValueSource {
-    fn from(kind: ValueSourceKind) -> Self {
-        Self(ValueSourceInner {
-            kind,
-            sensitive: false,
-            multiple: false.into(),
-        })
-    }
-}
-
 // Builder-like functions to make it easy to create value sources
 impl ValueSource {
     pub fn sensitive(mut self) -> Self {
-        self.0.sensitive = true;
+        self.0.sensitive = Some(true);
+        self
+    }
+
+    pub fn not_sensitive(mut self) -> Self {
+        self.0.sensitive = Some(false);
+        self
+    }
+
+    pub fn encoding(mut self, encoding: Encoding) -> Self {
+        self.0.encoding = Some(encoding);
+        self
+    }
+
+    pub fn transform(mut self, transform: Transform) -> Self {
+        self.0.transform.push(transform);
+        self
+    }
+
+    pub fn default(mut self, default: &str) -> Self {
+        self.0.default = Some(default.to_owned());
+        self
+    }
+
+    pub fn optional(mut self) -> Self {
+        self.0.optional = true;
+        self
+    }
+
+    pub fn timeout(mut self, seconds: u64) -> Self {
+        self.0.timeout = Some(ConfigDuration(Duration::from_secs(seconds)));
+        self
+    }
+
+    pub fn retries(mut self, retries: u32, delay_seconds: u64) -> Self {
+        self.0.retries = retries;
+        self.0.retry_delay =
+            Some(ConfigDuration(Duration::from_secs(delay_seconds)));
+        self
+    }
+
+    pub fn platform(mut self, platform: Platform) -> Self {
+        self.0.platform = Some(platform);
+        self
+    }
+
+    pub fn no_trim(mut self) -> Self {
+        self.0.trim = false;
         self
     }
 
@@ -42,6 +85,31 @@ impl ValueSource {
         self
     }
 
+    pub fn multiple_format(mut self, format: MultiVariableFormat) -> Self {
+        self.0.multiple_format = format;
+        self
+    }
+
+    pub fn prefix(mut self, prefix: &str) -> Self {
+        self.0.prefix = Some(prefix.to_owned());
+        self
+    }
+
+    pub fn mode(mut self, mode: ValueMode) -> Self {
+        self.0.mode = Some(mode);
+        self
+    }
+
+    pub fn dedupe(mut self, dedupe: bool) -> Self {
+        self.0.dedupe = Some(dedupe);
+        self
+    }
+
+    pub fn separator(mut self, separator: &str) -> Self {
+        self.0.separator = Some(separator.to_owned());
+        self
+    }
+
     pub fn multiple_filtered(mut self, values: &[&str]) -> Self {
         self.0.multiple = MultiVariable::List(
             values.iter().copied().map(String::from).collect(),
@@ -49,9 +117,43 @@ impl ValueSource {
         self
     }
 
+    pub fn multiple_renamed(mut self, variables: &[(&str, &str)]) -> Self {
+        self.0.multiple = MultiVariable::Map(
+            variables
+                .iter()
+                .map(|(from, to)| ((*from).to_owned(), (*to).to_owned()))
+                .collect(),
+        );
+        self
+    }
+
+    pub fn use_profile_env(mut self) -> Self {
+        match &mut self.0.kind {
+            ValueSourceKind::Command {
+                use_profile_env, ..
+            }
+            | ValueSourceKind::NativeCommand {
+                use_profile_env, ..
+            } => *use_profile_env = true,
+            _ => unimplemented!(),
+        }
+        self
+    }
+
+    pub fn stdin(mut self, input: &str) -> Self {
+        match &mut self.0.kind {
+            ValueSourceKind::Command { stdin, .. } => {
+                *stdin = Some(input.to_owned())
+            }
+            _ => unimplemented!(),
+        }
+        self
+    }
+
     pub fn cwd(mut self, cwd: &str) -> Self {
         match &mut self.0.kind {
-            ValueSourceKind::Command { cwd: dest, .. } => {
+            ValueSourceKind::Command { cwd: dest, .. }
+            | ValueSourceKind::NativeCommand { cwd: dest, .. } => {
                 *dest = Some(cwd.into())
             }
             _ => unimplemented!(),
@@ -69,6 +171,9 @@ pub fn config(applications: Vec<(&str, Vec<(&str, Profile)>)>) -> Config {
                 (
                     (*name).into(),
                     Application {
+                        aliases: IndexSet::new(),
+                        description: None,
+                        variables: IndexMap::new(),
                         profiles: profiles
                             .into_iter()
                             .map(|(name, profile)| ((*name).into(), profile))
@@ -77,14 +182,58 @@ pub fn config(applications: Vec<(&str, Vec<(&str, Profile)>)>) -> Config {
                 )
             })
             .collect(),
+        defaults: Defaults::default(),
+        imports: Vec::new(),
+        includes: Vec::new(),
+        strict_templates: None,
+        strict_conflicts: None,
+        masking: None,
+        sensitive_patterns: None,
+        root: false,
+        stop_at_home: false,
+        sources: Vec::new(),
+        definitions: Default::default(),
     }
 }
 
-/// Helper for building an IndexMap
-pub fn map<'a, K: Eq + Hash + PartialEq + From<&'a str>, V, const N: usize>(
+/// Assert that a result is the expected value, or an error with the expected
+/// message. The message includes the error's full context chain
+#[track_caller]
+pub fn assert_result<T, E, U>(result: Result<T, E>, expected: Result<U, &str>)
+where
+    T: Debug + PartialEq<U>,
+    E: Display,
+    U: Debug,
+{
+    match (result, expected) {
+        (Ok(actual), Ok(expected)) => assert_eq!(actual, expected),
+        (Err(error), Err(expected)) => {
+            assert_eq!(format!("{error:#}"), expected)
+        }
+        (Ok(actual), Err(expected)) => {
+            panic!("Expected error `{expected}`, got `{actual:?}`")
+        }
+        (Err(error), Ok(expected)) => {
+            panic!("Expected `{expected:?}`, got error `{error:#}`")
+        }
+    }
+}
+
+/// Helper for building an IndexMap. Values are converted, so value sources can
+/// be used directly as variable definitions
+pub fn map<
+    'a,
+    K: Eq + Hash + PartialEq + From<&'a str>,
+    V: Into<W>,
+    W,
+    const N: usize,
+>(
     items: [(&'a str, V); N],
-) -> IndexMap<K, V> {
-    items.into_iter().map(|(k, v)| (k.into(), v)).collect()
+) -> IndexMap<K, W> {
+    items
+        .into_iter()
+        .map(|(k, v)| (k.into(), v.into()))
+        .collect()
 }
 
 /// Helper for building an IndexSet
@@ -110,20 +259,77 @@ pub fn file(path: impl AsRef<Path>) -> ValueSource {
     .into()
 }
 
+/// Helper to create a .env file source
+pub fn dotenv(
+    path: impl AsRef<Path>,
+    only: Option<&[&str]>,
+    except: &[&str],
+) -> ValueSource {
+    let to_strings = |variables: &[&str]| {
+        variables.iter().copied().map(String::from).collect()
+    };
+    ValueSourceKind::Dotenv {
+        path: path.as_ref().to_owned(),
+        only: only.map(to_strings),
+        except: to_strings(except),
+    }
+    .into()
+}
+
+/// Helper to create a native command
+pub fn native_command(program: &str, args: &[&str]) -> ValueSource {
+    ValueSourceKind::NativeCommand {
+        program: program.to_owned(),
+        args: args.iter().copied().map(String::from).collect(),
+        cwd: None,
+        use_profile_env: false,
+    }
+    .into()
+}
+
+/// Helper to create a source that unsets its variable
+pub fn unset() -> ValueSource {
+    ValueSourceKind::Unset.into()
+}
+
 /// Helper to create a shell command
 pub fn command(command: &str) -> ValueSource {
     ValueSourceKind::Command {
         command: command.to_owned().into(),
         cwd: None,
+        stdin: None,
+        use_profile_env: false,
+    }
+    .into()
+}
+
+/// Helper to create a kubernetes command
+pub fn kubernetes(command: &[&str], pod_selector: &str) -> ValueSource {
+    ValueSourceKind::Kubernetes {
+        command: command.iter().copied().map(String::from).collect(),
+        pod_selector: pod_selector.to_owned(),
+        namespace: None,
+        container: None,
+    }
+    .into()
+}
+
+/// Helper to create a vault secret source
+pub fn vault(path: &str, field: Option<&str>) -> ValueSource {
+    ValueSourceKind::Vault {
+        path: path.to_owned(),
+        field: field.map(String::from),
+        mount: None,
     }
     .into()
 }
 
 /// Create a side effect from (setup, teardown)
 pub fn side_effect(setup: &str, teardown: &str) -> SideEffect {
-    SideEffect {
+    SideEffect::Shell {
         setup: Some(setup.to_owned().into()),
         teardown: Some(teardown.to_owned().into()),
+        background: false,
     }
 }
 
@@ -131,7 +337,7 @@ pub fn side_effect(setup: &str, teardown: &str) -> SideEffect {
 #[template]
 #[rstest]
 pub fn all_shells(
-    #[values(ShellKind::Bash, ShellKind::Zsh, ShellKind::Fish)]
+    #[values(ShellKind::Bash, ShellKind::Zsh, ShellKind::Fish, ShellKind::Sh)]
     shell_kind: ShellKind,
 ) {
 }
@@ -1,18 +1,27 @@
+// Not every test file uses every helper
+#![allow(dead_code, unused_macros)]
+
 use assert_cmd::Command;
 use rstest_reuse::{self, *};
-use std::path::{Path, PathBuf};
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
 
-/// Command to run env-select
+/// Command to run env-select. Selection history is kept out of the user's
+/// data directory
 pub fn env_select() -> Command {
     let mut command = Command::cargo_bin("es").unwrap();
-    command.current_dir(tests_dir());
+    command
+        .current_dir(tests_dir())
+        .env("XDG_DATA_HOME", data_dir());
     command
 }
 
 /// Test template to run test with all shells
 #[template]
 #[rstest]
-pub fn all_shells(#[values("bash", "zsh", "fish")] shell_kind: &str) {}
+pub fn all_shells(#[values("bash", "zsh", "fish", "sh")] shell_kind: &str) {}
 
 /// Get the path to the given shell
 pub fn shell_path(shell_kind: &str) -> PathBuf {
@@ -62,10 +71,61 @@ pub fn execute_script(
         // Run from the tests/ directory, so we can use a dedicated config
         .current_dir(tests_dir())
         .env("SHELL", &shell)
+        .env("XDG_DATA_HOME", data_dir())
         .args(["-c", &script]);
     command
 }
 
-fn tests_dir() -> PathBuf {
+/// Directory for env-select to store data in during tests
+fn data_dir() -> PathBuf {
+    env::temp_dir().join("es-test-data")
+}
+
+/// Directory that env-select runs from during tests
+pub fn tests_dir() -> PathBuf {
     Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/")
 }
+
+/// Config with a profile that switches the kubectl context, then fails to
+/// resolve a variable. Use with [fake_kubectl]
+pub const FAILING_KUBERNETES_CONFIG: &str = r#"
+[applications.app.profiles.kube]
+kubernetes_context = "new"
+variables.BROKEN = {type = "command", command = "false"}
+"#;
+
+/// Set up a fake `kubectl` that records each call in a log file, and claims
+/// the current context is `old`. Return `es` configured to use it with
+/// [FAILING_KUBERNETES_CONFIG], and the path of the log.
+#[cfg(unix)]
+pub fn fake_kubectl(name: &str) -> (Command, PathBuf) {
+    use std::{fs, os::unix::fs::PermissionsExt};
+
+    let dir = env::temp_dir().join(format!("es-test-kubectl-{name}"));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let log = dir.join("log");
+    let kubectl = dir.join("kubectl");
+    fs::write(
+        &kubectl,
+        format!(
+            "#!/bin/sh\necho \"$*\" >> '{}'\n\
+            [ \"$2\" = current-context ] && echo old\ntrue\n",
+            log.display()
+        ),
+    )
+    .unwrap();
+    fs::set_permissions(&kubectl, fs::Permissions::from_mode(0o755)).unwrap();
+    let config = dir.join("config.toml");
+    fs::write(&config, FAILING_KUBERNETES_CONFIG).unwrap();
+
+    let path = env::join_paths(
+        [dir.clone()]
+            .into_iter()
+            .chain(env::split_paths(&env::var_os("PATH").unwrap_or_default())),
+    )
+    .unwrap();
+    let mut command = env_select();
+    command.env("PATH", path).arg("--config").arg(config);
+    (command, log)
+}
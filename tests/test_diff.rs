@@ -0,0 +1,116 @@
+//! Test the `diff` subcommand
+
+mod common;
+
+use common::*;
+use rstest::rstest;
+use std::{env, fs};
+
+/// Test comparing two profiles, in each output format. Side effects don't
+/// run
+#[rstest]
+#[case::table(
+    &["test", "p1", "empty"],
+    "Only in test/p1:
+  VAR1 = abc
+  VAR2 = def
+  FILE_VAR1 = 123
+"
+)]
+#[case::json(
+    &["test", "empty", "p1", "--format", "json"],
+    r#"{
+  "left": "test/empty",
+  "right": "test/p1",
+  "only_left": {},
+  "only_right": {
+    "VAR1": "abc",
+    "VAR2": "def",
+    "FILE_VAR1": "123"
+  },
+  "changed": {}
+}
+"#
+)]
+#[case::same(&["test", "p1", "p1"], "No differences\n")]
+fn test_diff(#[case] arguments: &[&str], #[case] expected: &str) {
+    env_select()
+        .arg("diff")
+        .args(arguments)
+        .assert()
+        .success()
+        .stdout(expected.to_owned())
+        .stderr("");
+}
+
+/// Test `--current` compares against the shell's values of the variables
+/// that the profile sets
+#[test]
+fn test_diff_current() {
+    env_select()
+        .args(["diff", "test", "p1", "--current"])
+        .env("VAR1", "abc")
+        .env("VAR2", "xyz")
+        .env_remove("FILE_VAR1")
+        .env("UNRELATED", "ignored")
+        .assert()
+        .success()
+        .stdout(
+            "Only in test/p1:
+  FILE_VAR1 = 123
+Changed (test/p1 -> current):
+  VAR2 = def -> xyz
+",
+        );
+}
+
+/// Test `--current` masks shell values of variables that the profile marks
+/// sensitive, including ones it unsets, and of variables matching the
+/// sensitive patterns
+#[test]
+fn test_diff_current_sensitive() {
+    let path = env::temp_dir().join("es-test-diff-current-sensitive.toml");
+    fs::write(
+        &path,
+        r#"
+sensitive_patterns = ["*_TOKEN"]
+
+[applications.app.profiles.dev.variables]
+SECRET = {type = "literal", value = "hunter2", sensitive = true}
+GONE = {type = "unset", sensitive = true}
+API_TOKEN = "abc"
+"#,
+    )
+    .unwrap();
+
+    env_select()
+        .arg("--config")
+        .arg(&path)
+        .args(["diff", "app", "dev", "--current"])
+        .env("SECRET", "hunter3")
+        .env("GONE", "hunter4")
+        .env("API_TOKEN", "xyz")
+        .assert()
+        .success()
+        .stdout(
+            "Only in current:
+  GONE = <REDACTED>
+Changed (app/dev -> current):
+  SECRET = <REDACTED> -> <REDACTED>
+  API_TOKEN = <REDACTED> -> <REDACTED>
+",
+        );
+}
+
+/// Test a second profile or `--current` is required, but not both
+#[rstest]
+#[case::neither(&["test", "p1"])]
+#[case::both(&["test", "p1", "empty", "--current"])]
+fn test_diff_invalid_arguments(#[case] arguments: &[&str]) {
+    env_select()
+        .arg("diff")
+        .args(arguments)
+        .assert()
+        .failure()
+        .code(2);
+}
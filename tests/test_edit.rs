@@ -0,0 +1,139 @@
+//! Test the `edit` subcommand
+
+mod common;
+
+use common::*;
+use rstest::rstest;
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+/// Create a fresh directory to run `es edit` in, with a config file and an
+/// editor script that appends the given text to whatever file it's given
+fn setup(name: &str, appended: &str) -> (PathBuf, PathBuf) {
+    let directory = env::temp_dir().join(format!("es-test-edit-{name}"));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+    let directory = directory.canonicalize().unwrap();
+    fs::write(
+        directory.join(".env-select.toml"),
+        "[applications.app.profiles.base]\n",
+    )
+    .unwrap();
+    let editor = directory.join("editor.sh");
+    fs::write(&editor, format!("printf '{appended}' >> \"$1\"\n")).unwrap();
+    (directory, editor)
+}
+
+fn edit(directory: &Path, editor: &Path) -> assert_cmd::Command {
+    edit_with(directory, editor, &[])
+}
+
+/// Build an `es edit` command, with global arguments before the subcommand
+fn edit_with(
+    directory: &Path,
+    editor: &Path,
+    global_arguments: &[&str],
+) -> assert_cmd::Command {
+    let mut command = env_select();
+    command
+        .current_dir(directory)
+        .env_remove("ES_CONFIG_FILE")
+        .env_remove("VISUAL")
+        .env("EDITOR", format!("sh {}", editor.display()))
+        .env("XDG_CONFIG_HOME", directory.join("config"))
+        .args(global_arguments)
+        .arg("edit");
+    command
+}
+
+/// The nearest config file is edited, then validated
+#[test]
+fn test_edit_valid() {
+    let (directory, editor) = setup(
+        "valid",
+        "[applications.app.profiles.dev]\\nextends = [\"base\"]\\n",
+    );
+    let path = directory.join(".env-select.toml");
+    edit(&directory, &editor)
+        .assert()
+        .success()
+        .stdout(format!("{} is valid\n", path.display()));
+    assert!(fs::read_to_string(&path)
+        .unwrap()
+        .contains("[applications.app.profiles.dev]"));
+}
+
+/// Errors introduced while editing are reported right away
+#[test]
+fn test_edit_invalid() {
+    let (directory, editor) = setup(
+        "invalid",
+        "[applications.app.profiles.dev]\\nextends = [\"missing\"]\\n",
+    );
+    let assert = edit(&directory, &editor).assert().failure();
+    let stderr = String::from_utf8(assert.get_output().stderr.clone()).unwrap();
+    assert!(
+        stderr.contains("was saved, but the config is invalid")
+            && stderr.contains("Unknown profile: app/missing"),
+        "Unexpected stderr: {stderr}"
+    );
+}
+
+/// A config file given with `--config` or `ES_CONFIG_FILE` is edited instead
+/// of the nearest one, and validated on its own
+#[rstest]
+#[case::argument(true)]
+#[case::variable(false)]
+fn test_edit_config_file(#[case] argument: bool) {
+    let (directory, editor) = setup(
+        &format!("config-file-{argument}"),
+        "[applications.app.profiles.dev]\\nextends = [\"other\"]\\n",
+    );
+    let path = directory.join("other.toml");
+    fs::write(&path, "[applications.app.profiles.other]\n").unwrap();
+    let mut command = if argument {
+        edit_with(&directory, &editor, &["--config", "other.toml"])
+    } else {
+        let mut command = edit(&directory, &editor);
+        command.env("ES_CONFIG_FILE", "other.toml");
+        command
+    };
+    command
+        .assert()
+        .success()
+        .stdout(format!("{} is valid\n", path.display()));
+    assert!(fs::read_to_string(&path)
+        .unwrap()
+        .contains("[applications.app.profiles.dev]"));
+    assert!(!fs::read_to_string(directory.join(".env-select.toml"))
+        .unwrap()
+        .contains("[applications.app.profiles.dev]"));
+}
+
+/// `--global` creates the global config file from a template
+#[test]
+fn test_edit_global() {
+    let (directory, editor) = setup("global", "");
+    let path = directory.join("config/env-select/config.toml");
+    edit(&directory, &editor)
+        .arg("--global")
+        .assert()
+        .success()
+        .stdout(format!("{} is valid\n", path.display()));
+    assert!(fs::read_to_string(&path)
+        .unwrap()
+        .starts_with("# Personal profiles"));
+}
+
+/// Without a config file, there's nothing to edit
+#[test]
+fn test_edit_no_file() {
+    let (directory, editor) = setup("no-file", "");
+    fs::remove_file(directory.join(".env-select.toml")).unwrap();
+    edit(&directory, &editor).assert().failure().stderr(
+        "[ERROR] No config file found in the current directory or its \
+        ancestors\n",
+    );
+}
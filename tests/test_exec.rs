@@ -0,0 +1,71 @@
+//! Test the `exec` subcommand
+
+mod common;
+
+use common::*;
+use std::{env, path::Path};
+
+/// Test `es exec` runs setup side effects, then runs the program directly
+/// with the profile's variables. There's no teardown
+#[test]
+fn test_exec_subcommand() {
+    env_select()
+        .args(["-s", "bash", "exec", "test", "p1", "--"])
+        .args(["printenv", "VAR1", "VAR2", "FILE_VAR1"])
+        .assert()
+        .success()
+        .stdout(
+            "pre setup 1
+pre setup 2
+post setup 1 abc
+post setup 2 abc
+abc
+def
+123
+",
+        )
+        .stderr("");
+}
+
+/// Arguments are passed without going through a shell
+#[test]
+fn test_exec_no_shell() {
+    env_select()
+        .args(["-s", "bash", "exec", "test", "empty", "--"])
+        .args(["echo", "$HOME", "'quoted'"])
+        .assert()
+        .success()
+        .stdout("$HOME 'quoted'\n");
+}
+
+/// The program replaces env-select, so it keeps the same PID
+#[cfg(unix)]
+#[test]
+fn test_exec_replaces_process() {
+    let script = format!(
+        "'{es}' -s bash exec test empty -- sh -c 'echo $$' &
+        echo $!
+        wait",
+        es = assert_cmd::cargo::cargo_bin("es").display(),
+    );
+    let assert = assert_cmd::Command::new("bash")
+        .current_dir(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests"))
+        .env("XDG_DATA_HOME", env::temp_dir().join("es-test-data"))
+        .args(["-c", &script])
+        .assert()
+        .success();
+    let stdout = String::from_utf8(assert.get_output().stdout.clone()).unwrap();
+    let pids: Vec<&str> = stdout.lines().collect();
+    assert_eq!(pids.len(), 2, "Unexpected output: {stdout}");
+    assert_eq!(pids[0], pids[1]);
+}
+
+/// A program that can't be found is an error
+#[test]
+fn test_exec_not_found() {
+    env_select()
+        .args(["-s", "bash", "exec", "test", "empty", "--"])
+        .arg("es-test-not-a-program")
+        .assert()
+        .failure();
+}
@@ -0,0 +1,132 @@
+//! Test the `export` subcommand
+
+mod common;
+
+use common::*;
+use rstest::rstest;
+use std::{env, fs, path::PathBuf};
+
+/// Write a config file with a sensitive and an unset variable
+fn config_file(name: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!("es-test-export-{name}.toml"));
+    fs::write(
+        &path,
+        r#"
+[applications.app.profiles.dev.variables]
+VAR1 = "one two"
+SECRET = {type = "literal", value = "hunter2", sensitive = true}
+GONE = {type = "unset"}
+"#,
+    )
+    .unwrap();
+    path
+}
+
+/// Test each output format of `export`. Sensitive values are *not* masked
+#[rstest]
+#[case::dotenv("dotenv", "VAR1='one two'\nSECRET='hunter2'\n")]
+#[case::json(
+    "json",
+    r#"{
+  "VAR1": "one two",
+  "SECRET": "hunter2"
+}
+"#
+)]
+#[case::docker("docker", "--env VAR1=one\\ two\n--env SECRET=hunter2\n")]
+#[case::shell(
+    "shell",
+    "unset 'GONE'\nexport 'VAR1'='one two'\nexport 'SECRET'='hunter2'\n"
+)]
+fn test_export_format(#[case] format: &str, #[case] expected: &str) {
+    env_select()
+        .arg("--config")
+        .arg(config_file(format))
+        .args(["-s", "bash", "export", "app", "dev", "--format", format])
+        .assert()
+        .success()
+        .stdout(expected.to_owned());
+}
+
+/// Test `--output` writes to a file instead of stdout, replacing anything
+/// already there. The file is only readable by the current user
+#[test]
+fn test_export_output() {
+    let output = env::temp_dir().join("es-test-export-output.env");
+    fs::write(&output, "old").unwrap();
+
+    env_select()
+        .arg("--config")
+        .arg(config_file("output"))
+        .args(["-s", "bash", "export", "app", "dev", "--output"])
+        .arg(&output)
+        .assert()
+        .success()
+        .stdout("");
+    assert_eq!(
+        fs::read_to_string(&output).unwrap(),
+        "VAR1='one two'\nSECRET='hunter2'\n"
+    );
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(&output).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
+
+/// Test the direnv format watches the config file, and includes unset
+/// variables
+#[test]
+fn test_export_direnv() {
+    let config_file = config_file("direnv");
+    env_select()
+        .arg("--config")
+        .arg(&config_file)
+        .args(["export", "app", "dev", "--format", "direnv"])
+        .assert()
+        .success()
+        .stdout(format!(
+            "watch_file '{}'\nunset 'GONE'\nexport 'VAR1'='one two'\n\
+            export 'SECRET'='hunter2'\n",
+            config_file.display()
+        ));
+}
+
+/// direnv can't answer a prompt, so the profile must be given
+#[test]
+fn test_export_direnv_no_prompt() {
+    env_select()
+        .arg("--config")
+        .arg(config_file("direnv-no-prompt"))
+        .args(["export", "app", "--format", "direnv"])
+        .assert()
+        .failure()
+        .stdout("")
+        .stderr(
+            "[ERROR] direnv can't show prompts; pass an application and \
+            profile, e.g. `use env_select server dev`\n",
+        );
+}
+
+/// Test `use env_select` from the `es init --direnv` shim. `use` and
+/// `watch_file` are part of direnv's stdlib, so they're stubbed out here
+#[test]
+fn test_init_direnv() {
+    let assert = env_select().args(["init", "--direnv"]).assert().success();
+    let shim = String::from_utf8(assert.get_output().stdout.clone()).unwrap();
+    let script = format!(
+        "{shim}\nuse() {{ \"use_$1\" \"${{@:2}}\"; }}\nwatch_file() {{ :; }}\n\
+        use env_select app dev\necho \"$VAR1 $SECRET\""
+    );
+
+    let mut command = assert_cmd::Command::new("bash");
+    command
+        .env("ES_CONFIG_FILE", config_file("init-direnv"))
+        .env("XDG_DATA_HOME", env::temp_dir().join("es-test-data"))
+        .args(["-c", &script])
+        .assert()
+        .success()
+        .stdout("one two hunter2\n")
+        .stderr("");
+}
@@ -0,0 +1,90 @@
+//! Test the `import` subcommand
+
+mod common;
+
+use common::*;
+use std::{env, fs, path::PathBuf};
+
+/// Create a fresh directory with a dotenv file to import
+fn setup(name: &str) -> PathBuf {
+    let directory = env::temp_dir().join(format!("es-test-import-{name}"));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+    fs::write(
+        directory.join(".env.development"),
+        "HOST=localhost\nDB_PASSWORD='hunter2'\n",
+    )
+    .unwrap();
+    directory.canonicalize().unwrap()
+}
+
+/// The profile is added to the existing config file, without disturbing
+/// what's already there. Replacing it requires `--force`
+#[test]
+fn test_import_existing() {
+    let directory = setup("existing");
+    let config_file = directory.join(".env-select.toml");
+    fs::write(
+        &config_file,
+        "# My config\n[applications.server.profiles.prd]\nvariables = {HOST = \"prd\"} # Production\n",
+    )
+    .unwrap();
+    let import = || {
+        let mut command = env_select();
+        command
+            .current_dir(&directory)
+            .env_remove("ES_CONFIG_FILE")
+            .args(["import", "server", "dev", ".env.development"]);
+        command
+    };
+
+    import().assert().success().stdout(format!(
+        "Imported 2 variables into profile `server/dev` in {}\n\
+        Marked as sensitive: DB_PASSWORD\n",
+        config_file.display()
+    ));
+    let expected = r#"# My config
+[applications.server.profiles.prd]
+variables = {HOST = "prd"} # Production
+
+[applications.server.profiles.dev]
+variables.DB_PASSWORD = { type = "literal", value = "hunter2", sensitive = true }
+variables.HOST = "localhost"
+"#;
+    assert_eq!(fs::read_to_string(&config_file).unwrap(), expected);
+
+    // The new profile is usable
+    env_select()
+        .current_dir(&directory)
+        .args(["show", "env", "server", "dev"])
+        .assert()
+        .success()
+        .stdout("DB_PASSWORD = <REDACTED>\nHOST = localhost\n");
+
+    // Conflicts require --force
+    import().assert().failure().stderr(format!(
+        "[ERROR] Profile `server/dev` already exists in {config_file:?}; \
+        pass `--force` to replace it\n"
+    ));
+    import().arg("--force").assert().success();
+    assert_eq!(fs::read_to_string(&config_file).unwrap(), expected);
+}
+
+/// Without a config file, one is created in the current directory
+#[test]
+fn test_import_new_file() {
+    let directory = setup("new-file");
+    env_select()
+        .current_dir(&directory)
+        .env_remove("ES_CONFIG_FILE")
+        .args(["import", "server", "dev", ".env.development"])
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(directory.join(".env-select.toml")).unwrap(),
+        r#"[applications.server.profiles.dev]
+variables.DB_PASSWORD = { type = "literal", value = "hunter2", sensitive = true }
+variables.HOST = "localhost"
+"#
+    );
+}
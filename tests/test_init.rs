@@ -0,0 +1,37 @@
+//! Test the `init` subcommand, and shell detection
+
+use rstest::rstest;
+
+/// Test that the shell is detected from the parent process when $SHELL isn't
+/// usable, or when `auto-strict` is given
+#[rstest]
+#[case::from_env("sh", Some("/usr/bin/fish"), false, "function es")]
+#[case::fallback("sh", None, false, "# POSIX sh has no `local`")]
+#[case::fallback_unknown("bash", Some("/bin/nope"), false, "source $tmp_file")]
+#[case::strict("bash", Some("/usr/bin/fish"), true, "source $tmp_file")]
+#[case::strict_sh("sh", Some("/bin/bash"), true, "# POSIX sh has no `local`")]
+fn test_init_detect_shell(
+    #[case] parent_shell: &str,
+    #[case] env_shell: Option<&str>,
+    #[case] strict: bool,
+    #[case] expected: &str,
+) {
+    // Run something after es, so the shell can't replace itself with es
+    let script = format!(
+        "'{es}' {shell} init --no-completions; true",
+        es = assert_cmd::cargo::cargo_bin("es").display(),
+        shell = if strict { "-s auto-strict" } else { "" },
+    );
+    let mut command = assert_cmd::Command::new(parent_shell);
+    command.args(["-c", &script]);
+    match env_shell {
+        Some(path) => command.env("SHELL", path),
+        None => command.env_remove("SHELL"),
+    };
+    let assert = command.assert().success();
+    let stdout = String::from_utf8(assert.get_output().stdout.clone()).unwrap();
+    assert!(
+        stdout.contains(expected),
+        "Expected `{expected}` in output:\n{stdout}"
+    );
+}
@@ -5,6 +5,10 @@ mod common;
 use common::*;
 use rstest::rstest;
 use rstest_reuse::{self, *};
+use std::{
+    env, fs,
+    time::{Duration, Instant},
+};
 
 /// Test `es run` executes the command within a subshell, and the variables
 /// don't leak outside that subprocess
@@ -52,3 +56,606 @@ fn test_run_escaping(shell_kind: &str) {
     .stdout("$NOT_EXPANDED \"$(hello!!)\"")
     .stderr("");
 }
+
+/// Test `--no-shell` passes arguments to the program exactly as given, and
+/// forwards its exit code
+#[rstest]
+#[case::exotic(&["printf", "%s|", "a  b", "it's", "$HOME", "\\'"], 0, "a  b|it's|$HOME|\\'|")]
+#[case::exit_code(&["sh", "-c", "echo failed; exit 3"], 3, "failed\n")]
+fn test_run_no_shell(
+    #[case] command: &[&str],
+    #[case] code: i32,
+    #[case] expected: &str,
+) {
+    env_select()
+        .args(["-s", "bash", "run", "test", "empty", "--no-shell", "--"])
+        .args(command)
+        .assert()
+        .code(code)
+        .stdout(expected.to_owned());
+}
+
+/// Test a failed teardown doesn't hide the command's exit code. If the
+/// command succeeded, the teardown failure is the exit code. Teardown fails
+/// because the command deletes the `docker` that Compose teardown needs. Only
+/// the fake `docker` and what the command needs are on the `PATH`, so it
+/// can't fall back to a real one
+#[cfg(unix)]
+#[rstest]
+#[case::command_failed("exit 3", 3)]
+#[case::command_killed("kill -TERM $$", 128 + 15)]
+#[case::command_succeeded("true", 1)]
+fn test_run_teardown_error(#[case] command: &str, #[case] code: i32) {
+    use std::os::unix::fs::{symlink, PermissionsExt};
+
+    let dir = env::temp_dir()
+        .join(format!("es-test-run-teardown-error-{}", command.len()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let docker = dir.join("docker");
+    fs::write(&docker, "#!/bin/sh\ntrue\n").unwrap();
+    fs::set_permissions(&docker, fs::Permissions::from_mode(0o755)).unwrap();
+    let config = dir.join("config.toml");
+    fs::write(
+        &config,
+        r#"
+[applications.app.profiles.dev]
+pre_export = [{type = "docker-compose", file = "compose.yml"}]
+"#,
+    )
+    .unwrap();
+    for program in ["sh", "rm"] {
+        symlink(shell_path(program), dir.join(program)).unwrap();
+    }
+
+    let assert = env_select()
+        .env("PATH", &dir)
+        .arg("--config")
+        .arg(&config)
+        .args(["-s", "bash", "run", "app", "dev", "--no-shell", "--"])
+        .args(["sh", "-c"])
+        .arg(format!("rm '{}'; {command}", docker.display()))
+        .assert()
+        .code(code);
+    let stderr = String::from_utf8_lossy(&assert.get_output().stderr);
+    assert!(
+        stderr.starts_with("[ERROR] Error executing command `docker"),
+        "{stderr}"
+    );
+}
+
+/// Test `--last` reuses the profile that was last selected in the directory,
+/// and profiles named on the command line aren't remembered
+#[test]
+fn test_run_last() {
+    let data_dir = env::temp_dir().join("es-test-run-last");
+    let _ = fs::remove_dir_all(&data_dir);
+    let run = |selection: &[&str]| {
+        let mut command = env_select();
+        command
+            .env("XDG_DATA_HOME", &data_dir)
+            .args(["-s", "bash", "run", "test"])
+            .args(selection)
+            .args(["--", "printenv", "VAR1"]);
+        command
+    };
+
+    // Nothing to reuse yet
+    run(&["--last"]).assert().failure().stderr(
+        "[ERROR] No profile has been selected for application `test` in \
+        this directory yet\n",
+    );
+    run(&["p1"]).assert().success();
+    run(&["--last"]).assert().failure();
+
+    // Selections can only be recorded by a prompt, so fake one
+    let directory = tests_dir().canonicalize().unwrap();
+    let directory = directory.to_str().unwrap();
+    fs::create_dir_all(data_dir.join("env-select")).unwrap();
+    fs::write(
+        data_dir.join("env-select/history.json"),
+        serde_json::json!({directory: {"test": "p1"}}).to_string(),
+    )
+    .unwrap();
+    // VAR1 isn't set in this profile
+    run(&["empty"]).assert().code(1).stdout("");
+    env_select()
+        .env("XDG_DATA_HOME", &data_dir)
+        .args(["diff", "test", "empty", "p1"])
+        .assert()
+        .success();
+    run(&["--last"]).assert().success().stdout(
+        "pre setup 1\npre setup 2\npost setup 1 abc\npost setup 2 abc\nabc\n\
+        post teardown 2 abc\npost teardown 1 abc\npre teardown 2\n\
+        pre teardown 1\n",
+    );
+
+    // Forgetting clears the history
+    env_select()
+        .env("XDG_DATA_HOME", &data_dir)
+        .arg("forget")
+        .assert()
+        .success();
+    run(&["--last"]).assert().failure();
+}
+
+/// Test a `prompt` value source fails instead of prompting in non-interactive
+/// mode
+#[test]
+fn test_run_prompt_non_interactive() {
+    let config_file = env::temp_dir().join("es-test-run-prompt.toml");
+    fs::write(
+        &config_file,
+        r#"
+[applications.app.profiles.dev.variables]
+SECRET = {type = "prompt", message = "Enter secret"}
+"#,
+    )
+    .unwrap();
+
+    env_select()
+        .arg("--config")
+        .arg(&config_file)
+        .args(["--non-interactive", "-s", "bash", "run", "app", "dev"])
+        .args(["--", "printenv", "SECRET"])
+        .assert()
+        .failure()
+        .stdout("")
+        .stderr(
+            "[ERROR] Cannot prompt for `Enter secret` in non-interactive \
+            mode\n",
+        );
+}
+
+/// Test a `prompt` value source hides input for variables that match a
+/// sensitive pattern. This needs a terminal, so run `es` under `script`
+#[cfg(target_os = "linux")]
+#[test]
+fn test_run_prompt_sensitive_pattern() {
+    let config_file = env::temp_dir().join("es-test-run-prompt-sensitive.toml");
+    fs::write(
+        &config_file,
+        r#"
+sensitive_patterns = ["*_TOKEN"]
+
+[applications.app.profiles.dev.variables]
+API_TOKEN = {type = "prompt"}
+"#,
+    )
+    .unwrap();
+    let es = format!(
+        "{} --config {} --plain-prompt -s bash run app dev --no-shell -- true",
+        assert_cmd::cargo::cargo_bin("es").display(),
+        config_file.display(),
+    );
+
+    // Type the value once the prompt is up. Visible input would be echoed
+    // back by the terminal
+    let output = assert_cmd::Command::new("bash")
+        .env("ES_COMMAND", es)
+        .env("XDG_DATA_HOME", env::temp_dir().join("es-test-data"))
+        .args([
+            "-c",
+            r#"(sleep 1; printf 'hunter2\r') | script -qec "$ES_COMMAND" /dev/null"#,
+        ])
+        .timeout(Duration::from_secs(10))
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.contains("API_TOKEN: "), "{output:?}");
+    assert!(!output.contains("hunter2"), "{output:?}");
+}
+
+/// Test `--isolated` only passes the profile's variables and the allowlist.
+/// Lists are only combined with variables that are kept
+#[rstest]
+#[case::inherit(&[], "one kept leaked ./src:/parent /parent")]
+#[case::isolated(&["--isolated", "--keep", "KEPT"], "one kept unset ./src /parent")]
+fn test_run_isolated(#[case] arguments: &[&str], #[case] expected: &str) {
+    let config_file = env::temp_dir()
+        .join(format!("es-test-run-isolated-{}.toml", arguments.len()));
+    fs::write(
+        &config_file,
+        r#"
+[applications.app.profiles.dev.variables]
+VAR1 = "one"
+PYTHONPATH = {type = "literal", value = "./src", mode = "prepend"}
+PATH = {type = "literal", value = "/parent", mode = "append"}
+"#,
+    )
+    .unwrap();
+    let path = env::var("PATH").unwrap();
+
+    env_select()
+        .arg("--config")
+        .arg(&config_file)
+        .env("KEPT", "kept")
+        .env("LEAKED", "leaked")
+        .env("PYTHONPATH", "/parent")
+        .env("PATH", format!("/parent:{path}"))
+        .args(["-s", "bash", "run", "app", "dev"])
+        .args(arguments)
+        // Tokens are escaped, so run a nested shell to expand the variables
+        .args([
+            "--",
+            "bash",
+            "-c",
+            "echo $VAR1 $KEPT ${LEAKED-unset} $PYTHONPATH ${PATH%%:*}",
+        ])
+        .assert()
+        .success()
+        .stdout(format!("{expected}\n"));
+}
+
+/// Test `--no-side-effects` skips setup and teardown, and `--no-teardown` only
+/// skips teardown
+#[rstest]
+#[case::no_side_effects(&["--no-side-effects"], "abc\n")]
+#[case::no_teardown(
+    &["--no-teardown"],
+    "pre setup 1\npre setup 2\npost setup 1 abc\npost setup 2 abc\nabc\n"
+)]
+fn test_run_skip_side_effects(
+    #[case] arguments: &[&str],
+    #[case] expected: &str,
+) {
+    env_select()
+        .args(["-s", "bash", "run", "test", "p1"])
+        .args(arguments)
+        .args(["--", "printenv", "VAR1"])
+        .assert()
+        .success()
+        .stdout(expected.to_owned());
+}
+
+/// Test `--no-teardown` leaves background processes running, and logs their
+/// PIDs as a warning
+#[test]
+fn test_run_no_teardown_background() {
+    let config_file = env::temp_dir().join("es-test-run-no-teardown.toml");
+    fs::write(
+        &config_file,
+        r#"
+[applications.app.profiles.p]
+pre_export = [{setup = "exec sleep 13 2> /dev/null", background = true}]
+"#,
+    )
+    .unwrap();
+    let start = Instant::now();
+    let output = env_select()
+        .arg("--config")
+        .arg(&config_file)
+        .args([
+            "-s",
+            "bash",
+            "run",
+            "app",
+            "p",
+            "--no-teardown",
+            "--",
+            "true",
+        ])
+        .assert()
+        .success()
+        .get_output()
+        .stderr
+        .clone();
+    // The background process must not hold onto our stdout pipe, or we'd
+    // wait for it to exit to finish reading
+    assert!(start.elapsed() < Duration::from_secs(5));
+    let stderr = String::from_utf8(output).unwrap();
+    let pid = stderr
+        .strip_prefix("[WARN ] Left background processes running: ")
+        .and_then(|pid| pid.strip_suffix('\n'))
+        .unwrap_or_else(|| panic!("Unexpected stderr: {stderr:?}"));
+    assert!(std::process::Command::new("kill")
+        .arg(pid)
+        .status()
+        .unwrap()
+        .success());
+}
+
+/// Test `--env` overrides profile variables and adds new ones
+#[test]
+fn test_run_override() {
+    env_select()
+        .args(["-s", "bash", "run", "test", "p1", "--no-side-effects"])
+        .args(["-e", "VAR1=mine", "--env", "NEW=new"])
+        .args(["--", "printenv", "VAR1", "VAR2", "NEW"])
+        .assert()
+        .success()
+        .stdout("mine\ndef\nnew\n");
+}
+
+/// Test joining profiles with `+` combines them, with later profiles taking
+/// precedence
+#[rstest]
+#[case::base_first("base+debug", "us debug")]
+#[case::debug_first("debug+base", "us info")]
+fn test_run_compose(#[case] profile: &str, #[case] expected: &str) {
+    let config_file =
+        env::temp_dir().join(format!("es-test-run-compose-{profile}.toml"));
+    fs::write(
+        &config_file,
+        r#"
+[applications.app.profiles.base.variables]
+REGION = "us"
+LOG = "info"
+[applications.app.profiles.debug.variables]
+LOG = "debug"
+"#,
+    )
+    .unwrap();
+
+    env_select()
+        .arg("--config")
+        .arg(&config_file)
+        .args(["-s", "bash", "run", "app", profile])
+        .args(["--", "bash", "-c", "echo $REGION $LOG"])
+        .assert()
+        .success()
+        .stdout(format!("{expected}\n"));
+}
+
+/// Without a terminal, the selection prompt fails with the options instead
+/// of hanging
+#[test]
+fn test_run_non_interactive() {
+    env_select()
+        .args(["-s", "bash", "run", "test", "--", "true"])
+        .timeout(Duration::from_secs(5))
+        .assert()
+        .failure()
+        .stderr(
+            "[ERROR] No profile given, and can't prompt in non-interactive \
+            mode. Pass one of: p1, empty\n",
+        );
+}
+
+/// Test that terminating `es run` is forwarded to the command, and side
+/// effects are still torn down afterward
+#[test]
+fn test_run_terminated() {
+    let config_file = env::temp_dir().join("es-test-run-terminated.toml");
+    fs::write(
+        &config_file,
+        r#"
+[applications.app.profiles.dev]
+post_export = [{setup = "echo setup", teardown = "echo teardown"}]
+"#,
+    )
+    .unwrap();
+    let script = format!(
+        "'{es}' --config '{config}' -s bash run app dev -- sleep 10 &
+        pid=$!
+        sleep 1
+        kill -TERM $pid
+        wait $pid
+        echo exit $?",
+        es = assert_cmd::cargo::cargo_bin("es").display(),
+        config = config_file.display(),
+    );
+
+    assert_cmd::Command::new("bash")
+        .env("XDG_DATA_HOME", env::temp_dir().join("es-test-data"))
+        .args(["-c", &script])
+        .timeout(Duration::from_secs(5))
+        .assert()
+        .success()
+        .stdout("setup\nteardown\nexit 143\n");
+}
+
+/// Test that terminating `es run` signals everything the command started, not
+/// just the command itself
+#[test]
+fn test_run_terminated_process_group() {
+    let config_file = env::temp_dir().join("es-test-run-terminated-group.toml");
+    let pid_file = env::temp_dir().join("es-test-run-terminated-group.pid");
+    fs::write(&config_file, "[applications.app.profiles.dev]\n").unwrap();
+    let script = format!(
+        "'{es}' --config '{config}' -s bash run app dev -- \
+            sh -c 'sleep 10 & echo $! > {pid_file}; wait' &
+        pid=$!
+        sleep 1
+        kill -TERM $pid
+        wait $pid
+        echo exit $?
+        sleep 0.2
+        # Orphans may not get reaped, so zombies count as dead
+        case $(ps -o stat= -p $(cat '{pid_file}')) in
+            ''|Z*) echo dead ;;
+            *) echo alive ;;
+        esac",
+        es = assert_cmd::cargo::cargo_bin("es").display(),
+        config = config_file.display(),
+        pid_file = pid_file.display(),
+    );
+
+    assert_cmd::Command::new("bash")
+        .env("XDG_DATA_HOME", env::temp_dir().join("es-test-data"))
+        .args(["-c", &script])
+        .timeout(Duration::from_secs(5))
+        .assert()
+        .success()
+        .stdout("exit 143\ndead\n");
+}
+
+/// Test passing multiple profiles runs the command in each, with a header
+/// per run and a summary at the end
+#[rstest]
+#[case::listed(&["test", "p1", "empty"])]
+#[case::all_profiles(&["--all-profiles", "test"])]
+fn test_run_multiple_profiles(#[case] selection: &[&str]) {
+    env_select()
+        .args(["-s", "bash", "run", "--no-side-effects"])
+        .args(selection)
+        .args(["--", "printenv", "VAR1"])
+        .assert()
+        .failure()
+        .code(1)
+        .stdout("=== test/p1 ===\nabc\n=== test/empty ===\n")
+        .stderr(
+            "Summary:
+  test/p1     ok
+  test/empty  process failed with exit code: 1
+",
+        );
+}
+
+/// Test `--all-profiles` skips hidden and deprecated profiles, and a run that
+/// fails to start is logged as an error without stopping the others
+#[test]
+fn test_run_all_profiles_skipped() {
+    let config_file = env::temp_dir().join("es-test-run-all-profiles.toml");
+    fs::write(
+        &config_file,
+        r#"
+[applications.app.profiles.dev]
+variables.NAME = "dev"
+
+[applications.app.profiles.base]
+hidden = true
+variables.NAME = "base"
+
+[applications.app.profiles.old]
+deprecated = true
+variables.NAME = "old"
+
+[applications.app.profiles.broken]
+variables.NAME = {type = "command", command = "false"}
+"#,
+    )
+    .unwrap();
+    env_select()
+        .arg("--config")
+        .arg(&config_file)
+        .args(["-s", "bash", "run", "--all-profiles", "app"])
+        .args(["--", "printenv", "NAME"])
+        .assert()
+        .failure()
+        .code(1)
+        .stdout("=== app/dev ===\ndev\n=== app/broken ===\n")
+        .stderr(
+            r#"[WARN ] Skipping deprecated profile `app/old`
+[ERROR] `bash ["-c", "false"]` failed with exit code 1
+Summary:
+  app/dev     ok
+  app/broken  error: `bash ["-c", "false"]` failed with exit code 1
+"#,
+        );
+}
+
+/// Test runs in multiple profiles pass on the exit code if every failure
+/// agrees on it, and the summary keeps profile order with `--parallel`
+#[test]
+fn test_run_multiple_profiles_parallel() {
+    env_select()
+        .args([
+            "-s",
+            "bash",
+            "run",
+            "test",
+            "p1",
+            "empty",
+            "--parallel",
+            "2",
+        ])
+        .args(["--no-side-effects", "--no-shell", "--"])
+        .args(["sh", "-c", "exit 3"])
+        .timeout(Duration::from_secs(5))
+        .assert()
+        .code(3)
+        .stderr(
+            "Summary:
+  test/p1     process failed with exit code: 3
+  test/empty  process failed with exit code: 3
+",
+        );
+}
+
+/// Test parallel runs don't get stdin or the terminal, so a command that
+/// reads input can't wedge env-select. Stdin here is a pipe that stays open,
+/// so `cat` would block on it until the timeout
+#[test]
+fn test_run_multiple_profiles_parallel_stdin() {
+    execute_script(
+        "es run test p1 empty --parallel 2 --no-side-effects --no-shell \
+            -- cat > /dev/null 2>&1 < <(sleep 10 2> /dev/null)",
+        "bash",
+        false,
+    )
+    .timeout(Duration::from_secs(5))
+    .assert()
+    .success();
+}
+
+/// Config with two unrelated applications, for testing `--with`
+const WITH_CONFIG: &str = r#"
+[applications.backend.profiles.dev]
+variables = {DB = "dev", REGION = "backend"}
+post_export = [{setup = "echo backend setup", teardown = "echo backend teardown"}]
+
+[applications.aws.profiles.sandbox]
+variables = {AWS_PROFILE = "sandbox", REGION = "us-east-1"}
+post_export = [{setup = "echo aws setup", teardown = "echo aws teardown"}]
+"#;
+
+/// Test `--with` composes a profile from another application on top of the
+/// selection. Side effects are torn down in reverse, and conflicts name both
+/// profiles
+#[rstest]
+#[case::conflict_warning(
+    "",
+    0,
+    "backend setup
+aws setup
+dev sandbox us-east-1
+aws teardown
+backend teardown
+",
+    "[WARN ] Variable REGION is set by both `backend/dev` and `aws/sandbox`; \
+    using the value from `aws/sandbox`\n"
+)]
+#[case::strict_conflicts(
+    "strict_conflicts = true",
+    1,
+    "",
+    "[ERROR] Variable REGION is set by both `backend/dev` and `aws/sandbox`\n"
+)]
+fn test_run_with(
+    #[case] prelude: &str,
+    #[case] code: i32,
+    #[case] expected_stdout: &str,
+    #[case] expected_stderr: &str,
+) {
+    let config_file = env::temp_dir()
+        .join(format!("es-test-run-with-{}.toml", prelude.is_empty()));
+    fs::write(&config_file, format!("{prelude}\n{WITH_CONFIG}")).unwrap();
+    env_select()
+        .arg("--config")
+        .arg(&config_file)
+        .args(["-s", "bash", "run", "backend", "dev", "--with", "aws"])
+        .args(["sandbox", "--", "sh", "-c", "echo $DB $AWS_PROFILE $REGION"])
+        .assert()
+        .code(code)
+        .stdout(expected_stdout.to_owned())
+        .stderr(expected_stderr.to_owned());
+}
+
+/// Test `es run` restores the kubectl context when loading fails after switching
+#[cfg(unix)]
+#[test]
+fn test_run_kubernetes_restored_on_error() {
+    let (mut command, log) = fake_kubectl("run");
+    command
+        .args(["-s", "bash", "run", "app", "kube", "--", "true"])
+        .assert()
+        .failure();
+    assert_eq!(
+        std::fs::read_to_string(log).unwrap(),
+        "config current-context\nconfig use-context new\nconfig use-context old\n"
+    );
+}
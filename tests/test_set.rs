@@ -5,6 +5,7 @@ mod common;
 use common::*;
 use rstest::rstest;
 use rstest_reuse::{self, *};
+use std::{env, fs};
 
 /// Test all shell integrations with a simple `es set` command
 #[apply(all_shells)]
@@ -15,7 +16,7 @@ fn test_set_subcommand(
     execute_script(
         "
         es set test p1
-        echo -n $VAR1 $VAR2 $VAR3 $FILE_VAR1 $FILE_VAR2
+        echo -n $VAR1 $VAR2 $VAR3 $FILE_VAR1 $FILE_VAR2 $ENV_SELECT_ACTIVE_TEST
         ",
         shell_kind,
         detect_shell,
@@ -31,7 +32,216 @@ The following variables will be set:
 VAR1 = abc
 VAR2 = def
 FILE_VAR1 = 123
-abc def 123",
+abc def 123 p1",
     )
     .stderr("");
 }
+
+/// Switching an application away from its active profile prints a hint
+#[apply(all_shells)]
+fn test_set_switch_profile(shell_kind: &str) {
+    let assert = execute_script(
+        "
+        es set test p1 > /dev/null
+        es set test empty > /dev/null
+        ",
+        shell_kind,
+        true,
+    )
+    .assert()
+    .success();
+    let stderr = String::from_utf8(assert.get_output().stderr.clone()).unwrap();
+    assert!(
+        stderr.contains(
+            "Switching application `test` from profile `p1` to `empty`"
+        ),
+        "Unexpected stderr: {stderr}"
+    );
+}
+
+/// Switching profiles clears variables from the previous profile that the new
+/// one doesn't set, restoring their original values
+#[apply(all_shells)]
+fn test_set_switch_profile_stale(shell_kind: &str) {
+    execute_script(
+        "
+        es set test p1 > /dev/null
+        es set test empty
+        echo -n $VAR1 $VAR2 $FILE_VAR1
+        ",
+        shell_kind,
+        true,
+    )
+    .env("VAR2", "original")
+    .assert()
+    .success()
+    .stdout(
+        "The following variables will be set:
+VAR1 (unset)
+VAR2 = original
+FILE_VAR1 (unset)
+original",
+    );
+}
+
+/// The sourceable output is only readable by the current user, and empties
+/// itself once it's sourced, so secrets don't linger on disk
+#[test]
+fn test_set_source_file() {
+    let source_file = env::temp_dir().join("es-test-set-source-file");
+    fs::write(&source_file, "").unwrap();
+    env_select()
+        .arg("--source-file")
+        .arg(&source_file)
+        .args(["-s", "bash", "set", "test", "empty"])
+        .assert()
+        .success();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(&source_file).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+    assert!(!fs::read_to_string(&source_file).unwrap().is_empty());
+
+    assert_cmd::Command::new("bash")
+        .arg("-c")
+        .arg(format!("source '{}'", source_file.display()))
+        .assert()
+        .success();
+    assert_eq!(fs::read_to_string(&source_file).unwrap(), "");
+}
+
+/// Test `--with` sets variables from another application's profile too, with
+/// later profiles winning conflicts. `es unset` tears down both profiles
+#[test]
+fn test_set_with() {
+    let config_file = env::temp_dir().join("es-test-set-with.toml");
+    fs::write(
+        &config_file,
+        r#"
+[applications.backend.profiles.dev]
+variables = {DB = "dev", REGION = "backend"}
+post_export = [{teardown = "echo backend teardown"}]
+
+[applications.aws.profiles.sandbox]
+variables = {AWS_PROFILE = "sandbox", REGION = "us-east-1"}
+post_export = [{teardown = "echo aws teardown"}]
+"#,
+    )
+    .unwrap();
+    execute_script(
+        &format!(
+            "es --config '{config}' set backend dev --with aws sandbox > /dev/null
+            echo $DB $AWS_PROFILE $REGION
+            es --config '{config}' unset
+            echo Empty: $DB $AWS_PROFILE $REGION",
+            config = config_file.display()
+        ),
+        "bash",
+        false,
+    )
+    .assert()
+    .success()
+    .stdout(
+        "dev sandbox us-east-1
+aws teardown
+backend teardown
+The following variables will be restored:
+DB (unset)
+REGION (unset)
+AWS_PROFILE (unset)
+Empty:
+",
+    )
+    .stderr(
+        "[WARN ] Variable REGION is set by both `backend/dev` and \
+        `aws/sandbox`; using the value from `aws/sandbox`\n",
+    );
+}
+
+/// Test background processes from one application keep running when a
+/// profile is set for another application, and `es unset` kills them all
+#[test]
+fn test_set_background_per_application() {
+    let config_file = env::temp_dir().join("es-test-set-background.toml");
+    fs::write(
+        &config_file,
+        r#"
+[applications.a.profiles.p]
+pre_export = [{setup = "exec sleep 11", background = true}]
+[applications.b.profiles.p]
+pre_export = [{setup = "exec sleep 12", background = true}]
+"#,
+    )
+    .unwrap();
+    execute_script(
+        &format!(
+            "es --config '{config}' set a p > /dev/null
+            es --config '{config}' set b p > /dev/null
+            pgrep -fx 'sleep 11' > /dev/null && echo a running
+            pgrep -fx 'sleep 12' > /dev/null && echo b running
+            es --config '{config}' unset > /dev/null
+            pgrep -fx 'sleep 1[12]' || echo stopped",
+            config = config_file.display()
+        ),
+        "bash",
+        false,
+    )
+    .assert()
+    .success()
+    .stdout("a running\nb running\nstopped\n");
+}
+
+/// Without a terminal, or with `--non-interactive`, the selection prompt
+/// fails with the options, and the shell isn't modified
+#[rstest]
+fn test_set_non_interactive(
+    #[values("es set test", "es --non-interactive set test")] command: &str,
+) {
+    execute_script(
+        &format!("{command} || echo -n failed $VAR1"),
+        "bash",
+        false,
+    )
+    .assert()
+    .success()
+    .stdout("failed")
+    .stderr(
+        "[ERROR] No profile given, and can't prompt in non-interactive mode. \
+        Pass one of: p1, empty\n",
+    );
+}
+
+/// Test `--color` and NO_COLOR apply to both hints and logged errors. The
+/// flag takes precedence over NO_COLOR
+#[rstest]
+#[case::always(&["--color", "always"], None, true)]
+#[case::never(&["--color", "never"], None, false)]
+#[case::no_color(&["--color", "auto"], Some("1"), false)]
+#[case::always_no_color(&["--color", "always"], Some("1"), true)]
+fn test_set_color(
+    #[case] color: &[&str],
+    #[case] no_color: Option<&str>,
+    #[case] colored: bool,
+    #[values(&["test", "p1"], &["unknown"])] selection: &[&str],
+) {
+    let mut command = env_select();
+    command
+        .args(["-s", "bash"])
+        .args(color)
+        .arg("set")
+        .args(selection);
+    match no_color {
+        Some(no_color) => command.env("NO_COLOR", no_color),
+        None => command.env_remove("NO_COLOR"),
+    };
+    let output = command.output().unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(!stderr.is_empty());
+    assert_eq!(
+        stderr.contains('\x1b'),
+        colored,
+        "Unexpected stderr: {stderr}"
+    );
+}
@@ -0,0 +1,512 @@
+//! Test the `show` subcommand
+
+mod common;
+
+use common::*;
+use rstest::rstest;
+use std::{env, fs, path::Path};
+
+/// Test `--config` and `ES_CONFIG_FILE` load only the given file, relative to
+/// the current directory, with the argument taking precedence
+#[rstest]
+#[case::argument(
+    Some("../.env-select.toml"),
+    None,
+    "../.env-select.toml",
+    "1 application, 3 profiles"
+)]
+#[case::variable(
+    None,
+    Some("../.env-select.toml"),
+    "../.env-select.toml",
+    "1 application, 3 profiles"
+)]
+#[case::both(
+    Some(".env-select.toml"),
+    Some("../.env-select.toml"),
+    ".env-select.toml",
+    "1 application, 2 profiles"
+)]
+fn test_show_sources_override(
+    #[case] argument: Option<&str>,
+    #[case] variable: Option<&str>,
+    #[case] expected: &str,
+    #[case] expected_details: &str,
+) {
+    let mut command = env_select();
+    if let Some(argument) = argument {
+        command.args(["--config", argument]);
+    }
+    if let Some(variable) = variable {
+        command.env("ES_CONFIG_FILE", variable);
+    }
+    let expected = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join(expected)
+        .canonicalize()
+        .unwrap();
+    command
+        .args(["show", "sources"])
+        .assert()
+        .success()
+        .stdout(format!("{} ({expected_details})\n", expected.display()));
+}
+
+/// Test files that fail to parse are listed instead of failing
+#[test]
+fn test_show_sources_invalid() {
+    let config_file = env::temp_dir().join("es-test-show-sources-invalid.toml");
+    fs::write(&config_file, "applications = 3").unwrap();
+    env_select()
+        .arg("--config")
+        .arg(&config_file)
+        .args(["show", "sources"])
+        .assert()
+        .success()
+        .stdout(format!(
+            "{} (invalid, skipped)\n",
+            config_file.canonicalize().unwrap().display()
+        ));
+}
+
+/// Test the global config file is loaded last, and applications from it are
+/// available
+#[test]
+fn test_global_config() {
+    let config_home = env::temp_dir().join("es-test-global-config");
+    let global_file = config_home.join("env-select/config.toml");
+    fs::create_dir_all(global_file.parent().unwrap()).unwrap();
+    fs::write(
+        &global_file,
+        "[applications.personal.profiles.sandbox]\nvariables = {VAR1 = \"1\"}",
+    )
+    .unwrap();
+
+    let output = env_select()
+        .env("XDG_CONFIG_HOME", &config_home)
+        .args(["show", "sources"])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(
+        stdout.lines().last().unwrap(),
+        format!(
+            "{} (global, 1 application, 1 profile)",
+            global_file.canonicalize().unwrap().display()
+        )
+    );
+
+    env_select()
+        .env("XDG_CONFIG_HOME", &config_home)
+        .args(["show", "config", "personal", "sandbox"])
+        .assert()
+        .success();
+}
+
+/// Test `show config --resolved` includes inherited variables, annotated with
+/// where they were defined, while the default shows the profile as written
+#[rstest]
+#[case::raw(false, &["CHILD"])]
+#[case::resolved(
+    true,
+    &[
+        "# from defaults",
+        "DEFAULT",
+        "# from application app",
+        "SHARED",
+        "# from profile app/base",
+        "BASE",
+        "CHILD",
+    ],
+)]
+fn test_show_config_resolved(
+    #[case] resolved: bool,
+    #[case] expected: &[&str],
+) {
+    let config_file = env::temp_dir()
+        .join(format!("es-test-show-config-resolved-{resolved}.toml"));
+    fs::write(
+        &config_file,
+        r#"
+[defaults]
+variables = {DEFAULT = "default"}
+[applications.app]
+variables = {SHARED = "shared"}
+[applications.app.profiles.base]
+variables = {BASE = "base"}
+[applications.app.profiles.child]
+extends = ["base"]
+variables = {CHILD = "child"}
+"#,
+    )
+    .unwrap();
+
+    let mut command = env_select();
+    command.arg("--config").arg(&config_file);
+    command.args(["show", "config", "app", "child"]);
+    if resolved {
+        command.arg("--resolved");
+    }
+    let output = command.output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    // Strip values, which are verbose
+    let variables: Vec<&str> = stdout
+        .lines()
+        .skip_while(|line| *line != "[variables]")
+        .skip(1)
+        .filter(|line| !line.is_empty())
+        .map(|line| line.split(" = ").next().unwrap())
+        .collect();
+    assert_eq!(variables, expected);
+}
+
+/// Test `show config --origin` annotates every variable with the file that
+/// defined it, and the chain of profiles it was inherited through
+#[test]
+fn test_show_config_origin() {
+    let directory = env::temp_dir().join("es-test-show-config-origin");
+    fs::create_dir_all(&directory).unwrap();
+    let main_file = directory.join("main.toml");
+    let shared_file = directory.join("shared.toml");
+    fs::write(
+        &main_file,
+        r#"
+imports = ["shared.toml"]
+[applications.app]
+variables = {SHARED = "shared"}
+[applications.app.profiles.middle]
+extends = ["base/base"]
+variables = {MIDDLE = "middle"}
+[applications.app.profiles.child]
+extends = ["middle"]
+variables = {CHILD = "child"}
+"#,
+    )
+    .unwrap();
+    fs::write(
+        &shared_file,
+        r#"
+[defaults]
+variables = {DEFAULT = "default"}
+[applications.base.profiles.base]
+variables = {DATABASE_URL = "postgres://localhost"}
+"#,
+    )
+    .unwrap();
+    let main_file = main_file.canonicalize().unwrap();
+    let shared_file = shared_file.canonicalize().unwrap();
+
+    let output = env_select()
+        .arg("--config")
+        .arg(&main_file)
+        .args(["show", "config", "app", "child", "--origin"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    // Strip values, which are verbose
+    let variables: Vec<&str> = stdout
+        .lines()
+        .skip_while(|line| *line != "[variables]")
+        .skip(1)
+        .map(|line| line.split(" = ").next().unwrap())
+        .collect();
+    let main_file = main_file.display();
+    let shared_file = shared_file.display();
+    assert_eq!(
+        variables,
+        [
+            format!("# from {shared_file} via defaults"),
+            "DEFAULT".to_owned(),
+            format!("# from {main_file} via app/middle -> application app"),
+            "SHARED".to_owned(),
+            format!("# from {shared_file} via app/middle -> base/base"),
+            "DATABASE_URL".to_owned(),
+            format!("# from {main_file} via app/middle"),
+            "MIDDLE".to_owned(),
+            format!("# from {main_file}"),
+            "CHILD".to_owned(),
+        ]
+    );
+}
+
+/// Test each output format of `show config`, for a profile, an application,
+/// and the whole config. Map ordering from the config file must be preserved
+#[rstest]
+#[case::profile_toml(
+    &["app", "alpha"],
+    "toml",
+    r#"extends = ["app/zeta"]
+post_export = []
+pre_export = []
+"#
+)]
+#[case::profile_json(
+    &["app", "alpha"],
+    "json",
+    r#"{
+  "extends": [
+    "app/zeta"
+  ],
+  "variables": {},
+  "pre_export": [],
+  "post_export": []
+}
+"#
+)]
+#[case::profile_yaml(
+    &["app", "alpha"],
+    "yaml",
+    "extends:
+- app/zeta
+variables: {}
+pre_export: []
+post_export: []
+"
+)]
+#[case::application_json(
+    &["app"],
+    "json",
+    r#"{
+  "profiles": {
+    "zeta": {
+      "extends": [],
+      "variables": {},
+      "pre_export": [],
+      "post_export": [],
+      "kubernetes_context": "zeta"
+    },
+    "alpha": {
+      "extends": [
+        "app/zeta"
+      ],
+      "variables": {},
+      "pre_export": [],
+      "post_export": []
+    }
+  }
+}
+"#
+)]
+#[case::config_yaml(
+    &[],
+    "yaml",
+    "applications:
+  app:
+    profiles:
+      zeta:
+        extends: []
+        variables: {}
+        pre_export: []
+        post_export: []
+        kubernetes_context: zeta
+      alpha:
+        extends:
+        - app/zeta
+        variables: {}
+        pre_export: []
+        post_export: []
+"
+)]
+fn test_show_config_format(
+    #[case] selection: &[&str],
+    #[case] format: &str,
+    #[case] expected: &str,
+) {
+    // Each case gets its own file, since cases run in parallel
+    let config_file = env::temp_dir().join(format!(
+        "es-test-show-config-format-{format}-{}.toml",
+        selection.len()
+    ));
+    fs::write(
+        &config_file,
+        r#"
+[applications.app.profiles.zeta]
+kubernetes_context = "zeta"
+[applications.app.profiles.alpha]
+extends = ["zeta"]
+"#,
+    )
+    .unwrap();
+
+    env_select()
+        .arg("--config")
+        .arg(&config_file)
+        .args(["show", "config"])
+        .args(selection)
+        .args(["--format", format])
+        .assert()
+        .success()
+        .stdout(expected.to_owned());
+}
+
+/// Test each output format of `show env`. Sensitive values are masked in all
+/// of them
+#[rstest]
+#[case::table("table", "VAR1 = one\nSECRET = <REDACTED>\nGONE (unset)\n")]
+#[case::dotenv("dotenv", "VAR1='one'\nSECRET='<REDACTED>'\n")]
+#[case::json(
+    "json",
+    r#"{
+  "VAR1": "one",
+  "SECRET": "<REDACTED>",
+  "GONE": null
+}
+"#
+)]
+fn test_show_env_format(#[case] format: &str, #[case] expected: &str) {
+    let config_file =
+        env::temp_dir().join(format!("es-test-show-env-{format}.toml"));
+    fs::write(
+        &config_file,
+        r#"
+[applications.app.profiles.dev.variables]
+VAR1 = "one"
+SECRET = {type = "literal", value = "hunter2", sensitive = true}
+GONE = {type = "unset"}
+"#,
+    )
+    .unwrap();
+
+    env_select()
+        .arg("--config")
+        .arg(&config_file)
+        .args([
+            "-s", "bash", "show", "env", "app", "dev", "--format", format,
+        ])
+        .assert()
+        .success()
+        .stdout(expected.to_owned());
+}
+
+/// Test `show env` masks sensitive values according to the `masking` setting,
+/// and `--reveal` overrides it
+#[rstest]
+#[case::full("", false, "SECRET = <REDACTED>\n")]
+#[case::partial("masking = 'partial'", false, "SECRET = ****cdef\n")]
+#[case::reveal("masking = 'partial'", true, "SECRET = sk-abcdef\n")]
+fn test_show_env_masking(
+    #[case] setting: &str,
+    #[case] reveal: bool,
+    #[case] expected: &str,
+) {
+    let config_file = env::temp_dir().join(format!(
+        "es-test-show-env-masking-{setting:?}-{reveal}.toml"
+    ));
+    fs::write(
+        &config_file,
+        format!(
+            r#"
+{setting}
+[applications.app.profiles.dev.variables]
+SECRET = {{type = "literal", value = "sk-abcdef", sensitive = true}}
+"#
+        ),
+    )
+    .unwrap();
+
+    let mut command = env_select();
+    command
+        .arg("--config")
+        .arg(&config_file)
+        .args(["-s", "bash", "show", "env", "app", "dev"]);
+    if reveal {
+        command.arg("--reveal");
+    }
+    command.assert().success().stdout(expected.to_owned());
+}
+
+/// Test `show env` only runs side effects when asked to, and tears them down
+/// before exiting
+#[rstest]
+#[case::skip(false, "")]
+#[case::run(true, "setup\nteardown\n")]
+fn test_show_env_side_effects(
+    #[case] run_side_effects: bool,
+    #[case] expected: &str,
+) {
+    let temp_dir = env::temp_dir();
+    let log_file =
+        temp_dir.join(format!("es-test-show-env-{run_side_effects}.log"));
+    let config_file =
+        temp_dir.join(format!("es-test-show-env-{run_side_effects}.toml"));
+    let _ = fs::remove_file(&log_file);
+    fs::write(
+        &config_file,
+        format!(
+            r#"
+[applications.app.profiles.dev]
+pre_export = [{{setup = "echo setup >> '{log}'", teardown = "echo teardown >> '{log}'"}}]
+variables = {{VAR1 = "one"}}
+"#,
+            log = log_file.display()
+        ),
+    )
+    .unwrap();
+
+    let mut command = env_select();
+    command
+        .arg("--config")
+        .arg(&config_file)
+        .args(["-s", "bash", "show", "env", "app", "dev"]);
+    if run_side_effects {
+        command.arg("--run-side-effects");
+    }
+    command.assert().success().stdout("VAR1 = one\n");
+    assert_eq!(fs::read_to_string(&log_file).unwrap_or_default(), expected);
+}
+
+/// Test `show active` lists the active profile of each application. An
+/// application that's no longer in the config is listed by its variable name
+#[test]
+fn test_show_active() {
+    env_select()
+        .env("ENV_SELECT_ACTIVE_TEST", "p1")
+        .env("ENV_SELECT_ACTIVE_GONE", "old")
+        .args(["show", "active"])
+        .assert()
+        .success()
+        .stdout("GONE/old\ntest/p1\n");
+}
+
+/// Test `show env --run-side-effects` restores the kubectl context when loading fails after switching
+#[cfg(unix)]
+#[test]
+fn test_show_kubernetes_restored_on_error() {
+    let (mut command, log) = fake_kubectl("show");
+    command
+        .args(["show", "env", "app", "kube", "--run-side-effects"])
+        .assert()
+        .failure();
+    assert_eq!(
+        std::fs::read_to_string(log).unwrap(),
+        "config current-context\nconfig use-context new\nconfig use-context old\n"
+    );
+}
+
+/// Test a failing command source's stderr is shown to the user, along with
+/// the error
+#[test]
+fn test_show_env_command_stderr() {
+    let config = env::temp_dir().join("es-test-show-command-stderr.toml");
+    fs::write(
+        &config,
+        r#"
+[applications.app.profiles.dev.variables]
+BROKEN = {type = "command", command = "echo oops >&2; false"}
+"#,
+    )
+    .unwrap();
+    let output = env_select()
+        .arg("--config")
+        .arg(&config)
+        .args(["-s", "bash", "show", "env", "app", "dev"])
+        .assert()
+        .failure()
+        .get_output()
+        .stderr
+        .clone();
+    let stderr = String::from_utf8(output).unwrap();
+    assert!(stderr.starts_with("oops\n"), "{stderr}");
+}
@@ -0,0 +1,94 @@
+//! Test the `unset` subcommand
+
+mod common;
+
+use common::*;
+use rstest::rstest;
+use rstest_reuse::{self, *};
+use std::{env, fs};
+
+/// Test that `es unset` reverts everything `es set` did, including tearing
+/// down side effects and restoring overwritten values
+#[apply(all_shells)]
+fn test_unset_subcommand(shell_kind: &str) {
+    execute_script(
+        "
+        es set test p1
+        es unset
+        echo -n $VAR1 $VAR2 $FILE_VAR1 $ENV_SELECT_LAST_EXPORT $ENV_SELECT_ACTIVE_TEST
+        ",
+        shell_kind,
+        true,
+    )
+    .env("VAR2", "original")
+    .assert()
+    .success()
+    .stdout(
+        "pre setup 1
+pre setup 2
+post setup 1 abc
+post setup 2 abc
+The following variables will be set:
+VAR1 = abc
+VAR2 = def
+FILE_VAR1 = 123
+post teardown 2 abc
+post teardown 1 abc
+pre teardown 2
+pre teardown 1
+The following variables will be restored:
+VAR1 (unset)
+VAR2 = original
+FILE_VAR1 (unset)
+original",
+    )
+    .stderr("");
+}
+
+/// Test that restored values stay masked if the profile that replaced them
+/// was sensitive, or their names match the sensitive patterns. The working
+/// directory from before the set is restored too
+#[test]
+fn test_unset_sensitive_and_cwd() {
+    let directory = env::temp_dir().join("es-test-unset-sensitive");
+    fs::create_dir_all(&directory).unwrap();
+    let config = directory.join("config.toml");
+    fs::write(&config, "sensitive_patterns = [\"*_TOKEN\"]\n").unwrap();
+    let last_export = serde_json::json!({
+        "profile": "test/p1",
+        "side_effects": false,
+        "previous": {"SECRET": "hunter2", "API_TOKEN": "abc", "PLAIN": "xyz"},
+        "sensitive": ["SECRET"],
+        "cwd": directory,
+    });
+
+    env_select()
+        .arg("--config")
+        .arg(&config)
+        .arg("--source-file")
+        .arg(directory.join("source"))
+        .arg("unset")
+        .env("ENV_SELECT_LAST_EXPORT", last_export.to_string())
+        .assert()
+        .success()
+        .stdout(format!(
+            "The following variables will be restored:
+API_TOKEN = <REDACTED>
+PLAIN = xyz
+SECRET = <REDACTED>
+Changing directory to {}
+",
+            directory.display()
+        ));
+}
+
+/// Unsetting without a previous `es set` is an error
+#[test]
+fn test_unset_nothing() {
+    env_select()
+        .env_remove("ENV_SELECT_LAST_EXPORT")
+        .arg("unset")
+        .assert()
+        .failure()
+        .stderr("[ERROR] Nothing to unset; `es set` hasn't been run in this shell\n");
+}